@@ -13,8 +13,9 @@ fn gen_test_traj(num_atoms: usize, num_frames: usize) -> Result<NamedTempFile> {
     let mut frame = Frame {
         step: 1,
         time: 1.0,
-        box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+        box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
         coords: vec![[1.0, 1.1, 1.2]; num_atoms],
+        ..Default::default()
     };
 
     for _ in 0..num_frames {
@@ -1,17 +1,26 @@
+#[cfg(feature = "c-lib")]
 extern crate cc;
 
-use std::fs;
 use std::io::Result;
 
+#[cfg(feature = "c-lib")]
 fn main() -> Result<()> {
     // This builds gromacs' xdrfile library
-    let source_files = fs::read_dir("external/xdrfile/src")?
+    let source_files = std::fs::read_dir("external/xdrfile/src")?
         .map(|r| r.map(|f| f.path()))
         .collect::<Result<Vec<_>>>()?;
     cc::Build::new()
         .files(source_files)
         .include("external/xdrfile/include")
+        // 64 bit file offsets on 32 bit platforms, for files over 2 GB
+        .define("_FILE_OFFSET_BITS", "64")
         .warnings(false)
         .compile("libxdrfile.a");
     Ok(())
 }
+
+// Without the C library there is nothing to build
+#[cfg(not(feature = "c-lib"))]
+fn main() -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,36 @@
+#ifndef _xdr_stream_h
+#define _xdr_stream_h
+
+// for int64_t on older M$ Visual Studio
+#if _MSC_VER && _MSVC_VER < 1600 && !__INTEL_COMPILER
+    #include "ms_stdint.h"
+#else
+    #include <stdint.h>
+#endif
+
+#include <stddef.h>
+#include "xdrfile.h"
+
+/* Callbacks that an xdr stream uses instead of a FILE */
+typedef struct xdr_stream_ops
+{
+    /* read len bytes into buf, returns the number of bytes read */
+    size_t  (*read)(void *handle, char *buf, size_t len);
+    /* write len bytes from buf, returns the number of bytes written */
+    size_t  (*write)(void *handle, const char *buf, size_t len);
+    /* seek like fseek, returns the new position or -1 on error */
+    int64_t (*seek)(void *handle, int64_t offset, int whence);
+    /* returns 0 on success */
+    int     (*flush)(void *handle);
+} xdr_stream_ops;
+
+/* Open an XDRFILE that reads from or writes to handle through ops. The
+ * handle is not closed by xdr_stream_close. */
+XDRFILE *xdr_stream_open(void *handle, const xdr_stream_ops *ops, const char *mode);
+int xdr_stream_close(XDRFILE *xfp);
+
+int64_t xdr_stream_tell(XDRFILE *xd);
+int xdr_stream_seek(XDRFILE *xd, int64_t pos, int whence);
+int xdr_stream_flush(XDRFILE *xd);
+
+#endif
@@ -1,6 +1,7 @@
 /* 64 bit fileseek operations */
-#define _FILE_OFFSETS_BITS 64
+#define _FILE_OFFSET_BITS 64
 #include "xdr_seek.h"
+#include "xdr_stream.h"
 #include <stdio.h>
 
 /// copied from xtcfile.c (version 1.1.4)
@@ -19,11 +20,13 @@ struct XDRFILE
 int64_t xdr_tell(XDRFILE *xd)
 {
     FILE* fptr = xd->fp;
+    if (fptr == NULL)
+        return xdr_stream_tell(xd);
 
 #ifndef _WIN32
     // use posix 64 bit ftell version
     return ftello(fptr);
-#elif defined(_MSVC_VER) && !__INTEL_COMPILER
+#elif defined(_MSC_VER) || defined(__MINGW32__)
     return _ftelli64(fptr);
 #else
     return ftell(fptr);
@@ -34,11 +37,13 @@ int xdr_seek(XDRFILE *xd, int64_t pos, int whence)
 {
     int result = 1;
     FILE* fptr = xd->fp;
+    if (fptr == NULL)
+        return xdr_stream_seek(xd, pos, whence);
 
 #ifndef _WIN32
     // use posix 64 bit ftell version
     result = fseeko(fptr, pos, whence) < 0 ? exdrNR : exdrOK;
-#elif _MSVC_VER && !__INTEL_COMPILER
+#elif defined(_MSC_VER) || defined(__MINGW32__)
     result = _fseeki64(fptr, pos, whence) < 0 ? exdrNR : exdrOK;
 #else
     result = fseek(fptr, pos, whence) < 0 ? exdrNR : exdrOK;
@@ -51,5 +56,7 @@ int xdr_seek(XDRFILE *xd, int64_t pos, int whence)
 
 int xdr_flush(XDRFILE* xdr)
 {
+    if (xdr->fp == NULL)
+        return xdr_stream_flush(xdr);
     return fflush(xdr->fp);
 }
@@ -0,0 +1,216 @@
+/* XDRFILEs backed by user supplied callbacks instead of a FILE */
+#include "xdr_stream.h"
+#include <stdio.h>
+#include <stdlib.h>
+
+/// copied from xdrfile.c (version 1.1.4)
+enum xdr_op
+{
+    XDR_ENCODE = 0,
+    XDR_DECODE = 1,
+    XDR_FREE   = 2
+};
+
+typedef struct XDR XDR;
+
+struct XDR
+{
+    enum xdr_op x_op;
+    struct xdr_ops
+    {
+        int (*x_getlong) (XDR *__xdrs, int32_t *__lp);
+        int (*x_putlong) (XDR *__xdrs, int32_t *__lp);
+        int (*x_getbytes) (XDR *__xdrs, char *__addr, unsigned int __len);
+        int (*x_putbytes) (XDR *__xdrs, char *__addr, unsigned int __len);
+        unsigned int (*x_getpostn) (XDR *__xdrs);
+        int (*x_setpostn) (XDR *__xdrs, unsigned int __pos);
+        void (*x_destroy) (XDR *__xdrs);
+    }
+    *x_ops;
+    char *x_private;
+};
+
+struct XDRFILE
+{
+    FILE *   fp;       /**< pointer to standard C library file handle */
+    XDR *    xdr;      /**< pointer to corresponding XDR handle       */
+    char     mode;     /**< r=read, w=write, a=append                 */
+    int *    buf1;     /**< Buffer for internal use                   */
+    int      buf1size; /**< Current allocated length of buf1          */
+    int *    buf2;     /**< Buffer for internal use                   */
+    int      buf2size; /**< Current allocated length of buf2          */
+};
+//// end of copied
+
+typedef struct
+{
+    void *                 handle;
+    const xdr_stream_ops * ops;
+} xdr_stream_state;
+
+static xdr_stream_state *
+get_state(XDR *xdrs)
+{
+    return (xdr_stream_state *) xdrs->x_private;
+}
+
+static uint32_t
+from_big_endian(uint32_t x)
+{
+    unsigned char *b = (unsigned char *) &x;
+    return ((uint32_t) b[0] << 24) | ((uint32_t) b[1] << 16) | ((uint32_t) b[2] << 8) | b[3];
+}
+
+static int
+xdrstream_getlong(XDR *xdrs, int32_t *lp)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    uint32_t value;
+
+    if (state->ops->read(state->handle, (char *) &value, 4) != 4)
+        return 0;
+    *lp = (int32_t) from_big_endian(value);
+    return 1;
+}
+
+static int
+xdrstream_putlong(XDR *xdrs, int32_t *lp)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    uint32_t value = (uint32_t) *lp;
+    unsigned char bytes[4];
+
+    bytes[0] = (unsigned char) (value >> 24);
+    bytes[1] = (unsigned char) (value >> 16);
+    bytes[2] = (unsigned char) (value >> 8);
+    bytes[3] = (unsigned char) value;
+    return state->ops->write(state->handle, (char *) bytes, 4) == 4;
+}
+
+static int
+xdrstream_getbytes(XDR *xdrs, char *addr, unsigned int len)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    return len == 0 || state->ops->read(state->handle, addr, len) == len;
+}
+
+static int
+xdrstream_putbytes(XDR *xdrs, char *addr, unsigned int len)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    return len == 0 || state->ops->write(state->handle, addr, len) == len;
+}
+
+static unsigned int
+xdrstream_getpos(XDR *xdrs)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    return (unsigned int) state->ops->seek(state->handle, 0, SEEK_CUR);
+}
+
+static int
+xdrstream_setpos(XDR *xdrs, unsigned int pos)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    return state->ops->seek(state->handle, pos, SEEK_SET) < 0 ? 0 : 1;
+}
+
+static void
+xdrstream_destroy(XDR *xdrs)
+{
+    xdr_stream_state *state = get_state(xdrs);
+    (void) state->ops->flush(state->handle);
+}
+
+static const struct xdr_ops xdrstream_ops =
+    {
+        xdrstream_getlong,
+        xdrstream_putlong,
+        xdrstream_getbytes,
+        xdrstream_putbytes,
+        xdrstream_getpos,
+        xdrstream_setpos,
+        xdrstream_destroy,
+    };
+
+XDRFILE *
+xdr_stream_open(void *handle, const xdr_stream_ops *ops, const char *mode)
+{
+    enum xdr_op xdrmode;
+    XDRFILE *xfp;
+    xdr_stream_state *state;
+
+    if (*mode == 'w' || *mode == 'W' || *mode == 'a' || *mode == 'A')
+        xdrmode = XDR_ENCODE;
+    else if (*mode == 'r' || *mode == 'R')
+        xdrmode = XDR_DECODE;
+    else
+        return NULL;
+
+    if ((*mode == 'a' || *mode == 'A') && ops->seek(handle, 0, SEEK_END) < 0)
+        return NULL;
+
+    if ((xfp = (XDRFILE *) calloc(1, sizeof(XDRFILE))) == NULL)
+        return NULL;
+    if ((xfp->xdr = (XDR *) malloc(sizeof(XDR))) == NULL)
+    {
+        free(xfp);
+        return NULL;
+    }
+    if ((state = (xdr_stream_state *) malloc(sizeof(xdr_stream_state))) == NULL)
+    {
+        free(xfp->xdr);
+        free(xfp);
+        return NULL;
+    }
+    state->handle = handle;
+    state->ops = ops;
+
+    /* streams are recognized by their missing FILE */
+    xfp->fp = NULL;
+    xfp->mode = *mode;
+    xfp->xdr->x_op = xdrmode;
+    xfp->xdr->x_ops = (struct xdr_ops *) &xdrstream_ops;
+    xfp->xdr->x_private = (char *) state;
+    return xfp;
+}
+
+int
+xdr_stream_close(XDRFILE *xfp)
+{
+    int ret = exdrCLOSE;
+    if (xfp)
+    {
+        xdr_stream_state *state = get_state(xfp->xdr);
+        ret = state->ops->flush(state->handle);
+        free(state);
+        free(xfp->xdr);
+        if (xfp->buf1size)
+            free(xfp->buf1);
+        if (xfp->buf2size)
+            free(xfp->buf2);
+        free(xfp);
+    }
+    return ret;
+}
+
+int64_t
+xdr_stream_tell(XDRFILE *xd)
+{
+    xdr_stream_state *state = get_state(xd->xdr);
+    return state->ops->seek(state->handle, 0, SEEK_CUR);
+}
+
+int
+xdr_stream_seek(XDRFILE *xd, int64_t pos, int whence)
+{
+    xdr_stream_state *state = get_state(xd->xdr);
+    return state->ops->seek(state->handle, pos, whence) < 0 ? exdrNR : exdrOK;
+}
+
+int
+xdr_stream_flush(XDRFILE *xd)
+{
+    xdr_stream_state *state = get_state(xd->xdr);
+    return state->ops->flush(state->handle);
+}
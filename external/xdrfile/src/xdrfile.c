@@ -814,6 +814,11 @@ xdrfile_decompress_coord_float(float     *ptr,
     size3 = *size * 3;
     if(size3>xfp->buf1size) 
     {
+        /* replace the buffers of smaller frames instead of leaking them */
+        free(xfp->buf1);
+        free(xfp->buf2);
+        xfp->buf1 = xfp->buf2 = NULL;
+        xfp->buf1size = xfp->buf2size = 0;
         if((xfp->buf1=(int *)malloc(sizeof(int)*size3))==NULL) 
         {
             fprintf(stderr, "(xdrfile error) Cannot allocate memory for decompressing coordinates.\n");
@@ -1026,6 +1031,11 @@ xdrfile_compress_coord_float(float   *ptr,
 
     if(size3>xfp->buf1size)
     {
+        /* replace the buffers of smaller frames instead of leaking them */
+        free(xfp->buf1);
+        free(xfp->buf2);
+        xfp->buf1 = xfp->buf2 = NULL;
+        xfp->buf1size = xfp->buf2size = 0;
         if((xfp->buf1=(int *)malloc(sizeof(int)*size3))==NULL) 
         {
             fprintf(stderr, "(xdrfile error) Cannot allocate memory for compressing coordinates.\n");
@@ -1327,6 +1337,11 @@ xdrfile_decompress_coord_double(double     *ptr,
     size3 = *size * 3;
     if(size3>xfp->buf1size) 
     {
+        /* replace the buffers of smaller frames instead of leaking them */
+        free(xfp->buf1);
+        free(xfp->buf2);
+        xfp->buf1 = xfp->buf2 = NULL;
+        xfp->buf1size = xfp->buf2size = 0;
         if((xfp->buf1=(int *)malloc(sizeof(int)*size3))==NULL) 
         {
             fprintf(stderr, "(xdrfile error) Cannot allocate memory for decompression coordinates.\n");
@@ -1517,6 +1532,11 @@ xdrfile_compress_coord_double(double   *ptr,
         return -1;
     size3=3*size;
     if(size3>xfp->buf1size) {
+        /* replace the buffers of smaller frames instead of leaking them */
+        free(xfp->buf1);
+        free(xfp->buf2);
+        xfp->buf1 = xfp->buf2 = NULL;
+        xfp->buf1size = xfp->buf2size = 0;
         if((xfp->buf1=(int *)malloc(sizeof(int)*size3))==NULL) {
             fprintf(stderr, "(xdrfile error) Cannot allocate memory for compressing coordinates.\n");
             return -1;
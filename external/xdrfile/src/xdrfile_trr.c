@@ -174,14 +174,71 @@ extern int do_trnheader(XDRFILE *xd,mybool bRead,t_trnheader *sh)
     return exdrOK;
 }
 
+/* Number of vectors that are converted at a time, so that no buffer
+ * needs to be allocated for every frame */
+#define CHUNK 64
+
+/* Read or write natoms single precision vectors. If x is NULL while
+ * reading, the vectors are skipped. */
+static int do_rvecs_float(XDRFILE *xd,int natoms,rvec *x)
+{
+    float buf[CHUNK*DIM];
+    int   i,n;
+
+    if (NULL != x)
+    {
+        if (xdrfile_read_float(x[0],natoms*DIM,xd) != natoms*DIM)
+            return exdrFLOAT;
+        return exdrOK;
+    }
+    memset(buf,0,sizeof(buf));
+    for(i=0; (i<natoms); i+=CHUNK)
+    {
+        n = (natoms-i < CHUNK) ? natoms-i : CHUNK;
+        if (xdrfile_read_float(buf,n*DIM,xd) != n*DIM)
+            return exdrFLOAT;
+    }
+    return exdrOK;
+}
+
+/* Read or write natoms double precision vectors, converting them from or
+ * to the single precision vectors in x. If x is NULL while reading, the
+ * vectors are skipped. */
+static int do_rvecs_double(XDRFILE *xd,mybool bRead,int natoms,rvec *x)
+{
+    double buf[CHUNK*DIM];
+    int    i,j,n;
+
+    memset(buf,0,sizeof(buf));
+    for(i=0; (i<natoms); i+=CHUNK)
+    {
+        n = (natoms-i < CHUNK) ? natoms-i : CHUNK;
+        if (!bRead && NULL != x)
+            for(j=0; (j<n*DIM); j++)
+                buf[j] = x[i+j/DIM][j%DIM];
+        if (xdrfile_read_double(buf,n*DIM,xd) != n*DIM)
+            return exdrDOUBLE;
+        if (bRead && NULL != x)
+            for(j=0; (j<n*DIM); j++)
+                x[i+j/DIM][j%DIM] = buf[j];
+    }
+    return exdrOK;
+}
+
+static int do_rvecs(XDRFILE *xd,mybool bRead,t_trnheader *sh,rvec *x)
+{
+    if (sh->bDouble)
+        return do_rvecs_double(xd,bRead,sh->natoms,x);
+    else
+        return do_rvecs_float(xd,sh->natoms,x);
+}
+
 static int do_htrn(XDRFILE *xd,mybool bRead,t_trnheader *sh,
                    matrix box,rvec *x,rvec *v,rvec *f)
 {
     double pvd[DIM*DIM];
-    double *dx=NULL;
     float  pvf[DIM*DIM];
-    float  *fx=NULL;
-    int    i,j;
+    int    i,j,result;
     
     if (sh->bDouble) 
     {
@@ -220,91 +277,6 @@ static int do_htrn(XDRFILE *xd,mybool bRead,t_trnheader *sh,
             if (xdrfile_read_double(pvd,DIM*DIM,xd) != DIM*DIM) 
                 return exdrDOUBLE;
         }
-        
-        if ((sh->x_size != 0) || (sh->v_size != 0) || (sh->f_size != 0)) {
-            dx = (double *)calloc(sh->natoms*DIM,sizeof(dx[0]));
-            if (NULL == dx)
-                return exdrNOMEM;
-        }
-        if (sh->x_size   != 0) 
-        {
-            if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            dx[i*DIM+j] = x[i][j];
-                        }
-            }
-            if (xdrfile_read_double(dx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                if (bRead) 
-                {
-                    for(i=0; (i<sh->natoms); i++)
-                        for(j=0; (j<DIM); j++)
-                            if (NULL != x)
-                            {
-                                x[i][j] = dx[i*DIM+j];
-                            }
-                }
-            }
-            else
-                return exdrDOUBLE;
-        }
-        if (sh->v_size   != 0) 
-        {
-            if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            dx[i*DIM+j] = v[i][j];
-                        }
-            }
-            if (xdrfile_read_double(dx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != v)
-                        {
-                            v[i][j] = dx[i*DIM+j];
-                        }
-            }
-            else
-                return exdrDOUBLE;
-        }
-        if (sh->f_size   != 0) 
-        {
-            if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            dx[i*DIM+j] = f[i][j];
-                        }
-            }
-            if (xdrfile_read_double(dx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                for(i=0; (i<sh->natoms); i++)
-                {
-                    for(j=0; (j<DIM); j++)
-                    {
-                        if (NULL != f)
-                        {
-                            f[i][j] = dx[i*DIM+j];
-                        }
-                    }
-                }
-            }
-            else
-                return exdrDOUBLE;
-        }
-        if ((sh->x_size != 0) || (sh->v_size != 0) || (sh->f_size != 0)) {
-            free(dx);
-        }
     }
     else
         /* Float */
@@ -348,121 +320,51 @@ static int do_htrn(XDRFILE *xd,mybool bRead,t_trnheader *sh,
             if (xdrfile_read_float(pvf,DIM*DIM,xd) != DIM*DIM) 
                 return exdrFLOAT;
         }
-        
-        if ((sh->x_size != 0) || (sh->v_size != 0) || (sh->f_size != 0)) {
-            fx = (float *)calloc(sh->natoms*DIM,sizeof(fx[0]));
-            if (NULL == fx)
-                return exdrNOMEM;
-        }
-        if (sh->x_size   != 0) 
-        {
-            if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            fx[i*DIM+j] = x[i][j];
-                        }
-            }
-            if (xdrfile_read_float(fx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                if (bRead) 
-                {
-                    for(i=0; (i<sh->natoms); i++)
-                        for(j=0; (j<DIM); j++)
-                            if (NULL != x)
-                                x[i][j] = fx[i*DIM+j];
-                }
-            }
-            else
-                return exdrFLOAT;
-        }
-        if (sh->v_size   != 0) 
-        {
-            if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            fx[i*DIM+j] = v[i][j];
-                        }
-            }
-            if (xdrfile_read_float(fx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != v)
-                            v[i][j] = fx[i*DIM+j];
-            }
-            else
-                return exdrFLOAT;
-        }
-        if (sh->f_size   != 0) 
-        {
-           if (!bRead) 
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != x)
-                        {
-                            fx[i*DIM+j] = f[i][j];
-                        }
-            }
-             if (xdrfile_read_float(fx,sh->natoms*DIM,xd) == sh->natoms*DIM)
-            {
-                for(i=0; (i<sh->natoms); i++)
-                    for(j=0; (j<DIM); j++)
-                        if (NULL != f)
-                            f[i][j] = fx[i*DIM+j];
-            }
-            else
-                return exdrFLOAT;
-        }
-        if ((sh->x_size != 0) || (sh->v_size != 0) || (sh->f_size != 0)) {
-            free(fx);
-        }
     }
+
+    if (sh->x_size != 0 && (result = do_rvecs(xd,bRead,sh,x)) != exdrOK)
+        return result;
+    if (sh->v_size != 0 && (result = do_rvecs(xd,bRead,sh,v)) != exdrOK)
+        return result;
+    if (sh->f_size != 0 && (result = do_rvecs(xd,bRead,sh,f)) != exdrOK)
+        return result;
     return exdrOK;
 }
 
 static int do_trn(XDRFILE *xd,mybool bRead,int *step,float *t,float *lambda,
                   matrix box,int *natoms,rvec *x,rvec *v,rvec *f)
 {
-    t_trnheader *sh;
+    t_trnheader sh;
     int result;
   
-    sh = (t_trnheader *)calloc(1,sizeof(*sh));
+    memset(&sh,0,sizeof(sh));
   
     if (!bRead) {
-        sh->box_size = (NULL != box) ? sizeof(matrix):0;
-        sh->x_size   = ((NULL != x) ? (*natoms*sizeof(x[0])):0);
-        sh->v_size   = ((NULL != v) ? (*natoms*sizeof(v[0])):0);
-        sh->f_size   = ((NULL != f) ? (*natoms*sizeof(f[0])):0);
-        sh->natoms = *natoms;
-        sh->step   = *step;
-        sh->nre    = 0;
-        sh->td      = *t;
-        sh->lambdad = *lambda;
-        sh->tf      = *t;
-        sh->lambdaf = *lambda;
+        sh.box_size = (NULL != box) ? sizeof(matrix):0;
+        sh.x_size   = ((NULL != x) ? (*natoms*sizeof(x[0])):0);
+        sh.v_size   = ((NULL != v) ? (*natoms*sizeof(v[0])):0);
+        sh.f_size   = ((NULL != f) ? (*natoms*sizeof(f[0])):0);
+        sh.natoms = *natoms;
+        sh.step   = *step;
+        sh.nre    = 0;
+        sh.td      = *t;
+        sh.lambdad = *lambda;
+        sh.tf      = *t;
+        sh.lambdaf = *lambda;
     }
-    if ((result = do_trnheader(xd,bRead,sh)) != exdrOK)
+    if ((result = do_trnheader(xd,bRead,&sh)) != exdrOK)
         return result;
 
     if (bRead) {
-        *natoms = sh->natoms;
-        *step   = sh->step;
-        *t      = sh->td;
-        *lambda = sh->lambdad;
+        /* the vectors must fit into the buffers of the caller */
+        if (sh.natoms > *natoms)
+            return exdr3DX;
+        *natoms = sh.natoms;
+        *step   = sh.step;
+        *t      = sh.td;
+        *lambda = sh.lambdad;
     }
-    if ((result = do_htrn(xd,bRead,sh,box,x,v,f)) != exdrOK)
-        return result;
-
-    free(sh);
-  
-    return exdrOK;
+    return do_htrn(xd,bRead,&sh,box,x,v,f);
 }
 
 /************************************************************
@@ -136,12 +136,15 @@ int read_xtc(XDRFILE *xd,
 			 matrix box,rvec *x,float *prec)
 /* Read subsequent frames */
 {
-	int result;
+	int result,file_natoms;
   
-	if ((result = xtc_header(xd,&natoms,step,time,TRUE)) != exdrOK)
+	if ((result = xtc_header(xd,&file_natoms,step,time,TRUE)) != exdrOK)
 		return result;
+	/* the coordinates must fit into the buffer of the caller */
+	if (file_natoms > natoms)
+		return exdr3DX;
 	  
-	if ((result = xtc_coord(xd,&natoms,box,x,prec,1)) != exdrOK)
+	if ((result = xtc_coord(xd,&file_natoms,box,x,prec,1)) != exdrOK)
 		return result;
 
 	return exdrOK;
@@ -0,0 +1,534 @@
+//! Adapters for iterators over trajectory frames
+
+use crate::*;
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Conversion of the items yielded by trajectory iterators into owned frames
+pub trait IntoFrame {
+    /// Take the frame out of `self`, cloning it only if it is shared
+    fn into_frame(self) -> Frame;
+}
+
+impl IntoFrame for Frame {
+    fn into_frame(self) -> Frame {
+        self
+    }
+}
+
+impl IntoFrame for Rc<Frame> {
+    fn into_frame(self) -> Frame {
+        Rc::try_unwrap(self).unwrap_or_else(|frame| (*frame).clone())
+    }
+}
+
+impl IntoFrame for Arc<Frame> {
+    fn into_frame(self) -> Frame {
+        Arc::try_unwrap(self).unwrap_or_else(|frame| (*frame).clone())
+    }
+}
+
+/// Extension methods for iterators over frames, like `TrajectoryIterator`
+/// and `OwnedTrajectoryIterator`
+pub trait FrameIteratorExt<F>: Iterator<Item = Result<F>> + Sized
+where
+    F: IntoFrame,
+{
+    /// Yield the frames in blocks of `size` frames. The last block may be
+    /// shorter. An error is yielded after the frames read before it.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    fn chunks(self, size: usize) -> Chunks<Self> {
+        assert!(size != 0, "chunk size must be non-zero");
+        Chunks {
+            iter: self,
+            size,
+            error: None,
+        }
+    }
+
+    /// Yield overlapping windows of the last `size` frames, oldest first,
+    /// like `slice::windows`. Every frame is read once and shared between
+    /// the windows it is part of. No window is yielded if the trajectory has
+    /// fewer than `size` frames.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    fn windows(self, size: usize) -> Windows<Self> {
+        assert!(size != 0, "window size must be non-zero");
+        Windows {
+            iter: self,
+            size,
+            buffer: VecDeque::with_capacity(size),
+        }
+    }
+
+    /// Write every frame to `writer` while passing it on, e.g. to convert a
+    /// trajectory while analyzing it. The writer is flushed at the end of
+    /// the iteration. A failed write is yielded as an error and ends the
+    /// iteration.
+    fn tee_to<T>(self, writer: T) -> Tee<Self, T>
+    where
+        T: Trajectory,
+        F: Borrow<Frame>,
+    {
+        Tee {
+            iter: self,
+            writer,
+            done: false,
+        }
+    }
+
+    /// Randomly keep each frame with probability `fraction`. The same `seed`
+    /// selects the same frames. Errors are always passed on.
+    fn sample(self, fraction: f64, seed: u64) -> Sample<Self> {
+        Sample {
+            iter: self,
+            fraction,
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Randomly select `k` frames with equal probability by reservoir
+    /// sampling, without knowing the number of frames in advance. Every
+    /// frame is read, but at most `k` are kept in memory. The selected
+    /// frames are returned in trajectory order; all frames are returned if
+    /// there are fewer than `k`.
+    fn sample_n(self, k: usize, seed: u64) -> Result<Vec<Frame>> {
+        let mut rng = SplitMix64(seed);
+        let mut reservoir: Vec<(usize, Frame)> = Vec::with_capacity(k);
+        for (i, frame) in self.enumerate() {
+            let frame = frame?;
+            if reservoir.len() < k {
+                reservoir.push((i, frame.into_frame()));
+            } else {
+                let j = rng.below(i as u64 + 1) as usize;
+                if j < k {
+                    reservoir[j] = (i, frame.into_frame());
+                }
+            }
+        }
+        reservoir.sort_by_key(|(i, _)| *i);
+        Ok(reservoir.into_iter().map(|(_, frame)| frame).collect())
+    }
+}
+
+impl<I, F> FrameIteratorExt<F> for I
+where
+    I: Iterator<Item = Result<F>>,
+    F: IntoFrame,
+{
+}
+
+/// Iterator over blocks of frames, see `FrameIteratorExt::chunks`
+pub struct Chunks<I> {
+    iter: I,
+    size: usize,
+    error: Option<Error>,
+}
+
+impl<I, F> Iterator for Chunks<I>
+where
+    I: Iterator<Item = Result<F>>,
+    F: IntoFrame,
+{
+    type Item = Result<Vec<Frame>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        let mut chunk = Vec::with_capacity(self.size);
+        for result in self.iter.by_ref() {
+            match result {
+                Ok(frame) => chunk.push(frame.into_frame()),
+                Err(e) if chunk.is_empty() => return Some(Err(e)),
+                Err(e) => {
+                    self.error = Some(e);
+                    break;
+                }
+            }
+            if chunk.len() == self.size {
+                break;
+            }
+        }
+
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Iterator over overlapping windows of frames, see `FrameIteratorExt::windows`
+pub struct Windows<I> {
+    iter: I,
+    size: usize,
+    buffer: VecDeque<Rc<Frame>>,
+}
+
+impl<I, F> Iterator for Windows<I>
+where
+    I: Iterator<Item = Result<F>>,
+    F: IntoFrame,
+{
+    type Item = Result<Vec<Rc<Frame>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = match self.iter.next()? {
+                Ok(frame) => frame.into_frame(),
+                Err(e) => return Some(Err(e)),
+            };
+            if self.buffer.len() == self.size {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(Rc::new(frame));
+            if self.buffer.len() == self.size {
+                return Some(Ok(self.buffer.iter().cloned().collect()));
+            }
+        }
+    }
+}
+
+/// Iterator that writes a copy of every frame, see `FrameIteratorExt::tee_to`
+pub struct Tee<I, T> {
+    iter: I,
+    writer: T,
+    done: bool,
+}
+
+impl<I, T> Tee<I, T> {
+    /// Get back the trajectory that the frames are written to
+    pub fn into_writer(self) -> T {
+        self.writer
+    }
+}
+
+impl<I, T, F> Iterator for Tee<I, T>
+where
+    I: Iterator<Item = Result<F>>,
+    T: Trajectory,
+    F: Borrow<Frame>,
+{
+    type Item = Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = match self.iter.next() {
+            Some(Ok(frame)) => self.writer.write(frame.borrow()).map(|()| frame),
+            Some(Err(e)) => Err(e),
+            None => {
+                self.done = true;
+                return self.writer.flush().err().map(Err);
+            }
+        };
+        self.done = item.is_err();
+        Some(item)
+    }
+}
+
+/// Iterator over a random subset of frames, see `FrameIteratorExt::sample`
+pub struct Sample<I> {
+    iter: I,
+    fraction: f64,
+    rng: SplitMix64,
+}
+
+impl<I, F> Iterator for Sample<I>
+where
+    I: Iterator<Item = Result<F>>,
+{
+    type Item = Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let fraction = self.fraction;
+        let rng = &mut self.rng;
+        self.iter
+            .find(|frame| frame.is_err() || rng.next_f64() < fraction)
+    }
+}
+
+/// Small seedable pseudo random number generator, good enough for sampling
+/// frames
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform number in [0, n)
+    fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+}
+
+/// Iterate over two trajectories in lockstep, e.g. to compare an XTC file
+/// with the TRR file of the same run.
+///
+/// Yields an error if the paired frames have a different number of atoms, if
+/// one trajectory has more frames than the other, or if their times differ
+/// and `ZipFrames::check_times` is used.
+pub fn zip_frames<A, B, FA, FB>(a: A, b: B) -> ZipFrames<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator<Item = Result<FA>>,
+    B: IntoIterator<Item = Result<FB>>,
+    FA: Borrow<Frame>,
+    FB: Borrow<Frame>,
+{
+    ZipFrames {
+        a: a.into_iter(),
+        b: b.into_iter(),
+        index: 0,
+        time_tolerance: None,
+        has_error: false,
+    }
+}
+
+/// Iterator over pairs of frames, see `zip_frames`
+pub struct ZipFrames<A, B> {
+    a: A,
+    b: B,
+    index: usize,
+    time_tolerance: Option<f32>,
+    has_error: bool,
+}
+
+impl<A, B> ZipFrames<A, B> {
+    /// Also require the times of paired frames to differ by at most `tolerance`
+    pub fn check_times(mut self, tolerance: f32) -> Self {
+        self.time_tolerance = Some(tolerance);
+        self
+    }
+}
+
+impl<A, B, FA, FB> ZipFrames<A, B>
+where
+    A: Iterator<Item = Result<FA>>,
+    B: Iterator<Item = Result<FB>>,
+    FA: Borrow<Frame>,
+    FB: Borrow<Frame>,
+{
+    fn next_inner(&mut self) -> Option<Result<(FA, FB)>> {
+        let index = self.index;
+        let (a, b) = match (self.a.next(), self.b.next()) {
+            (None, None) => return None,
+            (Some(a), Some(b)) => (a, b),
+            (Some(Err(e)), None) | (None, Some(Err(e))) => return Some(Err(e)),
+            // the other trajectory has at least one more frame
+            (a, _) => {
+                return Some(Err(Error::FramesDiffer {
+                    index,
+                    difference: FrameDifference::Ended { left: a.is_none() },
+                }))
+            }
+        };
+        let (a, b) = match (a, b) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => return Some(Err(e)),
+        };
+
+        let (left, right) = (a.borrow(), b.borrow());
+        let difference = if left.len() != right.len() {
+            Some(FrameDifference::NumAtoms {
+                left: left.len(),
+                right: right.len(),
+            })
+        } else {
+            match self.time_tolerance {
+                Some(tol) if (left.time - right.time).abs() > tol => Some(FrameDifference::Time {
+                    left: left.time,
+                    right: right.time,
+                }),
+                _ => None,
+            }
+        };
+        if let Some(difference) = difference {
+            return Some(Err(Error::FramesDiffer { index, difference }));
+        }
+        self.index += 1;
+        Some(Ok((a, b)))
+    }
+}
+
+impl<A, B, FA, FB> Iterator for ZipFrames<A, B>
+where
+    A: Iterator<Item = Result<FA>>,
+    B: Iterator<Item = Result<FB>>,
+    FA: Borrow<Frame>,
+    FB: Borrow<Frame>,
+{
+    type Item = Result<(FA, FB)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.has_error {
+            return None;
+        }
+        let item = self.next_inner();
+        self.has_error = matches!(item, Some(Err(_)));
+        item
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() -> Result<()> {
+        let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let chunks: Vec<Vec<Frame>> = trj.into_iter().chunks(10).collect::<Result<_>>()?;
+        let sizes: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![10, 10, 10, 8]);
+        assert_eq!(chunks[1][0].step, 11);
+        assert_eq!(chunks[3][7].step, 38);
+
+        let frames = vec![Ok(Frame::new()), Err(Error::Unsupported("test"))];
+        let mut chunks = frames.into_iter().chunks(5);
+        assert_eq!(chunks.next().unwrap().unwrap().len(), 1);
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_windows() -> Result<()> {
+        let trj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let windows: Vec<_> = trj.into_frames().windows(3).collect::<Result<_>>()?;
+        assert_eq!(windows.len(), 36);
+        for (i, window) in windows.iter().enumerate() {
+            let steps: Vec<usize> = window.iter().map(|frame| frame.step).collect();
+            assert_eq!(steps, vec![i + 1, i + 2, i + 3]);
+        }
+        // frames are shared between windows instead of copied
+        assert!(Rc::ptr_eq(&windows[0][2], &windows[2][0]));
+
+        let trj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert_eq!(trj.into_iter().windows(39).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_frames() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut max_deviation: f32 = 0.0;
+        for pair in zip_frames(xtc, trr.into_frames()).check_times(1e-3) {
+            let (xtc_frame, trr_frame) = pair?;
+            max_deviation = max_deviation.max(xtc_frame.rmsd(&trr_frame)?);
+        }
+        assert!(max_deviation < 1e-3);
+
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let pairs: Vec<_> = zip_frames(xtc.into_iter().take(5), trr).collect();
+        assert_eq!(pairs.len(), 6);
+        assert!(matches!(
+            pairs[5],
+            Err(Error::FramesDiffer {
+                index: 5,
+                difference: FrameDifference::Ended { left: true }
+            })
+        ));
+
+        // an error of the longer trajectory is not hidden by its length
+        let failed = Error::Unsupported("test");
+        let none: Vec<Result<Frame>> = Vec::new();
+        let mut pairs = zip_frames(none.clone(), vec![Err::<Frame, _>(failed.clone())]);
+        assert_eq!(pairs.next().unwrap().unwrap_err(), failed);
+        let mut pairs = zip_frames(vec![Ok(Frame::with_len(2))], none);
+        let e = pairs.next().unwrap().unwrap_err();
+        assert!(e.to_string().ends_with("right trajectory ended"));
+
+        let mut pairs = zip_frames(
+            vec![Ok(Frame::with_len(2)), Ok(Frame::with_len(2))],
+            vec![Ok(Frame::with_len(2)), Ok(Frame::with_len(3))],
+        );
+        assert!(pairs.next().unwrap().is_ok());
+        assert_eq!(
+            pairs.next().unwrap().unwrap_err(),
+            Error::FramesDiffer {
+                index: 1,
+                difference: FrameDifference::NumAtoms { left: 2, right: 3 }
+            }
+        );
+        assert!(pairs.next().is_none());
+
+        let late = Frame {
+            time: 1.0,
+            ..Frame::with_len(2)
+        };
+        let mut pairs = zip_frames(vec![Ok(Frame::with_len(2))], vec![Ok(late)]).check_times(0.1);
+        assert!(matches!(
+            pairs.next(),
+            Some(Err(Error::FramesDiffer {
+                index: 0,
+                difference: FrameDifference::Time { .. }
+            }))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tee_to() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let copy = TRRTrajectory::open_write(tempfile.path())?;
+        let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let times: Vec<f32> = trj
+            .into_iter()
+            .tee_to(copy)
+            .map(|frame| frame.map(|f| f.time))
+            .collect::<Result<_>>()?;
+        assert_eq!(times.len(), 38);
+
+        let copied: Vec<f32> = TRRTrajectory::open_read(tempfile.path())?
+            .into_iter()
+            .map(|frame| frame.map(|f| f.time))
+            .collect::<Result<_>>()?;
+        assert_eq!(copied, times);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample() -> Result<()> {
+        let steps = |fraction, seed| -> Result<Vec<usize>> {
+            let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+            trj.into_iter()
+                .sample(fraction, seed)
+                .map(|frame| frame.map(|f| f.step))
+                .collect()
+        };
+        let sampled = steps(0.5, 7)?;
+        assert_eq!(sampled, steps(0.5, 7)?);
+        assert!(!sampled.is_empty() && sampled.len() < 38);
+        assert!(sampled.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(steps(1.0, 7)?.len(), 38);
+        assert!(steps(0.0, 7)?.is_empty());
+
+        let trj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let frames = trj.into_iter().sample_n(5, 42)?;
+        assert_eq!(frames.len(), 5);
+        assert!(frames.windows(2).all(|w| w[0].step < w[1].step));
+
+        let trj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert_eq!(trj.into_frames().sample_n(100, 42)?.len(), 38);
+        Ok(())
+    }
+}
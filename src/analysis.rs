@@ -0,0 +1,2971 @@
+//! Common analyses over whole trajectories
+//!
+//! ```no_run
+//! use xdrfile::*;
+//! use xdrfile::analysis::rmsd_series;
+//!
+//! # fn main() -> Result<()> {
+//! let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+//! let mut reference = Frame::with_len(trajectory.get_num_atoms()?);
+//! XTCTrajectory::open_read("traj.xtc")?.read(&mut reference)?;
+//! for (time, rmsd) in rmsd_series(&mut trajectory, &reference, None, true)? {
+//!     println!("{} {}", time, rmsd);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::frame::sub;
+use crate::xvg::XvgData;
+use crate::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Seek;
+use std::ops::ControlFlow;
+
+/// Root mean square deviation of every remaining frame of `trajectory` from
+/// `reference`, as pairs of time and RMSD, restricted to the atoms in `mask`
+/// (or all atoms if `None`).
+///
+/// If `align` is true, each frame is first fitted onto the reference with
+/// `Frame::superpose_onto` using the same atoms, like `gmx rms` does by
+/// default. Periodic boundaries are ignored.
+pub fn rmsd_series(
+    trajectory: &mut impl Trajectory,
+    reference: &Frame,
+    mask: Option<&[usize]>,
+    align: bool,
+) -> Result<Vec<(f32, f32)>> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    let mut series = Vec::new();
+    while read_next(trajectory, &mut frame)? {
+        if align {
+            frame.superpose_onto(reference, mask)?;
+        }
+        series.push((frame.time, frame.rmsd_with(reference, mask, false)?));
+    }
+    Ok(series)
+}
+
+/// Radius of gyration of the atoms in `mask` (or all atoms if `None`) in
+/// every remaining frame of `trajectory`, as pairs of time and radius, see
+/// `Frame::radius_of_gyration`. The radius is mass weighted if `masses` (one
+/// per atom) is given. Only one frame is kept in memory at a time.
+pub fn gyration_series(
+    trajectory: &mut impl Trajectory,
+    mask: Option<&[usize]>,
+    masses: Option<&[f32]>,
+) -> Result<Vec<(f32, f32)>> {
+    let mut series = Vec::new();
+    let error = trajectory.for_each_frame(|frame| {
+        match frame.radius_of_gyration(masses, mask) {
+            Ok(radius) => series.push((frame.time, radius)),
+            Err(e) => return ControlFlow::Break(e),
+        }
+        ControlFlow::Continue(())
+    })?;
+    match error {
+        Some(e) => Err(e),
+        None => Ok(series),
+    }
+}
+
+/// A geometric quantity measured in every frame by `geometry_series`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Observable {
+    /// Distance between two atoms in nm, see `Frame::distance_pbc`
+    Distance(usize, usize),
+    /// Angle at the second of three atoms in degrees, see `Frame::angle_pbc`
+    Angle(usize, usize, usize),
+    /// Dihedral angle of four atoms in degrees, see `Frame::dihedral_pbc`
+    Dihedral(usize, usize, usize, usize),
+}
+
+impl Observable {
+    /// Measure the observable in `frame` under the minimum image convention
+    pub fn measure(&self, frame: &Frame) -> Result<f32> {
+        match *self {
+            Observable::Distance(i, j) => frame.distance_pbc(i, j),
+            Observable::Angle(i, j, k) => frame.angle_pbc(i, j, k),
+            Observable::Dihedral(i, j, k, l) => frame.dihedral_pbc(i, j, k, l),
+        }
+    }
+}
+
+/// A short label like `distance 1-2`, used as legend by
+/// `GeometrySeries::to_xvg`
+impl fmt::Display for Observable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Observable::Distance(i, j) => write!(f, "distance {}-{}", i, j),
+            Observable::Angle(i, j, k) => write!(f, "angle {}-{}-{}", i, j, k),
+            Observable::Dihedral(i, j, k, l) => write!(f, "dihedral {}-{}-{}-{}", i, j, k, l),
+        }
+    }
+}
+
+/// The values of several observables over time, one column per observable,
+/// see `geometry_series`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeometrySeries {
+    /// The observables, in the order of `columns`
+    pub observables: Vec<Observable>,
+
+    /// Time of each frame
+    pub times: Vec<f32>,
+
+    /// The values of each observable, one per frame
+    pub columns: Vec<Vec<f32>>,
+}
+
+impl GeometrySeries {
+    /// The values of `observable`, if it was measured
+    pub fn column(&self, observable: Observable) -> Option<&[f32]> {
+        let index = self.observables.iter().position(|o| *o == observable)?;
+        Some(&self.columns[index])
+    }
+
+    /// Convert to xvg data with the time in the first column, e.g. to write
+    /// it with `xvg::write` and plot it with xmgrace
+    pub fn to_xvg(&self) -> XvgData {
+        let mut data = XvgData::new();
+        data.x_label = "Time (ps)".to_string();
+        data.legends = self.observables.iter().map(|o| o.to_string()).collect();
+        data.rows = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(frame, time)| {
+                std::iter::once(*time)
+                    .chain(self.columns.iter().map(|column| column[frame]))
+                    .map(f64::from)
+                    .collect()
+            })
+            .collect();
+        data
+    }
+}
+
+/// Measure each of `observables` in every remaining frame of `trajectory`,
+/// with periodic boundaries taken into account. Only one frame is kept in
+/// memory at a time.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::{geometry_series, Observable};
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+/// let observables = [Observable::Distance(0, 10), Observable::Dihedral(4, 6, 8, 14)];
+/// let series = geometry_series(&mut trajectory, &observables)?;
+/// xvg::write(&mut std::fs::File::create("geometry.xvg")?, &series.to_xvg())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn geometry_series(
+    trajectory: &mut impl Trajectory,
+    observables: &[Observable],
+) -> Result<GeometrySeries> {
+    let mut series = GeometrySeries {
+        observables: observables.to_vec(),
+        times: Vec::new(),
+        columns: vec![Vec::new(); observables.len()],
+    };
+    let error = trajectory.for_each_frame(|frame| {
+        for (observable, column) in observables.iter().zip(&mut series.columns) {
+            match observable.measure(frame) {
+                Ok(value) => column.push(value),
+                Err(e) => return ControlFlow::Break(e),
+            }
+        }
+        series.times.push(frame.time);
+        ControlFlow::Continue(())
+    })?;
+    match error {
+        Some(e) => Err(e),
+        None => Ok(series),
+    }
+}
+
+/// Mean squared displacement curves computed by `msd`, one per selection
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeanSquaredDisplacement {
+    /// Time between the frames compared, i.e. the time of each frame minus
+    /// the time of the first one
+    pub lag_times: Vec<f32>,
+
+    /// The mean squared displacement of each selection in nm², one value per
+    /// lag time
+    pub curves: Vec<Vec<f32>>,
+}
+
+impl MeanSquaredDisplacement {
+    /// Self-diffusion coefficient of `selection` in nm²/ps from a linear
+    /// least-squares fit of its curve between the lag times `start` and
+    /// `end` (inclusive), using the Einstein relation MSD = 6 D t. `gmx msd`
+    /// reports 1e-5 cm²/s, which is 1000 times this value.
+    ///
+    /// Returns `None` if the selection does not exist or fewer than two lag
+    /// times are in the range.
+    pub fn diffusion_coefficient(&self, selection: usize, start: f32, end: f32) -> Option<f32> {
+        let curve = self.curves.get(selection)?;
+        let points: Vec<(f64, f64)> = self
+            .lag_times
+            .iter()
+            .zip(curve)
+            .filter(|(t, _)| (start..=end).contains(*t))
+            .map(|(t, msd)| (f64::from(*t), f64::from(*msd)))
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_msd = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let covariance: f64 = points
+            .iter()
+            .map(|(t, msd)| (t - mean_t) * (msd - mean_msd))
+            .sum();
+        let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+        Some((covariance / variance / 6.0) as f32)
+    }
+}
+
+/// Mean squared displacement of the atoms of each of `selections` over the
+/// remaining frames of `trajectory`, averaged over all time origins and all
+/// atoms of the selection. The frames should be equally spaced in time.
+///
+/// If `unwrap` is true, atoms that cross the periodic boundaries are moved
+/// back next to their previous position, so that their displacement is not
+/// cut off at the box size; leave it false for trajectories that are
+/// already unwrapped, e.g. with `gmx trjconv -pbc nojump`. The averaging over
+/// time origins uses FFTs (Calandrini et al., 2011), so it takes
+/// `O(n log n)` instead of `O(n²)` time for `n` frames. The positions of the
+/// selected atoms in all frames are kept in memory.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::msd;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XTCTrajectory::open_read("water.xtc")?;
+/// let oxygens: Vec<usize> = (0..3000).step_by(3).collect();
+/// let msd = msd(&mut trajectory, &[&oxygens], true)?;
+/// let d = msd.diffusion_coefficient(0, 10.0, 100.0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn msd(
+    trajectory: &mut impl Trajectory,
+    selections: &[&[usize]],
+    unwrap: bool,
+) -> Result<MeanSquaredDisplacement> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    let mut frame = Frame::with_len(num_atoms);
+    for selection in selections {
+        frame.check_indices(selection)?;
+    }
+    // position of every selected atom in every frame, by atom
+    let mut positions: BTreeMap<usize, Vec<[f64; 3]>> = selections
+        .iter()
+        .flat_map(|selection| selection.iter())
+        .map(|&i| (i, Vec::new()))
+        .collect();
+    let mut previous: Option<Frame> = None;
+    let mut times = Vec::new();
+    while read_next(trajectory, &mut frame)? {
+        for (&i, path) in positions.iter_mut() {
+            let position = match (&previous, path.last()) {
+                (Some(previous), Some(last)) if unwrap => {
+                    let jump = sub(frame.coords[i], previous.coords[i]);
+                    let jump = frame.box_vector.minimum_image(jump);
+                    [0, 1, 2].map(|k| last[k] + f64::from(jump[k]))
+                }
+                _ => frame.coords[i].map(f64::from),
+            };
+            path.push(position);
+        }
+        times.push(frame.time);
+        if unwrap {
+            match &mut previous {
+                Some(previous) => previous.clone_from(&frame),
+                None => previous = Some(frame.clone()),
+            }
+        }
+    }
+
+    let per_atom: BTreeMap<usize, Vec<f64>> = positions
+        .iter()
+        .map(|(&i, path)| (i, msd_fft(path)))
+        .collect();
+    let curves = selections
+        .iter()
+        .map(|selection| {
+            (0..times.len())
+                .map(|m| {
+                    if selection.is_empty() {
+                        return 0.0;
+                    }
+                    let sum: f64 = selection.iter().map(|i| per_atom[i][m]).sum();
+                    (sum / selection.len() as f64) as f32
+                })
+                .collect()
+        })
+        .collect();
+    Ok(MeanSquaredDisplacement {
+        lag_times: times.iter().map(|t| t - times[0]).collect(),
+        curves,
+    })
+}
+
+/// Mean squared displacement of a single path for every lag, averaged over
+/// all time origins: `MSD(m) = S1(m) - 2 S2(m)`, where `S2` is the
+/// autocorrelation of the positions
+fn msd_fft(path: &[[f64; 3]]) -> Vec<f64> {
+    let n = path.len();
+    let squared: Vec<f64> = path.iter().map(|r| r.iter().map(|x| x * x).sum()).collect();
+    let mut correlation = vec![0.0; n];
+    for k in 0..3 {
+        let x: Vec<f64> = path.iter().map(|r| r[k]).collect();
+        for (c, a) in correlation.iter_mut().zip(linalg::autocorrelation(&x)) {
+            *c += a;
+        }
+    }
+    let mut q = 2.0 * squared.iter().sum::<f64>();
+    (0..n)
+        .map(|m| {
+            if m > 0 {
+                q -= squared[m - 1] + squared[n - m];
+            }
+            (q - 2.0 * correlation[m]) / (n - m) as f64
+        })
+        .collect()
+}
+
+/// A radial distribution function computed by `rdf`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RadialDistribution {
+    /// Center of each bin in nm
+    pub radii: Vec<f32>,
+
+    /// g(r) of each bin
+    pub g: Vec<f32>,
+
+    /// Number of frames that were averaged
+    pub frames: usize,
+}
+
+/// Radial distribution function g(r) of the atoms of `group_b` around the
+/// atoms of `group_a` up to `r_max` (in nm) in `bins` bins, averaged over the
+/// remaining frames of `trajectory`, like `gmx rdf`.
+///
+/// Distances follow the minimum image convention of the (possibly
+/// triclinic) box, and g(r) is normalized by the density of the pairs in the
+/// box, so it approaches one for uncorrelated atoms. Atoms in both groups are
+/// not paired with themselves. Neighbors are searched with a
+/// `NeighborSearch`, so the time per frame grows linearly with the number
+/// of atoms.
+///
+/// Every frame needs a box, and `r_max` may be at most half of its smallest
+/// width, see `Error::CutoffTooLarge`.
+pub fn rdf(
+    trajectory: &mut impl Trajectory,
+    group_a: &[usize],
+    group_b: &[usize],
+    r_max: f32,
+    bins: usize,
+) -> Result<RadialDistribution> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    frame.check_indices(group_a)?;
+    frame.check_indices(group_b)?;
+    if bins == 0 {
+        return Err(Error::InvalidArgument(
+            "radial distribution functions need at least one bin",
+        ));
+    }
+    if r_max.is_nan() || r_max <= 0.0 {
+        return Err(Error::InvalidArgument(
+            "radial distribution functions need a positive maximum radius",
+        ));
+    }
+    let dr = r_max / bins as f32;
+    let mut in_b = vec![false; frame.len()];
+    for &j in group_b {
+        in_b[j] = true;
+    }
+    let shared = group_a.iter().filter(|&&i| in_b[i]).count();
+    let pairs = (group_a.len() * group_b.len() - shared) as f64;
+    let mut counts = vec![0usize; bins];
+    // sum of the histogram of each frame divided by its pair density
+    let mut normalized = vec![0.0; bins];
+    let mut frames = 0;
+    while read_next(trajectory, &mut frame)? {
+        let volume = f64::from(frame.box_vector.volume());
+        if volume == 0.0 {
+            return Err(Error::Unsupported(
+                "radial distribution functions of frames without a box",
+            ));
+        }
+        counts.iter_mut().for_each(|count| *count = 0);
+        let search = NeighborSearch::new(&frame, Some(group_b), r_max)?;
+        for &i in group_a {
+            search.for_each_within(frame.coords[i], r_max, |j, r| {
+                if i != j && r < r_max {
+                    counts[((r / dr) as usize).min(bins - 1)] += 1;
+                }
+            });
+        }
+        for (sum, count) in normalized.iter_mut().zip(&counts) {
+            *sum += *count as f64 * volume / pairs;
+        }
+        frames += 1;
+    }
+
+    let shell = |bin: usize| {
+        let (inner, outer) = (f64::from(dr) * bin as f64, f64::from(dr) * (bin + 1) as f64);
+        4.0 / 3.0 * std::f64::consts::PI * (outer.powi(3) - inner.powi(3))
+    };
+    Ok(RadialDistribution {
+        radii: (0..bins).map(|bin| (bin as f32 + 0.5) * dr).collect(),
+        g: normalized
+            .iter()
+            .enumerate()
+            .map(|(bin, sum)| {
+                if frames == 0 || pairs == 0.0 {
+                    0.0
+                } else {
+                    (sum / frames as f64 / shell(bin)) as f32
+                }
+            })
+            .collect(),
+        frames,
+    })
+}
+
+/// A box axis, like the one along which `density_profile` bins atoms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Conversion of atomic mass units per nm³ to kg/m³
+const AMU_PER_NM3_IN_KG_PER_M3: f64 = 1.660_539;
+
+/// A density profile computed by `density_profile`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DensityProfile {
+    /// Center of each bin in nm from the lower face of the box, for the
+    /// average box width along the axis
+    pub positions: Vec<f32>,
+
+    /// Average density of each bin, in kg/m³ for mass densities and in
+    /// atoms/nm³ for number densities
+    pub density: Vec<f32>,
+
+    /// Number of frames that were averaged
+    pub frames: usize,
+}
+
+/// Density of the atoms in `selection` along `axis` in `bins` slabs,
+/// averaged over the remaining frames of `trajectory`, like `gmx density`.
+/// With `masses` (one per atom, in atomic mass units) this is the mass
+/// density in kg/m³, otherwise the number density in atoms/nm³.
+///
+/// The slabs divide the box of each frame into `bins` equal parts between
+/// the faces of the box perpendicular to `axis`, so their volume follows the
+/// box if it fluctuates, and atoms outside the box are wrapped into it.
+/// Every frame needs a box.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::{density_profile, Axis};
+///
+/// # fn main() -> Result<()> {
+/// let topology = Topology::read_top("topol.top")?;
+/// let mut trajectory = XTCTrajectory::open_read("membrane.xtc")?;
+/// let all: Vec<usize> = (0..topology.len()).collect();
+/// let masses = topology.masses();
+/// let profile = density_profile(&mut trajectory, &all, Axis::Z, 100, Some(&masses))?;
+/// for (z, rho) in profile.positions.iter().zip(&profile.density) {
+///     println!("{:8.3} {:10.3}", z, rho);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn density_profile(
+    trajectory: &mut impl Trajectory,
+    selection: &[usize],
+    axis: Axis,
+    bins: usize,
+    masses: Option<&[f32]>,
+) -> Result<DensityProfile> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    frame.check_indices(selection)?;
+    if let Some(masses) = masses {
+        frame.check_masses(masses)?;
+    }
+    if bins == 0 {
+        return Err(Error::Unsupported("density profiles without bins"));
+    }
+    let k = axis as usize;
+    let mut density = vec![0.0; bins];
+    let mut width = 0.0;
+    let mut frames = 0;
+    while read_next(trajectory, &mut frame)? {
+        let volume = f64::from(frame.box_vector.volume());
+        if volume == 0.0 {
+            return Err(Error::Unsupported(
+                "density profiles of frames without a box",
+            ));
+        }
+        let slab = volume / bins as f64;
+        for &i in selection {
+            let s = frame.box_vector.fractional(frame.coords[i])[k].rem_euclid(1.0);
+            let bin = ((s * bins as f64) as usize).min(bins - 1);
+            density[bin] += masses.map_or(1.0, |m| f64::from(m[i])) / slab;
+        }
+        width += frame.box_vector.face_distances()[k];
+        frames += 1;
+    }
+
+    let scale = match masses {
+        Some(_) => AMU_PER_NM3_IN_KG_PER_M3,
+        None => 1.0,
+    } / frames.max(1) as f64;
+    let width = width / frames.max(1) as f64;
+    Ok(DensityProfile {
+        positions: (0..bins)
+            .map(|bin| ((bin as f64 + 0.5) * width / bins as f64) as f32)
+            .collect(),
+        density: density.iter().map(|rho| (rho * scale) as f32).collect(),
+        frames,
+    })
+}
+
+/// Order parameters along lipid tails computed by `order_parameters`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderParameters {
+    /// Position of each carbon along the chains, as an index into the chain
+    /// definitions. The first and last carbon have no order parameter.
+    pub carbons: Vec<usize>,
+
+    /// Average `P2 = (3 cos²θ - 1) / 2` of the angle θ between the membrane
+    /// normal and the vector between the two neighbors of each carbon
+    pub p2: Vec<f32>,
+
+    /// Deuterium order parameter `S_CD = -P2 / 2` of each carbon, as
+    /// estimated for united-atom chains by `gmx order`
+    pub scd: Vec<f32>,
+
+    /// Number of frames that were averaged
+    pub frames: usize,
+}
+
+/// Order parameters of every carbon of lipid tails with respect to the
+/// membrane normal along `normal`, averaged over all chains and the
+/// remaining frames of `trajectory`.
+///
+/// Each chain definition lists the carbon atoms of one tail in order, and
+/// all chains must have the same number of carbons, e.g. the sn-1 tails of
+/// all lipids. The orientation of a carbon is given by the vector between
+/// its two neighbors in the chain, which works for united-atom force fields
+/// that have no explicit hydrogens. Periodic boundaries are taken into
+/// account if the frames have a box.
+pub fn order_parameters(
+    trajectory: &mut impl Trajectory,
+    chain_definitions: &[Vec<usize>],
+    normal: Axis,
+) -> Result<OrderParameters> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    let length = chain_definitions.first().map_or(0, Vec::len);
+    for chain in chain_definitions {
+        frame.check_indices(chain)?;
+        if chain.len() != length {
+            return Err(Error::LengthMismatch {
+                name: "chain",
+                expected: length,
+                found: chain.len(),
+            });
+        }
+    }
+    let carbons: Vec<usize> = (1..length.saturating_sub(1)).collect();
+    let k = normal as usize;
+    let mut sums = vec![0.0; carbons.len()];
+    let mut frames = 0;
+    while read_next(trajectory, &mut frame)? {
+        for chain in chain_definitions {
+            for (sum, &c) in sums.iter_mut().zip(&carbons) {
+                let d = sub(frame.coords[chain[c + 1]], frame.coords[chain[c - 1]]);
+                let d = frame.box_vector.minimum_image(d).map(f64::from);
+                let squared = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+                if squared > 0.0 {
+                    *sum += 1.5 * d[k] * d[k] / squared - 0.5;
+                }
+            }
+        }
+        frames += 1;
+    }
+
+    let count = (frames * chain_definitions.len()).max(1) as f64;
+    let p2: Vec<f32> = sums.iter().map(|sum| (sum / count) as f32).collect();
+    Ok(OrderParameters {
+        carbons,
+        scd: p2.iter().map(|p2| -0.5 * p2).collect(),
+        p2,
+        frames,
+    })
+}
+
+/// Which atoms of two groups are in contact in each frame, computed by
+/// `contact_map`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContactMap {
+    /// Number of atoms of the first group, i.e. rows of each matrix
+    pub rows: usize,
+
+    /// Number of atoms of the second group, i.e. columns of each matrix
+    pub columns: usize,
+
+    /// One row major `rows x columns` matrix per frame that is true where
+    /// the atoms are in contact
+    pub frames: Vec<Vec<bool>>,
+
+    /// Number of frames in which each pair is in contact, row major
+    pub counts: Vec<usize>,
+}
+
+impl ContactMap {
+    /// Whether atom `row` of the first group and atom `column` of the second
+    /// group are in contact in `frame`
+    pub fn in_contact(&self, frame: usize, row: usize, column: usize) -> bool {
+        self.frames[frame][row * self.columns + column]
+    }
+
+    /// Fraction of the frames in which each pair is in contact, row major
+    pub fn frequencies(&self) -> Vec<f32> {
+        self.counts
+            .iter()
+            .map(|&count| {
+                if self.frames.is_empty() {
+                    0.0
+                } else {
+                    count as f32 / self.frames.len() as f32
+                }
+            })
+            .collect()
+    }
+}
+
+/// Find the pairs of atoms of `group_a` and `group_b` that are within
+/// `cutoff` nm of each other in every remaining frame of `trajectory`, with
+/// periodic boundaries taken into account if the frames have a box. An atom
+/// in both groups is not in contact with itself. Pairs are found with a
+/// `NeighborSearch`, whose limits apply to `cutoff`.
+pub fn contact_map(
+    trajectory: &mut impl Trajectory,
+    group_a: &[usize],
+    group_b: &[usize],
+    cutoff: f32,
+) -> Result<ContactMap> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    frame.check_indices(group_a)?;
+    frame.check_indices(group_b)?;
+    let columns: HashMap<usize, usize> = group_b
+        .iter()
+        .enumerate()
+        .map(|(column, &j)| (j, column))
+        .collect();
+    let mut map = ContactMap {
+        rows: group_a.len(),
+        columns: group_b.len(),
+        frames: Vec::new(),
+        counts: vec![0; group_a.len() * group_b.len()],
+    };
+    while read_next(trajectory, &mut frame)? {
+        let mut contacts = vec![false; map.counts.len()];
+        let search = NeighborSearch::new(&frame, Some(group_b), cutoff)?;
+        for (row, &i) in group_a.iter().enumerate() {
+            search.for_each_within(frame.coords[i], cutoff, |j, _| {
+                if i != j {
+                    contacts[row * map.columns + columns[&j]] = true;
+                }
+            });
+        }
+        for (count, &contact) in map.counts.iter_mut().zip(&contacts) {
+            *count += usize::from(contact);
+        }
+        map.frames.push(contacts);
+    }
+    Ok(map)
+}
+
+/// The closest pair of atoms of two groups in a frame, computed by
+/// `min_distance_series`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimumDistance {
+    /// Time of the frame
+    pub time: f32,
+
+    /// Distance between the closest atoms
+    pub distance: f32,
+
+    /// Indices of the closest atoms, the first one from the first group
+    pub pair: (usize, usize),
+}
+
+/// Cutoff of the first neighbor search of `min_distance_series`
+const MIN_DISTANCE_INITIAL_CUTOFF: f32 = 0.5;
+
+/// How often `min_distance_series` doubles the cutoff of a frame before it
+/// compares all pairs, which stops it at coordinates that are never within
+/// any cutoff, like NaN
+const MIN_DISTANCE_MAX_DOUBLINGS: u32 = 40;
+
+/// Minimum distance between `group_a` and `group_b` and the closest pair of
+/// atoms in every remaining frame of `trajectory`, like `gmx mindist`, with
+/// periodic boundaries taken into account if the frames have a box. An atom
+/// in both groups is not paired with itself.
+///
+/// The closest pair is found with a `NeighborSearch` whose cutoff starts at
+/// the distance of the previous frame and grows until a pair is found. Only
+/// if the groups are further apart than half the box, or no pair is found
+/// within a very large cutoff, are all pairs compared.
+pub fn min_distance_series(
+    trajectory: &mut impl Trajectory,
+    group_a: &[usize],
+    group_b: &[usize],
+) -> Result<Vec<MinimumDistance>> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    frame.check_indices(group_a)?;
+    frame.check_indices(group_b)?;
+    if !group_a.iter().any(|&i| group_b.iter().any(|&j| i != j)) {
+        return Err(Error::Unsupported(
+            "minimum distances between groups without a pair of atoms",
+        ));
+    }
+    let mut series = Vec::new();
+    let mut cutoff = MIN_DISTANCE_INITIAL_CUTOFF;
+    while read_next(trajectory, &mut frame)? {
+        let mut doublings = 0;
+        let closest = loop {
+            let search = match NeighborSearch::new(&frame, Some(group_b), cutoff) {
+                Ok(search) => search,
+                Err(Error::CutoffTooLarge { .. }) => break closest_pair(&frame, group_a, group_b),
+                Err(e) => return Err(e),
+            };
+            let mut closest: Option<(f32, (usize, usize))> = None;
+            for &i in group_a {
+                search.for_each_within(frame.coords[i], cutoff, |j, r| {
+                    if i != j && !matches!(closest, Some((min, _)) if r >= min) {
+                        closest = Some((r, (i, j)));
+                    }
+                });
+            }
+            match closest {
+                Some(closest) => break closest,
+                None if doublings == MIN_DISTANCE_MAX_DOUBLINGS => {
+                    break closest_pair(&frame, group_a, group_b)
+                }
+                None => {
+                    cutoff *= 2.0;
+                    doublings += 1;
+                }
+            }
+        };
+        let (distance, pair) = closest;
+        series.push(MinimumDistance {
+            time: frame.time,
+            distance,
+            pair,
+        });
+        // a little more than the last distance is likely to find a pair
+        cutoff = if distance.is_finite() {
+            (1.1 * distance).max(MIN_DISTANCE_INITIAL_CUTOFF / 100.0)
+        } else {
+            MIN_DISTANCE_INITIAL_CUTOFF
+        };
+    }
+    Ok(series)
+}
+
+/// The closest pair of distinct atoms of two groups by comparing all pairs,
+/// under the minimum image convention
+fn closest_pair(frame: &Frame, group_a: &[usize], group_b: &[usize]) -> (f32, (usize, usize)) {
+    let mut closest = (f32::INFINITY, (group_a[0], group_b[0]));
+    for &i in group_a {
+        for &j in group_b {
+            let d = frame
+                .box_vector
+                .minimum_image(sub(frame.coords[j], frame.coords[i]));
+            let r = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+            if i != j && r < closest.0 {
+                closest = (r, (i, j));
+            }
+        }
+    }
+    closest
+}
+
+/// A hydrogen bond between a donor with a hydrogen and an acceptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HydrogenBond {
+    pub donor: usize,
+    pub hydrogen: usize,
+    pub acceptor: usize,
+}
+
+/// The hydrogen bonds in each frame, computed by `hydrogen_bonds`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HydrogenBonds {
+    /// The hydrogen bonds of each frame, sorted
+    pub frames: Vec<Vec<HydrogenBond>>,
+}
+
+impl HydrogenBonds {
+    /// Number of hydrogen bonds in each frame
+    pub fn counts(&self) -> Vec<usize> {
+        self.frames.iter().map(Vec::len).collect()
+    }
+
+    /// Every hydrogen bond that was found and the fraction of the frames in
+    /// which it exists, most persistent first
+    pub fn occupancy(&self) -> Vec<(HydrogenBond, f32)> {
+        let mut counts: HashMap<HydrogenBond, usize> = HashMap::new();
+        for bond in self.frames.iter().flatten() {
+            *counts.entry(*bond).or_insert(0) += 1;
+        }
+        let mut occupancy: Vec<(HydrogenBond, f32)> = counts
+            .into_iter()
+            .map(|(bond, count)| (bond, count as f32 / self.frames.len() as f32))
+            .collect();
+        occupancy.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        occupancy
+    }
+}
+
+/// Find the hydrogen bonds between `donors`, given as pairs of a donor atom
+/// and a hydrogen bonded to it, and `acceptors` in every remaining frame of
+/// `trajectory`, with the geometric criterion of `gmx hbond`: the donor and
+/// acceptor are at most `dist_cutoff` nm apart (0.35 by default in
+/// GROMACS), and the angle between hydrogen, donor and acceptor is at most
+/// `angle_cutoff` degrees (30 by default). Periodic boundaries are taken
+/// into account if the frames have a box.
+///
+/// Trajectories do not store bonds, see `Topology::hydrogen_bond_donors` and
+/// `Topology::hydrogen_bond_acceptors` to find donors and acceptors.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::hydrogen_bonds;
+///
+/// # fn main() -> Result<()> {
+/// let topology = Topology::read_top("topol.top")?;
+/// let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+/// let mut first = Frame::with_len(trajectory.get_num_atoms()?);
+/// XTCTrajectory::open_read("traj.xtc")?.read(&mut first)?;
+/// let donors = topology.hydrogen_bond_donors(&first)?;
+/// let acceptors = topology.hydrogen_bond_acceptors();
+/// let bonds = hydrogen_bonds(&mut trajectory, &donors, &acceptors, 0.35, 30.0)?;
+/// for (bond, occupancy) in bonds.occupancy().iter().take(10) {
+///     println!("{:?}: {:.0}%", bond, 100.0 * occupancy);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn hydrogen_bonds(
+    trajectory: &mut impl Trajectory,
+    donors: &[(usize, usize)],
+    acceptors: &[usize],
+    dist_cutoff: f32,
+    angle_cutoff: f32,
+) -> Result<HydrogenBonds> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    for &(donor, hydrogen) in donors {
+        frame.check_indices(&[donor, hydrogen])?;
+    }
+    frame.check_indices(acceptors)?;
+    let mut bonds = HydrogenBonds::default();
+    while read_next(trajectory, &mut frame)? {
+        let search = NeighborSearch::new(&frame, Some(acceptors), dist_cutoff)?;
+        let mut found = Vec::new();
+        for &(donor, hydrogen) in donors {
+            let mut candidates = Vec::new();
+            search.for_each_within(frame.coords[donor], dist_cutoff, |acceptor, _| {
+                if acceptor != donor {
+                    candidates.push(acceptor);
+                }
+            });
+            for acceptor in candidates {
+                if frame.angle_pbc(hydrogen, donor, acceptor)? <= angle_cutoff {
+                    found.push(HydrogenBond {
+                        donor,
+                        hydrogen,
+                        acceptor,
+                    });
+                }
+            }
+        }
+        found.sort_unstable();
+        bonds.frames.push(found);
+    }
+    Ok(bonds)
+}
+
+impl Topology {
+    /// Indices of the nitrogen and oxygen atoms, which are the usual
+    /// hydrogen bond acceptors
+    pub fn hydrogen_bond_acceptors(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| matches!(self.atoms[i].element_or_guess(), "N" | "O"))
+            .collect()
+    }
+
+    /// Pairs of nitrogen or oxygen atoms and the hydrogens bonded to them,
+    /// which are the usual hydrogen bond donors. Topologies do not store
+    /// bonds, so hydrogens are assigned to the nearest nitrogen or oxygen
+    /// atom within 0.12 nm in `frame`.
+    pub fn hydrogen_bond_donors(&self, frame: &Frame) -> Result<Vec<(usize, usize)>> {
+        if frame.len() != self.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.len(),
+                found: frame.len(),
+            });
+        }
+        let heavy = self.hydrogen_bond_acceptors();
+        let search = NeighborSearch::new(frame, Some(&heavy), 0.12)?;
+        let mut donors = Vec::new();
+        for hydrogen in (0..self.len()).filter(|&i| self.atoms[i].element_or_guess() == "H") {
+            let nearest = search
+                .neighbors_of(hydrogen, 0.12)?
+                .into_iter()
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((donor, _)) = nearest {
+                donors.push((donor, hydrogen));
+            }
+        }
+        donors.sort_unstable();
+        Ok(donors)
+    }
+}
+
+/// The RMSD between two frames, as a distance for `cluster`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rmsd {
+    /// Atoms the RMSD is computed from, all atoms if `None`
+    pub mask: Option<Vec<usize>>,
+
+    /// Whether frames are fitted onto each other before the RMSD is
+    /// computed, see `Frame::superpose_onto`
+    pub fit: bool,
+}
+
+impl Rmsd {
+    fn distance(&self, a: &Frame, b: &Frame) -> Result<f32> {
+        if self.fit {
+            let mut fitted = a.clone();
+            fitted.superpose_onto(b, None)?;
+            fitted.rmsd_with(b, None, false)
+        } else {
+            a.rmsd_with(b, None, false)
+        }
+    }
+}
+
+/// A group of similar frames found by `cluster`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cluster {
+    /// The frame with the most neighbors, which represents the cluster
+    pub centroid: usize,
+
+    /// Indices of the frames in the cluster in ascending order, including
+    /// the centroid
+    pub members: Vec<usize>,
+}
+
+impl Cluster {
+    /// Number of frames in the cluster
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+/// The result of `cluster`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clustering {
+    /// Time of each frame
+    pub times: Vec<f32>,
+
+    /// Cluster of each frame, as an index into `clusters`
+    pub assignments: Vec<usize>,
+
+    /// The clusters from largest to smallest
+    pub clusters: Vec<Cluster>,
+}
+
+/// Cluster the remaining frames of `trajectory` with the GROMOS algorithm
+/// (Daura et al. 1999), like `gmx cluster -method gromos`: two frames are
+/// neighbors if their distance by `metric` is at most `cutoff` nm. The frame
+/// with the most neighbors forms a cluster with all of its neighbors, these
+/// frames are removed, and this repeats until every frame is in a cluster.
+///
+/// Only the atoms of `metric.mask` are kept for each frame, and only the
+/// pairs of neighbors instead of the full distance matrix, so memory use
+/// grows with the number of neighbors rather than the number of pairs. The
+/// time still grows quadratically with the number of frames.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::{cluster, Rmsd};
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+/// let metric = Rmsd {
+///     mask: Some((0..100).collect()),
+///     fit: true,
+/// };
+/// let clustering = cluster(&mut trajectory, &metric, 0.15)?;
+/// for cluster in &clustering.clusters {
+///     println!("{} frames around frame {}", cluster.size(), cluster.centroid);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn cluster(trajectory: &mut impl Trajectory, metric: &Rmsd, cutoff: f32) -> Result<Clustering> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    if let Some(mask) = &metric.mask {
+        frame.check_indices(mask)?;
+    }
+    let mut frames = Vec::new();
+    let mut times = Vec::new();
+    while read_next(trajectory, &mut frame)? {
+        times.push(frame.time);
+        frames.push(match &metric.mask {
+            Some(mask) => frame.subset(mask)?,
+            None => frame.clone(),
+        });
+    }
+
+    let mut neighbors = vec![Vec::new(); frames.len()];
+    for i in 0..frames.len() {
+        for j in i + 1..frames.len() {
+            if metric.distance(&frames[i], &frames[j])? <= cutoff {
+                neighbors[i].push(j);
+                neighbors[j].push(i);
+            }
+        }
+    }
+
+    const UNASSIGNED: usize = usize::MAX;
+    let mut assignments = vec![UNASSIGNED; frames.len()];
+    let mut clusters = Vec::new();
+    loop {
+        let remaining = |i: usize| assignments[i] == UNASSIGNED;
+        // the first frame with the most remaining neighbors
+        let centroid = (0..frames.len())
+            .filter(|&i| remaining(i))
+            .map(|i| (neighbors[i].iter().filter(|&&j| remaining(j)).count(), i))
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        let centroid = match centroid {
+            Some((_, centroid)) => centroid,
+            None => break,
+        };
+        let mut members: Vec<usize> = neighbors[centroid]
+            .iter()
+            .copied()
+            .filter(|&j| remaining(j))
+            .collect();
+        members.push(centroid);
+        members.sort_unstable();
+        for &i in &members {
+            assignments[i] = clusters.len();
+        }
+        clusters.push(Cluster { centroid, members });
+    }
+    Ok(Clustering {
+        times,
+        assignments,
+        clusters,
+    })
+}
+
+/// Principal components of the atom positions computed by `pca`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrincipalComponents {
+    /// Indices of the atoms in the analysis
+    pub atoms: Vec<usize>,
+
+    /// Average position of each atom
+    pub mean: Vec<[f32; 3]>,
+
+    /// Variance along each component in nm², in descending order
+    pub eigenvalues: Vec<f32>,
+
+    /// The components as unit vectors of `3 * atoms.len()` entries
+    /// (x, y and z of each atom in turn), matching `eigenvalues`
+    pub eigenvectors: Vec<Vec<f32>>,
+
+    /// Number of frames that were accumulated
+    pub frames: usize,
+}
+
+impl PrincipalComponents {
+    /// Fraction of the total variance along each component
+    pub fn explained_variance(&self) -> Vec<f32> {
+        let total: f32 = self.eigenvalues.iter().map(|value| value.max(0.0)).sum();
+        self.eigenvalues
+            .iter()
+            .map(|value| {
+                if total > 0.0 {
+                    value.max(0.0) / total
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Coordinates of `frame` along the first `components` principal
+    /// components, i.e. its projections onto them relative to the mean
+    /// structure. The frame should be fitted like the analyzed frames.
+    pub fn project(&self, frame: &Frame, components: usize) -> Result<Vec<f32>> {
+        frame.check_indices(&self.atoms)?;
+        let displacement: Vec<f64> = self
+            .atoms
+            .iter()
+            .zip(&self.mean)
+            .flat_map(|(&i, mean)| (0..3).map(move |k| f64::from(frame.coords[i][k] - mean[k])))
+            .collect();
+        Ok(self
+            .eigenvectors
+            .iter()
+            .take(components)
+            .map(|vector| {
+                let projection: f64 = vector
+                    .iter()
+                    .zip(&displacement)
+                    .map(|(v, d)| f64::from(*v) * d)
+                    .sum();
+                projection as f32
+            })
+            .collect())
+    }
+}
+
+/// Principal component analysis of the positions of the atoms in `mask` (or
+/// all atoms if `None`) over the remaining frames of `trajectory`, like
+/// `gmx covar`. The covariance matrix of the coordinates is accumulated in a
+/// single pass and then diagonalized; use `PrincipalComponents::project` to
+/// follow frames along the components, like `gmx anaeig`.
+///
+/// The frames are not fitted, so overall rotation and translation should be
+/// removed beforehand, e.g. with `transform::fit_trajectory`. The covariance
+/// matrix has `(3 * atoms)²` entries and is diagonalized with the Jacobi
+/// method, so this is meant for up to a few hundred atoms, such as the
+/// C-alpha atoms of a protein.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::pca;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XTCTrajectory::open_read("fitted.xtc")?;
+/// let backbone: Vec<usize> = (0..300).collect();
+/// let components = pca(&mut trajectory, Some(&backbone))?;
+/// println!("{:?}", &components.explained_variance()[..5]);
+/// for frame in XTCTrajectory::open_read("fitted.xtc")?.into_iter() {
+///     println!("{:?}", components.project(&*frame?, 2)?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn pca(
+    trajectory: &mut impl Trajectory,
+    mask: Option<&[usize]>,
+) -> Result<PrincipalComponents> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    let atoms: Vec<usize> = match mask {
+        Some(mask) => {
+            frame.check_indices(mask)?;
+            mask.to_vec()
+        }
+        None => (0..frame.len()).collect(),
+    };
+    let n = 3 * atoms.len();
+    let mut mean = vec![0.0; n];
+    // sum of the products of the deviations from the mean (Welford)
+    let mut comoments = vec![0.0; n * n];
+    let mut x = vec![0.0; n];
+    let mut before = vec![0.0; n];
+    let mut frames = 0;
+    while read_next(trajectory, &mut frame)? {
+        frames += 1;
+        for (a, &i) in atoms.iter().enumerate() {
+            for k in 0..3 {
+                x[3 * a + k] = f64::from(frame.coords[i][k]);
+            }
+        }
+        for p in 0..n {
+            before[p] = x[p] - mean[p];
+            mean[p] += before[p] / frames as f64;
+        }
+        for p in 0..n {
+            let after = x[p] - mean[p];
+            for (comoment, b) in comoments[p * n..(p + 1) * n].iter_mut().zip(&before) {
+                *comoment += after * b;
+            }
+        }
+    }
+
+    let covariance: Vec<f64> = comoments.iter().map(|c| c / frames.max(1) as f64).collect();
+    let (values, vectors) = linalg::symmetric_eigen(&covariance, n);
+    Ok(PrincipalComponents {
+        mean: mean
+            .chunks(3)
+            .map(|x| [x[0] as f32, x[1] as f32, x[2] as f32])
+            .collect(),
+        atoms,
+        eigenvalues: values.iter().map(|&value| value as f32).collect(),
+        eigenvectors: (0..n)
+            .map(|column| (0..n).map(|row| vectors[row * n + column] as f32).collect())
+            .collect(),
+        frames,
+    })
+}
+
+/// Autocorrelation function `C(m)` of `series` for every lag `m` from 0 to
+/// `series.len() - 1`, i.e. the average of `x[k] * x[k + m]` over all `k`.
+/// If `normalize` is true, the function is divided by `C(0)` so that it
+/// starts at one.
+///
+/// With `fft` the sums are computed with a fast Fourier transform in
+/// `O(n log n)` time, otherwise directly in `O(n²)`, which is exact but only
+/// practical for short series. Both agree up to rounding errors.
+///
+/// ```
+/// use xdrfile::analysis::autocorrelation;
+///
+/// let acf = autocorrelation(&[1.0, -1.0, 1.0, -1.0], true, true);
+/// assert!((acf[1] + 1.0).abs() < 1e-6);
+/// ```
+pub fn autocorrelation(series: &[f32], normalize: bool, fft: bool) -> Vec<f32> {
+    let x: Vec<f64> = series.iter().map(|&x| f64::from(x)).collect();
+    let acf = averaged_autocorrelation(&x, fft);
+    scale_autocorrelation(&acf, normalize)
+}
+
+/// Autocorrelation sums of `x` divided by the number of terms of each lag
+fn averaged_autocorrelation(x: &[f64], fft: bool) -> Vec<f64> {
+    let n = x.len();
+    let sums = if fft {
+        linalg::autocorrelation(x)
+    } else {
+        (0..n)
+            .map(|m| (0..n - m).map(|k| x[k] * x[k + m]).sum())
+            .collect()
+    };
+    sums.iter()
+        .enumerate()
+        .map(|(m, sum)| sum / (n - m) as f64)
+        .collect()
+}
+
+fn scale_autocorrelation(acf: &[f64], normalize: bool) -> Vec<f32> {
+    let scale = match acf.first() {
+        Some(&c0) if normalize && c0 != 0.0 => 1.0 / c0,
+        _ => 1.0,
+    };
+    acf.iter().map(|c| (c * scale) as f32).collect()
+}
+
+/// Velocity autocorrelation functions computed by `velocity_autocorrelation`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VelocityAutocorrelation {
+    /// Time lag of each point in ps, assuming equally spaced frames
+    pub lag_times: Vec<f32>,
+
+    /// Indices of the atoms
+    pub atoms: Vec<usize>,
+
+    /// Autocorrelation function of the velocity of each atom
+    pub per_atom: Vec<Vec<f32>>,
+}
+
+impl VelocityAutocorrelation {
+    /// Average autocorrelation function of all atoms
+    pub fn average(&self) -> Vec<f32> {
+        let mut average = vec![0.0; self.lag_times.len()];
+        for acf in &self.per_atom {
+            for (sum, c) in average.iter_mut().zip(acf) {
+                *sum += c / self.per_atom.len() as f32;
+            }
+        }
+        average
+    }
+}
+
+/// Autocorrelation function `<v(t) · v(t + τ)>` of the velocity of each atom
+/// in `atoms` (or all atoms if `None`) over the remaining frames of
+/// `trajectory`, like `gmx velacc`. The velocities are read from the frames,
+/// so this needs a TRR file written with velocities in every frame;
+/// `normalize` and `fft` are used like in `autocorrelation`.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::velocity_autocorrelation;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = TRRTrajectory::open_read("traj.trr")?;
+/// let vacf = velocity_autocorrelation(&mut trajectory, None, true, true)?;
+/// for (tau, c) in vacf.lag_times.iter().zip(vacf.average()) {
+///     println!("{} {}", tau, c);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn velocity_autocorrelation(
+    trajectory: &mut impl Trajectory,
+    atoms: Option<&[usize]>,
+    normalize: bool,
+    fft: bool,
+) -> Result<VelocityAutocorrelation> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    let atoms: Vec<usize> = match atoms {
+        Some(atoms) => {
+            frame.check_indices(atoms)?;
+            atoms.to_vec()
+        }
+        None => (0..frame.len()).collect(),
+    };
+    // x, y and z of the velocity of each atom over time
+    let mut components = vec![Vec::new(); 3 * atoms.len()];
+    let mut times = Vec::new();
+    while read_next(trajectory, &mut frame)? {
+        let velocities = frame.velocities.as_ref().ok_or(Error::Unsupported(
+            "velocity autocorrelations of frames without velocities",
+        ))?;
+        for (a, &i) in atoms.iter().enumerate() {
+            for k in 0..3 {
+                components[3 * a + k].push(f64::from(velocities[i][k]));
+            }
+        }
+        times.push(frame.time);
+    }
+
+    let per_atom = components
+        .chunks(3)
+        .map(|xyz| {
+            let mut acf = vec![0.0; times.len()];
+            for x in xyz {
+                for (sum, c) in acf.iter_mut().zip(averaged_autocorrelation(x, fft)) {
+                    *sum += c;
+                }
+            }
+            scale_autocorrelation(&acf, normalize)
+        })
+        .collect();
+    let dt = if times.len() > 1 {
+        times[1] - times[0]
+    } else {
+        0.0
+    };
+    Ok(VelocityAutocorrelation {
+        lag_times: (0..times.len()).map(|m| m as f32 * dt).collect(),
+        atoms,
+        per_atom,
+    })
+}
+
+/// Velocity autocorrelation function `<v(t) · v(t + τ)>` in nm²/ps² averaged
+/// over the atoms in `mask` (or all atoms if `None`) and the remaining frames
+/// of `trajectory`, as pairs of time lag and correlation. This needs the
+/// velocities of a TRR file, see `velocity_autocorrelation`, and is the
+/// input of `power_spectrum`.
+pub fn vacf(trajectory: &mut impl Trajectory, mask: Option<&[usize]>) -> Result<Vec<(f32, f32)>> {
+    let acf = velocity_autocorrelation(trajectory, mask, false, true)?;
+    Ok(acf.lag_times.iter().copied().zip(acf.average()).collect())
+}
+
+/// Speed of light in cm/ps
+const SPEED_OF_LIGHT: f32 = 0.029_979_246;
+
+/// A spectrum computed by `power_spectrum`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PowerSpectrum {
+    /// Frequency of each point in 1/ps (THz)
+    pub frequencies: Vec<f32>,
+
+    /// Spectral density at each frequency, in the unit of the
+    /// autocorrelation function times ps
+    pub intensities: Vec<f32>,
+}
+
+impl PowerSpectrum {
+    /// Frequencies as wavenumbers in 1/cm, like `gmx velacc -os` prints them
+    pub fn wavenumbers(&self) -> Vec<f32> {
+        self.frequencies
+            .iter()
+            .map(|frequency| frequency / SPEED_OF_LIGHT)
+            .collect()
+    }
+}
+
+/// Power spectrum of a velocity autocorrelation function `vacf` sampled every
+/// `dt` ps, i.e. its Fourier transform, which is the vibrational density of
+/// states. The spectrum is computed with an FFT from zero up to the Nyquist
+/// frequency `1 / (2 dt)`, with a resolution of at least
+/// `1 / (2 * vacf.len() * dt)`.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::{power_spectrum, vacf};
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = TRRTrajectory::open_read("traj.trr")?;
+/// let correlation = vacf(&mut trajectory, None)?;
+/// let dt = correlation[1].0;
+/// let values: Vec<f32> = correlation.iter().map(|&(_, c)| c).collect();
+/// let spectrum = power_spectrum(&values, dt);
+/// for (wavenumber, intensity) in spectrum.wavenumbers().iter().zip(&spectrum.intensities) {
+///     println!("{} {}", wavenumber, intensity);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn power_spectrum(vacf: &[f32], dt: f32) -> PowerSpectrum {
+    let x: Vec<f64> = vacf.iter().map(|&c| f64::from(c)).collect();
+    let transform = linalg::cosine_transform(&x);
+    let size = 2 * transform.len().saturating_sub(1);
+    PowerSpectrum {
+        frequencies: (0..transform.len())
+            .map(|k| k as f32 / (size as f32 * dt))
+            .collect(),
+        intensities: transform
+            .iter()
+            .map(|value| (value * f64::from(dt)) as f32)
+            .collect(),
+    }
+}
+
+/// Boltzmann constant in kJ/(mol K)
+const BOLTZMANN: f64 = 0.008_314_462_618;
+
+/// Temperatures computed by `kinetic_temperature`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KineticTemperature {
+    /// Time and instantaneous temperature in K of each frame
+    pub series: Vec<(f32, f32)>,
+}
+
+impl KineticTemperature {
+    /// Average temperature over all frames, `None` without frames
+    pub fn average(&self) -> Option<f32> {
+        if self.series.is_empty() {
+            return None;
+        }
+        let sum: f64 = self.series.iter().map(|&(_, t)| f64::from(t)).sum();
+        Some((sum / self.series.len() as f64) as f32)
+    }
+}
+
+/// Instantaneous temperature `T = 2 E_kin / (ndf k_B)` of every remaining
+/// frame of `trajectory` from the velocities of a TRR file and `masses` (one
+/// per atom, in atomic mass units), like the temperature GROMACS writes to
+/// its energy file.
+///
+/// `ndf` is the number of degrees of freedom, which `gmx mdrun` prints in
+/// its log file. If `None`, it is `3 * atoms - 3`, which is right for
+/// systems without constraints whose center of mass motion is removed.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::kinetic_temperature;
+///
+/// # fn main() -> Result<()> {
+/// let topology = Topology::read_top("topol.top")?;
+/// let mut trajectory = TRRTrajectory::open_read("traj.trr")?;
+/// let temperature = kinetic_temperature(&mut trajectory, &topology.masses(), None)?;
+/// println!("average temperature: {:?} K", temperature.average());
+/// # Ok(())
+/// # }
+/// ```
+pub fn kinetic_temperature(
+    trajectory: &mut impl Trajectory,
+    masses: &[f32],
+    ndf: Option<usize>,
+) -> Result<KineticTemperature> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    frame.check_masses(masses)?;
+    let ndf = ndf.unwrap_or_else(|| (3 * frame.len()).saturating_sub(3));
+    if ndf == 0 {
+        return Err(Error::Unsupported(
+            "temperatures without degrees of freedom",
+        ));
+    }
+    let mut temperature = KineticTemperature::default();
+    while read_next(trajectory, &mut frame)? {
+        let velocities = frame.velocities.as_ref().ok_or(Error::Unsupported(
+            "temperatures of frames without velocities",
+        ))?;
+        // twice the kinetic energy in kJ/mol
+        let energy: f64 = velocities
+            .iter()
+            .zip(masses)
+            .map(|(v, &m)| f64::from(m) * v.iter().map(|&x| f64::from(x).powi(2)).sum::<f64>())
+            .sum();
+        let kelvin = energy / (ndf as f64 * BOLTZMANN);
+        temperature.series.push((frame.time, kelvin as f32));
+    }
+    Ok(temperature)
+}
+
+/// The backbone atoms of a residue that define its phi and psi angles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackboneDihedrals {
+    /// Residue number as in the topology
+    pub residue_number: usize,
+
+    /// The C of the previous residue and N, CA and C of this one
+    pub phi: [usize; 4],
+
+    /// N, CA and C of this residue and the N of the next one
+    pub psi: [usize; 4],
+}
+
+impl Topology {
+    /// The backbone dihedrals of every residue that has N, CA and C atoms and
+    /// neighbors with consecutive residue numbers on both sides, so the first
+    /// and last residue of each chain are left out
+    pub fn backbone_dihedrals(&self) -> Vec<BackboneDihedrals> {
+        // residue number and the indices of N, CA and C of each residue
+        let mut residues: Vec<(usize, [Option<usize>; 3])> = Vec::new();
+        for (i, atom) in self.atoms.iter().enumerate() {
+            if residues.last().map(|r| r.0) != Some(atom.residue_number) {
+                residues.push((atom.residue_number, [None; 3]));
+            }
+            let backbone = &mut residues.last_mut().expect("just added").1;
+            match atom.name.as_str() {
+                "N" => backbone[0] = Some(i),
+                "CA" => backbone[1] = Some(i),
+                "C" => backbone[2] = Some(i),
+                _ => {}
+            }
+        }
+        residues
+            .windows(3)
+            .filter_map(|window| {
+                let [(before, previous), (number, current), (after, next)] =
+                    [window[0], window[1], window[2]];
+                if before + 1 != number || number + 1 != after {
+                    return None;
+                }
+                let [n, ca, c] = current;
+                Some(BackboneDihedrals {
+                    residue_number: number,
+                    phi: [previous[2]?, n?, ca?, c?],
+                    psi: [n?, ca?, c?, next[0]?],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Backbone dihedrals of residues over time computed by `ramachandran`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ramachandran {
+    /// Time of each frame
+    pub times: Vec<f32>,
+
+    /// Residue number of each residue
+    pub residues: Vec<usize>,
+
+    /// `(phi, psi)` in degrees of each residue in each frame, indexed by
+    /// residue and then by frame
+    pub angles: Vec<Vec<(f32, f32)>>,
+}
+
+impl Ramachandran {
+    /// Two-dimensional histogram of the angles of the residue at `index` in
+    /// `residues` (or of all residues if `None`) with `bins` bins from -180 to
+    /// 180 degrees along each axis, indexed by the phi bin and then by the
+    /// psi bin. `None` if there is no residue at `index` or `bins` is 0.
+    pub fn histogram(&self, index: Option<usize>, bins: usize) -> Option<Vec<Vec<usize>>> {
+        if bins == 0 {
+            return None;
+        }
+        let series = match index {
+            Some(index) => std::slice::from_ref(self.angles.get(index)?),
+            None => &self.angles[..],
+        };
+        let mut histogram = vec![vec![0; bins]; bins];
+        let bin = |angle: f32| (((angle + 180.0) / 360.0 * bins as f32) as usize).min(bins - 1);
+        for &(phi, psi) in series.iter().flatten() {
+            histogram[bin(phi)][bin(psi)] += 1;
+        }
+        Some(histogram)
+    }
+}
+
+/// Phi and psi angles of `residues` in every remaining frame of
+/// `trajectory`, like `gmx rama`. Use `Topology::backbone_dihedrals` to find
+/// the backbone atoms of a protein.
+///
+/// ```no_run
+/// use xdrfile::*;
+/// use xdrfile::analysis::ramachandran;
+///
+/// # fn main() -> Result<()> {
+/// let topology = Topology::read_top("topol.top")?;
+/// let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+/// let rama = ramachandran(&mut trajectory, &topology.backbone_dihedrals())?;
+/// let histogram = rama.histogram(None, 36).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub fn ramachandran(
+    trajectory: &mut impl Trajectory,
+    residues: &[BackboneDihedrals],
+) -> Result<Ramachandran> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    for residue in residues {
+        frame.check_indices(&residue.phi)?;
+        frame.check_indices(&residue.psi)?;
+    }
+    let mut rama = Ramachandran {
+        residues: residues.iter().map(|r| r.residue_number).collect(),
+        angles: vec![Vec::new(); residues.len()],
+        ..Default::default()
+    };
+    while read_next(trajectory, &mut frame)? {
+        for (residue, series) in residues.iter().zip(&mut rama.angles) {
+            let [a, b, c, d] = residue.phi;
+            let phi = frame.dihedral_pbc(a, b, c, d)?;
+            let [a, b, c, d] = residue.psi;
+            let psi = frame.dihedral_pbc(a, b, c, d)?;
+            series.push((phi, psi));
+        }
+        rama.times.push(frame.time);
+    }
+    Ok(rama)
+}
+
+/// Root mean square fluctuation of each atom in `mask` (or of all atoms if
+/// `None`) around its average position over the remaining frames of
+/// `trajectory`, in the order of `mask`. Returns zeros if there are no
+/// frames.
+///
+/// Positions are accumulated with Welford's algorithm, so memory use does
+/// not grow with the number of frames. If `align_to_mean` is true, each
+/// frame is fitted on the atoms in `mask`, like `gmx rmsf -fit`, which takes
+/// two passes: the average structure is computed from the frames fitted
+/// onto the first one, and the fluctuations from the frames fitted onto the
+/// average structure. The trajectory is seeked back to where it started
+/// between the passes.
+pub fn rmsf<T: Trajectory + Seek>(
+    trajectory: &mut T,
+    mask: Option<&[usize]>,
+    align_to_mean: bool,
+) -> Result<Vec<f32>> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    let indices: Vec<usize> = match mask {
+        Some(indices) => {
+            Frame::with_len(num_atoms).check_indices(indices)?;
+            indices.to_vec()
+        }
+        None => (0..num_atoms).collect(),
+    };
+    if !align_to_mean {
+        return Ok(Fluctuations::accumulate(trajectory, num_atoms, &indices, Fit::None)?.rmsf());
+    }
+
+    let start = trajectory.stream_position()?;
+    let first = Fluctuations::accumulate(trajectory, num_atoms, &indices, Fit::First)?;
+    if first.count == 0 {
+        return Ok(first.rmsf());
+    }
+    let mut average = Frame::with_len(num_atoms);
+    for (&i, mean) in indices.iter().zip(&first.mean) {
+        average.coords[i] = mean.map(|x| x as f32);
+    }
+    trajectory.seek(SeekFrom::Start(start))?;
+    Ok(Fluctuations::accumulate(trajectory, num_atoms, &indices, Fit::Onto(&average))?.rmsf())
+}
+
+/// Average positions of the atoms in `mask` (or of all atoms if `None`) over
+/// the remaining frames of `trajectory`, as a frame with these atoms in the
+/// order of `mask`, e.g. to write as a PDB file. The frame has no box, step
+/// or time. Fails if there are no frames.
+///
+/// If `align` is true, each frame is first fitted onto the first one on the
+/// atoms in `mask`, like the average structure of `gmx rmsf -fit -ox`.
+/// Positions are accumulated with Welford's algorithm, so memory use does
+/// not grow with the number of frames.
+pub fn average_structure(
+    trajectory: &mut impl Trajectory,
+    mask: Option<&[usize]>,
+    align: bool,
+) -> Result<Frame> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    let indices: Vec<usize> = match mask {
+        Some(indices) => {
+            Frame::with_len(num_atoms).check_indices(indices)?;
+            indices.to_vec()
+        }
+        None => (0..num_atoms).collect(),
+    };
+    let fit = if align { Fit::First } else { Fit::None };
+    let fluctuations = Fluctuations::accumulate(trajectory, num_atoms, &indices, fit)?;
+    if fluctuations.count == 0 {
+        return Err(Error::Unsupported("average structures without frames"));
+    }
+    Ok(Frame {
+        coords: fluctuations
+            .mean
+            .iter()
+            .map(|mean| mean.map(|x| x as f32))
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// Crystallographic B-factors `8π²/3 · RMSF²` of every atom in Å² from its
+/// fluctuations over the remaining frames of `trajectory`, like
+/// `gmx rmsf -oq`. If `reference` is given, each frame is fitted onto it
+/// first. Write them to a PDB file with `pdb::write_frame_with_bfactors`.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use xdrfile::*;
+/// use xdrfile::analysis::bfactors;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XTCTrajectory::open_read("traj.xtc")?;
+/// let mut reference = Frame::with_len(trajectory.get_num_atoms()?);
+/// XTCTrajectory::open_read("traj.xtc")?.read(&mut reference)?;
+/// let b = bfactors(&mut trajectory, Some(&reference))?;
+/// let mut out = File::create("bfactors.pdb")?;
+/// pdb::write_frame_with_bfactors(&mut out, &reference, None, &b)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn bfactors(trajectory: &mut impl Trajectory, reference: Option<&Frame>) -> Result<Vec<f32>> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    let indices: Vec<usize> = (0..num_atoms).collect();
+    let fit = match reference {
+        Some(reference) => {
+            if reference.len() != num_atoms {
+                return Err(Error::WrongSizeFrame {
+                    expected: num_atoms,
+                    found: reference.len(),
+                });
+            }
+            Fit::Onto(reference)
+        }
+        None => Fit::None,
+    };
+    let rmsf = Fluctuations::accumulate(trajectory, num_atoms, &indices, fit)?.rmsf();
+    let factor = 8.0 * std::f32::consts::PI.powi(2) / 3.0 * 100.0;
+    Ok(rmsf.iter().map(|rmsf| factor * rmsf * rmsf).collect())
+}
+
+/// What the frames are fitted onto before their positions are accumulated
+enum Fit<'a> {
+    None,
+    First,
+    Onto(&'a Frame),
+}
+
+/// Running mean and sum of squared deviations of atom positions
+struct Fluctuations {
+    count: usize,
+    mean: Vec<[f64; 3]>,
+    squared_deviations: Vec<f64>,
+}
+
+impl Fluctuations {
+    /// Accumulate the positions of the atoms at `indices` over all remaining
+    /// frames, fitting each frame as given by `fit`
+    fn accumulate(
+        trajectory: &mut impl Trajectory,
+        num_atoms: usize,
+        indices: &[usize],
+        fit: Fit<'_>,
+    ) -> Result<Fluctuations> {
+        let mut fluctuations = Fluctuations {
+            count: 0,
+            mean: vec![[0.0; 3]; indices.len()],
+            squared_deviations: vec![0.0; indices.len()],
+        };
+        let mut frame = Frame::with_len(num_atoms);
+        let mut first = None;
+        while read_next(trajectory, &mut frame)? {
+            match fit {
+                Fit::Onto(reference) => frame.superpose_onto(reference, Some(indices))?,
+                Fit::First => match &first {
+                    Some(first) => frame.superpose_onto(first, Some(indices))?,
+                    None => first = Some(frame.clone()),
+                },
+                Fit::None => {}
+            }
+            fluctuations.add(&frame, indices);
+        }
+        Ok(fluctuations)
+    }
+
+    fn add(&mut self, frame: &Frame, indices: &[usize]) {
+        self.count += 1;
+        let n = self.count as f64;
+        for ((&i, mean), squared) in indices
+            .iter()
+            .zip(&mut self.mean)
+            .zip(&mut self.squared_deviations)
+        {
+            for (x, mean) in frame.coords[i].iter().zip(mean.iter_mut()) {
+                let x = f64::from(*x);
+                let delta = x - *mean;
+                *mean += delta / n;
+                *squared += delta * (x - *mean);
+            }
+        }
+    }
+
+    fn rmsf(&self) -> Vec<f32> {
+        self.squared_deviations
+            .iter()
+            .map(|&sum| {
+                if self.count == 0 {
+                    0.0
+                } else {
+                    (sum / self.count as f64).sqrt() as f32
+                }
+            })
+            .collect()
+    }
+}
+
+/// Number of points on the sphere around each atom in `sasa`
+const SASA_POINTS: usize = 240;
+
+/// Solvent accessible surface areas computed by `sasa`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SurfaceArea {
+    /// Time of each frame
+    pub times: Vec<f32>,
+
+    /// Total area in nm² of each frame
+    pub total: Vec<f32>,
+
+    /// Area in nm² of each residue in each frame, indexed like
+    /// `Topology::residues`
+    pub residues: Vec<Vec<f32>>,
+}
+
+impl SurfaceArea {
+    /// Area of residue `residue` (an index into `Topology::residues`) in
+    /// each frame
+    pub fn residue_series(&self, residue: usize) -> Vec<f32> {
+        self.residues.iter().map(|areas| areas[residue]).collect()
+    }
+}
+
+/// Solvent accessible surface area of every remaining frame of `trajectory`
+/// in total and per residue of `topology`, like `gmx sasa`.
+///
+/// Uses the Shrake-Rupley algorithm: points are spread evenly over a sphere
+/// around each atom, with the van der Waals radius from
+/// `Topology::vdw_radii` plus `probe_radius` (0.14 nm for water), and the
+/// area of the sphere is scaled by the fraction of points that are not
+/// inside the sphere of another atom. Periodic boundaries are taken into
+/// account if the frames have a box, which must be at least twice as wide
+/// as the largest sphere diameter.
+pub fn sasa(
+    trajectory: &mut impl Trajectory,
+    topology: &Topology,
+    probe_radius: f32,
+) -> Result<SurfaceArea> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    if topology.len() != num_atoms {
+        return Err(Error::LengthMismatch {
+            name: "topology atoms",
+            expected: num_atoms,
+            found: topology.len(),
+        });
+    }
+    if probe_radius.is_nan() || probe_radius < 0.0 {
+        return Err(Error::Unsupported("negative probe radii"));
+    }
+    let radii: Vec<f32> = topology
+        .vdw_radii()
+        .iter()
+        .map(|radius| radius + probe_radius)
+        .collect();
+    let sphere = sphere_points(SASA_POINTS);
+    let mut frame = Frame::with_len(num_atoms);
+    let mut areas = SurfaceArea::default();
+    while read_next(trajectory, &mut frame)? {
+        let atom_areas = accessible_areas(&frame, &radii, &sphere)?;
+        areas.times.push(frame.time);
+        areas.total.push(atom_areas.iter().sum());
+        areas.residues.push(
+            topology
+                .residues
+                .iter()
+                .map(|residue| atom_areas[residue.atoms.clone()].iter().sum())
+                .collect(),
+        );
+    }
+    Ok(areas)
+}
+
+/// `n` points evenly spread over the unit sphere along a golden spiral
+fn sphere_points(n: usize) -> Vec<[f32; 3]> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+    (0..n)
+        .map(|k| {
+            let z = 1.0 - (2 * k + 1) as f64 / n as f64;
+            let r = (1.0 - z * z).sqrt();
+            let phi = golden_angle * k as f64;
+            [(r * phi.cos()) as f32, (r * phi.sin()) as f32, z as f32]
+        })
+        .collect()
+}
+
+/// Accessible area of each atom of `frame` with the given sphere radii,
+/// estimated from the points of `sphere` that no other sphere covers
+fn accessible_areas(frame: &Frame, radii: &[f32], sphere: &[[f32; 3]]) -> Result<Vec<f32>> {
+    let max_radius = radii.iter().copied().fold(0.0, f32::max);
+    if max_radius <= 0.0 {
+        return Ok(vec![0.0; frame.len()]);
+    }
+    let search = NeighborSearch::new(frame, None, 2.0 * max_radius)?;
+    let mut areas = Vec::with_capacity(frame.len());
+    let mut neighbors = Vec::new();
+    for (i, &radius) in radii.iter().enumerate() {
+        // nearest images of the overlapping spheres relative to atom i
+        neighbors.clear();
+        search.for_each_within(frame.coords[i], radius + max_radius, |j, r| {
+            if j != i && r < radius + radii[j] {
+                let d = sub(frame.coords[j], frame.coords[i]);
+                neighbors.push((frame.box_vector.minimum_image(d), radii[j] * radii[j]));
+            }
+        });
+        // the sphere that covered the last point likely covers the next one
+        let mut last = 0;
+        let accessible = sphere
+            .iter()
+            .filter(|point| {
+                let point = point.map(|x| x * radius);
+                let covers = |&(d, r2): &([f32; 3], f32)| {
+                    let v = sub(point, d);
+                    v[0] * v[0] + v[1] * v[1] + v[2] * v[2] < r2
+                };
+                if neighbors.get(last).is_some_and(covers) {
+                    return false;
+                }
+                match neighbors.iter().position(covers) {
+                    Some(k) => {
+                        last = k;
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .count();
+        let area = 4.0 * std::f32::consts::PI * radius * radius;
+        areas.push(area * accessible as f32 / sphere.len() as f32);
+    }
+    Ok(areas)
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rmsd_series() -> Result<()> {
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut reference = Frame::with_len(trajectory.get_num_atoms()?);
+        trajectory.read(&mut reference)?;
+        trajectory.rewind()?;
+
+        let fitted = rmsd_series(&mut trajectory, &reference, None, true)?;
+        assert_eq!(fitted.len(), 38);
+        assert_eq!(fitted[0].0, 1.0);
+        assert_approx_eq!(fitted[0].1, 0.0, 1e-5);
+        assert_eq!(fitted[37].0, 38.0);
+
+        trajectory.rewind()?;
+        let unfitted = rmsd_series(&mut trajectory, &reference, None, false)?;
+        for (fitted, unfitted) in fitted.iter().zip(&unfitted) {
+            assert_eq!(fitted.0, unfitted.0);
+            assert!(fitted.1 <= unfitted.1 + 1e-5);
+        }
+
+        // a shifted copy of the reference fits perfectly
+        let mut shifted = reference.clone();
+        shifted.translate([1.0, 2.0, 3.0]);
+        let mut memory = MemoryTrajectory::new(MemoryFormat::Trr);
+        memory.write(&shifted)?;
+        let mask = [0, 1, 2, 3];
+        let series = rmsd_series(&mut memory, &reference, Some(&mask), true)?;
+        assert_approx_eq!(series[0].1, 0.0, 1e-5);
+
+        let mut other = MemoryTrajectory::new(MemoryFormat::Trr);
+        other.write(&Frame::with_len(3))?;
+        assert!(rmsd_series(&mut other, &reference, None, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rmsf() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("rattle.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        let mut frame = Frame::with_len(3);
+        frame.coords = vec![[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        // atom 2 moves along z between -0.1 and 0.1, the others stay put
+        for z in &[-0.1, 0.1, -0.1, 0.1] {
+            frame.coords[2][2] = *z;
+            trr.write(&frame)?;
+        }
+        drop(trr);
+
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        let values = rmsf(&mut trr, None, false)?;
+        assert_approx_eq!(values[0], 0.0);
+        assert_approx_eq!(values[1], 0.0);
+        assert_approx_eq!(values[2], 0.1);
+
+        trr.rewind()?;
+        let values = rmsf(&mut trr, Some(&[2, 0]), false)?;
+        assert_eq!(values.len(), 2);
+        assert_approx_eq!(values[0], 0.1);
+        assert!(rmsf(&mut trr, Some(&[3]), false).is_err());
+
+        // rigid motion of the whole frame is removed by fitting
+        let mut reference = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut reference)?;
+        let path = dir.path().join("rigid.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        for (i, offset) in [0.0, 1.0, -2.0].iter().enumerate() {
+            let mut frame = reference.clone();
+            for _ in 0..i {
+                // rotate by 90 degrees around z
+                for xyz in frame.coords.iter_mut() {
+                    *xyz = [-xyz[1], xyz[0], xyz[2]];
+                }
+            }
+            frame.translate([*offset; 3]);
+            trr.write(&frame)?;
+        }
+        drop(trr);
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        let fitted = rmsf(&mut trr, None, true)?;
+        assert_eq!(fitted.len(), 304);
+        assert!(fitted.iter().all(|x| *x < 1e-3), "{:?}", fitted);
+        trr.rewind()?;
+        assert!(rmsf(&mut trr, None, false)?.iter().all(|x| *x > 0.1));
+
+        // at the end of the trajectory, nothing fluctuates
+        assert_eq!(rmsf(&mut trr, Some(&[0]), true)?, [0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gyration_series() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let series = gyration_series(&mut xtc, None, None)?;
+        assert_eq!(series.len(), 38);
+        xtc.rewind()?;
+        let mut frame = Frame::with_len(304);
+        for (time, radius) in series {
+            xtc.read(&mut frame)?;
+            assert_eq!(time, frame.time);
+            assert_eq!(radius, frame.radius_of_gyration(None, None)?);
+        }
+
+        xtc.rewind()?;
+        let masses = vec![1.0; 304];
+        let weighted = gyration_series(&mut xtc, Some(&[0, 1, 2]), Some(&masses))?;
+        xtc.rewind()?;
+        let unweighted = gyration_series(&mut xtc, Some(&[0, 1, 2]), None)?;
+        for (weighted, unweighted) in weighted.iter().zip(&unweighted) {
+            assert_approx_eq!(weighted.1, unweighted.1, 1e-5);
+        }
+
+        xtc.rewind()?;
+        assert!(matches!(
+            gyration_series(&mut xtc, None, Some(&[1.0])),
+            Err(Error::LengthMismatch { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry_series() -> Result<()> {
+        let observables = [
+            Observable::Distance(0, 4),
+            Observable::Angle(0, 4, 8),
+            Observable::Dihedral(0, 4, 8, 12),
+        ];
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let series = geometry_series(&mut xtc, &observables)?;
+        assert_eq!(series.times.len(), 38);
+        assert_eq!(series.columns.len(), 3);
+
+        xtc.rewind()?;
+        let mut frame = Frame::with_len(304);
+        for i in 0..38 {
+            xtc.read(&mut frame)?;
+            assert_eq!(series.times[i], frame.time);
+            assert_eq!(series.columns[0][i], frame.distance_pbc(0, 4)?);
+            assert_eq!(series.columns[1][i], frame.angle_pbc(0, 4, 8)?);
+            assert_eq!(series.columns[2][i], frame.dihedral_pbc(0, 4, 8, 12)?);
+        }
+        assert_eq!(series.column(observables[1]), Some(&series.columns[1][..]));
+        assert_eq!(series.column(Observable::Distance(1, 2)), None);
+
+        let xvg = series.to_xvg();
+        assert_eq!(xvg.legends[2], "dihedral 0-4-8-12");
+        assert_eq!(xvg.rows.len(), 38);
+        assert_eq!(xvg.rows[5][0], f64::from(series.times[5]));
+        assert_eq!(xvg.rows[5][1], f64::from(series.columns[0][5]));
+
+        xtc.rewind()?;
+        assert!(matches!(
+            geometry_series(&mut xtc, &[Observable::Distance(0, 304)]),
+            Err(Error::AtomIndexOutOfBounds { index: 304, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_msd() -> Result<()> {
+        // compare with the direct average over all time origins
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let selections: [&[usize]; 2] = [&[0, 10, 20], &[5]];
+        let result = msd(&mut xtc, &selections, false)?;
+        assert_eq!(result.lag_times.len(), 38);
+        assert_eq!(result.lag_times[0], 0.0);
+        assert_eq!(result.lag_times[3], 3.0);
+        for (selection, curve) in selections.iter().zip(&result.curves) {
+            for m in 0..38 {
+                let mut sum = 0.0;
+                for k in 0..38 - m {
+                    for &i in selection.iter() {
+                        let d = sub(frames[k + m].coords[i], frames[k].coords[i]);
+                        sum += d.iter().map(|x| f64::from(*x).powi(2)).sum::<f64>();
+                    }
+                }
+                let expected = sum / ((38 - m) * selection.len()) as f64;
+                assert_approx_eq!(f64::from(curve[m]), expected, 1e-4);
+            }
+        }
+
+        // an atom that moves 0.3 nm per frame through a 1 nm box
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("drift.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        for step in 0..10 {
+            let x = (0.3 * step as f32).rem_euclid(1.0);
+            trr.write(&Frame {
+                step,
+                time: step as f32,
+                box_vector: BoxVector::rectangular(1.0, 1.0, 1.0),
+                coords: vec![[x, 0.5, 0.5]],
+                ..Default::default()
+            })?;
+        }
+        drop(trr);
+        let unwrapped = msd(&mut TRRTrajectory::open_read(&path)?, &[&[0]], true)?;
+        for (m, value) in unwrapped.curves[0].iter().enumerate() {
+            assert_approx_eq!(*value, (0.3 * m as f32).powi(2), 1e-4);
+        }
+        let wrapped = msd(&mut TRRTrajectory::open_read(&path)?, &[&[0]], false)?;
+        assert!(wrapped.curves[0][9] < 1.0);
+
+        assert!(msd(&mut xtc, &[&[304]], false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diffusion_coefficient() {
+        let lag_times: Vec<f32> = (0..20).map(|t| t as f32).collect();
+        let msd = MeanSquaredDisplacement {
+            curves: vec![lag_times.iter().map(|t| 6.0 * 0.002 * t + 0.1).collect()],
+            lag_times,
+        };
+        assert_approx_eq!(msd.diffusion_coefficient(0, 2.0, 15.0).unwrap(), 0.002);
+        assert_eq!(msd.diffusion_coefficient(0, 2.0, 2.5), None);
+        assert_eq!(msd.diffusion_coefficient(1, 2.0, 15.0), None);
+    }
+
+    /// Uniform pseudo random numbers in [0, 1)
+    fn random(state: &mut u64) -> f32 {
+        *state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (*state >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    #[test]
+    fn test_rdf() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("gas.trr");
+        let box_vector = BoxVector::from_lengths_angles([3.0, 3.2, 3.4], [70.0, 80.0, 75.0]);
+        let mut state = 1;
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        let mut frames = Vec::new();
+        for step in 0..2 {
+            let coords = (0..500)
+                .map(|_| {
+                    let s = [random(&mut state), random(&mut state), random(&mut state)];
+                    [0, 1, 2].map(|k| (0..3).map(|d| s[d] * box_vector[d][k]).sum())
+                })
+                .collect();
+            let frame = Frame {
+                step,
+                box_vector,
+                coords,
+                ..Default::default()
+            };
+            trr.write(&frame)?;
+            frames.push(frame);
+        }
+        drop(trr);
+
+        let group_a: Vec<usize> = (0..300).collect();
+        let group_b: Vec<usize> = (200..500).collect();
+        let result = rdf(
+            &mut TRRTrajectory::open_read(&path)?,
+            &group_a,
+            &group_b,
+            1.2,
+            12,
+        )?;
+        assert_eq!(result.frames, 2);
+        assert_approx_eq!(result.radii[0], 0.05);
+        // an ideal gas is uncorrelated
+        for g in &result.g[4..] {
+            assert_approx_eq!(*g, 1.0, 0.1);
+        }
+
+        // the cell list finds the same pairs as comparing all of them
+        let volume = f64::from(box_vector.volume());
+        let pairs = (300 * 300 - 100) as f64;
+        let mut expected = vec![0.0; 12];
+        for frame in &frames {
+            for &i in &group_a {
+                for &j in &group_b {
+                    let r = frame.distance_pbc(i, j)?;
+                    if i != j && r < 1.2 {
+                        expected[(r / 0.1) as usize] += volume / pairs;
+                    }
+                }
+            }
+        }
+        for (bin, (g, expected)) in result.g.iter().zip(&expected).enumerate() {
+            let (inner, outer) = (0.1 * bin as f64, 0.1 * (bin + 1) as f64);
+            let shell = 4.0 / 3.0 * std::f64::consts::PI * (outer.powi(3) - inner.powi(3));
+            assert_approx_eq!(f64::from(*g), expected / 2.0 / shell, 1e-3);
+        }
+
+        assert!(matches!(
+            rdf(
+                &mut TRRTrajectory::open_read(&path)?,
+                &group_a,
+                &group_b,
+                1.5,
+                10
+            ),
+            Err(Error::CutoffTooLarge { .. })
+        ));
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        for (r_max, bins) in [(1.0, 0), (0.0, 10), (-1.0, 10), (f32::NAN, 10)] {
+            assert!(matches!(
+                rdf(&mut trr, &group_a, &group_b, r_max, bins),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(rdf(&mut xtc, &[0], &[1], 1.0, 10).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_contact_map() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("contacts.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        // atom 3 approaches atom 0 across the boundary in the second frame
+        for x in &[1.5, 2.8] {
+            trr.write(&Frame {
+                box_vector: BoxVector::rectangular(3.0, 3.0, 3.0),
+                coords: vec![
+                    [0.1, 0.1, 0.1],
+                    [0.4, 0.1, 0.1],
+                    [2.0, 2.0, 2.0],
+                    [*x, 0.1, 0.1],
+                ],
+                ..Default::default()
+            })?;
+        }
+        drop(trr);
+
+        let map = contact_map(
+            &mut TRRTrajectory::open_read(&path)?,
+            &[0, 1],
+            &[1, 2, 3],
+            0.5,
+        )?;
+        assert_eq!((map.rows, map.columns, map.frames.len()), (2, 3, 2));
+        assert!(map.in_contact(0, 0, 0));
+        assert!(!map.in_contact(0, 0, 2));
+        assert!(map.in_contact(1, 0, 2));
+        // atom 1 is in both groups, but not in contact with itself
+        assert!(!map.in_contact(0, 1, 0));
+        assert_eq!(map.counts, [2, 0, 1, 0, 0, 0]);
+        assert_eq!(map.frequencies(), [1.0, 0.0, 0.5, 0.0, 0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_distance_series() -> Result<()> {
+        let mut trj = MemoryTrajectory::new(MemoryFormat::Trr);
+        let cube = BoxVector::rectangular(3.0, 3.0, 3.0);
+        let frames = [
+            // atom 3 approaches atom 0 across the boundary
+            (
+                cube,
+                [
+                    [0.1, 0.1, 0.1],
+                    [0.4, 0.1, 0.1],
+                    [2.0, 2.0, 2.0],
+                    [1.5, 0.1, 0.1],
+                ],
+            ),
+            (
+                cube,
+                [
+                    [0.1, 0.1, 0.1],
+                    [0.4, 0.1, 0.1],
+                    [2.0, 2.0, 2.0],
+                    [2.8, 0.1, 0.1],
+                ],
+            ),
+            // further apart than half the box
+            (cube, [[0.0; 3], [0.2, 0.0, 0.0], [1.5; 3], [1.6, 1.5, 1.5]]),
+            // without a box
+            (
+                BoxVector::default(),
+                [[0.0; 3], [0.2, 0.0, 0.0], [5.0, 0.0, 0.0], [10.0, 0.0, 0.0]],
+            ),
+        ];
+        for (step, (box_vector, coords)) in frames.iter().enumerate() {
+            trj.write(&Frame {
+                step,
+                time: step as f32,
+                box_vector: *box_vector,
+                coords: coords.to_vec(),
+                ..Default::default()
+            })?;
+        }
+        trj.rewind();
+        let series = min_distance_series(&mut trj, &[0, 1], &[2, 3])?;
+        let pairs: Vec<(usize, usize)> = series.iter().map(|d| d.pair).collect();
+        assert_eq!(pairs, [(1, 3), (0, 3), (1, 2), (1, 2)]);
+        assert_approx_eq!(series[0].distance, 1.1, 1e-5);
+        assert_approx_eq!(series[1].distance, 0.3, 1e-5);
+        assert_approx_eq!(series[2].distance, 6.19f32.sqrt(), 1e-5);
+        assert_approx_eq!(series[3].distance, 4.8, 1e-5);
+        assert_eq!(series[3].time, 3.0);
+
+        // an atom in both groups is not paired with itself
+        trj.rewind();
+        let series = min_distance_series(&mut trj, &[0, 1], &[1, 2])?;
+        assert_eq!(series[0].pair, (0, 1));
+        trj.rewind();
+        assert!(min_distance_series(&mut trj, &[1], &[1]).is_err());
+        assert!(min_distance_series(&mut trj, &[0], &[4]).is_err());
+
+        // NaN coordinates are never within a cutoff, which must not loop
+        let mut trj = MemoryTrajectory::new(MemoryFormat::Trr);
+        for x in [f32::NAN, 1.0].iter() {
+            trj.write(&Frame {
+                coords: vec![[0.0; 3], [*x, 0.0, 0.0]],
+                ..Default::default()
+            })?;
+        }
+        trj.rewind();
+        let series = min_distance_series(&mut trj, &[0], &[1])?;
+        assert_eq!(series[0].distance, f32::INFINITY);
+        assert_approx_eq!(series[1].distance, 1.0, 1e-5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sasa() -> Result<()> {
+        let mut atoms = vec![Atom::new("C1", "LIG", 1), Atom::new("C2", "LIG", 1)];
+        atoms.push(Atom::new("OW", "SOL", 2));
+        let topology = Topology::new(atoms);
+        let mut trj = MemoryTrajectory::new(MemoryFormat::Trr);
+        let frames = [
+            // isolated atoms
+            (
+                BoxVector::default(),
+                [[0.0; 3], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]],
+            ),
+            // two overlapping carbons, across the boundary of the box
+            (
+                BoxVector::rectangular(3.0, 3.0, 3.0),
+                [[0.1, 1.0, 1.0], [2.9, 1.0, 1.0], [1.5, 2.0, 2.0]],
+            ),
+        ];
+        for (box_vector, coords) in &frames {
+            trj.write(&Frame {
+                box_vector: *box_vector,
+                coords: coords.to_vec(),
+                ..Default::default()
+            })?;
+        }
+        trj.rewind();
+        let areas = sasa(&mut trj, &topology, 0.14)?;
+        let sphere = |r: f32| 4.0 * std::f32::consts::PI * r * r;
+        let (carbon, oxygen) = (0.17 + 0.14, 0.152 + 0.14);
+        assert_eq!(areas.total.len(), 2);
+        assert_approx_eq!(areas.total[0], 2.0 * sphere(carbon) + sphere(oxygen), 1e-4);
+        assert_eq!(areas.residue_series(1), [sphere(oxygen); 2]);
+
+        // each carbon loses a cap of height r - d / 2
+        let cap = 2.0 * std::f32::consts::PI * carbon * (carbon - 0.1);
+        let expected = 2.0 * (sphere(carbon) - cap);
+        assert!((areas.residues[1][0] - expected).abs() < 0.01 * expected);
+
+        trj.rewind();
+        let smaller = Topology::new(topology.atoms[..2].to_vec());
+        assert!(matches!(
+            sasa(&mut trj, &smaller, 0.14),
+            Err(Error::LengthMismatch { .. })
+        ));
+        assert!(sasa(&mut trj, &topology, -1.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hydrogen_bonds() -> Result<()> {
+        // two water molecules, the second one turning away
+        let topology = Topology::new(
+            ["OW", "HW1", "HW2", "OW", "HW1", "HW2"]
+                .iter()
+                .map(|name| Atom::new(name, "SOL", 1))
+                .collect(),
+        );
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("water.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        let mut frames = Vec::new();
+        let second = [
+            [[0.29, 0.0, 0.0], [0.32, 0.09, 0.0], [0.32, -0.09, 0.0]],
+            [[0.29, 0.0, 0.0], [0.32, 0.09, 0.0], [0.32, -0.09, 0.0]],
+            [[0.0, 0.29, 0.0], [0.09, 0.32, 0.0], [-0.09, 0.32, 0.0]],
+        ];
+        for water in &second {
+            let frame = Frame {
+                box_vector: BoxVector::rectangular(3.0, 3.0, 3.0),
+                coords: vec![
+                    [0.0, 0.0, 0.0],
+                    [0.1, 0.0, 0.0],
+                    [-0.03, -0.09, 0.0],
+                    water[0],
+                    water[1],
+                    water[2],
+                ],
+                ..Default::default()
+            };
+            trr.write(&frame)?;
+            frames.push(frame);
+        }
+        drop(trr);
+
+        let donors = topology.hydrogen_bond_donors(&frames[0])?;
+        assert_eq!(donors, [(0, 1), (0, 2), (3, 4), (3, 5)]);
+        let acceptors = topology.hydrogen_bond_acceptors();
+        assert_eq!(acceptors, [0, 3]);
+
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        let bonds = hydrogen_bonds(&mut trr, &donors, &acceptors, 0.35, 30.0)?;
+        let bond = HydrogenBond {
+            donor: 0,
+            hydrogen: 1,
+            acceptor: 3,
+        };
+        assert_eq!(bonds.frames[0], [bond]);
+        assert_eq!(bonds.counts(), [1, 1, 0]);
+        let occupancy = bonds.occupancy();
+        assert_eq!(occupancy.len(), 1);
+        assert_eq!(occupancy[0].0, bond);
+        assert_approx_eq!(occupancy[0].1, 2.0 / 3.0);
+
+        assert!(topology.hydrogen_bond_donors(&Frame::with_len(3)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_density_profile() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("slab.trr");
+        let mut trr = TRRTrajectory::open_write(&path)?;
+        for (step, length) in [2.0, 4.0].iter().enumerate() {
+            // two atoms in the lower half along z, one wrapped from below
+            let frame = Frame {
+                step,
+                box_vector: BoxVector::rectangular(1.0, 1.0, *length),
+                coords: vec![
+                    [0.5, 0.5, 0.1],
+                    [0.2, 0.7, -0.1 * length],
+                    [0.5, 0.5, 0.2 * length],
+                ],
+                ..Default::default()
+            };
+            trr.write(&frame)?;
+        }
+        drop(trr);
+
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        let profile = density_profile(&mut trr, &[0, 1, 2], Axis::Z, 2, None)?;
+        assert_eq!(profile.frames, 2);
+        assert_eq!(profile.positions, [0.75, 2.25]);
+        // slabs of 1 and 2 nm³
+        assert_approx_eq!(profile.density[0], (2.0 / 1.0 + 2.0 / 2.0) / 2.0);
+        assert_approx_eq!(profile.density[1], (1.0 / 1.0 + 1.0 / 2.0) / 2.0);
+
+        let masses = [16.0, 1.0, 1.0];
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        let profile = density_profile(&mut trr, &[0, 2], Axis::Z, 2, Some(&masses))?;
+        assert_approx_eq!(profile.density[0], 17.0 * 0.75 * 1.660_539, 1e-4);
+
+        let mut trr = TRRTrajectory::open_read(&path)?;
+        assert!(density_profile(&mut trr, &[0], Axis::X, 2, Some(&[1.0])).is_err());
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(density_profile(&mut xtc, &[304], Axis::X, 2, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster() -> Result<()> {
+        let mut reference = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut reference)?;
+        let mut stretched = reference.clone();
+        for xyz in stretched.coords.iter_mut() {
+            xyz[0] *= 1.5;
+        }
+
+        // rotated copies of two conformations: 0, 2, 3 and 5 look alike
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for (step, base) in [
+            &reference, &stretched, &reference, &reference, &stretched, &reference,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let (sin, cos) = (0.3 * step as f32).sin_cos();
+            let mut frame = (*base).clone();
+            frame.time = step as f32;
+            for xyz in frame.coords.iter_mut() {
+                *xyz = [
+                    xyz[0],
+                    cos * xyz[1] - sin * xyz[2],
+                    sin * xyz[1] + cos * xyz[2],
+                ];
+            }
+            trajectory.write(&frame)?;
+        }
+
+        let metric = Rmsd {
+            mask: Some((0..100).collect()),
+            fit: true,
+        };
+        trajectory.rewind();
+        let clustering = cluster(&mut trajectory, &metric, 0.01)?;
+        assert_eq!(clustering.times, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(clustering.clusters.len(), 2);
+        assert_eq!(clustering.clusters[0].members, [0, 2, 3, 5]);
+        assert_eq!(clustering.clusters[0].centroid, 0);
+        assert_eq!(clustering.clusters[1].members, [1, 4]);
+        assert_eq!(clustering.clusters[1].size(), 2);
+        assert_eq!(clustering.assignments, [0, 1, 0, 0, 1, 0]);
+
+        // without fitting, every rotated frame is on its own
+        trajectory.rewind();
+        let metric = Rmsd {
+            mask: None,
+            fit: false,
+        };
+        let clustering = cluster(&mut trajectory, &metric, 0.01)?;
+        assert_eq!(clustering.clusters.len(), 6);
+
+        trajectory.rewind();
+        let metric = Rmsd {
+            mask: Some(vec![304]),
+            fit: false,
+        };
+        assert!(cluster(&mut trajectory, &metric, 0.01).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pca() -> Result<()> {
+        // the first atom oscillates along x, the third one less along y
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        let offsets = [-1.0, 0.0, 1.0, 0.0];
+        for (step, d) in offsets.iter().enumerate() {
+            let frame = Frame {
+                step,
+                coords: vec![
+                    [1.0 + d, 0.0, 0.0],
+                    [2.0, 2.0, 2.0],
+                    [0.0, 3.0 - 0.2 * d, 0.0],
+                ],
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        trajectory.rewind();
+        let components = pca(&mut trajectory, Some(&[0, 2]))?;
+        assert_eq!(components.frames, 4);
+        assert_eq!(components.mean, [[1.0, 0.0, 0.0], [0.0, 3.0, 0.0]]);
+        assert_eq!(components.eigenvalues.len(), 6);
+        // variance of (-1, 0, 1, 0) scaled by 1 + 0.2²
+        assert_approx_eq!(components.eigenvalues[0], 0.5 * 1.04, 1e-5);
+        assert_approx_eq!(components.eigenvalues[1], 0.0, 1e-5);
+        assert_approx_eq!(components.explained_variance()[0], 1.0, 1e-5);
+        let first = &components.eigenvectors[0];
+        assert_approx_eq!(first[0].abs(), 1.0 / 1.04f32.sqrt(), 1e-5);
+        assert_approx_eq!(first[4] / first[0], -0.2, 1e-5);
+
+        let mut frame = Frame::with_len(3);
+        frame.coords = vec![[3.0, 0.0, 0.0], [0.0; 3], [0.0, 2.6, 0.0]];
+        let projection = components.project(&frame, 1)?;
+        assert_eq!(projection.len(), 1);
+        assert_approx_eq!(projection[0].abs(), 2.0 * 1.04f32.sqrt(), 1e-5);
+        assert!(components.project(&Frame::with_len(2), 1).is_err());
+
+        trajectory.rewind();
+        assert!(pca(&mut trajectory, Some(&[3])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_autocorrelation() {
+        let series: Vec<f32> = (0..50).map(|k| (0.3 * k as f32).cos() + 0.1).collect();
+        let direct = autocorrelation(&series, false, false);
+        let fft = autocorrelation(&series, false, true);
+        assert_eq!(direct.len(), 50);
+        for (a, b) in direct.iter().zip(&fft) {
+            assert_approx_eq!(a, b, 1e-4);
+        }
+        let mean_square = series.iter().map(|x| x * x).sum::<f32>() / 50.0;
+        assert_approx_eq!(direct[0], mean_square, 1e-4);
+        assert_approx_eq!(direct[49], series[0] * series[49], 1e-4);
+
+        let normalized = autocorrelation(&series, true, false);
+        assert_approx_eq!(normalized[0], 1.0);
+        assert_approx_eq!(normalized[7], direct[7] / direct[0], 1e-5);
+        assert!(autocorrelation(&[], true, true).is_empty());
+        assert_eq!(autocorrelation(&[0.0, 0.0], true, false), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_velocity_autocorrelation() -> Result<()> {
+        // the first atom flips its velocity every frame, the second keeps it
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for step in 0..4 {
+            let sign = if step % 2 == 0 { 1.0 } else { -1.0 };
+            let frame = Frame {
+                step,
+                time: 0.5 * step as f32,
+                coords: vec![[0.0; 3]; 2],
+                velocities: Some(vec![[sign, 0.0, 2.0 * sign], [0.0, 3.0, 0.0]]),
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        trajectory.rewind();
+        let vacf = velocity_autocorrelation(&mut trajectory, None, false, true)?;
+        assert_eq!(vacf.lag_times, [0.0, 0.5, 1.0, 1.5]);
+        assert_eq!(vacf.atoms, [0, 1]);
+        let expected = [[5.0, -5.0, 5.0, -5.0], [9.0, 9.0, 9.0, 9.0]];
+        for (acf, expected) in vacf.per_atom.iter().zip(&expected) {
+            for (c, e) in acf.iter().zip(expected) {
+                assert_approx_eq!(c, e, 1e-4);
+            }
+        }
+
+        trajectory.rewind();
+        let vacf = velocity_autocorrelation(&mut trajectory, Some(&[0, 1]), true, false)?;
+        assert_eq!(vacf.average(), [1.0, 0.0, 1.0, 0.0]);
+
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(velocity_autocorrelation(&mut xtc, None, true, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacf_and_power_spectrum() -> Result<()> {
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for step in 0..3 {
+            let frame = Frame {
+                step,
+                time: 0.1 * step as f32,
+                coords: vec![[0.0; 3]; 2],
+                velocities: Some(vec![[1.0, 0.0, 0.0], [0.0, 0.0, 2.0 * step as f32]]),
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        trajectory.rewind();
+        let correlation = vacf(&mut trajectory, Some(&[0, 1]))?;
+        let expected = [(0.0, 23.0 / 6.0), (0.1, 2.5), (0.2, 0.5)];
+        for ((tau, c), (expected_tau, expected_c)) in correlation.iter().zip(&expected) {
+            assert_approx_eq!(tau, expected_tau, 1e-6);
+            assert_approx_eq!(c, expected_c, 1e-5);
+        }
+
+        // a damped oscillation at 5/ps peaks at 5/ps, or about 167 1/cm
+        let dt = 0.01;
+        let vacf: Vec<f32> = (0..256)
+            .map(|k| {
+                let t = k as f32 * dt;
+                (2.0 * std::f32::consts::PI * 5.0 * t).cos() * (-t).exp()
+            })
+            .collect();
+        let spectrum = power_spectrum(&vacf, dt);
+        assert_eq!(spectrum.frequencies.len(), 257);
+        assert_approx_eq!(spectrum.frequencies[256], 50.0, 1e-3);
+        let peak = (0..257)
+            .max_by(|&a, &b| spectrum.intensities[a].total_cmp(&spectrum.intensities[b]))
+            .unwrap();
+        assert!((spectrum.frequencies[peak] - 5.0).abs() < 0.2);
+        assert!((spectrum.wavenumbers()[peak] - 166.8).abs() < 7.0);
+        assert!(power_spectrum(&[], dt).frequencies.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_kinetic_temperature() -> Result<()> {
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for step in 1..=2 {
+            let v = step as f32;
+            let frame = Frame {
+                step,
+                time: step as f32,
+                coords: vec![[0.0; 3]; 2],
+                velocities: Some(vec![[v, 0.0, 0.0], [0.0, -v, 0.0]]),
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        let masses = [2.0, 1.0];
+
+        // 2 E_kin = 2 v² + v² with 3 degrees of freedom
+        trajectory.rewind();
+        let temperature = kinetic_temperature(&mut trajectory, &masses, None)?;
+        let expected = |v: f32| 3.0 * v * v / (3.0 * 0.008_314_463);
+        assert_eq!(temperature.series.len(), 2);
+        assert_eq!(temperature.series[1].0, 2.0);
+        assert_approx_eq!(temperature.series[0].1, expected(1.0), 1e-2);
+        assert_approx_eq!(temperature.series[1].1, expected(2.0), 1e-2);
+        assert_approx_eq!(
+            temperature.average().unwrap(),
+            (expected(1.0) + expected(2.0)) / 2.0,
+            1e-2
+        );
+
+        trajectory.rewind();
+        let temperature = kinetic_temperature(&mut trajectory, &masses, Some(6))?;
+        assert_approx_eq!(temperature.series[0].1, expected(1.0) / 2.0, 1e-2);
+
+        trajectory.rewind();
+        assert!(kinetic_temperature(&mut trajectory, &[1.0], None).is_err());
+        trajectory.rewind();
+        assert!(kinetic_temperature(&mut trajectory, &masses, Some(0)).is_err());
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(kinetic_temperature(&mut xtc, &[1.0; 304], None).is_err());
+        assert_eq!(KineticTemperature::default().average(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_parameters() -> Result<()> {
+        // a straight chain along z and a zigzag chain in the xy plane
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        let mut frame = Frame {
+            box_vector: BoxVector::rectangular(3.0, 3.0, 3.0),
+            coords: Vec::new(),
+            ..Default::default()
+        };
+        for c in 0..4 {
+            frame.coords.push([1.0, 1.0, (2.5 + 0.15 * c as f32) % 3.0]);
+        }
+        for c in 0..4 {
+            let y = if c % 2 == 0 { 2.0 } else { 2.1 };
+            frame.coords.push([0.2 + 0.12 * c as f32, y, 1.0]);
+        }
+        trajectory.write(&frame)?;
+        trajectory.write(&frame)?;
+
+        let straight = vec![vec![0, 1, 2, 3]];
+        trajectory.rewind();
+        let order = order_parameters(&mut trajectory, &straight, Axis::Z)?;
+        assert_eq!(order.frames, 2);
+        assert_eq!(order.carbons, [1, 2]);
+        for (p2, scd) in order.p2.iter().zip(&order.scd) {
+            assert_approx_eq!(p2, 1.0, 1e-5);
+            assert_approx_eq!(scd, -0.5, 1e-5);
+        }
+
+        let both = vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]];
+        trajectory.rewind();
+        let order = order_parameters(&mut trajectory, &both, Axis::Z)?;
+        assert_approx_eq!(order.p2[0], 0.25, 1e-5);
+        assert_approx_eq!(order.scd[1], -0.125, 1e-5);
+
+        trajectory.rewind();
+        let uneven = vec![vec![0, 1, 2, 3], vec![4, 5, 6]];
+        assert!(order_parameters(&mut trajectory, &uneven, Axis::Z).is_err());
+        trajectory.rewind();
+        assert!(order_parameters(&mut trajectory, &[vec![0, 8, 1]], Axis::Z).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_average_structure() -> Result<()> {
+        let mut reference = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut reference)?;
+
+        // the reference rotated back and forth around z
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for angle in &[0.0f32, 0.3, -0.3] {
+            let (sin, cos) = angle.sin_cos();
+            let mut frame = reference.clone();
+            for xyz in frame.coords.iter_mut() {
+                *xyz = [
+                    cos * xyz[0] - sin * xyz[1],
+                    sin * xyz[0] + cos * xyz[1],
+                    xyz[2],
+                ];
+            }
+            trajectory.write(&frame)?;
+        }
+        let mask = [0, 5, 10, 20];
+
+        trajectory.rewind();
+        let aligned = average_structure(&mut trajectory, Some(&mask), true)?;
+        assert_eq!(aligned.len(), 4);
+        assert_eq!(aligned.box_vector, BoxVector::default());
+        assert!(aligned.rmsd(&reference.subset(&mask)?)? < 1e-4);
+
+        // without fitting, the average is pulled towards the rotation axis
+        trajectory.rewind();
+        let average = average_structure(&mut trajectory, None, false)?;
+        assert_eq!(average.len(), 304);
+        assert!(average.rmsd(&reference)? > 1e-3);
+
+        assert!(average_structure(&mut trajectory, None, false).is_err());
+        trajectory.rewind();
+        assert!(average_structure(&mut trajectory, Some(&[304]), false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bfactors() -> Result<()> {
+        // the second atom jumps between ±0.1 nm along x
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        for x in &[0.1, -0.1, 0.1, -0.1] {
+            let frame = Frame {
+                coords: vec![[0.0; 3], [*x, 0.0, 0.0]],
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        trajectory.rewind();
+        let b = bfactors(&mut trajectory, None)?;
+        assert_eq!(b[0], 0.0);
+        // RMSF of 1 Å
+        assert_approx_eq!(b[1], 8.0 * std::f32::consts::PI.powi(2) / 3.0, 1e-3);
+
+        trajectory.rewind();
+        assert!(bfactors(&mut trajectory, Some(&Frame::with_len(3))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ramachandran() -> Result<()> {
+        let names = ["N", "CA", "C", "O"];
+        let mut atoms = Vec::new();
+        for residue in 1..=3 {
+            atoms.extend(names.iter().map(|name| Atom::new(name, "ALA", residue)));
+        }
+        // a second chain starting again at 1
+        atoms.extend(names.iter().map(|name| Atom::new(name, "GLY", 1)));
+        let topology = Topology::new(atoms);
+        let residues = topology.backbone_dihedrals();
+        assert_eq!(
+            residues,
+            [BackboneDihedrals {
+                residue_number: 2,
+                phi: [2, 4, 5, 6],
+                psi: [4, 5, 6, 8],
+            }]
+        );
+
+        // the position of d for a dihedral angle of a, b, c and d
+        let place = |a: [f32; 3], b: [f32; 3], c: [f32; 3], degrees: f32| {
+            let norm = |v: [f32; 3]| {
+                let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+                v.map(|x| x / length)
+            };
+            let u = norm(sub(c, b));
+            let ab = sub(a, b);
+            let along = ab[0] * u[0] + ab[1] * u[1] + ab[2] * u[2];
+            let v = norm([0, 1, 2].map(|k| ab[k] - along * u[k]));
+            let w = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            [0, 1, 2].map(|k| c[k] + cos * v[k] + sin * w[k])
+        };
+        let mut frame = Frame::with_len(16);
+        frame.coords[2] = [0.0, 1.0, 0.0];
+        frame.coords[4] = [0.0, 0.0, 0.0];
+        frame.coords[5] = [1.0, 0.0, 0.0];
+        frame.coords[6] = place(frame.coords[2], frame.coords[4], frame.coords[5], -60.0);
+        frame.coords[8] = place(frame.coords[4], frame.coords[5], frame.coords[6], 120.0);
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        trajectory.write(&frame)?;
+        trajectory.write(&frame)?;
+        trajectory.rewind();
+        let rama = ramachandran(&mut trajectory, &residues)?;
+        assert_eq!(rama.residues, [2]);
+        assert_eq!(rama.times.len(), 2);
+        let (phi, psi) = rama.angles[0][0];
+        assert_approx_eq!(phi, -60.0, 1e-3);
+        assert_approx_eq!(psi, 120.0, 1e-3);
+
+        let histogram = rama.histogram(Some(0), 4).unwrap();
+        assert_eq!(histogram[1][3], 2);
+        assert_eq!(rama.histogram(None, 4), Some(histogram));
+        assert_eq!(rama.histogram(Some(1), 4), None);
+        assert_eq!(rama.histogram(None, 0), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+//! Writing trajectories that only appear under their name once complete
+
+use crate::*;
+use std::fs::{File, OpenOptions};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes the temporary files of several writers in one process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A trajectory that is written to a temporary file in the directory of its
+/// destination, and renamed to the destination by `close`. Until then, an
+/// existing file at the destination is left untouched, so a conversion that
+/// is interrupted or fails never leaves a half-written trajectory under the
+/// final name. The temporary file is removed if the writer is dropped
+/// without being closed.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let mut writer = SafeWriter::create("out.xtc", XTCTrajectory::open_write)?;
+/// for frame in XTCTrajectory::open_read("in.xtc")?.into_iter() {
+///     writer.write(&*frame?)?;
+/// }
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SafeWriter<T: Trajectory> {
+    trajectory: Option<T>,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl<T: Trajectory> SafeWriter<T> {
+    /// Create a writer for `path`, opening the temporary file with `open`,
+    /// e.g. `XTCTrajectory::open_write`
+    pub fn create<F>(path: impl AsRef<Path>, open: F) -> Result<SafeWriter<T>>
+    where
+        F: FnOnce(PathBuf) -> Result<T>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file name", path.display()),
+            )
+        })?;
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp_path = parent.join(temp_name);
+        // reserve the name, so that no other file is overwritten
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        match open(temp_path.clone()) {
+            Ok(trajectory) => Ok(SafeWriter {
+                trajectory: Some(trajectory),
+                temp_path,
+                path,
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+
+    /// The destination of the trajectory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The temporary file that is written until `close` is called
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Close the trajectory, write it to disk and rename it to its
+    /// destination, replacing an existing file. On error, the temporary file
+    /// is removed and the destination is unchanged.
+    pub fn close(mut self) -> Result<()> {
+        let mut trajectory = self.trajectory.take().expect("closed only once");
+        let result = trajectory.flush().and_then(|()| {
+            drop(trajectory);
+            File::open(&self.temp_path)?.sync_all()?;
+            std::fs::rename(&self.temp_path, &self.path)?;
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+        result
+    }
+
+    fn trajectory(&mut self) -> &mut T {
+        self.trajectory.as_mut().expect("not closed")
+    }
+}
+
+impl<T: Trajectory> Trajectory for SafeWriter<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        self.trajectory().read(frame)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.trajectory().write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.trajectory().flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.trajectory().get_num_atoms()
+    }
+}
+
+impl<T: Trajectory> Drop for SafeWriter<T> {
+    /// Remove the temporary file unless the writer was closed
+    fn drop(&mut self) {
+        if let Some(trajectory) = self.trajectory.take() {
+            drop(trajectory);
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn steps(path: &Path) -> Result<Vec<usize>> {
+        TRRTrajectory::open_read(path)?
+            .into_frames()
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect()
+    }
+
+    #[test]
+    fn test_safe_writer() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("traj.trr");
+        let mut frame = Frame::with_len(3);
+        TRRTrajectory::write_from_iter(&path, &[frame.clone()])?;
+
+        let mut writer = SafeWriter::create(&path, TRRTrajectory::open_write)?;
+        let temp_path = writer.temp_path().to_path_buf();
+        assert_eq!(temp_path.parent(), Some(dir.path()));
+        for step in 1..=3 {
+            frame.step = step;
+            writer.write(&frame)?;
+        }
+        // the old file is untouched until the writer is closed
+        assert_eq!(steps(&path)?, [0]);
+        writer.close()?;
+        assert_eq!(steps(&path)?, [1, 2, 3]);
+        assert!(!temp_path.exists());
+
+        // an abandoned writer leaves nothing behind
+        let mut writer = SafeWriter::create(&path, TRRTrajectory::open_write)?;
+        writer.write(&frame)?;
+        let temp_path = writer.temp_path().to_path_buf();
+        drop(writer);
+        assert!(!temp_path.exists());
+        assert_eq!(steps(&path)?, [1, 2, 3]);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+        let missing = dir.path().join("missing").join("traj.xtc");
+        assert!(SafeWriter::create(&missing, XTCTrajectory::open_write).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Keeping existing files as GROMACS-style backups instead of overwriting them
+
+use crate::*;
+use std::io;
+
+/// Maximum number of backups of a file, like GROMACS' default for
+/// `GMX_MAXBACKUP`
+pub const MAX_BACKUPS: usize = 99;
+
+/// Rename the file at `path`, if it exists, to the first free backup name
+/// `#name.1#`, `#name.2#`, ... in the same directory, like GROMACS does
+/// before writing an output file. Returns the path of the backup, or `None`
+/// if there was no file to back up. Fails if all `MAX_BACKUPS` backup names
+/// are taken.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// if let Some(backup) = backup_file("traj.xtc")? {
+///     println!("Backed up traj.xtc to {}", backup.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn backup_file(path: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+    let path = path.as_ref();
+    if std::fs::symlink_metadata(path).is_err() {
+        return Ok(None);
+    }
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file name", path.display()),
+        )
+    })?;
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    for n in 1..=MAX_BACKUPS {
+        let mut backup_name = std::ffi::OsString::from("#");
+        backup_name.push(name);
+        backup_name.push(format!(".{}#", n));
+        let backup = parent.join(backup_name);
+        if std::fs::symlink_metadata(&backup).is_err() {
+            std::fs::rename(path, &backup)?;
+            return Ok(Some(backup));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "{} already has {} backups, remove some of them",
+            path.display(),
+            MAX_BACKUPS
+        ),
+    )
+    .into())
+}
+
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Open a file in write mode after moving an existing file at `path` out
+    /// of the way, see `backup_file`. `open_write` overwrites it instead.
+    pub fn open_write_with_backup(path: impl AsRef<Path>) -> Result<Self> {
+        backup_file(path.as_ref())?;
+        XTCTrajectory::open_write(path)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Open a file in write mode after moving an existing file at `path` out
+    /// of the way, see `backup_file`. `open_write` overwrites it instead.
+    pub fn open_write_with_backup(path: impl AsRef<Path>) -> Result<Self> {
+        backup_file(path.as_ref())?;
+        TRRTrajectory::open_write(path)
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("traj.xtc");
+        assert_eq!(backup_file(&path)?, None);
+
+        let mut frame = Frame::with_len(2);
+        for step in 1..=3 {
+            frame.step = step;
+            let mut xtc = XTCTrajectory::open_write_with_backup(&path)?;
+            xtc.write(&frame)?;
+        }
+        let steps = |path: &Path| -> Result<Vec<usize>> {
+            XTCTrajectory::open_read(path)?
+                .into_frames()
+                .map(|frame| frame.map(|frame| frame.step))
+                .collect()
+        };
+        assert_eq!(steps(&path)?, [3]);
+        assert_eq!(steps(&dir.path().join("#traj.xtc.1#"))?, [1]);
+        assert_eq!(steps(&dir.path().join("#traj.xtc.2#"))?, [2]);
+
+        let trr = dir.path().join("traj.trr");
+        TRRTrajectory::open_write_with_backup(&trr)?.write(&frame)?;
+        TRRTrajectory::open_write_with_backup(&trr)?;
+        assert_eq!(std::fs::metadata(&trr)?.len(), 0);
+        assert!(std::fs::metadata(dir.path().join("#traj.trr.1#"))?.len() > 0);
+
+        for n in 3..=MAX_BACKUPS {
+            std::fs::write(dir.path().join(format!("#traj.xtc.{}#", n)), b"")?;
+        }
+        let err = backup_file(&path).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Io {
+                kind: io::ErrorKind::AlreadyExists,
+                ..
+            }
+        ));
+        assert!(path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::ops::{Index, IndexMut};
+
+/// The unit cell of a frame, stored as the three box vectors a, b and c
+/// (one per row) in the lower triangular form used by GROMACS.
+///
+/// Lengths are usually given in nanometers, angles in degrees.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoxVector(pub [[f32; 3]; 3]);
+
+impl BoxVector {
+    /// Create a box from its three box vectors
+    pub fn new(vectors: [[f32; 3]; 3]) -> BoxVector {
+        BoxVector(vectors)
+    }
+
+    /// Create a rectangular box with the given edge lengths
+    pub fn rectangular(a: f32, b: f32, c: f32) -> BoxVector {
+        BoxVector([[a, 0.0, 0.0], [0.0, b, 0.0], [0.0, 0.0, c]])
+    }
+
+    /// Create a box from the edge lengths `[a, b, c]` and the angles
+    /// `[alpha, beta, gamma]` (in degrees) between b and c, a and c, and a
+    /// and b respectively.
+    pub fn from_lengths_angles(lengths: [f32; 3], angles: [f32; 3]) -> BoxVector {
+        let [a, b, c] = lengths.map(f64::from);
+        let [cos_alpha, cos_beta, cos_gamma] = angles.map(|angle| {
+            // avoid tiny off-diagonal elements for right angles
+            if angle == 90.0 {
+                0.0
+            } else {
+                f64::from(angle).to_radians().cos()
+            }
+        });
+        let sin_gamma = (1.0 - cos_gamma * cos_gamma).sqrt();
+
+        let bx = b * cos_gamma;
+        let by = b * sin_gamma;
+        let cx = c * cos_beta;
+        let cy = if sin_gamma == 0.0 {
+            0.0
+        } else {
+            c * (cos_alpha - cos_beta * cos_gamma) / sin_gamma
+        };
+        let cz = (c * c - cx * cx - cy * cy).max(0.0).sqrt();
+
+        BoxVector([
+            [a as f32, 0.0, 0.0],
+            [bx as f32, by as f32, 0.0],
+            [cx as f32, cy as f32, cz as f32],
+        ])
+    }
+
+    /// Edge lengths `[a, b, c]` and angles `[alpha, beta, gamma]` (in degrees)
+    pub fn lengths_and_angles(&self) -> ([f32; 3], [f32; 3]) {
+        let vectors = self.0.map(|v| v.map(f64::from));
+        let lengths = vectors.map(|v| dot(v, v).sqrt());
+        let angle = |i: usize, j: usize| -> f32 {
+            if lengths[i] == 0.0 || lengths[j] == 0.0 {
+                90.0
+            } else {
+                let cos = dot(vectors[i], vectors[j]) / (lengths[i] * lengths[j]);
+                cos.clamp(-1.0, 1.0).acos().to_degrees() as f32
+            }
+        };
+        (
+            lengths.map(|l| l as f32),
+            [angle(1, 2), angle(0, 2), angle(0, 1)],
+        )
+    }
+
+    /// Volume of the unit cell
+    pub fn volume(&self) -> f32 {
+        let [a, b, c] = self.0.map(|v| v.map(f64::from));
+        dot(a, cross(b, c)).abs() as f32
+    }
+
+    /// True if all box vectors are zero, i.e. the frame has no unit cell
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().flatten().all(|&x| x == 0.0)
+    }
+
+    /// True if the box is rectangular with three edges of equal length
+    pub fn is_cubic(&self) -> bool {
+        let [a, b, c] = [self.0[0][0], self.0[1][1], self.0[2][2]];
+        !self.is_triclinic() && a > 0.0 && approx_equal(a, b) && approx_equal(a, c)
+    }
+
+    /// True if any of the box vectors has an off-diagonal component
+    pub fn is_triclinic(&self) -> bool {
+        (0..3).any(|i| (0..3).any(|j| i != j && self.0[i][j] != 0.0))
+    }
+
+    /// Reduce the displacement `d` to its shortest periodic image. A box
+    /// without volume disables periodic boundaries.
+    pub(crate) fn minimum_image(&self, mut d: [f32; 3]) -> [f32; 3] {
+        let box_vector = &self.0;
+
+        // GROMACS boxes are lower triangular, so reduce along c, b and a in turn
+        for dim in (0..3).rev() {
+            let length = box_vector[dim][dim];
+            if length == 0.0 {
+                return d;
+            }
+            let shift = (d[dim] / length).round();
+            for (dk, bk) in d.iter_mut().zip(&box_vector[dim]) {
+                *dk -= shift * bk;
+            }
+        }
+
+        // For strongly skewed cells the reduced vector is not necessarily the
+        // shortest one, so check the neighbouring images as well
+        let mut best = d;
+        let mut best_norm = norm2(d);
+        for i in -1..=1 {
+            for j in -1..=1 {
+                for k in -1..=1 {
+                    let mut candidate = d;
+                    for (m, c) in candidate.iter_mut().enumerate() {
+                        *c += i as f32 * box_vector[0][m]
+                            + j as f32 * box_vector[1][m]
+                            + k as f32 * box_vector[2][m];
+                    }
+                    let norm = norm2(candidate);
+                    if norm < best_norm {
+                        best = candidate;
+                        best_norm = norm;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Distances between the opposite faces of the unit cell, i.e. its
+    /// heights perpendicular to the planes spanned by the other two box
+    /// vectors. Half of the smallest one is the largest distance for which
+    /// the minimum image is unique.
+    pub(crate) fn face_distances(&self) -> [f64; 3] {
+        let [a, b, c] = self.0.map(|v| v.map(f64::from));
+        let volume = f64::from(self.volume());
+        [cross(b, c), cross(c, a), cross(a, b)].map(|n| volume / dot(n, n).sqrt())
+    }
+
+    /// Coordinates of `x` in units of the box vectors, for boxes with volume
+    /// in the lower triangular form that GROMACS uses
+    pub(crate) fn fractional(&self, x: [f32; 3]) -> [f64; 3] {
+        let [a, b, c] = self.0.map(|v| v.map(f64::from));
+        let x = x.map(f64::from);
+        let sc = x[2] / c[2];
+        let sb = (x[1] - sc * c[1]) / b[1];
+        let sa = (x[0] - sb * b[0] - sc * c[0]) / a[0];
+        [sa, sb, sc]
+    }
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm2(d: [f32; 3]) -> f32 {
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+fn approx_equal(a: f32, b: f32) -> bool {
+    (a - b).abs() <= 1e-6 * a.abs().max(b.abs())
+}
+
+impl From<[[f32; 3]; 3]> for BoxVector {
+    fn from(vectors: [[f32; 3]; 3]) -> Self {
+        BoxVector(vectors)
+    }
+}
+
+impl From<BoxVector> for [[f32; 3]; 3] {
+    fn from(box_vector: BoxVector) -> Self {
+        box_vector.0
+    }
+}
+
+impl Index<usize> for BoxVector {
+    type Output = [f32; 3];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for BoxVector {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+/// The box vectors become the columns of the matrix, so that multiplying it
+/// with fractional coordinates yields cartesian ones
+#[cfg(feature = "glam")]
+impl From<BoxVector> for glam::Mat3 {
+    fn from(box_vector: BoxVector) -> Self {
+        glam::Mat3::from_cols_array_2d(&box_vector.0)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Mat3> for BoxVector {
+    fn from(matrix: glam::Mat3) -> Self {
+        BoxVector(matrix.to_cols_array_2d())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion() {
+        let array = [[1.0, 0.0, 0.0], [0.5, 2.0, 0.0], [0.0, 0.0, 3.0]];
+        let box_vector: BoxVector = array.into();
+        assert_eq!(box_vector[1][0], 0.5);
+        let back: [[f32; 3]; 3] = box_vector.into();
+        assert_eq!(back, array);
+    }
+
+    #[test]
+    fn test_rectangular() {
+        let box_vector = BoxVector::rectangular(2.0, 3.0, 4.0);
+        assert_approx_eq!(box_vector.volume(), 24.0);
+        assert!(!box_vector.is_triclinic());
+        assert!(!box_vector.is_cubic());
+        assert!(BoxVector::rectangular(2.0, 2.0, 2.0).is_cubic());
+        assert!(!BoxVector::default().is_cubic());
+        assert!(BoxVector::default().is_zero());
+
+        let (lengths, angles) = box_vector.lengths_and_angles();
+        assert_eq!(lengths, [2.0, 3.0, 4.0]);
+        assert_eq!(angles, [90.0, 90.0, 90.0]);
+        assert_eq!(BoxVector::from_lengths_angles(lengths, angles), box_vector);
+    }
+
+    #[test]
+    fn test_triclinic() {
+        // rhombic dodecahedron (xy-square) as generated by gmx editconf
+        let lengths = [5.0, 5.0, 5.0];
+        let angles = [60.0, 60.0, 90.0];
+        let box_vector = BoxVector::from_lengths_angles(lengths, angles);
+        assert!(box_vector.is_triclinic());
+        assert!(!box_vector.is_cubic());
+        assert_approx_eq!(box_vector[2][0], 2.5, 1e-5);
+        assert_approx_eq!(box_vector[2][1], 2.5, 1e-5);
+        assert_approx_eq!(box_vector[2][2], 3.535534, 1e-5);
+        assert_approx_eq!(box_vector.volume(), 125.0 * 0.5_f32.sqrt(), 1e-3);
+
+        let (new_lengths, new_angles) = box_vector.lengths_and_angles();
+        for k in 0..3 {
+            assert_approx_eq!(new_lengths[k], lengths[k], 1e-5);
+            assert_approx_eq!(new_angles[k], angles[k], 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_minimum_image() {
+        let box_vector = BoxVector::rectangular(3.0, 3.0, 3.0);
+        let d = box_vector.minimum_image([2.8, -0.2, 4.0]);
+        assert_approx_eq!(d[0], -0.2, 1e-5);
+        assert_approx_eq!(d[1], -0.2, 1e-5);
+        assert_approx_eq!(d[2], 1.0, 1e-5);
+
+        // no box, no periodicity
+        assert_eq!(BoxVector::default().minimum_image([5.0; 3]), [5.0; 3]);
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam() {
+        let box_vector = BoxVector::from_lengths_angles([2.0, 3.0, 4.0], [70.0, 80.0, 60.0]);
+        let matrix = glam::Mat3::from(box_vector);
+        assert_eq!(matrix.x_axis, glam::Vec3::from(box_vector[0]));
+        assert_approx_eq!(matrix.determinant(), box_vector.volume(), 1e-4);
+        assert_eq!(BoxVector::from(matrix), box_vector);
+    }
+}
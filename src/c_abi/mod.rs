@@ -2,6 +2,7 @@
 #![allow(non_upper_case_globals, non_camel_case_types)]
 
 pub mod xdr_seek;
+pub mod xdr_stream;
 pub mod xdrfile;
 pub mod xdrfile_trr;
 pub mod xdrfile_xtc;
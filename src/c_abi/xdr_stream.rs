@@ -0,0 +1,53 @@
+use super::xdrfile::*;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdr_stream_ops {
+    pub read: ::std::option::Option<
+        unsafe extern "C" fn(
+            handle: *mut ::std::os::raw::c_void,
+            buf: *mut ::std::os::raw::c_char,
+            len: usize,
+        ) -> usize,
+    >,
+    pub write: ::std::option::Option<
+        unsafe extern "C" fn(
+            handle: *mut ::std::os::raw::c_void,
+            buf: *const ::std::os::raw::c_char,
+            len: usize,
+        ) -> usize,
+    >,
+    pub seek: ::std::option::Option<
+        unsafe extern "C" fn(
+            handle: *mut ::std::os::raw::c_void,
+            offset: i64,
+            whence: ::std::os::raw::c_int,
+        ) -> i64,
+    >,
+    pub flush: ::std::option::Option<
+        unsafe extern "C" fn(handle: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int,
+    >,
+}
+extern "C" {
+    pub fn xdr_stream_open(
+        handle: *mut ::std::os::raw::c_void,
+        ops: *const xdr_stream_ops,
+        mode: *const ::std::os::raw::c_char,
+    ) -> *mut XDRFILE;
+}
+extern "C" {
+    pub fn xdr_stream_close(xfp: *mut XDRFILE) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn xdr_stream_tell(xd: *mut XDRFILE) -> i64;
+}
+extern "C" {
+    pub fn xdr_stream_seek(
+        xd: *mut XDRFILE,
+        pos: i64,
+        whence: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn xdr_stream_flush(xd: *mut XDRFILE) -> ::std::os::raw::c_int;
+}
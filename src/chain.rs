@@ -0,0 +1,278 @@
+//! Reading several trajectory files back-to-back as one trajectory
+
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::rc::Rc;
+
+/// Open an XTC, TRR or (read-only) trj trajectory, choosing the format by file
+/// extension
+pub(crate) fn open_by_extension(path: &Path, filemode: FileMode) -> Result<Box<dyn Trajectory>> {
+    // the format of "traj.xtc.gz" is given by the inner extension
+    let format_path = match compression::Compression::from_path(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_owned(),
+    };
+    let extension = format_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    match extension.to_ascii_lowercase().as_str() {
+        #[cfg(feature = "c-lib")]
+        "xtc" => Ok(Box::new(XTCTrajectory::open(path, filemode)?)),
+        #[cfg(feature = "c-lib")]
+        "trr" => Ok(Box::new(TRRTrajectory::open(path, filemode)?)),
+        #[cfg(not(feature = "c-lib"))]
+        "xtc" | "trr" => Err(Error::Unsupported(
+            "XTC and TRR trajectories without the c-lib feature",
+        )),
+        "trj" if filemode == FileMode::Read => Ok(Box::new(TrjTrajectory::open_read(path)?)),
+        "trj" => Err(Error::Unsupported("writing trj trajectories")),
+        _ => Err(Error::InvalidData(format!(
+            "Unknown trajectory format of {:?}",
+            path
+        ))),
+    }
+}
+
+/// A sequence of trajectory files that are read as one continuous trajectory,
+/// e.g. the parts of a restarted simulation.
+///
+/// Files are opened lazily in the given order; the format of each file is
+/// chosen by its extension (xtc, trr or trj). All files must contain the same
+/// number of atoms.
+pub struct TrajectoryChain {
+    paths: Vec<PathBuf>,
+    next_path: usize,
+    current: Option<Box<dyn Trajectory>>,
+    num_atoms: Option<usize>,
+    drop_overlap: bool,
+    last_time: Option<f32>,
+    overlap_until: Option<f32>,
+}
+
+impl TrajectoryChain {
+    /// Create a chain of the trajectory files at `paths`
+    pub fn new<I, P>(paths: I) -> TrajectoryChain
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        TrajectoryChain {
+            paths: paths.into_iter().map(|p| p.as_ref().to_owned()).collect(),
+            next_path: 0,
+            current: None,
+            num_atoms: None,
+            drop_overlap: false,
+            last_time: None,
+            overlap_until: None,
+        }
+    }
+
+    /// Find the parts of a simulation that was continued with `gmx mdrun
+    /// -noappend`: for `basename` "md.xtc", these are `md.xtc` followed by
+    /// `md.part0002.xtc`, `md.part0003.xtc` and so on, ordered by part number.
+    /// Files in `dir` that do not follow this naming are ignored.
+    pub fn discover(dir: impl AsRef<Path>, basename: &str) -> Result<TrajectoryChain> {
+        let dir = dir.as_ref();
+        let (stem, extension) = match basename.rfind('.') {
+            Some(dot) => basename.split_at(dot),
+            None => (basename, ""),
+        };
+        let prefix = format!("{}.part", stem);
+        let mut parts = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let part = if name == basename {
+                Some(1)
+            } else {
+                name.strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_suffix(extension))
+                    .filter(|number| number.bytes().all(|b| b.is_ascii_digit()))
+                    .and_then(|number| number.parse::<usize>().ok())
+            };
+            if let Some(part) = part {
+                parts.push((part, dir.join(name)));
+            }
+        }
+        if parts.is_empty() {
+            return Err(Error::Io {
+                kind: std::io::ErrorKind::NotFound,
+                message: format!("no parts of {} found in {}", basename, dir.display()),
+            });
+        }
+        parts.sort();
+        Ok(TrajectoryChain::new(
+            parts.into_iter().map(|(_, path)| path),
+        ))
+    }
+
+    /// Skip frames at the start of a file whose time is not later than the
+    /// last frame of the previous file, like `gmx trjcat` does when a run was
+    /// restarted from a checkpoint. Disabled by default.
+    pub fn drop_overlapping_frames(mut self, drop_overlap: bool) -> Self {
+        self.drop_overlap = drop_overlap;
+        self
+    }
+
+    /// Paths of all files in the chain
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Open the next file of the chain. Returns false if there is none.
+    fn open_next(&mut self) -> Result<bool> {
+        let path = match self.paths.get(self.next_path) {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+        let mut trajectory = open_by_extension(path, FileMode::Read)?;
+        let num_atoms = trajectory.get_num_atoms()?;
+        match self.num_atoms {
+            Some(expected) if expected != num_atoms => {
+                return Err(Error::WrongSizeFrame {
+                    expected,
+                    found: num_atoms,
+                })
+            }
+            _ => self.num_atoms = Some(num_atoms),
+        }
+
+        if self.drop_overlap {
+            self.overlap_until = self.last_time;
+        }
+        self.current = Some(trajectory);
+        self.next_path += 1;
+        Ok(true)
+    }
+}
+
+impl Trajectory for TrajectoryChain {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        loop {
+            if self.current.is_none() && !self.open_next()? {
+                return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+            }
+            let trajectory = self.current.as_mut().expect("a file is open");
+            match trajectory.read(frame) {
+                Ok(()) => {}
+                Err(e) if e.is_eof() => {
+                    self.current = None;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            if let Some(until) = self.overlap_until {
+                if frame.time <= until {
+                    continue;
+                }
+                self.overlap_until = None;
+            }
+            self.last_time = Some(frame.time);
+            return Ok(());
+        }
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing to a trajectory chain"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        if self.num_atoms.is_none() && self.current.is_none() {
+            self.open_next()?;
+        }
+        self.num_atoms
+            .ok_or_else(|| Error::InvalidData("Trajectory chain is empty".to_string()))
+    }
+}
+
+impl IntoIterator for TrajectoryChain {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<TrajectoryChain>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_part(path: &Path, times: &[f32]) -> Result<()> {
+        let mut trajectory = open_by_extension(path, FileMode::Write)?;
+        for (i, &time) in times.iter().enumerate() {
+            let frame = Frame {
+                step: i,
+                time,
+                coords: vec![[time; 3]; 2],
+                ..Default::default()
+            };
+            trajectory.write(&frame)?;
+        }
+        trajectory.flush()
+    }
+
+    #[test]
+    fn test_chain() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let parts = [
+            dir.path().join("run1.xtc"),
+            dir.path().join("run2.trr"),
+            dir.path().join("run3.xtc"),
+        ];
+        write_part(&parts[0], &[0.0, 1.0, 2.0])?;
+        write_part(&parts[1], &[2.0, 3.0])?;
+        write_part(&parts[2], &[1.0, 4.0])?;
+
+        let times = |chain: TrajectoryChain| -> Result<Vec<f32>> {
+            chain.into_iter().map(|f| f.map(|f| f.time)).collect()
+        };
+        let all = times(TrajectoryChain::new(&parts))?;
+        assert_eq!(all, vec![0.0, 1.0, 2.0, 2.0, 3.0, 1.0, 4.0]);
+
+        let chain = TrajectoryChain::new(&parts).drop_overlapping_frames(true);
+        assert_eq!(times(chain)?, vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+
+        let mut chain = TrajectoryChain::new(&parts);
+        assert_eq!(chain.get_num_atoms()?, 2);
+        assert!(matches!(
+            chain.write(&Frame::new()),
+            Err(Error::Unsupported(_))
+        ));
+
+        let result = TrajectoryChain::discover(dir.path(), "md.xtc");
+        assert!(matches!(result, Err(Error::Io { .. })));
+        for name in &[
+            "md.part0010.xtc",
+            "md.part0002.xtc",
+            "md.xtc",
+            "md.partX.xtc",
+            "md.trr",
+        ] {
+            std::fs::copy(&parts[0], dir.path().join(name))?;
+        }
+        let chain = TrajectoryChain::discover(dir.path(), "md.xtc")?;
+        let names: Vec<_> = chain
+            .paths()
+            .iter()
+            .map(|p| p.file_name().unwrap())
+            .collect();
+        assert_eq!(names, vec!["md.xtc", "md.part0002.xtc", "md.part0010.xtc"]);
+        assert_eq!(times(chain)?.len(), 9);
+
+        let mixed = TrajectoryChain::new(vec![&parts[0], Path::new("tests/1l2y.xtc")]);
+        let result: Result<Vec<_>> = mixed.into_iter().collect();
+        assert!(matches!(result, Err(Error::WrongSizeFrame { .. })));
+        Ok(())
+    }
+}
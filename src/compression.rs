@@ -0,0 +1,167 @@
+//! Transparent decompression of gzip (.gz) and zstd (.zst) trajectories
+//!
+//! The C library needs a file on disk, so compressed files are decompressed
+//! to a temporary file when they are opened. The temporary file is removed
+//! when the trajectory is dropped.
+
+use crate::*;
+
+/// Compression formats recognized by file extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Guess the compression of a file from its extension
+    pub(crate) fn from_path(path: &Path) -> Option<Compression> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// A decompressed copy of a trajectory that is deleted on drop
+#[cfg(all(feature = "c-lib", any(feature = "flate2", feature = "zstd")))]
+pub(crate) type TempPath = tempfile::TempPath;
+
+/// A decompressed copy of a trajectory. Cannot exist without the `flate2`
+/// or `zstd` feature.
+#[cfg(all(feature = "c-lib", not(any(feature = "flate2", feature = "zstd"))))]
+pub(crate) enum TempPath {}
+
+#[cfg(all(feature = "c-lib", not(any(feature = "flate2", feature = "zstd"))))]
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "c-lib")]
+/// Decompress the file at `path` to a temporary file if its extension marks
+/// it as compressed. Compressed files can only be read.
+pub(crate) fn decompress_if_needed(path: &Path, filemode: &FileMode) -> Result<Option<TempPath>> {
+    let compression = match Compression::from_path(path) {
+        Some(compression) => compression,
+        None => return Ok(None),
+    };
+    if *filemode != FileMode::Read {
+        return Err(Error::Unsupported("writing compressed trajectories"));
+    }
+    decompress(path, compression).map(Some)
+}
+
+#[cfg(all(feature = "c-lib", any(feature = "flate2", feature = "zstd")))]
+fn decompress(path: &Path, compression: Compression) -> Result<TempPath> {
+    use std::fs::File;
+    use std::io::{BufReader, Read};
+
+    let file = BufReader::new(File::open(path)?);
+    let mut decoder: Box<dyn Read> = match compression {
+        #[cfg(feature = "flate2")]
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+        #[allow(unreachable_patterns)]
+        _ => return Err(unsupported(compression)),
+    };
+
+    // keep the inner extension, e.g. ".xtc" for "traj.xtc.gz"
+    let inner = path.with_extension("");
+    let suffix = inner
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut temp = tempfile::Builder::new()
+        .prefix("xdrfile-")
+        .suffix(&suffix)
+        .tempfile()?;
+    std::io::copy(&mut decoder, temp.as_file_mut())?;
+    Ok(temp.into_temp_path())
+}
+
+#[cfg(all(feature = "c-lib", not(any(feature = "flate2", feature = "zstd"))))]
+fn decompress(_path: &Path, compression: Compression) -> Result<TempPath> {
+    Err(unsupported(compression))
+}
+
+#[allow(dead_code)]
+fn unsupported(compression: Compression) -> Error {
+    match compression {
+        Compression::Gzip => Error::Unsupported("reading gzip files without the flate2 feature"),
+        Compression::Zstd => Error::Unsupported("reading zstd files without the zstd feature"),
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_from_path() {
+        let compression = |path: &str| Compression::from_path(Path::new(path));
+        assert_eq!(compression("traj.xtc.gz"), Some(Compression::Gzip));
+        assert_eq!(compression("traj.trr.ZST"), Some(Compression::Zstd));
+        assert_eq!(compression("traj.xtc"), None);
+    }
+
+    fn read_coords(path: &Path) -> Result<Vec<Vec<[f32; 3]>>> {
+        let trajectory = XTCTrajectory::open_read(path)?;
+        trajectory
+            .into_iter()
+            .map(|frame| frame.map(|frame| frame.coords.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_open_compressed() -> Result<()> {
+        let dir = TempDir::new()?;
+        let original = std::fs::read("tests/1l2y.xtc")?;
+
+        let gzip = dir.path().join("1l2y.xtc.gz");
+        #[cfg(feature = "flate2")]
+        {
+            use std::io::Write;
+            let file = std::fs::File::create(&gzip)?;
+            let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            encoder.write_all(&original)?;
+            encoder.finish()?;
+            assert_eq!(
+                read_coords(&gzip)?,
+                read_coords(Path::new("tests/1l2y.xtc"))?
+            );
+
+            let chain = TrajectoryChain::new(vec![&gzip]);
+            assert_eq!(chain.into_iter().count(), 38);
+        }
+        #[cfg(not(feature = "flate2"))]
+        {
+            std::fs::write(&gzip, &original)?;
+            assert!(matches!(read_coords(&gzip), Err(Error::Unsupported(_))));
+        }
+
+        let zstd = dir.path().join("1l2y.xtc.zst");
+        #[cfg(feature = "zstd")]
+        {
+            std::fs::write(&zstd, zstd::encode_all(&original[..], 0)?)?;
+            assert_eq!(
+                read_coords(&zstd)?,
+                read_coords(Path::new("tests/1l2y.xtc"))?
+            );
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            std::fs::write(&zstd, &original)?;
+            assert!(matches!(read_coords(&zstd), Err(Error::Unsupported(_))));
+        }
+
+        let result = XTCTrajectory::open_write(&gzip);
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Concatenating trajectory files into one, like `gmx trjcat`
+
+use crate::chain::open_by_extension;
+use crate::convert::open_output;
+use crate::*;
+
+/// How `concat` joins its inputs, see `ConcatOptions::new`
+pub struct ConcatOptions {
+    time_offsets: Option<Vec<f32>>,
+    continue_time: bool,
+    drop_duplicates: bool,
+    precision: Option<f32>,
+}
+
+impl Default for ConcatOptions {
+    fn default() -> Self {
+        ConcatOptions::new()
+    }
+}
+
+impl ConcatOptions {
+    /// Copy all frames of all inputs with their times unchanged
+    pub fn new() -> ConcatOptions {
+        ConcatOptions {
+            time_offsets: None,
+            continue_time: false,
+            drop_duplicates: false,
+            precision: None,
+        }
+    }
+
+    /// Add `offsets[i]` (in ps) to the time of every frame of input `i`.
+    /// There must be one offset per input. Takes precedence over
+    /// `continue_time`.
+    pub fn time_offsets(mut self, offsets: Vec<f32>) -> Self {
+        self.time_offsets = Some(offsets);
+        self
+    }
+
+    /// Shift the times of every input after the first so that its first
+    /// frame has the time of the last frame of the previous input, like
+    /// `gmx trjcat -settime` with `c`. Useful for runs whose time was reset
+    /// when restarting; combine with `drop_duplicates` to drop the repeated
+    /// frame. Disabled by default.
+    pub fn continue_time(mut self, continue_time: bool) -> Self {
+        self.continue_time = continue_time;
+        self
+    }
+
+    /// Skip frames at the start of an input whose time (after shifting) is
+    /// not later than the last frame written, e.g. the frame a run was
+    /// restarted from. Disabled by default.
+    pub fn drop_duplicates(mut self, drop_duplicates: bool) -> Self {
+        self.drop_duplicates = drop_duplicates;
+        self
+    }
+
+    /// Precision of the coordinates when writing an XTC file, see
+    /// `XTCTrajectory::set_precision`
+    pub fn precision(mut self, precision: f32) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+}
+
+/// Append the trajectories at `inputs` to a new trajectory at `output` and
+/// return the number of frames written. Formats are chosen by file
+/// extension, and all inputs must contain the same number of atoms.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let options = ConcatOptions::new().continue_time(true).drop_duplicates(true);
+/// let num_frames = concat(&["md.xtc", "md.part0002.xtc"], "all.xtc", options)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn concat<P: AsRef<Path>>(
+    inputs: &[P],
+    output: impl AsRef<Path>,
+    options: ConcatOptions,
+) -> Result<usize> {
+    if let Some(offsets) = &options.time_offsets {
+        if offsets.len() != inputs.len() {
+            return Err(Error::InvalidData(format!(
+                "{} time offsets for {} inputs",
+                offsets.len(),
+                inputs.len()
+            )));
+        }
+    }
+    let mut dst = open_output(output.as_ref(), options.precision)?;
+    let mut frame = Frame::new();
+    let mut num_atoms = None;
+    let mut last_time: Option<f32> = None;
+    let mut frames_written = 0;
+    for (i, input) in inputs.iter().enumerate() {
+        let mut src = open_by_extension(input.as_ref(), FileMode::Read)?;
+        let found = src.get_num_atoms()?;
+        match num_atoms {
+            Some(expected) if expected != found => {
+                return Err(Error::WrongSizeFrame { expected, found })
+            }
+            Some(_) => {}
+            None => {
+                num_atoms = Some(found);
+                frame = Frame::with_len(found);
+            }
+        }
+
+        let mut offset = options.time_offsets.as_ref().map(|offsets| offsets[i]);
+        let mut at_start = i > 0;
+        loop {
+            match src.read(&mut frame) {
+                Ok(()) => {}
+                Err(e) if e.is_eof() => break,
+                Err(e) => return Err(e),
+            }
+            let offset = *offset.get_or_insert(match last_time {
+                Some(last) if options.continue_time => last - frame.time,
+                _ => 0.0,
+            });
+            frame.time += offset;
+            let duplicate = last_time.is_some_and(|last| frame.time <= last);
+            if at_start && options.drop_duplicates && duplicate {
+                continue;
+            }
+            at_start = false;
+            dst.write(&frame)?;
+            last_time = Some(frame.time);
+            frames_written += 1;
+        }
+    }
+    dst.flush()?;
+    Ok(frames_written)
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn times(path: &Path) -> Result<Vec<f32>> {
+        XTCTrajectory::open_read(path)?
+            .into_frames()
+            .map(|frame| frame.map(|frame| frame.time))
+            .collect()
+    }
+
+    #[test]
+    fn test_concat() -> Result<()> {
+        let dir = TempDir::new()?;
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let expected: Vec<f32> = frames.iter().map(|frame| frame.time).collect();
+
+        // the second part restarts from the last frame of the first one
+        let first = dir.path().join("first.xtc");
+        let second = dir.path().join("second.trr");
+        let reset = dir.path().join("reset.xtc");
+        let mut xtc = XTCTrajectory::open_write(&first)?;
+        for frame in &frames[..21] {
+            xtc.write(frame)?;
+        }
+        drop(xtc);
+        let mut trr = TRRTrajectory::open_write(&second)?;
+        let mut xtc = XTCTrajectory::open_write(&reset)?;
+        for frame in &frames[20..] {
+            trr.write(frame)?;
+            let mut frame = frame.clone();
+            frame.time -= expected[20];
+            xtc.write(&frame)?;
+        }
+        drop(trr);
+        drop(xtc);
+
+        let out = dir.path().join("out.xtc");
+        let options = ConcatOptions::new().drop_duplicates(true);
+        assert_eq!(concat(&[&first, &second], &out, options)?, 38);
+        assert_eq!(times(&out)?, expected);
+        let written = XTCTrajectory::open_read(&out)?.into_frames();
+        for (frame, written) in frames.iter().zip(written) {
+            assert!(frame.approx_eq(&written?, 1e-3));
+        }
+
+        assert_eq!(concat(&[&first, &second], &out, ConcatOptions::new())?, 39);
+
+        let options = ConcatOptions::new()
+            .continue_time(true)
+            .drop_duplicates(true);
+        assert_eq!(concat(&[&first, &reset], &out, options)?, 38);
+        assert_eq!(times(&out)?, expected);
+
+        let options = ConcatOptions::new().time_offsets(vec![0.0, 100.0]);
+        assert_eq!(concat(&[&first, &reset], &out, options)?, 39);
+        assert_eq!(times(&out)?[21], 100.0);
+
+        let options = ConcatOptions::new().time_offsets(vec![0.0]);
+        assert!(concat(&[&first, &reset], &out, options).is_err());
+        let sub = dir.path().join("sub.xtc");
+        let mut xtc = XTCTrajectory::open_write(&sub)?;
+        xtc.write(&frames[0].subset(&[0, 1])?)?;
+        drop(xtc);
+        assert!(matches!(
+            concat(&[&first, &sub], &out, ConcatOptions::new()),
+            Err(Error::WrongSizeFrame { .. })
+        ));
+        Ok(())
+    }
+}
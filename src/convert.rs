@@ -0,0 +1,214 @@
+//! Converting trajectories between formats, e.g. a TRR into a smaller XTC
+
+use crate::chain::open_by_extension;
+use crate::*;
+
+/// Which frames and atoms `convert` copies, see `ConvertOptions::new`
+pub struct ConvertOptions {
+    stride: usize,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    atoms: Option<Vec<usize>>,
+    precision: Option<f32>,
+    progress: Option<Box<dyn FnMut(usize)>>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions::new()
+    }
+}
+
+impl ConvertOptions {
+    /// Copy all frames and atoms
+    pub fn new() -> ConvertOptions {
+        ConvertOptions {
+            stride: 1,
+            start_time: None,
+            end_time: None,
+            atoms: None,
+            precision: None,
+            progress: None,
+        }
+    }
+
+    /// Only copy every `stride`-th frame of the selected time range,
+    /// starting with the first. Panics if `stride` is 0.
+    pub fn stride(mut self, stride: usize) -> Self {
+        assert!(stride > 0, "stride must be at least 1");
+        self.stride = stride;
+        self
+    }
+
+    /// Skip frames before `time` (in ps)
+    pub fn start_time(mut self, time: f32) -> Self {
+        self.start_time = Some(time);
+        self
+    }
+
+    /// Stop at the first frame after `time` (in ps)
+    pub fn end_time(mut self, time: f32) -> Self {
+        self.end_time = Some(time);
+        self
+    }
+
+    /// Only copy the atoms at `indices`, e.g. a group from an index file
+    pub fn atoms(mut self, indices: Vec<usize>) -> Self {
+        self.atoms = Some(indices);
+        self
+    }
+
+    /// Precision of the coordinates when `convert_files` writes an XTC file,
+    /// see `XTCTrajectory::set_precision`. When calling `convert` directly,
+    /// set the precision on the output trajectory instead.
+    pub fn precision(mut self, precision: f32) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Call `callback` with the number of frames read so far after every
+    /// frame that was read
+    pub fn on_progress(mut self, callback: impl FnMut(usize) + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+}
+
+/// Copy the frames of `src` selected by `options` to `dst` and return the
+/// number of frames written. Data that the output format cannot store, like
+/// the velocities and forces of a TRR file written as XTC, is dropped.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let src = TRRTrajectory::open_read("traj.trr")?;
+/// let dst = XTCTrajectory::open_write("traj.xtc")?;
+/// let options = ConvertOptions::new().start_time(1000.0).stride(10);
+/// let num_frames = convert(src, dst, options)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert<S, D>(mut src: S, mut dst: D, mut options: ConvertOptions) -> Result<usize>
+where
+    S: Trajectory,
+    D: Trajectory,
+{
+    let mut frame = Frame::with_len(src.get_num_atoms()?);
+    let mut subset = Frame::new();
+    let mut frames_read = 0;
+    let mut frames_in_range = 0;
+    let mut frames_written = 0;
+    loop {
+        match src.read(&mut frame) {
+            Ok(()) => {}
+            Err(e) if e.is_eof() => break,
+            Err(e) => return Err(e),
+        }
+        frames_read += 1;
+        if let Some(progress) = options.progress.as_mut() {
+            progress(frames_read);
+        }
+        if options.start_time.is_some_and(|start| frame.time < start) {
+            continue;
+        }
+        if options.end_time.is_some_and(|end| frame.time > end) {
+            break;
+        }
+        frames_in_range += 1;
+        if (frames_in_range - 1) % options.stride != 0 {
+            continue;
+        }
+        match &options.atoms {
+            Some(indices) => {
+                frame.subset_into(indices, &mut subset)?;
+                dst.write(&subset)?;
+            }
+            None => dst.write(&frame)?,
+        }
+        frames_written += 1;
+    }
+    dst.flush()?;
+    Ok(frames_written)
+}
+
+/// Open a new trajectory at `dst` for writing, choosing the format by file
+/// extension, with the given precision if it is an XTC file
+pub(crate) fn open_output(dst: &Path, precision: Option<f32>) -> Result<Box<dyn Trajectory>> {
+    #[cfg(feature = "c-lib")]
+    let is_xtc = dst
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xtc"));
+    match precision {
+        #[cfg(feature = "c-lib")]
+        Some(precision) if is_xtc => {
+            let mut xtc = XTCTrajectory::open_write(dst)?;
+            xtc.set_precision(precision);
+            Ok(Box::new(xtc))
+        }
+        _ => open_by_extension(dst, FileMode::Write),
+    }
+}
+
+/// Convert the trajectory at `src` into a new trajectory at `dst`, choosing
+/// both formats by file extension (xtc or trr, or trj for the input)
+pub fn convert_files(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    options: ConvertOptions,
+) -> Result<usize> {
+    let src = open_by_extension(src.as_ref(), FileMode::Read)?;
+    let dst = open_output(dst.as_ref(), options.precision)?;
+    convert(src, dst, options)
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_convert() -> Result<()> {
+        let dir = TempDir::new()?;
+        let xtc = dir.path().join("1l2y.xtc");
+        let num_frames = convert_files("tests/1l2y.trr", &xtc, ConvertOptions::new())?;
+        assert_eq!(num_frames, 38);
+        let expected = TRRTrajectory::open_read("tests/1l2y.trr")?.into_frames();
+        let converted = XTCTrajectory::open_read(&xtc)?.into_frames();
+        for (expected, converted) in expected.zip(converted) {
+            assert!(expected?.approx_eq(&converted?, 1e-3));
+        }
+
+        let frames_read = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&frames_read);
+        let options = ConvertOptions::new()
+            .start_time(5.0)
+            .end_time(20.0)
+            .stride(5)
+            .atoms(vec![0, 2])
+            .on_progress(move |frames| counter.set(frames));
+        let src = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trr = dir.path().join("subset.trr");
+        assert_eq!(convert(src, TRRTrajectory::open_write(&trr)?, options)?, 4);
+        assert_eq!(frames_read.get(), 21);
+        let times: Vec<f32> = TRRTrajectory::open_read(&trr)?
+            .into_frames()
+            .map(|frame| frame.map(|frame| frame.time))
+            .collect::<Result<_>>()?;
+        assert_eq!(times, vec![5.0, 10.0, 15.0, 20.0]);
+        assert_eq!(TRRTrajectory::open_read(&trr)?.get_num_atoms()?, 2);
+
+        let coarse = dir.path().join("coarse.xtc");
+        convert_files(&xtc, &coarse, ConvertOptions::new().precision(10.0))?;
+        let frame = XTCTrajectory::open_read(&coarse)?
+            .into_frames()
+            .next()
+            .unwrap()?;
+        for x in frame.coords.iter().flatten() {
+            assert_approx_eq!((x * 10.0).round(), x * 10.0, 1e-4);
+        }
+        Ok(())
+    }
+}
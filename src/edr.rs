@@ -0,0 +1,456 @@
+//! Reading GROMACS energy (.edr) files
+//!
+//! Energy files store one value per energy term (potential energy,
+//! temperature, box size, ...) for every energy frame of a simulation.
+//! `read` returns them as one time series per term.
+//!
+//! ```no_run
+//! use xdrfile::*;
+//!
+//! # fn main() -> Result<()> {
+//! let energies = edr::read("ener.edr")?;
+//! if let Some(temperature) = energies.temperature() {
+//!     let mean = temperature.iter().sum::<f64>() / temperature.len() as f64;
+//!     println!("mean temperature: {} K", mean);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::xdr::XdrFile;
+use crate::*;
+use std::io::{Seek, SeekFrom};
+
+const HEADER_MAGIC: i32 = -55555;
+const FRAME_MAGIC: i32 = -7777777;
+const FRAME_SENTINEL: f32 = -7777777.0;
+const MAX_VERSION: i32 = 5;
+const MAX_STRING_LEN: usize = 4096;
+
+/// Name and unit of an energy term
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnergyTerm {
+    /// Name as shown by `gmx energy`, e.g. "Potential" or "Box-X"
+    pub name: String,
+
+    /// Unit, e.g. "kJ/mol" or "nm"
+    pub unit: String,
+}
+
+/// Contents of an energy file, with one time series per energy term
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Energies {
+    /// All energy terms, in the order of the file
+    pub terms: Vec<EnergyTerm>,
+
+    /// Time of each frame in ps
+    pub times: Vec<f64>,
+
+    /// Simulation step of each frame
+    pub steps: Vec<usize>,
+
+    /// Values of each term, indexed by term and then by frame. Frames that
+    /// contain no energies store NaN.
+    values: Vec<Vec<f64>>,
+}
+
+impl Energies {
+    /// Get the number of frames
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Check if the file contained no frames
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Get the values of the term called `name` for all frames
+    pub fn series(&self, name: &str) -> Option<&[f64]> {
+        let index = self.terms.iter().position(|term| term.name == name)?;
+        Some(&self.values[index])
+    }
+
+    /// Get the unit of the term called `name`
+    pub fn unit(&self, name: &str) -> Option<&str> {
+        let term = self.terms.iter().find(|term| term.name == name)?;
+        Some(&term.unit)
+    }
+
+    /// Potential energy in kJ/mol
+    pub fn potential(&self) -> Option<&[f64]> {
+        self.series("Potential")
+    }
+
+    /// Kinetic energy in kJ/mol
+    pub fn kinetic(&self) -> Option<&[f64]> {
+        self.series("Kinetic En.")
+    }
+
+    /// Total energy in kJ/mol
+    pub fn total(&self) -> Option<&[f64]> {
+        self.series("Total Energy")
+    }
+
+    /// Temperature in K
+    pub fn temperature(&self) -> Option<&[f64]> {
+        self.series("Temperature")
+    }
+
+    /// Pressure in bar
+    pub fn pressure(&self) -> Option<&[f64]> {
+        self.series("Pressure")
+    }
+
+    /// Volume in nm^3
+    pub fn volume(&self) -> Option<&[f64]> {
+        self.series("Volume")
+    }
+
+    /// Box of each frame, from the Box-X/Y/Z terms of rectangular boxes or
+    /// the Box-XX/YY/ZZ/YX/ZX/ZY terms of triclinic boxes. Returns `None`
+    /// for simulations at constant volume, whose boxes are not stored.
+    pub fn boxes(&self) -> Option<Vec<BoxVector>> {
+        if let (Some(x), Some(y), Some(z)) = (
+            self.series("Box-X"),
+            self.series("Box-Y"),
+            self.series("Box-Z"),
+        ) {
+            let boxes = (0..self.len())
+                .map(|i| BoxVector::rectangular(x[i] as f32, y[i] as f32, z[i] as f32))
+                .collect();
+            return Some(boxes);
+        }
+        let names = ["Box-XX", "Box-YY", "Box-ZZ", "Box-YX", "Box-ZX", "Box-ZY"];
+        let series = names
+            .iter()
+            .map(|name| self.series(name))
+            .collect::<Option<Vec<_>>>()?;
+        let boxes = (0..self.len())
+            .map(|i| {
+                let v = |term: usize| series[term][i] as f32;
+                BoxVector::new([[v(0), 0.0, 0.0], [v(3), v(1), 0.0], [v(4), v(5), v(2)]])
+            })
+            .collect();
+        Some(boxes)
+    }
+}
+
+/// Read the energy file at `path`. Files written by GROMACS in single and
+/// double precision are supported, but not those of GROMACS versions older
+/// than 4.0. Additional data blocks, e.g. of free energy calculations, are
+/// skipped.
+pub fn read(path: impl AsRef<Path>) -> Result<Energies> {
+    let mut reader = EdrReader {
+        file: XdrFile::open_read(path)?,
+        double: false,
+    };
+    let terms = reader.read_header()?;
+    let mut energies = Energies {
+        values: vec![Vec::new(); terms.len()],
+        terms,
+        ..Energies::default()
+    };
+    while reader.read_frame(&mut energies)? {}
+    Ok(energies)
+}
+
+/// Type of the values in a sub-block of a frame
+#[derive(Clone, Copy)]
+enum SubBlockType {
+    Int,
+    Float,
+    Double,
+    Int64,
+    Char,
+    String,
+}
+
+impl SubBlockType {
+    fn from_i32(value: i32) -> Result<SubBlockType> {
+        Ok(match value {
+            0 => SubBlockType::Int,
+            1 => SubBlockType::Float,
+            2 => SubBlockType::Double,
+            3 => SubBlockType::Int64,
+            4 => SubBlockType::Char,
+            5 => SubBlockType::String,
+            _ => return Err(invalid(format!("unknown block type {}", value))),
+        })
+    }
+}
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::InvalidData(format!("edr: {}", message))
+}
+
+struct EdrReader {
+    file: XdrFile,
+    /// Whether reals are stored in double precision
+    double: bool,
+}
+
+impl EdrReader {
+    fn read_real(&mut self) -> Result<f64> {
+        if self.double {
+            self.file.read_f64()
+        } else {
+            self.file.read_f32().map(f64::from)
+        }
+    }
+
+    fn read_i64(&mut self) -> Result<i64> {
+        let high = self.file.read_i32()?;
+        let low = self.file.read_u32()?;
+        Ok((i64::from(high) << 32) | i64::from(low))
+    }
+
+    fn read_count(&mut self) -> Result<usize> {
+        let count = self.file.read_i32()?;
+        usize::try_from(count).map_err(|_| invalid(format!("negative count {}", count)))
+    }
+
+    /// Read the names and units of the energy terms
+    fn read_header(&mut self) -> Result<Vec<EnergyTerm>> {
+        let magic = self.file.read_i32()?;
+        if magic > 0 {
+            return Err(Error::Unsupported(
+                "energy files of GROMACS versions before 4.0",
+            ));
+        } else if magic != HEADER_MAGIC {
+            return Err(invalid("not an energy file"));
+        }
+        let version = self.file.read_i32()?;
+        if !(1..=MAX_VERSION).contains(&version) {
+            return Err(invalid(format!("unsupported file version {}", version)));
+        }
+        let num_terms = self.read_count()?;
+        let mut terms = Vec::with_capacity(num_terms);
+        for _ in 0..num_terms {
+            let name = self.file.read_string(MAX_STRING_LEN)?;
+            let unit = if version >= 2 {
+                self.file.read_string(MAX_STRING_LEN)?
+            } else {
+                "kJ/mol".to_string()
+            };
+            terms.push(EnergyTerm { name, unit });
+        }
+        Ok(terms)
+    }
+
+    /// Detect the precision from the sentinel at the start of a frame.
+    /// Returns false at the end of the file.
+    fn read_sentinel(&mut self) -> Result<bool> {
+        let start = self.file.tell()?;
+        match self.file.read_f32() {
+            Ok(FRAME_SENTINEL) => {
+                self.double = false;
+                return Ok(true);
+            }
+            Ok(_) => {}
+            Err(e) if e.is_eof() => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        self.file.seek(SeekFrom::Start(start))?;
+        match self.file.read_f64() {
+            Ok(sentinel) if sentinel == f64::from(FRAME_SENTINEL) => {
+                self.double = true;
+                Ok(true)
+            }
+            Ok(_) => Err(Error::Unsupported(
+                "energy frames of GROMACS versions before 4.0",
+            )),
+            Err(e) if e.is_eof() => Err(invalid("truncated frame")),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the next frame into `energies`. Returns false at the end of the
+    /// file.
+    fn read_frame(&mut self, energies: &mut Energies) -> Result<bool> {
+        if !self.read_sentinel()? {
+            return Ok(false);
+        }
+        self.read_frame_body(energies).map_err(|e| {
+            if e.is_eof() {
+                invalid("truncated frame")
+            } else {
+                e
+            }
+        })?;
+        Ok(true)
+    }
+
+    fn read_frame_body(&mut self, energies: &mut Energies) -> Result<()> {
+        if self.file.read_i32()? != FRAME_MAGIC {
+            return Err(invalid("wrong frame magic number"));
+        }
+        let version = self.file.read_i32()?;
+        if !(1..=MAX_VERSION).contains(&version) {
+            return Err(invalid(format!("unsupported frame version {}", version)));
+        }
+        let time = self.file.read_f64()?;
+        let step = to(self.read_i64()?, ErrorTask::Read, "step")?;
+        let num_sums = self.file.read_i32()?;
+        if version >= 3 {
+            self.read_i64()?; // number of steps in the sums
+        }
+        if version >= 5 {
+            self.file.read_f64()?; // time step
+        }
+        let num_terms = self.read_count()?;
+        let num_disres = self.file.read_i32()?;
+        if version < 4 && num_disres != 0 {
+            return Err(Error::Unsupported(
+                "distance restraints in energy files before version 4",
+            ));
+        }
+        let num_blocks = self.read_count()?;
+
+        // sizes of all sub-blocks
+        let mut blocks = Vec::with_capacity(num_blocks);
+        for _ in 0..num_blocks {
+            if version >= 4 {
+                self.file.read_i32()?; // block id
+                let num_subblocks = self.read_count()?;
+                for _ in 0..num_subblocks {
+                    let kind = SubBlockType::from_i32(self.file.read_i32()?)?;
+                    blocks.push((kind, self.read_count()?));
+                }
+            } else {
+                let kind = if self.double {
+                    SubBlockType::Double
+                } else {
+                    SubBlockType::Float
+                };
+                blocks.push((kind, self.read_count()?));
+            }
+        }
+        self.file.read_i32()?; // size of the energies
+        self.file.read_i32()?; // reserved
+        self.file.read_i32()?; // reserved
+
+        if num_terms != 0 && num_terms != energies.terms.len() {
+            return Err(invalid(format!(
+                "frame has {} energy terms instead of {}",
+                num_terms,
+                energies.terms.len()
+            )));
+        }
+        for values in energies.values.iter_mut().take(num_terms) {
+            values.push(self.read_real()?);
+            if version == 1 || num_sums > 0 {
+                self.read_real()?; // average
+                self.read_real()?; // sum
+                if version == 1 {
+                    self.read_real()?;
+                }
+            }
+        }
+        if num_terms == 0 {
+            for values in energies.values.iter_mut() {
+                values.push(f64::NAN);
+            }
+        }
+
+        for (kind, count) in blocks {
+            self.skip_subblock(kind, count)?;
+        }
+        energies.times.push(time);
+        energies.steps.push(step);
+        Ok(())
+    }
+
+    fn skip_subblock(&mut self, kind: SubBlockType, count: usize) -> Result<()> {
+        let value_size = match kind {
+            SubBlockType::Int | SubBlockType::Float | SubBlockType::Char => 4,
+            SubBlockType::Double | SubBlockType::Int64 => 8,
+            SubBlockType::String => {
+                for _ in 0..count {
+                    self.file.read_i32()?; // length including the nul byte
+                    self.file.read_string(MAX_STRING_LEN)?;
+                }
+                return Ok(());
+            }
+        };
+        let num_bytes = to(count * value_size, ErrorTask::Read, "block size")?;
+        self.file.seek(SeekFrom::Current(num_bytes))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    const TERMS: [(&str, &str); 6] = [
+        ("Potential", "kJ/mol"),
+        ("Temperature", "K"),
+        ("Box-X", "nm"),
+        ("Box-Y", "nm"),
+        ("Box-Z", "nm"),
+        ("Pressure", "bar"),
+    ];
+
+    fn write_i64(file: &mut XdrFile, value: i64) -> Result<()> {
+        file.write_i32((value >> 32) as i32)?;
+        file.write_u32(value as u32)
+    }
+
+    /// Write a version 5 energy file in single precision
+    fn write_edr(file: &mut XdrFile, frames: &[(f64, i64, [f32; 6])]) -> Result<()> {
+        file.write_i32s(&[HEADER_MAGIC, 5, TERMS.len() as i32])?;
+        for (name, unit) in TERMS.iter() {
+            file.write_string(name)?;
+            file.write_string(unit)?;
+        }
+        for (time, step, values) in frames {
+            file.write_f32(FRAME_SENTINEL)?;
+            file.write_i32s(&[FRAME_MAGIC, 5])?;
+            file.write_f64(*time)?;
+            write_i64(file, *step)?;
+            file.write_i32(0)?; // sums
+            write_i64(file, 0)?;
+            file.write_f64(0.002)?;
+            // terms, reserved, one block with an id and two sub-blocks
+            file.write_i32s(&[TERMS.len() as i32, 0, 1, 7, 2])?;
+            file.write_i32s(&[2, 3, 5, 1])?; // three doubles and a string
+            file.write_i32s(&[TERMS.len() as i32 * 4, 0, 0])?;
+            file.write_f32s(values)?;
+            file.write_f64s(&[1.0, 2.0, 3.0])?;
+            file.write_i32(6)?;
+            file.write_string("lambda")?;
+        }
+        file.flush()
+    }
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let frames = [
+            (0.0, 0, [-1000.0, 300.0, 3.0, 3.0, 3.0, 1.0]),
+            (1.0, 500, [-1010.0, 301.5, 3.1, 3.2, 3.3, -2.0]),
+        ];
+        write_edr(&mut XdrFile::open_write(tempfile.path())?, &frames)?;
+
+        let energies = read(tempfile.path())?;
+        assert_eq!(energies.len(), 2);
+        assert_eq!(energies.terms.len(), 6);
+        assert_eq!(energies.times, vec![0.0, 1.0]);
+        assert_eq!(energies.steps, vec![0, 500]);
+        assert_eq!(energies.potential(), Some(&[-1000.0, -1010.0][..]));
+        assert_eq!(energies.temperature(), Some(&[300.0, 301.5][..]));
+        assert_eq!(energies.pressure(), Some(&[1.0, -2.0][..]));
+        assert_eq!(energies.unit("Temperature"), Some("K"));
+        assert_eq!(energies.kinetic(), None);
+        let boxes = energies.boxes().unwrap();
+        assert_eq!(boxes[1], BoxVector::rectangular(3.1, 3.2, 3.3));
+
+        // truncated frames are errors
+        let bytes = std::fs::read(tempfile.path())?;
+        std::fs::write(tempfile.path(), &bytes[..bytes.len() - 8])?;
+        assert!(matches!(read(tempfile.path()), Err(Error::InvalidData(_))));
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
+#[cfg(feature = "c-lib")]
 use crate::c_abi;
+use crate::BoxVector;
 use crate::FileMode;
 use crate::Frame;
+use crate::FrameDifference;
 use std::error::Error as StdError;
 use std::path::{Path, PathBuf};
 
@@ -24,6 +27,57 @@ pub enum Error {
         value: String,
         target: &'static str,
     },
+    /// An atom index does not exist in the frame
+    AtomIndexOutOfBounds { index: usize, num_atoms: usize },
+    /// A frame index does not exist in the trajectory
+    FrameIndexOutOfBounds { index: usize, num_frames: usize },
+    /// Two frames that need to share a unit cell have different boxes
+    IncompatibleBoxes { left: BoxVector, right: BoxVector },
+    /// A per-atom array does not have one entry per atom
+    LengthMismatch {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// A frame did not pass validation
+    ValidationFailed(ValidationError),
+    /// An I/O error outside of the C API
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    /// The contents of a file do not match the expected format
+    InvalidData(String),
+    /// The operation is not supported by this kind of trajectory
+    Unsupported(&'static str),
+    /// An argument is outside of the values the operation accepts, like a
+    /// histogram without bins
+    InvalidArgument(&'static str),
+    /// The frames at `index` of two trajectories that are compared do not match
+    FramesDiffer {
+        index: usize,
+        difference: FrameDifference,
+    },
+    /// Reading or writing the frame at byte `offset` of the file at `path`
+    /// failed. `frame` is the number of the frame, if known (it is not after
+    /// seeking or when appending). The path is empty for streams.
+    InFrame {
+        path: PathBuf,
+        offset: u64,
+        frame: Option<usize>,
+        source: Box<Error>,
+    },
+    /// A previous frame could not be read or written, so the position in
+    /// the file is unknown. Rewind, seek or reset the trajectory to continue.
+    HandlePoisoned(Box<Error>),
+    /// The file claims more atoms than the limit set with `set_max_atoms`
+    TooManyAtoms { num_atoms: usize, max_atoms: usize },
+    /// A cutoff is longer than half the smallest width of the box, so the
+    /// minimum image of a pair of atoms within it is not unique
+    CutoffTooLarge { cutoff: f32, max: f32 },
+    /// An atom selection could not be parsed; `position` is the byte offset
+    /// of the problem in the expression
+    InvalidSelection { position: usize, message: String },
 }
 
 impl Error {
@@ -53,6 +107,60 @@ impl Error {
     pub fn is_eof(&self) -> bool {
         self.code().map_or(false, |e| e.is_eof())
     }
+
+    /// True if the data in the file is damaged or invalid, e.g. has a wrong
+    /// magic number, cannot be decoded or fails validation in strict mode.
+    /// Skipping to the next intact frame may work, retrying will not.
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            Error::CApiError { code, .. } => code.is_corruption(),
+            Error::OutOfRange { task, .. } => {
+                matches!(task, ErrorTask::Read | ErrorTask::ReadNumAtoms)
+            }
+            Error::InvalidData(_) | Error::TooManyAtoms { .. } | Error::ValidationFailed(_) => true,
+            Error::CouldNotCheckNAtoms(source) | Error::InFrame { source, .. } => {
+                source.is_corruption()
+            }
+            _ => false,
+        }
+    }
+
+    /// True if the file could not be opened, closed, read or written by the
+    /// operating system, e.g. because it does not exist or the disk is full.
+    /// Such errors may go away when retrying.
+    pub fn is_io(&self) -> bool {
+        match self {
+            Error::CApiError { code, .. } => code.is_io(),
+            Error::CouldNotOpen { .. } | Error::Io { .. } => true,
+            Error::CouldNotCheckNAtoms(source) | Error::InFrame { source, .. } => source.is_io(),
+            _ => false,
+        }
+    }
+
+    /// True if the library was used in a way that cannot work, e.g. with a
+    /// frame of the wrong size, an index out of bounds, an unsupported
+    /// operation, an invalid argument or a poisoned handle. Neither retrying
+    /// nor skipping helps.
+    pub fn is_usage_error(&self) -> bool {
+        match self {
+            Error::OutOfRange { task, .. } => {
+                !matches!(task, ErrorTask::Read | ErrorTask::ReadNumAtoms)
+            }
+            Error::WrongSizeFrame { .. }
+            | Error::InvalidOsStr(_)
+            | Error::AtomIndexOutOfBounds { .. }
+            | Error::FrameIndexOutOfBounds { .. }
+            | Error::IncompatibleBoxes { .. }
+            | Error::LengthMismatch { .. }
+            | Error::Unsupported(_)
+            | Error::InvalidArgument(_)
+            | Error::HandlePoisoned(_)
+            | Error::CutoffTooLarge { .. }
+            | Error::InvalidSelection { .. } => true,
+            Error::InFrame { source, .. } => source.is_usage_error(),
+            _ => false,
+        }
+    }
 }
 
 impl std::error::Error for Error {
@@ -66,6 +174,9 @@ impl std::error::Error for Error {
                 }
             }
             Error::CouldNotCheckNAtoms(err) => Some(err.as_ref()),
+            Error::ValidationFailed(err) => Some(err),
+            Error::InFrame { source, .. } => Some(source.as_ref()),
+            Error::HandlePoisoned(cause) => Some(cause.as_ref()),
             _ => None,
         }
     }
@@ -132,6 +243,139 @@ impl std::fmt::Display for Error {
                 value = value,
                 target = target
             ),
+            Error::AtomIndexOutOfBounds { index, num_atoms } => write!(
+                f,
+                "Atom index {} is out of bounds for a frame with {} atoms",
+                index, num_atoms
+            ),
+            Error::FrameIndexOutOfBounds { index, num_frames } => write!(
+                f,
+                "Frame index {} is out of bounds for a trajectory with {} frames",
+                index, num_frames
+            ),
+            Error::IncompatibleBoxes { left, right } => write!(
+                f,
+                "Frames have incompatible boxes {:?} and {:?}",
+                left.0, right.0
+            ),
+            Error::LengthMismatch {
+                name,
+                expected,
+                found,
+            } => write!(f, "Expected {} {}, found {}", expected, name, found),
+            Error::ValidationFailed(err) => write!(f, "Frame failed validation: {}", err),
+            Error::Io { message, .. } => write!(f, "I/O error: {}", message),
+            Error::InvalidData(message) => write!(f, "Invalid data: {}", message),
+            Error::Unsupported(operation) => write!(f, "Unsupported operation: {}", operation),
+            Error::InvalidArgument(message) => write!(f, "Invalid argument: {}", message),
+            Error::FramesDiffer { index, difference } => {
+                write!(f, "Frames at index {} differ: {}", index, difference)
+            }
+            Error::InFrame {
+                path,
+                offset,
+                frame,
+                source,
+            } => {
+                write!(f, "{}", source)?;
+                if let Some(frame) = frame {
+                    write!(f, " in frame {}", frame)?;
+                }
+                write!(f, " at byte {}", offset)?;
+                if !path.as_os_str().is_empty() {
+                    write!(f, " of {}", path.display())?;
+                }
+                Ok(())
+            }
+            Error::HandlePoisoned(cause) => write!(
+                f,
+                "Trajectory cannot be used after a failed frame ({}), rewind it first",
+                cause
+            ),
+            Error::TooManyAtoms {
+                num_atoms,
+                max_atoms,
+            } => write!(
+                f,
+                "File claims {} atoms, more than the limit of {}; its header is probably corrupt",
+                num_atoms, max_atoms
+            ),
+            Error::CutoffTooLarge { cutoff, max } => write!(
+                f,
+                "Cutoff {} is longer than half the box width {}",
+                cutoff, max
+            ),
+            Error::InvalidSelection { position, message } => {
+                write!(f, "Invalid selection at position {}: {}", position, message)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        // errors of this crate passed through `io::Seek` and the like
+        if let Some(err) = err.get_ref().and_then(|e| e.downcast_ref::<Error>()) {
+            return err.clone();
+        }
+        Error::Io {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Error::ValidationFailed(err)
+    }
+}
+
+/// Problems with the contents of a frame detected by `Frame::validate` or
+/// when reading in strict mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The coordinates of an atom contain NaN or infinity
+    NonFiniteCoordinates { index: usize },
+    /// The time is NaN or infinite
+    NonFiniteTime(f32),
+    /// The time is negative
+    NegativeTime(f32),
+    /// The box vector contains NaN or infinity
+    NonFiniteBox(BoxVector),
+    /// All box vectors are zero
+    ZeroBox,
+    /// The box vectors do not span a volume
+    DegenerateBox(BoxVector),
+    /// The step is not larger than the one of the previous frame read
+    StepNotIncreasing { previous: usize, step: usize },
+    /// The time is not later than the one of the previous frame read
+    TimeNotIncreasing { previous: f32, time: f32 },
+}
+
+impl std::error::Error for ValidationError {}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::NonFiniteCoordinates { index } => {
+                write!(f, "Coordinates of atom {} are not finite", index)
+            }
+            ValidationError::NonFiniteTime(time) => write!(f, "Time {} is not finite", time),
+            ValidationError::NegativeTime(time) => write!(f, "Time {} is negative", time),
+            ValidationError::NonFiniteBox(box_vector) => {
+                write!(f, "Box vector {:?} is not finite", box_vector.0)
+            }
+            ValidationError::ZeroBox => write!(f, "Box vector is zero"),
+            ValidationError::DegenerateBox(box_vector) => {
+                write!(f, "Box vector {:?} has no volume", box_vector.0)
+            }
+            ValidationError::StepNotIncreasing { previous, step } => {
+                write!(f, "Step {} follows step {}", step, previous)
+            }
+            ValidationError::TimeNotIncreasing { previous, time } => {
+                write!(f, "Time {} follows time {}", time, previous)
+            }
         }
     }
 }
@@ -149,6 +393,8 @@ pub enum ErrorTask {
     Flush,
     /// A seek operation was being run on a file
     Seek,
+    /// The frames of a file were being indexed
+    Index,
 }
 
 impl std::fmt::Display for ErrorTask {
@@ -159,12 +405,14 @@ impl std::fmt::Display for ErrorTask {
             ErrorTask::Write => write!(f, "writing trajectory"),
             ErrorTask::Flush => write!(f, "flushing trajectory"),
             ErrorTask::Seek => write!(f, "seeking in trajectory"),
+            ErrorTask::Index => write!(f, "indexing trajectory"),
         }
     }
 }
 
 /// Error codes returned from the C API
 #[derive(Debug, Clone, PartialEq, Copy)]
+#[non_exhaustive]
 pub enum ErrorCode {
     /// No error, C API returned successfully
     ExdrOk,
@@ -203,8 +451,32 @@ impl ErrorCode {
     pub fn is_eof(self) -> bool {
         matches!(self, Self::ExdrEndOfFile)
     }
+
+    /// True if the contents of the file could not be decoded
+    pub fn is_corruption(self) -> bool {
+        matches!(
+            self,
+            Self::ExdrHeader
+                | Self::ExdrString
+                | Self::ExdrDouble
+                | Self::ExdrInt
+                | Self::ExdrFloat
+                | Self::ExdrUint
+                | Self::Exdr3dx
+                | Self::ExdrMagic
+        )
+    }
+
+    /// True if opening, closing or seeking the file failed
+    pub fn is_io(self) -> bool {
+        matches!(
+            self,
+            Self::ExdrClose | Self::ExdrFileNotFound | Self::ExdrNr
+        )
+    }
 }
 
+#[cfg(feature = "c-lib")]
 impl From<i32> for ErrorCode {
     fn from(code: i32) -> Self {
         match code {
@@ -240,10 +512,47 @@ impl std::fmt::Display for ErrorCode {
 /// `Result` type for errors in the `xdrfile` crate
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "c-lib"))]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_classification() {
+        let magic: Error = (ErrorCode::ExdrMagic, ErrorTask::Read).into();
+        let not_found: Error = (ErrorCode::ExdrFileNotFound, ErrorTask::Read).into();
+        let eof: Error = (ErrorCode::ExdrEndOfFile, ErrorTask::Read).into();
+        let in_frame = Error::InFrame {
+            path: PathBuf::from("traj.xtc"),
+            offset: 0,
+            frame: Some(0),
+            source: Box::new(magic.clone()),
+        };
+        let poisoned = Error::HandlePoisoned(Box::new(in_frame.clone()));
+        let wrong_size = Error::WrongSizeFrame {
+            expected: 2,
+            found: 3,
+        };
+        let io_error: Error = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+
+        let classify = |e: &Error| (e.is_eof(), e.is_corruption(), e.is_io(), e.is_usage_error());
+        assert_eq!(classify(&magic), (false, true, false, false));
+        assert_eq!(classify(&in_frame), (false, true, false, false));
+        assert_eq!(classify(&not_found), (false, false, true, false));
+        assert_eq!(classify(&io_error), (false, false, true, false));
+        assert_eq!(classify(&eof), (true, false, false, false));
+        assert_eq!(classify(&poisoned), (false, false, false, true));
+        assert_eq!(classify(&wrong_size), (false, false, false, true));
+        let invalid = Error::InvalidArgument("histograms need at least one bin");
+        assert_eq!(classify(&invalid), (false, false, false, true));
+
+        let negative_step = crate::to::<i32, usize>(-1, ErrorTask::Read, "step").unwrap_err();
+        assert!(negative_step.is_corruption());
+        let huge_step = crate::to::<usize, i32>(usize::MAX, ErrorTask::Write, "step").unwrap_err();
+        assert!(huge_step.is_usage_error());
+        assert!(ErrorCode::from(c_abi::xdrfile::exdrNR).is_io());
+        assert!(!ErrorCode::from(255).is_corruption());
+    }
+
     #[test]
     fn test_is_eof() {
         let error = Error::CApiError {
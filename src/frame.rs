@@ -1,3 +1,6 @@
+use crate::errors::*;
+use crate::linalg;
+use crate::BoxVector;
 use std::ops::{Index, IndexMut};
 
 /// A frame represents a single step in a trajectory.
@@ -10,10 +13,16 @@ pub struct Frame {
     pub time: f32,
 
     /// 3x3 box vector
-    pub box_vector: [[f32; 3]; 3],
+    pub box_vector: BoxVector,
 
     /// 3D coordinates for N atoms where N is num_atoms
     pub coords: Vec<[f32; 3]>,
+
+    /// Velocities for N atoms, if the trajectory format stores them
+    pub velocities: Option<Vec<[f32; 3]>>,
+
+    /// Forces for N atoms, if the trajectory format stores them
+    pub forces: Option<Vec<[f32; 3]>>,
 }
 
 impl Default for Frame {
@@ -21,8 +30,10 @@ impl Default for Frame {
         Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
+            box_vector: BoxVector::default(),
             coords: Vec::with_capacity(0),
+            velocities: None,
+            forces: None,
         }
     }
 }
@@ -42,14 +53,20 @@ impl Frame {
     }
 
     /// Filters the frame by removing all atoms not matching the given indeces.
+    /// Velocities and forces, if present, are filtered the same way.
     pub fn filter_coords(self: &mut Frame, indices: &[usize]) {
-        self.coords = self
-            .coords
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| indices.contains(i))
-            .map(|(_, elem)| *elem)
-            .collect();
+        let filter = |values: &[[f32; 3]]| -> Vec<[f32; 3]> {
+            values
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| indices.contains(i))
+                .map(|(_, elem)| *elem)
+                .collect()
+        };
+        self.coords = filter(&self.coords);
+        for values in self.velocities.iter_mut().chain(self.forces.iter_mut()) {
+            *values = filter(values);
+        }
     }
 
     /// Length of the frame (number of atoms)
@@ -62,9 +79,655 @@ impl Frame {
         self.coords.len()
     }
 
-    /// Resize the frame to have exactly `num_atoms` atoms, filling coords with zeros if necessary
+    /// Resize the frame to have exactly `num_atoms` atoms, filling coords with zeros if necessary.
+    ///
+    /// Velocities and forces, if present, are resized along with the
+    /// coordinates. Shrinking keeps the allocated capacity, so a frame can be
+    /// reused for trajectories with different numbers of atoms.
     pub fn resize(&mut self, num_atoms: usize) {
-        self.coords.resize(num_atoms, [0.0; 3])
+        self.coords.resize(num_atoms, [0.0; 3]);
+        for values in self.velocities.iter_mut().chain(self.forces.iter_mut()) {
+            values.resize(num_atoms, [0.0; 3]);
+        }
+    }
+
+    /// View the coordinates as one contiguous slice `[x0, y0, z0, x1, ...]`
+    /// of length `3 * len()` without copying
+    pub fn as_flat(&self) -> &[f32] {
+        // [f32; 3] has the size of three f32 and no padding, so a slice of
+        // N positions is a valid slice of 3N floats
+        unsafe {
+            std::slice::from_raw_parts(self.coords.as_ptr() as *const f32, self.coords.len() * 3)
+        }
+    }
+
+    /// Mutable version of `as_flat`
+    pub fn as_flat_mut(&mut self) -> &mut [f32] {
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.coords.as_mut_ptr() as *mut f32,
+                self.coords.len() * 3,
+            )
+        }
+    }
+
+    /// View the coordinates as `glam` vectors without copying
+    #[cfg(feature = "glam")]
+    pub fn coords_as_vec3(&self) -> &[glam::Vec3] {
+        // glam::Vec3 is a #[repr(C)] struct of three f32, just like [f32; 3]
+        unsafe {
+            std::slice::from_raw_parts(self.coords.as_ptr() as *const glam::Vec3, self.coords.len())
+        }
+    }
+
+    /// Mutable version of `coords_as_vec3`
+    #[cfg(feature = "glam")]
+    pub fn coords_as_vec3_mut(&mut self) -> &mut [glam::Vec3] {
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.coords.as_mut_ptr() as *mut glam::Vec3,
+                self.coords.len(),
+            )
+        }
+    }
+
+    /// Replace the coordinates with the given `glam` vectors, resizing the
+    /// frame if necessary
+    #[cfg(feature = "glam")]
+    pub fn set_coords_from_vec3(&mut self, positions: &[glam::Vec3]) {
+        self.resize(positions.len());
+        self.coords_as_vec3_mut().copy_from_slice(positions);
+    }
+
+    /// Iterate over the atoms of the frame as `(index, position)` pairs
+    pub fn iter_atoms(&self) -> std::iter::Enumerate<std::slice::Iter<'_, [f32; 3]>> {
+        self.coords.iter().enumerate()
+    }
+
+    /// Iterate over the atoms of the frame as `(index, position)` pairs,
+    /// allowing the positions to be modified
+    pub fn iter_atoms_mut(&mut self) -> std::iter::Enumerate<std::slice::IterMut<'_, [f32; 3]>> {
+        self.coords.iter_mut().enumerate()
+    }
+
+    /// Parallel version of `iter_atoms`
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_atoms(&self) -> rayon::iter::Enumerate<rayon::slice::Iter<'_, [f32; 3]>> {
+        use rayon::prelude::*;
+        self.coords.par_iter().enumerate()
+    }
+
+    /// Parallel version of `iter_atoms_mut`
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_atoms_mut(
+        &mut self,
+    ) -> rayon::iter::Enumerate<rayon::slice::IterMut<'_, [f32; 3]>> {
+        use rayon::prelude::*;
+        self.coords.par_iter_mut().enumerate()
+    }
+
+    /// Remove all atoms and reset step, time and box vector, keeping the
+    /// allocated buffers for reuse
+    pub fn clear(&mut self) {
+        self.step = 0;
+        self.time = 0.0;
+        self.box_vector = BoxVector::default();
+        self.resize(0);
+    }
+
+    /// Euclidean distance between atoms `i` and `j`, ignoring periodic boundaries
+    pub fn distance(&self, i: usize, j: usize) -> Result<f32> {
+        self.check_indices(&[i, j])?;
+        Ok(norm(sub(self.coords[j], self.coords[i])))
+    }
+
+    /// Distance between atoms `i` and `j` under the minimum image convention
+    /// of the frame's (possibly triclinic) box. Falls back to `distance` if
+    /// the box has no volume.
+    pub fn distance_pbc(&self, i: usize, j: usize) -> Result<f32> {
+        self.check_indices(&[i, j])?;
+        let d = sub(self.coords[j], self.coords[i]);
+        Ok(norm(self.box_vector.minimum_image(d)))
+    }
+
+    /// Angle in degrees between the bonds `j`-`i` and `j`-`k` under the
+    /// minimum image convention, like `gmx angle`
+    pub fn angle_pbc(&self, i: usize, j: usize, k: usize) -> Result<f32> {
+        self.check_indices(&[i, j, k])?;
+        let a = self.bond_pbc(j, i);
+        let b = self.bond_pbc(j, k);
+        let cos = dot(a, b) / (dot(a, a) * dot(b, b)).sqrt();
+        Ok(cos.clamp(-1.0, 1.0).acos().to_degrees() as f32)
+    }
+
+    /// Dihedral angle in degrees between the planes `i`-`j`-`k` and
+    /// `j`-`k`-`l` under the minimum image convention, in the range -180 to
+    /// 180 with the IUPAC sign convention, like `gmx angle -type dihedral`
+    pub fn dihedral_pbc(&self, i: usize, j: usize, k: usize, l: usize) -> Result<f32> {
+        self.check_indices(&[i, j, k, l])?;
+        let b1 = self.bond_pbc(i, j);
+        let b2 = self.bond_pbc(j, k);
+        let b3 = self.bond_pbc(k, l);
+        let n1 = cross(b1, b2);
+        let n2 = cross(b2, b3);
+        let y = dot(b2, b2).sqrt() * dot(b1, n2);
+        Ok(y.atan2(dot(n1, n2)).to_degrees() as f32)
+    }
+
+    /// Minimum image of the vector from atom `i` to atom `j`
+    fn bond_pbc(&self, i: usize, j: usize) -> [f64; 3] {
+        let d = self.box_vector.minimum_image(sub(self.coords[j], self.coords[i]));
+        d.map(f64::from)
+    }
+
+    /// Root mean square deviation between the coordinates of two frames.
+    ///
+    /// Both frames must contain the same number of atoms. No fitting is
+    /// performed and periodic boundaries are ignored; see `rmsd_with`.
+    pub fn rmsd(&self, other: &Frame) -> Result<f32> {
+        self.rmsd_with(other, None, false)
+    }
+
+    /// Root mean square deviation between two frames, optionally restricted
+    /// to the atoms in `mask`.
+    ///
+    /// If `pbc` is true, each displacement is reduced to its minimum image
+    /// using the box vector of `self`.
+    pub fn rmsd_with(&self, other: &Frame, mask: Option<&[usize]>, pbc: bool) -> Result<f32> {
+        if self.len() != other.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.len(),
+                found: other.len(),
+            });
+        }
+
+        let squared_dist = |i: usize| -> f64 {
+            let mut d = sub(self.coords[i], other.coords[i]);
+            if pbc {
+                d = self.box_vector.minimum_image(d);
+            }
+            d.iter().map(|x| f64::from(*x).powi(2)).sum()
+        };
+
+        let (sum, n): (f64, usize) = match mask {
+            Some(indices) => {
+                self.check_indices(indices)?;
+                (
+                    indices.iter().map(|&i| squared_dist(i)).sum(),
+                    indices.len(),
+                )
+            }
+            None => ((0..self.len()).map(squared_dist).sum(), self.len()),
+        };
+
+        if n == 0 {
+            Ok(0.0)
+        } else {
+            Ok((sum / n as f64).sqrt() as f32)
+        }
+    }
+
+    /// Center of mass of the frame, given one mass per atom. Returns the
+    /// origin if the total mass is zero.
+    pub fn center_of_mass(&self, masses: &[f32]) -> Result<[f32; 3]> {
+        self.check_masses(masses)?;
+        let indices: Vec<usize> = (0..self.len()).collect();
+        let (center, _) = weighted_centroid(&self.coords, Some(masses), &indices);
+        Ok(center.map(|x| x as f32))
+    }
+
+    /// Shift all atoms by `offset`
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for xyz in self.coords.iter_mut() {
+            for (x, dx) in xyz.iter_mut().zip(&offset) {
+                *x += dx;
+            }
+        }
+    }
+
+    /// Radius of gyration of the atoms in `mask` (or all atoms if `None`).
+    ///
+    /// If `masses` is given (one entry per atom of the frame), the radius is
+    /// mass weighted, otherwise all atoms are weighted equally.
+    pub fn radius_of_gyration(
+        &self,
+        masses: Option<&[f32]>,
+        mask: Option<&[usize]>,
+    ) -> Result<f32> {
+        if let Some(masses) = masses {
+            self.check_masses(masses)?;
+        }
+        if let Some(indices) = mask {
+            self.check_indices(indices)?;
+        }
+        let all: Vec<usize>;
+        let indices = match mask {
+            Some(indices) => indices,
+            None => {
+                all = (0..self.len()).collect();
+                &all
+            }
+        };
+
+        let (center, total_mass) = weighted_centroid(&self.coords, masses, indices);
+        if total_mass == 0.0 {
+            return Ok(0.0);
+        }
+        let sum: f64 = indices
+            .iter()
+            .map(|&i| {
+                let weight = masses.map_or(1.0, |m| f64::from(m[i]));
+                let squared_dist: f64 = (0..3)
+                    .map(|k| (f64::from(self.coords[i][k]) - center[k]).powi(2))
+                    .sum();
+                weight * squared_dist
+            })
+            .sum();
+        Ok((sum / total_mass).sqrt() as f32)
+    }
+
+    /// Least-squares fit the frame onto `reference` (Kabsch algorithm).
+    ///
+    /// The optimal translation and rotation are computed from the atoms in
+    /// `mask` (or all atoms if `None`) and then applied to every atom of the
+    /// frame. Both frames must contain the same number of atoms.
+    pub fn superpose_onto(&mut self, reference: &Frame, mask: Option<&[usize]>) -> Result<()> {
+        if self.len() != reference.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: reference.len(),
+                found: self.len(),
+            });
+        }
+        if let Some(indices) = mask {
+            self.check_indices(indices)?;
+        }
+        let all: Vec<usize>;
+        let indices = match mask {
+            Some(indices) => indices,
+            None => {
+                all = (0..self.len()).collect();
+                &all
+            }
+        };
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let center = centroid(&self.coords, indices);
+        let ref_center = centroid(&reference.coords, indices);
+
+        let mut correlation = [[0.0_f64; 3]; 3];
+        for &i in indices {
+            for (a, row) in correlation.iter_mut().enumerate() {
+                let x = f64::from(self.coords[i][a]) - center[a];
+                for (b, s) in row.iter_mut().enumerate() {
+                    *s += x * (f64::from(reference.coords[i][b]) - ref_center[b]);
+                }
+            }
+        }
+        let rotation = linalg::optimal_rotation(&correlation);
+
+        for xyz in self.coords.iter_mut() {
+            let x = [
+                f64::from(xyz[0]) - center[0],
+                f64::from(xyz[1]) - center[1],
+                f64::from(xyz[2]) - center[2],
+            ];
+            let rotated = rotate(&rotation, x);
+            for (k, value) in xyz.iter_mut().enumerate() {
+                *value = (rotated[k] + ref_center[k]) as f32;
+            }
+        }
+
+        // velocities and forces are only rotated, not translated
+        for vectors in self.velocities.iter_mut().chain(self.forces.iter_mut()) {
+            for xyz in vectors.iter_mut() {
+                let rotated = rotate(&rotation, xyz.map(f64::from));
+                *xyz = rotated.map(|x| x as f32);
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a new frame that only contains the atoms at `indices` (in
+    /// that order). Velocities and forces are carried along if present.
+    pub fn subset(&self, indices: &[usize]) -> Result<Frame> {
+        let mut frame = Frame::new();
+        self.subset_into(indices, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Like `subset`, but writes into an existing frame to reuse its buffers
+    pub fn subset_into(&self, indices: &[usize], out: &mut Frame) -> Result<()> {
+        self.check_indices(indices)?;
+        out.step = self.step;
+        out.time = self.time;
+        out.box_vector = self.box_vector;
+        select_into(&self.coords, indices, &mut out.coords);
+        match &self.velocities {
+            Some(velocities) => select_into(
+                velocities,
+                indices,
+                out.velocities.get_or_insert_with(Vec::new),
+            ),
+            None => out.velocities = None,
+        }
+        match &self.forces {
+            Some(forces) => select_into(forces, indices, out.forces.get_or_insert_with(Vec::new)),
+            None => out.forces = None,
+        }
+        Ok(())
+    }
+
+    /// Create a new frame with the atoms of `other` appended to the atoms of
+    /// this frame, e.g. to merge a solute with its solvent.
+    ///
+    /// Step and time are taken from `self`. The boxes must agree, unless one
+    /// of them is zero, in which case the other one is used. Velocities and
+    /// forces are only kept if both frames carry them.
+    pub fn concat(&self, other: &Frame) -> Result<Frame> {
+        let box_vector = if other.box_vector.is_zero() {
+            self.box_vector
+        } else if self.box_vector.is_zero() || self.box_vector == other.box_vector {
+            other.box_vector
+        } else {
+            return Err(Error::IncompatibleBoxes {
+                left: self.box_vector,
+                right: other.box_vector,
+            });
+        };
+
+        let join = |a: &Option<Vec<[f32; 3]>>, b: &Option<Vec<[f32; 3]>>| match (a, b) {
+            (Some(a), Some(b)) => Some([a.as_slice(), b.as_slice()].concat()),
+            _ => None,
+        };
+        Ok(Frame {
+            step: self.step,
+            time: self.time,
+            box_vector,
+            coords: [self.coords.as_slice(), other.coords.as_slice()].concat(),
+            velocities: join(&self.velocities, &other.velocities),
+            forces: join(&self.forces, &other.forces),
+        })
+    }
+
+    /// True if both frames have the same step and number of atoms and their
+    /// time, box vector and coordinates agree within the absolute tolerance
+    /// `tol`. Velocities and forces are not compared.
+    pub fn approx_eq(&self, other: &Frame, tol: f32) -> bool {
+        let mut equal = true;
+        self.visit_differences(other, tol, |_| {
+            equal = false;
+            false
+        });
+        equal
+    }
+
+    /// List all differences between two frames that exceed the absolute
+    /// tolerance `tol`, e.g. to produce a helpful message in a failing test.
+    /// The list is empty if `approx_eq` returns true.
+    pub fn diff(&self, other: &Frame, tol: f32) -> Vec<FrameDifference> {
+        let mut differences = Vec::new();
+        self.visit_differences(other, tol, |difference| {
+            differences.push(difference);
+            true
+        });
+        differences
+    }
+
+    /// Call `visit` for every difference until it returns false
+    fn visit_differences<F>(&self, other: &Frame, tol: f32, mut visit: F)
+    where
+        F: FnMut(FrameDifference) -> bool,
+    {
+        let close = |a: f32, b: f32| (a - b).abs() <= tol;
+        let close_xyz = |a: &[f32; 3], b: &[f32; 3]| a.iter().zip(b).all(|(a, b)| close(*a, *b));
+
+        if self.step != other.step
+            && !visit(FrameDifference::Step {
+                left: self.step,
+                right: other.step,
+            })
+        {
+            return;
+        }
+        if !close(self.time, other.time)
+            && !visit(FrameDifference::Time {
+                left: self.time,
+                right: other.time,
+            })
+        {
+            return;
+        }
+        let same_box = (0..3).all(|i| close_xyz(&self.box_vector[i], &other.box_vector[i]));
+        if !same_box
+            && !visit(FrameDifference::BoxVector {
+                left: self.box_vector,
+                right: other.box_vector,
+            })
+        {
+            return;
+        }
+        if self.len() != other.len() {
+            visit(FrameDifference::NumAtoms {
+                left: self.len(),
+                right: other.len(),
+            });
+            return;
+        }
+        for (index, (left, right)) in self.coords.iter().zip(&other.coords).enumerate() {
+            if !close_xyz(left, right)
+                && !visit(FrameDifference::Coords {
+                    index,
+                    left: *left,
+                    right: *right,
+                })
+            {
+                return;
+            }
+        }
+    }
+
+    /// Check the frame for non-finite coordinates, a zero or degenerate box
+    /// and a negative or non-finite time
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !self.time.is_finite() {
+            return Err(ValidationError::NonFiniteTime(self.time));
+        }
+        if self.time < 0.0 {
+            return Err(ValidationError::NegativeTime(self.time));
+        }
+        if !self.box_vector.0.iter().flatten().all(|x| x.is_finite()) {
+            return Err(ValidationError::NonFiniteBox(self.box_vector));
+        }
+        if self.box_vector.is_zero() {
+            return Err(ValidationError::ZeroBox);
+        }
+        if self.box_vector.volume() <= f32::EPSILON {
+            return Err(ValidationError::DegenerateBox(self.box_vector));
+        }
+        match self
+            .coords
+            .iter()
+            .position(|xyz| !xyz.iter().all(|x| x.is_finite()))
+        {
+            Some(index) => Err(ValidationError::NonFiniteCoordinates { index }),
+            None => Ok(()),
+        }
+    }
+
+    /// Make sure there is one mass per atom
+    pub(crate) fn check_masses(&self, masses: &[f32]) -> Result<()> {
+        if masses.len() == self.len() {
+            Ok(())
+        } else {
+            Err(Error::LengthMismatch {
+                name: "masses",
+                expected: self.len(),
+                found: masses.len(),
+            })
+        }
+    }
+
+    /// Make sure every index refers to an atom of the frame
+    pub(crate) fn check_indices(&self, indices: &[usize]) -> Result<()> {
+        match indices.iter().find(|&&i| i >= self.len()) {
+            Some(&index) => Err(Error::AtomIndexOutOfBounds {
+                index,
+                num_atoms: self.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A single difference between two frames as reported by `Frame::diff`, or
+/// between two trajectories as reported by `zip_frames`
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameDifference {
+    /// The frames belong to different steps
+    Step { left: usize, right: usize },
+    /// The times differ by more than the tolerance
+    Time { left: f32, right: f32 },
+    /// At least one box vector component differs by more than the tolerance
+    BoxVector { left: BoxVector, right: BoxVector },
+    /// The frames have a different number of atoms (coordinates are not compared)
+    NumAtoms { left: usize, right: usize },
+    /// The coordinates of atom `index` differ by more than the tolerance
+    Coords {
+        index: usize,
+        left: [f32; 3],
+        right: [f32; 3],
+    },
+    /// One trajectory ended before the other, which still has a frame at
+    /// this index; `left` is true if the left one ended (only `zip_frames`)
+    Ended { left: bool },
+}
+
+impl std::fmt::Display for FrameDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameDifference::Step { left, right } => write!(f, "step {} != {}", left, right),
+            FrameDifference::Time { left, right } => write!(f, "time {} != {}", left, right),
+            FrameDifference::BoxVector { left, right } => {
+                write!(f, "box vector {:?} != {:?}", left.0, right.0)
+            }
+            FrameDifference::NumAtoms { left, right } => {
+                write!(f, "number of atoms {} != {}", left, right)
+            }
+            FrameDifference::Coords { index, left, right } => {
+                write!(f, "coordinates of atom {} {:?} != {:?}", index, left, right)
+            }
+            FrameDifference::Ended { left: true } => write!(f, "left trajectory ended"),
+            FrameDifference::Ended { left: false } => write!(f, "right trajectory ended"),
+        }
+    }
+}
+
+/// Replace the contents of `out` with the selected elements of `values`
+fn select_into(values: &[[f32; 3]], indices: &[usize], out: &mut Vec<[f32; 3]>) {
+    out.clear();
+    out.extend(indices.iter().map(|&i| values[i]));
+}
+
+/// Geometric center of the selected coordinates
+fn centroid(coords: &[[f32; 3]], indices: &[usize]) -> [f64; 3] {
+    let mut center = [0.0; 3];
+    for &i in indices {
+        for (c, x) in center.iter_mut().zip(&coords[i]) {
+            *c += f64::from(*x);
+        }
+    }
+    center.iter_mut().for_each(|c| *c /= indices.len() as f64);
+    center
+}
+
+/// Weighted center of the selected coordinates and the sum of their
+/// weights. Without weights every atom counts as one.
+fn weighted_centroid(
+    coords: &[[f32; 3]],
+    weights: Option<&[f32]>,
+    indices: &[usize],
+) -> ([f64; 3], f64) {
+    let mut center = [0.0; 3];
+    let mut total = 0.0;
+    for &i in indices {
+        let weight = weights.map_or(1.0, |w| f64::from(w[i]));
+        for (c, x) in center.iter_mut().zip(&coords[i]) {
+            *c += weight * f64::from(*x);
+        }
+        total += weight;
+    }
+    if total != 0.0 {
+        center.iter_mut().for_each(|c| *c /= total);
+    }
+    (center, total)
+}
+
+fn rotate(rotation: &[[f64; 3]; 3], x: [f64; 3]) -> [f64; 3] {
+    let mut result = [0.0; 3];
+    for (r, row) in result.iter_mut().zip(rotation) {
+        *r = row[0] * x[0] + row[1] * x[1] + row[2] * x[2];
+    }
+    result
+}
+
+pub(crate) fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn norm(d: [f32; 3]) -> f32 {
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Summary of the frame that stays readable for large systems: step, time,
+/// box lengths and angles, and the first, last, minimum and maximum coordinates
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (lengths, angles) = self.box_vector.lengths_and_angles();
+        write!(
+            f,
+            "Frame {{ step: {}, time: {}, atoms: {}, box: {:?} {:?}",
+            self.step,
+            self.time,
+            self.len(),
+            lengths,
+            angles
+        )?;
+        if let (Some(first), Some(last)) = (self.coords.first(), self.coords.last()) {
+            let mut min = [f32::INFINITY; 3];
+            let mut max = [f32::NEG_INFINITY; 3];
+            for xyz in &self.coords {
+                for k in 0..3 {
+                    min[k] = min[k].min(xyz[k]);
+                    max[k] = max[k].max(xyz[k]);
+                }
+            }
+            write!(
+                f,
+                ", first: {:?}, last: {:?}, min: {:?}, max: {:?}",
+                first, last, min, max
+            )?;
+        }
+        if self.velocities.is_some() {
+            write!(f, ", with velocities")?;
+        }
+        if self.forces.is_some() {
+            write!(f, ", with forces")?;
+        }
+        write!(f, " }}")
     }
 }
 
@@ -118,8 +781,9 @@ mod tests {
         let mut frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: [[0.0; 3]; 3].into(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            ..Default::default()
         };
 
         frame.filter_coords(&[1]);
@@ -128,6 +792,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_filter_coords_with_velocities_and_forces() {
+        let mut frame = Frame {
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            velocities: Some(vec![[10.0; 3], [11.0; 3], [12.0; 3]]),
+            forces: Some(vec![[20.0; 3], [21.0; 3], [22.0; 3]]),
+            ..Default::default()
+        };
+
+        frame.filter_coords(&[0, 2]);
+        assert_eq!(frame.coords, vec![[0.0; 3], [2.0; 3]]);
+        assert_eq!(frame.velocities, Some(vec![[10.0; 3], [12.0; 3]]));
+        assert_eq!(frame.forces, Some(vec![[20.0; 3], [22.0; 3]]));
+    }
+
     #[test]
     #[allow(unused_mut)]
     fn test_index() {
@@ -135,8 +814,9 @@ mod tests {
         let frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: [[0.0; 3]; 3].into(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            ..Default::default()
         };
         for i in 0..frame.len() {
             for j in 0..3 {
@@ -148,8 +828,9 @@ mod tests {
         let mut frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: [[0.0; 3]; 3].into(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            ..Default::default()
         };
         for i in 0..frame.len() {
             for j in 0..3 {
@@ -168,4 +849,462 @@ mod tests {
         }
 
     }
+
+    #[test]
+    fn test_rmsd() -> Result<()> {
+        let frame = Frame {
+            step: 0,
+            time: 0.0,
+            box_vector: [[0.0; 3]; 3].into(),
+            coords: vec![[0.0; 3], [1.0; 3]],
+            ..Default::default()
+        };
+        let mut other = frame.clone();
+        assert_approx_eq!(frame.rmsd(&other)?, 0.0);
+
+        other[0] = [2.0, 0.0, 0.0];
+        assert_approx_eq!(frame.rmsd(&other)?, 2.0_f32.sqrt());
+        assert_approx_eq!(frame.rmsd_with(&other, Some(&[0]), false)?, 2.0);
+        assert_approx_eq!(frame.rmsd_with(&other, Some(&[1]), false)?, 0.0);
+
+        let result = frame.rmsd_with(&other, Some(&[2]), false);
+        assert_eq!(
+            result,
+            Err(Error::AtomIndexOutOfBounds {
+                index: 2,
+                num_atoms: 2
+            })
+        );
+
+        let result = frame.rmsd(&Frame::with_len(3));
+        assert!(matches!(result, Err(Error::WrongSizeFrame { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rmsd_pbc() -> Result<()> {
+        let frame = Frame {
+            step: 0,
+            time: 0.0,
+            box_vector: [[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]].into(),
+            coords: vec![[0.1, 0.1, 0.1]],
+            ..Default::default()
+        };
+        let mut other = frame.clone();
+        other[0] = [2.9, 0.1, 0.1];
+
+        assert_approx_eq!(frame.rmsd(&other)?, 2.8);
+        assert_approx_eq!(frame.rmsd_with(&other, None, true)?, 0.2, 1e-5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_superpose_onto() -> Result<()> {
+        let reference = Frame {
+            step: 0,
+            time: 0.0,
+            box_vector: [[0.0; 3]; 3].into(),
+            coords: vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 2.0, 0.0],
+                [0.0, 0.0, 3.0],
+            ],
+            ..Default::default()
+        };
+
+        // rotate by 90 degrees around z and shift
+        let mut frame = reference.clone();
+        for xyz in frame.coords.iter_mut() {
+            *xyz = [-xyz[1] + 5.0, xyz[0] - 1.0, xyz[2] + 2.0];
+        }
+        assert!(frame.rmsd(&reference)? > 1.0);
+
+        frame.superpose_onto(&reference, None)?;
+        assert_approx_eq!(frame.rmsd(&reference)?, 0.0, 1e-5);
+
+        // fitting on a subset moves all atoms along
+        let mut frame = reference.clone();
+        frame[3] = [0.0, 0.0, 4.0];
+        for xyz in frame.coords.iter_mut() {
+            *xyz = [xyz[0] + 1.0, xyz[2], -xyz[1]];
+        }
+        frame.superpose_onto(&reference, Some(&[0, 1, 2]))?;
+        assert_approx_eq!(
+            frame.rmsd_with(&reference, Some(&[0, 1, 2]), false)?,
+            0.0,
+            1e-5
+        );
+        for k in 0..3 {
+            assert_approx_eq!(frame[3][k], [0.0, 0.0, 4.0][k], 1e-5);
+        }
+
+        let result = frame.superpose_onto(&Frame::with_len(2), None);
+        assert!(matches!(result, Err(Error::WrongSizeFrame { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subset() -> Result<()> {
+        let frame = Frame {
+            step: 3,
+            time: 1.5,
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            velocities: Some(vec![[0.1; 3], [1.1; 3], [2.1; 3]]),
+            ..Default::default()
+        };
+
+        let subset = frame.subset(&[2, 0])?;
+        assert_eq!(subset.step, 3);
+        assert_eq!(subset.time, 1.5);
+        assert_eq!(subset.coords, vec![[2.0; 3], [0.0; 3]]);
+        assert_eq!(subset.velocities, Some(vec![[2.1; 3], [0.1; 3]]));
+        assert_eq!(subset.forces, None);
+
+        let mut out = Frame::with_len(10);
+        out.forces = Some(vec![[1.0; 3]; 10]);
+        frame.subset_into(&[1], &mut out)?;
+        assert_eq!(out.coords, vec![[1.0; 3]]);
+        assert_eq!(out.velocities, Some(vec![[1.1; 3]]));
+        assert_eq!(out.forces, None);
+
+        let result = frame.subset(&[0, 3]);
+        assert_eq!(
+            result.unwrap_err(),
+            Error::AtomIndexOutOfBounds {
+                index: 3,
+                num_atoms: 3
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let frame = Frame {
+            step: 5,
+            time: 2.0,
+            box_vector: BoxVector::rectangular(3.0, 3.0, 3.0),
+            coords: vec![[0.0; 3], [1.0; 3]],
+            ..Default::default()
+        };
+        let mut other = frame.clone();
+        other.coords[1][2] += 1e-4;
+        assert!(frame.approx_eq(&other, 1e-3));
+        assert!(frame.diff(&other, 1e-3).is_empty());
+        assert!(!frame.approx_eq(&other, 1e-5));
+
+        other.time = 2.5;
+        other.box_vector[0][0] = 4.0;
+        assert_eq!(
+            frame.diff(&other, 1e-5),
+            vec![
+                FrameDifference::Time {
+                    left: 2.0,
+                    right: 2.5
+                },
+                FrameDifference::BoxVector {
+                    left: frame.box_vector,
+                    right: other.box_vector
+                },
+                FrameDifference::Coords {
+                    index: 1,
+                    left: [1.0; 3],
+                    right: other.coords[1]
+                },
+            ]
+        );
+
+        let shorter = frame.subset(&[0]).unwrap();
+        assert_eq!(
+            frame.diff(&shorter, 1.0),
+            vec![FrameDifference::NumAtoms { left: 2, right: 1 }]
+        );
+        assert_eq!(
+            FrameDifference::Step { left: 1, right: 2 }.to_string(),
+            "step 1 != 2"
+        );
+    }
+
+    #[test]
+    fn test_resize_and_clear() {
+        let mut frame = Frame::with_len(4);
+        frame.step = 2;
+        frame.time = 1.0;
+        frame.velocities = Some(vec![[1.0; 3]; 4]);
+
+        frame.resize(6);
+        assert_eq!(frame.len(), 6);
+        assert_eq!(frame.velocities.as_ref().unwrap().len(), 6);
+        assert_eq!(frame.velocities.as_ref().unwrap()[5], [0.0; 3]);
+        assert_eq!(frame.forces, None);
+
+        frame.resize(2);
+        assert_eq!(frame.velocities.as_ref().unwrap().len(), 2);
+
+        frame.clear();
+        assert_eq!(frame.step, 0);
+        assert_eq!(frame.time, 0.0);
+        assert_eq!(frame.len(), 0);
+        assert!(frame.coords.capacity() >= 6);
+        assert_eq!(frame.velocities, Some(vec![]));
+    }
+
+    #[test]
+    fn test_iter_atoms() {
+        let mut frame = Frame::with_len(3);
+        for (i, position) in frame.iter_atoms_mut() {
+            position[0] = i as f32;
+        }
+        let xs: Vec<_> = frame.iter_atoms().map(|(i, p)| (i, p[0])).collect();
+        assert_eq!(xs, vec![(0, 0.0), (1, 1.0), (2, 2.0)]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_iter_atoms() {
+        use rayon::prelude::*;
+
+        let mut frame = Frame::with_len(100);
+        frame.par_iter_atoms_mut().for_each(|(i, position)| {
+            position[1] = i as f32;
+        });
+        let sum: f32 = frame.par_iter_atoms().map(|(_, p)| p[1]).sum();
+        assert_eq!(sum, 4950.0);
+    }
+
+    #[test]
+    fn test_as_flat() {
+        let mut frame = Frame {
+            coords: vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+            ..Default::default()
+        };
+        assert_eq!(frame.as_flat(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        frame.as_flat_mut()[4] = 0.5;
+        assert_eq!(frame[1], [4.0, 0.5, 6.0]);
+        assert!(Frame::new().as_flat().is_empty());
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam() {
+        use glam::Vec3;
+
+        let mut frame = Frame {
+            coords: vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+            ..Default::default()
+        };
+        assert_eq!(frame.coords_as_vec3()[1], Vec3::new(4.0, 5.0, 6.0));
+
+        frame.coords_as_vec3_mut()[0] += Vec3::ONE;
+        assert_eq!(frame[0], [2.0, 3.0, 4.0]);
+
+        frame.set_coords_from_vec3(&[Vec3::X, Vec3::Y, Vec3::Z]);
+        assert_eq!(
+            frame.coords,
+            vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        );
+    }
+
+    #[test]
+    fn test_distance() -> Result<()> {
+        let frame = Frame {
+            box_vector: BoxVector::rectangular(4.0, 4.0, 4.0),
+            coords: vec![[0.5, 0.5, 0.5], [3.5, 0.5, 0.5], [0.5, 2.5, 0.5]],
+            ..Default::default()
+        };
+        assert_approx_eq!(frame.distance(0, 1)?, 3.0);
+        assert_approx_eq!(frame.distance_pbc(0, 1)?, 1.0);
+        assert_approx_eq!(frame.distance_pbc(0, 2)?, 2.0);
+        assert_eq!(frame.distance(1, 1)?, 0.0);
+        assert!(matches!(
+            frame.distance_pbc(0, 3),
+            Err(Error::AtomIndexOutOfBounds { index: 3, .. })
+        ));
+
+        // truncated octahedron, second atom close to the periodic image along c
+        let box_vector = BoxVector::from_lengths_angles([3.0; 3], [70.53, 109.47, 70.53]);
+        let triclinic = Frame {
+            box_vector,
+            coords: vec![[0.0; 3], box_vector[2].map(|x| 0.95 * x)],
+            ..Default::default()
+        };
+        assert_approx_eq!(triclinic.distance(0, 1)?, 2.85, 1e-4);
+        assert_approx_eq!(triclinic.distance_pbc(0, 1)?, 0.15, 1e-4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_angle_and_dihedral() -> Result<()> {
+        let mut frame = Frame {
+            box_vector: BoxVector::rectangular(4.0, 4.0, 4.0),
+            coords: vec![
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 1.0, 1.0],
+            ],
+            ..Default::default()
+        };
+        assert_approx_eq!(frame.angle_pbc(0, 1, 2)?, 90.0, 1e-4);
+        assert_approx_eq!(frame.dihedral_pbc(0, 1, 2, 3)?, -90.0, 1e-4);
+        frame.coords[3] = [0.0, 1.0, -1.0];
+        assert_approx_eq!(frame.dihedral_pbc(0, 1, 2, 3)?, 90.0, 1e-4);
+        frame.coords[3] = [1.0, 1.0, 0.0];
+        assert_approx_eq!(frame.dihedral_pbc(0, 1, 2, 3)?, 0.0, 1e-4);
+        frame.coords[3] = [-1.0, 1.0, 0.0];
+        assert_approx_eq!(frame.dihedral_pbc(0, 1, 2, 3)?.abs(), 180.0, 1e-4);
+
+        // the first atom is moved to the other side of the box
+        frame.coords[0] = [-3.0, 0.0, 0.0];
+        assert_approx_eq!(frame.angle_pbc(0, 1, 2)?, 90.0, 1e-4);
+        frame.coords[0] = [-1.0, 1.0, 0.0];
+        assert_approx_eq!(frame.angle_pbc(0, 1, 2)?, 45.0, 1e-4);
+        assert!(frame.angle_pbc(0, 1, 4).is_err());
+        assert!(frame.dihedral_pbc(0, 1, 2, 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat() -> Result<()> {
+        let solute = Frame {
+            step: 7,
+            coords: vec![[1.0; 3]],
+            velocities: Some(vec![[0.1; 3]]),
+            forces: Some(vec![[0.2; 3]]),
+            ..Default::default()
+        };
+        let mut solvent = Frame {
+            box_vector: BoxVector::rectangular(5.0, 5.0, 5.0),
+            coords: vec![[2.0; 3], [3.0; 3]],
+            velocities: Some(vec![[0.3; 3], [0.4; 3]]),
+            ..Default::default()
+        };
+
+        let system = solute.concat(&solvent)?;
+        assert_eq!(system.step, 7);
+        assert_eq!(system.box_vector, solvent.box_vector);
+        assert_eq!(system.coords, vec![[1.0; 3], [2.0; 3], [3.0; 3]]);
+        assert_eq!(system.velocities, Some(vec![[0.1; 3], [0.3; 3], [0.4; 3]]));
+        assert_eq!(system.forces, None);
+
+        let mut other = solute.clone();
+        other.box_vector = BoxVector::rectangular(4.0, 4.0, 4.0);
+        solvent.box_vector[1][1] = 4.0;
+        assert!(matches!(
+            other.concat(&solvent),
+            Err(Error::IncompatibleBoxes { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_radius_of_gyration() -> Result<()> {
+        let frame = Frame {
+            coords: vec![[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 3.0, 0.0]],
+            ..Default::default()
+        };
+        assert_approx_eq!(frame.radius_of_gyration(None, Some(&[0, 1]))?, 1.0);
+        assert_approx_eq!(
+            frame.radius_of_gyration(None, None)?,
+            (8.0_f32 / 3.0).sqrt()
+        );
+
+        // a heavy atom pulls the center of mass towards itself
+        let masses = [1.0, 3.0, 0.0];
+        assert_approx_eq!(
+            frame.radius_of_gyration(Some(&masses), None)?,
+            0.75_f32.sqrt()
+        );
+        assert_eq!(frame.radius_of_gyration(None, Some(&[]))?, 0.0);
+        assert_eq!(
+            frame.radius_of_gyration(Some(&[1.0]), None),
+            Err(Error::LengthMismatch {
+                name: "masses",
+                expected: 3,
+                found: 1
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_center_of_mass() -> Result<()> {
+        let mut frame = Frame {
+            coords: vec![[0.0, 0.0, 0.0], [4.0, 2.0, -2.0]],
+            ..Default::default()
+        };
+        let com = frame.center_of_mass(&[3.0, 1.0])?;
+        assert_eq!(com, [1.0, 0.5, -0.5]);
+        assert_eq!(frame.center_of_mass(&[0.0, 0.0])?, [0.0; 3]);
+        assert!(matches!(
+            frame.center_of_mass(&[1.0; 3]),
+            Err(Error::LengthMismatch { .. })
+        ));
+
+        frame.translate(com.map(|x| -x));
+        assert_eq!(frame.center_of_mass(&[3.0, 1.0])?, [0.0; 3]);
+        assert_eq!(frame[1], [3.0, 1.5, -1.5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut frame = Frame {
+            time: 1.0,
+            box_vector: BoxVector::rectangular(2.0, 2.0, 2.0),
+            coords: vec![[0.0; 3], [1.0; 3]],
+            ..Default::default()
+        };
+        assert_eq!(frame.validate(), Ok(()));
+
+        frame.coords[1][2] = f32::NAN;
+        assert_eq!(
+            frame.validate(),
+            Err(ValidationError::NonFiniteCoordinates { index: 1 })
+        );
+
+        frame.time = -1.0;
+        assert_eq!(frame.validate(), Err(ValidationError::NegativeTime(-1.0)));
+        frame.time = 0.0;
+
+        frame.box_vector[2] = [0.0; 3];
+        assert!(matches!(
+            frame.validate(),
+            Err(ValidationError::DegenerateBox(_))
+        ));
+        frame.box_vector = BoxVector::default();
+        assert_eq!(frame.validate(), Err(ValidationError::ZeroBox));
+        frame.box_vector[0][0] = f32::INFINITY;
+        assert!(matches!(
+            frame.validate(),
+            Err(ValidationError::NonFiniteBox(_))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut frame = Frame {
+            step: 3,
+            time: 1.5,
+            box_vector: BoxVector::rectangular(2.0, 2.0, 2.0),
+            coords: vec![[0.0, 1.0, 2.0], [1.0, -1.0, 0.5], [0.5, 0.5, 0.5]],
+            ..Default::default()
+        };
+        assert_eq!(
+            frame.to_string(),
+            "Frame { step: 3, time: 1.5, atoms: 3, box: [2.0, 2.0, 2.0] [90.0, 90.0, 90.0], \
+             first: [0.0, 1.0, 2.0], last: [0.5, 0.5, 0.5], \
+             min: [0.0, -1.0, 0.5], max: [1.0, 1.0, 2.0] }"
+        );
+
+        frame.resize(0);
+        frame.velocities = Some(vec![]);
+        assert_eq!(
+            frame.to_string(),
+            "Frame { step: 3, time: 1.5, atoms: 0, box: [2.0, 2.0, 2.0] [90.0, 90.0, 90.0], \
+             with velocities }"
+        );
+    }
 }
@@ -0,0 +1,481 @@
+//! Reading HOOMD-blue GSD trajectories (.gsd)
+//!
+//! GSD files consist of a header, data chunks, an index of the chunks and a
+//! list of chunk names. The format is simple enough to be parsed directly
+//! without linking libgsd.
+
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::rc::Rc;
+
+const MAGIC: u64 = 0x65DF_65DF_65DF_65DF;
+const HEADER_SIZE: usize = 256;
+const INDEX_ENTRY_SIZE: usize = 32;
+/// Size of a chunk name in files of version 1
+const NAME_SIZE: usize = 64;
+
+/// Box of frames that do not store one, as defined by the HOOMD schema
+const DEFAULT_BOX: [f64; 6] = [1.0, 1.0, 1.0, 0.0, 0.0, 0.0];
+
+/// Location and shape of a data chunk
+#[derive(Clone, Copy, Debug)]
+struct Chunk {
+    /// Number of rows
+    n: u64,
+    /// Number of columns
+    m: u32,
+    location: u64,
+    data_type: u8,
+}
+
+impl Chunk {
+    fn type_size(self) -> Result<usize> {
+        match self.data_type {
+            1 | 5 => Ok(1),
+            2 | 6 => Ok(2),
+            3 | 7 | 9 => Ok(4),
+            4 | 8 | 10 => Ok(8),
+            t => Err(Error::InvalidData(format!("unknown GSD data type {}", t))),
+        }
+    }
+
+    fn len(self) -> usize {
+        self.n as usize * self.m as usize
+    }
+}
+
+/// Decode little-endian values of a GSD data type as f64
+fn decode(bytes: &[u8], data_type: u8, out: &mut Vec<f64>) {
+    fn values<const N: usize>(bytes: &[u8]) -> impl Iterator<Item = [u8; N]> + '_ {
+        bytes
+            .chunks_exact(N)
+            .map(|chunk| chunk.try_into().expect("chunk has N bytes"))
+    }
+    out.clear();
+    match data_type {
+        1 => out.extend(bytes.iter().map(|&b| f64::from(b))),
+        2 => out.extend(values(bytes).map(|b| f64::from(u16::from_le_bytes(b)))),
+        3 => out.extend(values(bytes).map(|b| f64::from(u32::from_le_bytes(b)))),
+        4 => out.extend(values(bytes).map(|b| u64::from_le_bytes(b) as f64)),
+        5 => out.extend(bytes.iter().map(|&b| f64::from(b as i8))),
+        6 => out.extend(values(bytes).map(|b| f64::from(i16::from_le_bytes(b)))),
+        7 => out.extend(values(bytes).map(|b| f64::from(i32::from_le_bytes(b)))),
+        8 => out.extend(values(bytes).map(|b| i64::from_le_bytes(b) as f64)),
+        9 => out.extend(values(bytes).map(|b| f64::from(f32::from_le_bytes(b)))),
+        _ => out.extend(values(bytes).map(f64::from_le_bytes)),
+    }
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"))
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("4 bytes"))
+}
+
+fn to_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidData("unexpected end of file".to_string()),
+        _ => err.into(),
+    }
+}
+
+/// Read-only handle to a GSD trajectory written with the HOOMD schema.
+///
+/// Frames contain the particle positions (`particles/position`),
+/// velocities (`particles/velocity`), the box (`configuration/box`) and the
+/// time step (`configuration/step`). As defined by the schema, data that a
+/// frame does not store is taken from the first frame. GSD files do not
+/// store the time, so it is always zero.
+///
+/// HOOMD positions are centered around the origin; they are shifted so that
+/// the box starts at the origin, as in GROMACS. Lengths are multiplied by a
+/// length scale that converts them to nanometers (1.0 by default).
+pub struct GsdTrajectory {
+    reader: BufReader<File>,
+    chunks: HashMap<(u64, String), Chunk>,
+    num_frames: usize,
+    num_atoms: usize,
+    next_frame: usize,
+    length_scale: f32,
+    buffer: Vec<f64>,
+}
+
+impl GsdTrajectory {
+    /// Open the trajectory at `path` for reading
+    pub fn open_read(path: impl AsRef<Path>) -> Result<GsdTrajectory> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(to_error)?;
+        if le_u64(&header[0..]) != MAGIC {
+            return Err(Error::InvalidData("not a GSD file".to_string()));
+        }
+        let index_location = le_u64(&header[8..]);
+        let index_entries = le_u64(&header[16..]);
+        let namelist_location = le_u64(&header[24..]);
+        let namelist_entries = le_u64(&header[32..]);
+        let major_version = le_u32(&header[44..]) >> 16;
+        if !(1..=2).contains(&major_version) {
+            return Err(Error::InvalidData(format!(
+                "unsupported GSD version {}",
+                major_version
+            )));
+        }
+
+        // names are stored in slots of 64 bytes (version 1) or back-to-back
+        // (version 2), in the order of their ids; an empty name ends the list
+        let namelist_len =
+            (namelist_entries * NAME_SIZE as u64).min(file_len.saturating_sub(namelist_location));
+        let mut namelist = vec![0; namelist_len as usize];
+        reader.seek(SeekFrom::Start(namelist_location))?;
+        reader.read_exact(&mut namelist).map_err(to_error)?;
+        let names: Vec<String> = if major_version == 1 {
+            namelist
+                .chunks(NAME_SIZE)
+                .map(|slot| slot.split(|&b| b == 0).next().unwrap_or_default())
+                .take_while(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect()
+        } else {
+            namelist
+                .split(|&b| b == 0)
+                .take_while(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect()
+        };
+
+        let index_len =
+            (index_entries * INDEX_ENTRY_SIZE as u64).min(file_len.saturating_sub(index_location));
+        let mut index = vec![0; index_len as usize];
+        reader.seek(SeekFrom::Start(index_location))?;
+        reader.read_exact(&mut index).map_err(to_error)?;
+        let mut chunks = HashMap::new();
+        let mut num_frames = 0;
+        for entry in index.chunks_exact(INDEX_ENTRY_SIZE) {
+            let chunk = Chunk {
+                n: le_u64(&entry[8..]),
+                m: le_u32(&entry[24..]),
+                location: le_u64(&entry[16..]),
+                data_type: entry[30],
+            };
+            // unused entries at the end of the index have no location
+            if chunk.location == 0 {
+                break;
+            }
+            let frame = le_u64(&entry[0..]);
+            let id = usize::from(u16::from_le_bytes([entry[28], entry[29]]));
+            let name = names
+                .get(id)
+                .ok_or_else(|| Error::InvalidData(format!("GSD chunk id {} has no name", id)))?;
+            chunk.type_size()?;
+            num_frames = num_frames.max(frame + 1);
+            chunks.insert((frame, name.clone()), chunk);
+        }
+
+        let mut trajectory = GsdTrajectory {
+            reader,
+            chunks,
+            num_frames: crate::to(num_frames, ErrorTask::Read, "num_frames")?,
+            num_atoms: 0,
+            next_frame: 0,
+            length_scale: 1.0,
+            buffer: Vec::new(),
+        };
+        if trajectory.num_frames > 0 {
+            trajectory.num_atoms = trajectory.read_num_atoms(0)?;
+        }
+        Ok(trajectory)
+    }
+
+    /// Set the factor that converts lengths in the file to nanometers (1.0
+    /// by default)
+    pub fn set_length_scale(&mut self, nm_per_unit: f32) {
+        self.length_scale = nm_per_unit;
+    }
+
+    /// Number of frames in the file
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Find the chunk `name` of `frame`, falling back to the first frame
+    fn chunk(&self, frame: usize, name: &str) -> Option<Chunk> {
+        let key = |frame: usize| (frame as u64, name.to_string());
+        self.chunks
+            .get(&key(frame))
+            .or_else(|| self.chunks.get(&key(0)))
+            .copied()
+    }
+
+    /// Read the chunk `name` of `frame` into the buffer, checking that it
+    /// has `columns` columns. Returns false if the chunk does not exist.
+    fn read_chunk(&mut self, frame: usize, name: &str, columns: u32) -> Result<bool> {
+        let chunk = match self.chunk(frame, name) {
+            Some(chunk) => chunk,
+            None => return Ok(false),
+        };
+        if chunk.m != columns {
+            return Err(Error::InvalidData(format!(
+                "GSD chunk {} has {} instead of {} columns",
+                name, chunk.m, columns
+            )));
+        }
+        let mut bytes = vec![0; chunk.len() * chunk.type_size()?];
+        self.reader.seek(SeekFrom::Start(chunk.location))?;
+        self.reader.read_exact(&mut bytes).map_err(to_error)?;
+        decode(&bytes, chunk.data_type, &mut self.buffer);
+        Ok(true)
+    }
+
+    fn read_num_atoms(&mut self, frame: usize) -> Result<usize> {
+        if !self.read_chunk(frame, "particles/N", 1)? {
+            return Ok(0);
+        }
+        let num_atoms = self.buffer.first().copied().unwrap_or_default();
+        Ok(num_atoms as usize)
+    }
+
+    /// Read the chunk `name` as rows of three values, scaled by `scale`.
+    /// Returns false if the chunk does not exist.
+    fn read_xyz(
+        &mut self,
+        frame: usize,
+        name: &str,
+        scale: f32,
+        out: &mut Vec<[f32; 3]>,
+    ) -> Result<bool> {
+        if !self.read_chunk(frame, name, 3)? {
+            return Ok(false);
+        }
+        if self.buffer.len() != 3 * self.num_atoms {
+            return Err(Error::LengthMismatch {
+                name: "GSD particle data",
+                expected: self.num_atoms,
+                found: self.buffer.len() / 3,
+            });
+        }
+        out.clear();
+        out.extend(self.buffer.chunks_exact(3).map(|xyz| {
+            [
+                xyz[0] as f32 * scale,
+                xyz[1] as f32 * scale,
+                xyz[2] as f32 * scale,
+            ]
+        }));
+        Ok(true)
+    }
+}
+
+impl Trajectory for GsdTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if frame.len() != self.num_atoms {
+            return Err((&*frame, self.num_atoms).into());
+        }
+        let index = self.next_frame;
+        if index >= self.num_frames {
+            return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+        }
+        let num_atoms = self.read_num_atoms(index)?;
+        if num_atoms != self.num_atoms {
+            return Err(Error::WrongSizeFrame {
+                expected: self.num_atoms,
+                found: num_atoms,
+            });
+        }
+        let scale = self.length_scale;
+
+        frame.step = if self.read_chunk(index, "configuration/step", 1)? {
+            self.buffer.first().copied().unwrap_or_default() as usize
+        } else {
+            0
+        };
+        let mut hoomd_box = DEFAULT_BOX;
+        if self.read_chunk(index, "configuration/box", 1)? && self.buffer.len() == 6 {
+            hoomd_box.copy_from_slice(&self.buffer);
+        }
+        let [lx, ly, lz, xy, xz, yz] = hoomd_box.map(|x| x as f32);
+        frame.box_vector = BoxVector::new([
+            [lx * scale, 0.0, 0.0],
+            [xy * ly * scale, ly * scale, 0.0],
+            [xz * lz * scale, yz * lz * scale, lz * scale],
+        ]);
+
+        if !self.read_xyz(index, "particles/position", scale, &mut frame.coords)? {
+            frame.coords.clear();
+            frame.coords.resize(self.num_atoms, [0.0; 3]);
+        }
+        // the box is centered around the origin
+        let [a, b, c] = frame.box_vector.0;
+        let shift = [0, 1, 2].map(|k| (a[k] + b[k] + c[k]) / 2.0);
+        for xyz in frame.coords.iter_mut() {
+            for k in 0..3 {
+                xyz[k] += shift[k];
+            }
+        }
+
+        let mut velocities = frame.velocities.take().unwrap_or_default();
+        if self.read_xyz(index, "particles/velocity", scale, &mut velocities)? {
+            frame.velocities = Some(velocities);
+        }
+        frame.forces = None;
+        frame.time = 0.0;
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing GSD trajectories"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        Ok(self.num_atoms)
+    }
+}
+
+impl IntoIterator for GsdTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<GsdTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    /// Build a GSD 2.0 file from `(frame, name, type, columns, data)` chunks
+    fn write_gsd(chunks: &[(u64, &str, u8, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut names: Vec<&str> = Vec::new();
+        let mut data = Vec::new();
+        let mut index = Vec::new();
+        for (frame, name, data_type, m, bytes) in chunks {
+            let id = match names.iter().position(|n| n == name) {
+                Some(id) => id,
+                None => {
+                    names.push(name);
+                    names.len() - 1
+                }
+            };
+            let size = [0, 1, 2, 4, 8, 1, 2, 4, 8, 4, 8][*data_type as usize];
+            index.extend_from_slice(&frame.to_le_bytes());
+            index.extend_from_slice(&((bytes.len() / size) as u64 / u64::from(*m)).to_le_bytes());
+            index.extend_from_slice(&((HEADER_SIZE + data.len()) as u64).to_le_bytes());
+            index.extend_from_slice(&m.to_le_bytes());
+            index.extend_from_slice(&(id as u16).to_le_bytes());
+            index.extend_from_slice(&[*data_type, 0]);
+            data.extend_from_slice(bytes);
+        }
+        // one unused index entry
+        index.extend_from_slice(&[0; INDEX_ENTRY_SIZE]);
+        // at least one nul byte terminates the last name
+        let mut namelist: Vec<u8> = names.join("\0").into_bytes();
+        let namelist_entries = namelist.len() / NAME_SIZE + 1;
+        namelist.resize(namelist_entries * NAME_SIZE, 0);
+
+        let index_location = (HEADER_SIZE + data.len()) as u64;
+        let namelist_location = index_location + index.len() as u64;
+        let mut file = Vec::new();
+        file.extend_from_slice(&MAGIC.to_le_bytes());
+        file.extend_from_slice(&index_location.to_le_bytes());
+        file.extend_from_slice(&((index.len() / INDEX_ENTRY_SIZE) as u64).to_le_bytes());
+        file.extend_from_slice(&namelist_location.to_le_bytes());
+        file.extend_from_slice(&(namelist_entries as u64).to_le_bytes());
+        file.extend_from_slice(&(1u32 << 16).to_le_bytes());
+        file.extend_from_slice(&(2u32 << 16).to_le_bytes());
+        file.resize(HEADER_SIZE, 0);
+        file.extend(data);
+        file.extend(index);
+        file.extend(namelist);
+        file
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_gsd() -> Result<()> {
+        let gsd = write_gsd(&[
+            (0, "configuration/step", 4, 1, 100u64.to_le_bytes().to_vec()),
+            (
+                0,
+                "configuration/box",
+                9,
+                1,
+                floats(&[2.0, 4.0, 6.0, 0.0, 0.0, 0.5]),
+            ),
+            (0, "particles/N", 3, 1, 2u32.to_le_bytes().to_vec()),
+            (
+                0,
+                "particles/position",
+                9,
+                3,
+                floats(&[0.0, 0.0, 0.0, -1.0, 1.0, 2.0]),
+            ),
+            (1, "configuration/step", 4, 1, 200u64.to_le_bytes().to_vec()),
+            (
+                1,
+                "particles/position",
+                9,
+                3,
+                floats(&[0.5, 0.5, 0.5, 0.0, 0.0, 0.0]),
+            ),
+            (
+                1,
+                "particles/velocity",
+                9,
+                3,
+                floats(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            ),
+        ]);
+        let tempfile = NamedTempFile::new()?;
+        std::fs::write(tempfile.path(), &gsd)?;
+
+        let trj = GsdTrajectory::open_read(tempfile.path())?;
+        assert_eq!(trj.num_frames(), 2);
+        let frames: Vec<Rc<Frame>> = trj.into_iter().collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 2);
+
+        // box vectors of the tilted box: (2, 0, 0), (0, 4, 0), (0, 3, 6)
+        let box_vector = BoxVector::new([[2.0, 0.0, 0.0], [0.0, 4.0, 0.0], [0.0, 3.0, 6.0]]);
+        assert_eq!(frames[0].step, 100);
+        assert_eq!(frames[0].box_vector, box_vector);
+        assert_eq!(frames[0].coords, vec![[1.0, 3.5, 3.0], [0.0, 4.5, 5.0]]);
+        assert_eq!(frames[0].velocities, None);
+
+        // the box is taken from the first frame
+        assert_eq!(frames[1].step, 200);
+        assert_eq!(frames[1].box_vector, box_vector);
+        assert_eq!(frames[1].coords[0], [1.5, 4.0, 3.5]);
+        assert_eq!(
+            frames[1].velocities,
+            Some(vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]])
+        );
+
+        let mut trj = GsdTrajectory::open_read(tempfile.path())?;
+        trj.set_length_scale(0.1);
+        let mut frame = Frame::with_len(2);
+        trj.read(&mut frame)?;
+        assert_approx_eq!(frame.box_vector.0[2][2], 0.6);
+
+        std::fs::write(tempfile.path(), &gsd[8..])?;
+        let result = GsdTrajectory::open_read(tempfile.path());
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! Reading and writing H5MD trajectories (.h5)
+//!
+//! H5MD stores particle data as time series in an HDF5 file. Positions are
+//! read from `/particles/<group>/position`, velocities from `velocity` and
+//! the unit cell from `box/edges`, each with `step`, `time` and `value`
+//! datasets. Lengths are assumed to be in nanometers.
+
+use crate::iterator::into_iter_inner;
+use crate::*;
+use hdf5::types::VarLenUnicode;
+use ndarray::{s, Array2};
+use std::rc::Rc;
+
+/// Name of the particle group written by `H5MDTrajectory::open_write`
+const DEFAULT_GROUP: &str = "all";
+
+impl From<hdf5::Error> for Error {
+    fn from(err: hdf5::Error) -> Self {
+        Error::InvalidData(format!("HDF5 error: {}", err))
+    }
+}
+
+/// Time series of a single quantity (`step`, `time` and `value` datasets)
+struct TimeSeries {
+    step: hdf5::Dataset,
+    time: hdf5::Dataset,
+    value: hdf5::Dataset,
+}
+
+impl TimeSeries {
+    fn open(group: &hdf5::Group) -> Result<TimeSeries> {
+        Ok(TimeSeries {
+            step: group.dataset("step")?,
+            time: group.dataset("time")?,
+            value: group.dataset("value")?,
+        })
+    }
+
+    /// Create resizable datasets for frames of `num_rows` rows of three
+    /// values each
+    fn create(group: &hdf5::Group, num_rows: usize) -> Result<TimeSeries> {
+        let step = group
+            .new_dataset::<i64>()
+            .chunk(1024)
+            .shape(0..)
+            .create("step")?;
+        let time = group
+            .new_dataset::<f32>()
+            .chunk(1024)
+            .shape(0..)
+            .create("time")?;
+        write_string_attr(&time, "unit", "ps")?;
+        let value = group
+            .new_dataset::<f32>()
+            .chunk((1, num_rows, 3))
+            .shape((0.., num_rows, 3))
+            .create("value")?;
+        Ok(TimeSeries { step, time, value })
+    }
+
+    fn len(&self) -> usize {
+        self.value.shape().first().copied().unwrap_or(0)
+    }
+
+    /// Read the step and time of frame `i`
+    fn read_step_time(&self, i: usize) -> Result<(usize, f32)> {
+        let step = self.step.read_slice_1d::<i64, _>(s![i..i + 1])?[0];
+        let time = self.time.read_slice_1d::<f32, _>(s![i..i + 1])?[0];
+        Ok((crate::to(step, ErrorTask::Read, "step")?, time))
+    }
+
+    /// Read all values of frame `i` in row-major order
+    fn read_values(&self, i: usize) -> Result<Vec<f32>> {
+        let values = match self.value.ndim() {
+            2 => self.value.read_slice_1d::<f32, _>(s![i, ..])?.to_vec(),
+            _ => self
+                .value
+                .read_slice_2d::<f32, _>(s![i, .., ..])?
+                .iter()
+                .copied()
+                .collect(),
+        };
+        Ok(values)
+    }
+
+    /// Read the values of frame `i` as rows of three numbers
+    fn read_rows(&self, i: usize, out: &mut [[f32; 3]]) -> Result<()> {
+        let rows = self.value.read_slice_2d::<f32, _>(s![i, .., ..])?;
+        if rows.nrows() != out.len() || rows.ncols() != 3 {
+            return Err(Error::LengthMismatch {
+                name: "rows",
+                expected: out.len(),
+                found: rows.nrows(),
+            });
+        }
+        for (xyz, row) in out.iter_mut().zip(rows.outer_iter()) {
+            *xyz = [row[0], row[1], row[2]];
+        }
+        Ok(())
+    }
+
+    fn append(&self, step: usize, time: f32, rows: &[[f32; 3]]) -> Result<()> {
+        let i = self.len();
+        let step = crate::to(step, ErrorTask::Write, "step")?;
+        self.step.resize(i + 1)?;
+        self.step.write_slice(&[step], s![i..i + 1])?;
+        self.time.resize(i + 1)?;
+        self.time.write_slice(&[time], s![i..i + 1])?;
+
+        let values =
+            Array2::from_shape_vec((rows.len(), 3), rows.iter().flatten().copied().collect())
+                .expect("rows have three columns");
+        self.value.resize((i + 1, rows.len(), 3))?;
+        self.value.write_slice(&values, s![i, .., ..])?;
+        Ok(())
+    }
+}
+
+/// Box of the particle group, which is either fixed or a time series
+enum Edges {
+    None,
+    Fixed(BoxVector),
+    TimeSeries(TimeSeries),
+}
+
+/// Read and write handle to an H5MD trajectory.
+///
+/// Reading uses the first particle group of the file unless another one is
+/// chosen with `open_read_group`; writing creates the group "all".
+pub struct H5MDTrajectory {
+    file: hdf5::File,
+    num_atoms: Option<usize>,
+    next_frame: usize,
+    position: Option<TimeSeries>,
+    velocity: Option<TimeSeries>,
+    edges: Edges,
+    group: hdf5::Group,
+}
+
+impl H5MDTrajectory {
+    /// Open the first particle group of the H5MD file at `path` for reading
+    pub fn open_read(path: impl AsRef<Path>) -> Result<H5MDTrajectory> {
+        let file = hdf5::File::open(path)?;
+        let names = file.group("particles")?.member_names()?;
+        let name = names
+            .first()
+            .ok_or_else(|| Error::InvalidData("H5MD file has no particle groups".to_string()))?
+            .clone();
+        H5MDTrajectory::read_group(file, &name)
+    }
+
+    /// Open the particle group `group` of the H5MD file at `path` for reading
+    pub fn open_read_group(path: impl AsRef<Path>, group: &str) -> Result<H5MDTrajectory> {
+        H5MDTrajectory::read_group(hdf5::File::open(path)?, group)
+    }
+
+    fn read_group(file: hdf5::File, name: &str) -> Result<H5MDTrajectory> {
+        let group = file.group(&format!("particles/{}", name))?;
+        let position = TimeSeries::open(&group.group("position")?)?;
+        let num_atoms = position.value.shape().get(1).copied().unwrap_or(0);
+        let velocity = match group.group("velocity") {
+            Ok(velocity) => Some(TimeSeries::open(&velocity)?),
+            Err(_) => None,
+        };
+
+        let edges = if let Ok(edges) = group.group("box/edges") {
+            Edges::TimeSeries(TimeSeries::open(&edges)?)
+        } else if let Ok(edges) = group.dataset("box/edges") {
+            Edges::Fixed(box_from_values(&edges.read_raw::<f32>()?)?)
+        } else {
+            Edges::None
+        };
+
+        Ok(H5MDTrajectory {
+            file,
+            num_atoms: Some(num_atoms),
+            next_frame: 0,
+            position: Some(position),
+            velocity,
+            edges,
+            group,
+        })
+    }
+
+    /// Create a new H5MD file at `path` for writing, replacing an existing
+    /// file. The datasets are created when the first frame is written.
+    pub fn open_write(path: impl AsRef<Path>) -> Result<H5MDTrajectory> {
+        let file = hdf5::File::create(path)?;
+        let h5md = file.create_group("h5md")?;
+        h5md.new_attr::<i32>()
+            .shape(2)
+            .create("version")?
+            .write_raw(&[1, 1])?;
+        let creator = h5md.create_group("creator")?;
+        write_string_attr(&creator, "name", env!("CARGO_PKG_NAME"))?;
+        write_string_attr(&creator, "version", env!("CARGO_PKG_VERSION"))?;
+
+        let group = file
+            .create_group("particles")?
+            .create_group(DEFAULT_GROUP)?;
+        Ok(H5MDTrajectory {
+            file,
+            num_atoms: None,
+            next_frame: 0,
+            position: None,
+            velocity: None,
+            edges: Edges::None,
+            group,
+        })
+    }
+
+    /// Number of frames in the file
+    pub fn num_frames(&self) -> usize {
+        self.position.as_ref().map_or(0, TimeSeries::len)
+    }
+
+    /// Create the datasets for frames like `frame`
+    fn create_datasets(&mut self, frame: &Frame) -> Result<()> {
+        let num_atoms = frame.len();
+        let position = TimeSeries::create(&self.group.create_group("position")?, num_atoms)?;
+        write_string_attr(&position.value, "unit", "nm")?;
+        self.position = Some(position);
+        if frame.velocities.is_some() {
+            let velocity = TimeSeries::create(&self.group.create_group("velocity")?, num_atoms)?;
+            write_string_attr(&velocity.value, "unit", "nm ps-1")?;
+            self.velocity = Some(velocity);
+        }
+
+        let box_group = self.group.create_group("box")?;
+        box_group
+            .new_attr::<i32>()
+            .create("dimension")?
+            .write_scalar(&3)?;
+        let edges = box_group.create_group("edges")?;
+        self.edges = Edges::TimeSeries(TimeSeries::create(&edges, 3)?);
+        self.num_atoms = Some(num_atoms);
+        Ok(())
+    }
+}
+
+fn write_string_attr(location: &hdf5::Location, name: &str, value: &str) -> Result<()> {
+    let value: VarLenUnicode = value
+        .parse()
+        .map_err(|_| Error::InvalidData(format!("invalid attribute value {:?}", value)))?;
+    location
+        .new_attr::<VarLenUnicode>()
+        .create(name)?
+        .write_scalar(&value)?;
+    Ok(())
+}
+
+/// Convert box edges stored as three lengths (rectangular box) or as a 3x3
+/// matrix
+fn box_from_values(values: &[f32]) -> Result<BoxVector> {
+    match *values {
+        [a, b, c] => Ok(BoxVector::rectangular(a, b, c)),
+        [ax, ay, az, bx, by, bz, cx, cy, cz] => {
+            Ok(BoxVector([[ax, ay, az], [bx, by, bz], [cx, cy, cz]]))
+        }
+        _ => Err(Error::InvalidData(format!(
+            "box edges with {} values",
+            values.len()
+        ))),
+    }
+}
+
+impl Trajectory for H5MDTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        let num_atoms = self.get_num_atoms()?;
+        if frame.len() != num_atoms {
+            return Err((&*frame, num_atoms).into());
+        }
+        let i = self.next_frame;
+        let position = match &self.position {
+            Some(position) if i < position.len() => position,
+            _ => return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into()),
+        };
+
+        position.read_rows(i, &mut frame.coords)?;
+        let (step, time) = position.read_step_time(i)?;
+        frame.step = step;
+        frame.time = time;
+
+        frame.velocities = match &self.velocity {
+            Some(velocity) if i < velocity.len() => {
+                let mut velocities = vec![[0.0; 3]; num_atoms];
+                velocity.read_rows(i, &mut velocities)?;
+                Some(velocities)
+            }
+            _ => None,
+        };
+        frame.forces = None;
+
+        frame.box_vector = match &self.edges {
+            Edges::None => BoxVector::default(),
+            Edges::Fixed(box_vector) => *box_vector,
+            Edges::TimeSeries(edges) => box_from_values(&edges.read_values(i)?)?,
+        };
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        if self.position.is_none() {
+            self.create_datasets(frame)?;
+        }
+        let num_atoms = self.get_num_atoms()?;
+        if frame.len() != num_atoms {
+            return Err((frame, num_atoms).into());
+        }
+
+        let position = self.position.as_ref().expect("datasets were created");
+        position.append(frame.step, frame.time, &frame.coords)?;
+        if let (Some(velocity), Some(velocities)) = (&self.velocity, &frame.velocities) {
+            velocity.append(frame.step, frame.time, velocities)?;
+        }
+        if let Edges::TimeSeries(edges) = &self.edges {
+            edges.append(frame.step, frame.time, &frame.box_vector.0)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.num_atoms.ok_or_else(|| {
+            Error::InvalidData("no frames have been written to the H5MD file yet".to_string())
+        })
+    }
+}
+
+impl IntoIterator for H5MDTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<H5MDTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_h5md_roundtrip() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("traj.h5");
+
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .take(5)
+            .collect::<Result<_>>()?;
+        let mut trj = H5MDTrajectory::open_write(&path)?;
+        for frame in &frames {
+            trj.write(frame)?;
+        }
+        trj.flush()?;
+        drop(trj);
+
+        let trj = H5MDTrajectory::open_read(&path)?;
+        assert_eq!(trj.num_frames(), 5);
+        let read: Vec<Rc<Frame>> = trj.into_iter().collect::<Result<_>>()?;
+        for (frame, expected) in read.iter().zip(&frames) {
+            assert!(frame.approx_eq(expected, 0.0));
+            assert_eq!(frame.box_vector, expected.box_vector);
+        }
+        Ok(())
+    }
+}
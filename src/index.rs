@@ -0,0 +1,681 @@
+//! Frame index built by scanning the frame headers of a trajectory file
+
+use crate::errors::*;
+use crate::trace::operation;
+use crate::BoxVector;
+#[cfg(feature = "c-lib")]
+use crate::{TRRTrajectory, XTCTrajectory};
+use std::convert::TryFrom;
+use std::fs::File;
+#[cfg(feature = "c-lib")]
+use std::io::{Seek, SeekFrom};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub(crate) const XTC_MAGIC: i32 = 1995;
+const TRR_MAGIC: i32 = 1993;
+const TRR_VERSION: &[u8] = b"GMX_trn_file";
+const INDEX_MAGIC: &[u8; 8] = b"XDRFIDX1";
+
+/// Byte offsets of all frames in a trajectory file.
+///
+/// The index is built by reading only the frame headers, which is much
+/// faster than decoding the coordinates. It enables random access and
+/// splitting a trajectory into independent parts.
+///
+/// For large trajectories, `load_or_build` keeps the index in a file next to
+/// the trajectory, so that it only needs to be built once.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameIndex {
+    offsets: Vec<u64>,
+    times: Vec<f32>,
+    num_atoms: usize,
+}
+
+impl FrameIndex {
+    /// Scan the XTC or TRR file at `path` (detected from the magic number
+    /// of the first frame). A truncated last frame is not part of the index.
+    pub fn build(path: impl AsRef<Path>) -> Result<FrameIndex> {
+        let path = path.as_ref();
+        let operation = operation!(DEBUG, "build_index", path = %path.display());
+        let result = FrameIndex::scan(path);
+        operation.finish(&result);
+        result
+    }
+
+    fn scan(path: &Path) -> Result<FrameIndex> {
+        let mut index = FrameIndex::default();
+        for header in FrameHeaders::open(path)? {
+            let header = header?;
+            if index.offsets.is_empty() {
+                index.num_atoms = header.num_atoms;
+            }
+            index.offsets.push(header.offset);
+            index.times.push(header.time);
+        }
+        Ok(index)
+    }
+
+    /// Path of the index file that `load_or_build` keeps next to the
+    /// trajectory at `path`, e.g. "traj.xtcidx" for "traj.xtc"
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        let mut extension = path.extension().unwrap_or_default().to_owned();
+        extension.push("idx");
+        path.with_extension(extension)
+    }
+
+    /// Load the index of the trajectory at `path` from its sidecar file if
+    /// the trajectory did not change since, or build the index and save it
+    /// to the sidecar file. Failing to save the index, e.g. in a read-only
+    /// directory, is not an error.
+    pub fn load_or_build(path: impl AsRef<Path>) -> Result<FrameIndex> {
+        let path = path.as_ref();
+        let index_path = FrameIndex::sidecar_path(path);
+        // a missing or corrupt index file is replaced
+        if let Ok(Some(index)) = FrameIndex::load(path, &index_path) {
+            return Ok(index);
+        }
+        let index = FrameIndex::build(path)?;
+        let _ = index.save(path, &index_path);
+        Ok(index)
+    }
+
+    /// Save the index to `index_path` together with the size and
+    /// modification time of the trajectory at `path` it was built from
+    pub fn save(&self, path: impl AsRef<Path>, index_path: impl AsRef<Path>) -> Result<()> {
+        let (len, modified) = file_stamp(path.as_ref())?;
+        let mut bytes = Vec::with_capacity(48 + 12 * self.len());
+        bytes.extend_from_slice(INDEX_MAGIC);
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&modified.to_le_bytes());
+        bytes.extend_from_slice(&(self.num_atoms as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.len() as u64).to_le_bytes());
+        for (offset, time) in self.offsets.iter().zip(&self.times) {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&time.to_le_bytes());
+        }
+        std::fs::write(index_path, bytes)?;
+        Ok(())
+    }
+
+    /// Load an index saved with `save`. Returns `None` if the size or
+    /// modification time of the trajectory at `path` changed since.
+    pub fn load(
+        path: impl AsRef<Path>,
+        index_path: impl AsRef<Path>,
+    ) -> Result<Option<FrameIndex>> {
+        let bytes = std::fs::read(index_path)?;
+        let mut reader = &bytes[..];
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(Error::InvalidData("Not a frame index file".to_string()));
+        }
+        let len = read_u64_le(&mut reader)?;
+        let modified = read_u128_le(&mut reader)?;
+        if file_stamp(path.as_ref())? != (len, modified) {
+            return Ok(None);
+        }
+        let num_atoms = crate::to(read_u64_le(&mut reader)?, ErrorTask::Index, "natoms")?;
+        let num_frames: usize = crate::to(read_u64_le(&mut reader)?, ErrorTask::Index, "frames")?;
+        if reader.len() != num_frames.saturating_mul(12) {
+            return Err(Error::InvalidData(
+                "Frame index file has the wrong size".to_string(),
+            ));
+        }
+        let mut index = FrameIndex {
+            offsets: Vec::with_capacity(num_frames),
+            times: Vec::with_capacity(num_frames),
+            num_atoms,
+        };
+        for _ in 0..num_frames {
+            index.offsets.push(read_u64_le(&mut reader)?);
+            let mut time = [0; 4];
+            reader.read_exact(&mut time)?;
+            index.times.push(f32::from_le_bytes(time));
+        }
+        Ok(Some(index))
+    }
+
+    /// Number of frames in the trajectory
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// True if the trajectory contains no frames
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Number of atoms in the first frame
+    pub fn num_atoms(&self) -> usize {
+        self.num_atoms
+    }
+
+    /// Byte offset of frame `i`, if it exists
+    pub fn offset(&self, i: usize) -> Option<u64> {
+        self.offsets.get(i).copied()
+    }
+
+    /// Byte offsets of all frames
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Time of frame `i`, if it exists
+    pub fn time(&self, i: usize) -> Option<f32> {
+        self.times.get(i).copied()
+    }
+
+    /// Times of all frames
+    pub fn times(&self) -> &[f32] {
+        &self.times
+    }
+}
+
+/// Size and modification time (in nanoseconds since the epoch) of a file,
+/// which tell whether a saved index is still valid
+fn file_stamp(path: &Path) -> Result<(u64, u128)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos());
+    Ok((metadata.len(), modified))
+}
+
+fn read_u64_le(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u128_le(reader: &mut impl Read) -> io::Result<u128> {
+    let mut bytes = [0; 16];
+    reader.read_exact(&mut bytes)?;
+    Ok(u128::from_le_bytes(bytes))
+}
+
+/// The metadata of a single frame, read without decoding its coordinates
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameHeader {
+    /// Trajectory step
+    pub step: usize,
+
+    /// Time step (usually in picoseconds)
+    pub time: f32,
+
+    /// 3x3 box vector (zero if the frame has no box)
+    pub box_vector: BoxVector,
+
+    /// Number of atoms in the frame
+    pub num_atoms: usize,
+
+    /// Byte offset of the frame in the file
+    pub offset: u64,
+}
+
+/// Iterator over the headers of all frames in a trajectory file, see
+/// `XTCTrajectory::headers` and `TRRTrajectory::headers`
+pub struct FrameHeaders {
+    reader: BufReader<File>,
+    file_len: u64,
+    offset: u64,
+    has_error: bool,
+}
+
+impl FrameHeaders {
+    /// Read the headers of the XTC or TRR file at `path`. A truncated last
+    /// frame is skipped.
+    pub fn open(path: impl AsRef<Path>) -> Result<FrameHeaders> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        Ok(FrameHeaders {
+            reader: BufReader::new(file),
+            file_len,
+            offset: 0,
+            has_error: false,
+        })
+    }
+
+    /// End of the last frame that was read, i.e. the start of the next one
+    pub(crate) fn end_offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn next_inner(&mut self) -> Result<Option<FrameHeader>> {
+        let header = match read_header(&mut self.reader) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(Error::InvalidData(e.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // make sure the whole frame is present before yielding it
+        let end = self.offset + header.consumed + header.remaining;
+        if end > self.file_len {
+            return Ok(None);
+        }
+        let remaining = crate::to(header.remaining, ErrorTask::Index, "frame size")?;
+        self.reader.seek_relative(remaining)?;
+        let offset = std::mem::replace(&mut self.offset, end);
+        Ok(Some(FrameHeader {
+            step: crate::to(header.step, ErrorTask::Index, "step")?,
+            time: header.time,
+            box_vector: header.box_vector,
+            num_atoms: crate::to(header.num_atoms, ErrorTask::Index, "natoms")?,
+            offset,
+        }))
+    }
+}
+
+impl Iterator for FrameHeaders {
+    type Item = Result<FrameHeader>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.has_error {
+            return None;
+        }
+        match self.next_inner() {
+            Ok(header) => header.map(Ok),
+            Err(e) => {
+                self.has_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Iterate over the step, time and box of all frames without decoding
+    /// their coordinates, which is much faster than reading the frames. The
+    /// file is read with a separate handle.
+    pub fn headers(&self) -> Result<FrameHeaders> {
+        FrameHeaders::open(self.handle.file_path()?)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Iterate over the step, time and box of all frames without decoding
+    /// their coordinates, which is much faster than reading the frames. The
+    /// file is read with a separate handle.
+    pub fn headers(&self) -> Result<FrameHeaders> {
+        FrameHeaders::open(self.handle.file_path()?)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+/// Find the start of the first complete frame with `num_atoms` atoms that
+/// begins after byte `after`, e.g. to skip over a corrupt frame. Frames
+/// start at multiples of four bytes.
+pub(crate) fn find_next_frame(
+    path: &Path,
+    after: u64,
+    num_atoms: usize,
+) -> io::Result<Option<u64>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut candidate = (after / 4 + 1) * 4;
+    reader.seek(SeekFrom::Start(candidate))?;
+
+    while candidate + 4 <= file_len {
+        let magic = read_i32(&mut reader)?;
+        if magic == XTC_MAGIC || magic == TRR_MAGIC {
+            reader.seek(SeekFrom::Start(candidate))?;
+            if let Ok(Some(header)) = read_header(&mut reader) {
+                let complete = candidate + header.consumed + header.remaining <= file_len;
+                if complete && usize::try_from(header.num_atoms) == Ok(num_atoms) {
+                    return Ok(Some(candidate));
+                }
+            }
+            reader.seek(SeekFrom::Start(candidate + 4))?;
+        }
+        candidate += 4;
+    }
+    Ok(None)
+}
+
+/// The header of a single frame
+#[cfg_attr(not(feature = "c-lib"), allow(dead_code))]
+pub(crate) struct RawHeader {
+    pub(crate) num_atoms: i32,
+    pub(crate) step: i32,
+    pub(crate) time: f32,
+    pub(crate) box_vector: BoxVector,
+    /// Precision of the compressed XTC coordinates
+    pub(crate) precision: Option<f32>,
+    /// Whether the TRR frame stores velocities and forces
+    pub(crate) velocities: bool,
+    pub(crate) forces: bool,
+    /// Number of bytes read from the start of the frame
+    pub(crate) consumed: u64,
+    /// Number of bytes until the start of the next frame
+    pub(crate) remaining: u64,
+}
+
+/// Read the header of the next XTC or TRR frame. Returns `None` at the end of
+/// the file and leaves the reader `consumed` bytes after the frame start.
+pub(crate) fn read_header(reader: &mut impl Read) -> io::Result<Option<RawHeader>> {
+    match read_magic(reader)? {
+        Some(magic) => read_header_after(reader, magic).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_magic(reader: &mut impl Read) -> io::Result<Option<i32>> {
+    let mut magic = [0; 4];
+    match reader.read(&mut magic)? {
+        0 => return Ok(None),
+        4 => {}
+        n => reader.read_exact(&mut magic[n..])?,
+    }
+    Ok(Some(i32::from_be_bytes(magic)))
+}
+
+fn read_header_after(reader: &mut impl Read, magic: i32) -> io::Result<RawHeader> {
+    match magic {
+        XTC_MAGIC => read_xtc_header(reader),
+        TRR_MAGIC => read_trr_header(reader),
+        magic => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown magic number {}", magic),
+        )),
+    }
+}
+
+#[cfg(feature = "c-lib")]
+/// Read the number of atoms from the header of the next frame. An unknown
+/// magic number is reported like the C library does.
+pub(crate) fn read_num_atoms(reader: &mut impl Read) -> Result<usize> {
+    let eof = || Err((ErrorCode::ExdrEndOfFile, ErrorTask::ReadNumAtoms).into());
+    let header = match read_magic(reader) {
+        Ok(Some(XTC_MAGIC)) => read_header_after(reader, XTC_MAGIC),
+        Ok(Some(TRR_MAGIC)) => read_header_after(reader, TRR_MAGIC),
+        Ok(Some(_)) => return Err((ErrorCode::ExdrMagic, ErrorTask::ReadNumAtoms).into()),
+        Ok(None) => return eof(),
+        Err(e) => Err(e),
+    };
+    match header {
+        Ok(header) => crate::to(header.num_atoms, ErrorTask::ReadNumAtoms, "natoms"),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => eof(),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(Error::InvalidData(e.to_string())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_xtc_header(reader: &mut impl Read) -> io::Result<RawHeader> {
+    let num_atoms = read_i32(reader)?;
+    let step = read_i32(reader)?;
+    let time = read_f32(reader)?;
+    let box_vector = read_box(reader, false)?;
+    let mut consumed = 56;
+
+    if read_i32(reader)? != num_atoms {
+        return Err(invalid_data("number of atoms does not match"));
+    }
+    let mut precision = None;
+    let remaining = if num_atoms <= 9 {
+        12 * to_u64(num_atoms)?
+    } else {
+        precision = Some(read_f32(reader)?);
+        // minint, maxint and smallidx
+        let mut skipped = [0; 28];
+        reader.read_exact(&mut skipped)?;
+        let num_bytes = to_u64(read_i32(reader)?)?;
+        consumed += 36;
+        num_bytes.div_ceil(4) * 4
+    };
+
+    Ok(RawHeader {
+        num_atoms,
+        step,
+        time,
+        box_vector,
+        precision,
+        velocities: false,
+        forces: false,
+        consumed,
+        remaining,
+    })
+}
+
+fn read_trr_header(reader: &mut impl Read) -> io::Result<RawHeader> {
+    let version_len = read_i32(reader)?;
+    let string_len = read_i32(reader)?;
+    if to_u64(version_len)? != TRR_VERSION.len() as u64 + 1
+        || to_u64(string_len)? != TRR_VERSION.len() as u64
+    {
+        return Err(invalid_data("unexpected TRR version string"));
+    }
+    let mut version = [0; 12];
+    reader.read_exact(&mut version)?;
+
+    // ir, e, box, vir, pres, top, sym, x, v and f sizes
+    let mut sizes = [0; 10];
+    for size in sizes.iter_mut() {
+        *size = to_u64(read_i32(reader)?)?;
+    }
+    let [_, _, box_size, vir_size, pres_size, _, _, x_size, v_size, f_size] = sizes;
+    let num_atoms = read_i32(reader)?;
+    let step = read_i32(reader)?;
+    let _nre = read_i32(reader)?;
+
+    let real_size = if box_size != 0 {
+        box_size / 9
+    } else if num_atoms > 0 {
+        x_size.max(v_size).max(f_size) / (3 * to_u64(num_atoms)?)
+    } else {
+        4
+    };
+    let double = match real_size {
+        4 => false,
+        8 => true,
+        _ => return Err(invalid_data("cannot determine TRR precision")),
+    };
+    let time = if double {
+        let time = read_f64(reader)? as f32;
+        read_f64(reader)?; // lambda
+        time
+    } else {
+        let time = read_f32(reader)?;
+        read_f32(reader)?; // lambda
+        time
+    };
+
+    let mut consumed = 76 + 2 * real_size;
+    let mut box_vector = BoxVector::default();
+    if box_size != 0 {
+        box_vector = read_box(reader, double)?;
+        consumed += box_size;
+    }
+
+    Ok(RawHeader {
+        num_atoms,
+        step,
+        time,
+        box_vector,
+        precision: None,
+        velocities: v_size != 0,
+        forces: f_size != 0,
+        consumed,
+        remaining: vir_size + pres_size + x_size + v_size + f_size,
+    })
+}
+
+fn read_box(reader: &mut impl Read, double: bool) -> io::Result<BoxVector> {
+    let mut box_vector = BoxVector::default();
+    for x in box_vector.0.iter_mut().flatten() {
+        *x = if double {
+            read_f64(reader)? as f32
+        } else {
+            read_f32(reader)?
+        };
+    }
+    Ok(box_vector)
+}
+
+pub(crate) fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes))
+}
+
+pub(crate) fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_be_bytes(bytes))
+}
+
+pub(crate) fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_be_bytes(bytes))
+}
+
+fn to_u64(value: i32) -> io::Result<u64> {
+    u64::try_from(value).map_err(|_| invalid_data("negative size in header"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "c-lib")]
+    use crate::{Frame, Trajectory};
+    #[cfg(feature = "c-lib")]
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_index() -> Result<()> {
+        for path in &["tests/1l2y.xtc", "tests/1l2y.trr"] {
+            let index = FrameIndex::build(path)?;
+            assert_eq!(index.len(), 38);
+            assert_eq!(index.num_atoms(), 304);
+            assert_eq!(index.offset(0), Some(0));
+            assert_eq!(index.offset(38), None);
+        }
+
+        // TRR frames have a fixed size
+        let index = FrameIndex::build("tests/1l2y.trr")?;
+        let frame_size = index.offset(1).unwrap();
+        for (i, offset) in index.offsets().iter().enumerate() {
+            assert_eq!(*offset, i as u64 * frame_size);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_index_offsets_match_reader() -> Result<()> {
+        let index = FrameIndex::build("tests/1l2y.xtc")?;
+        let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(index.num_atoms());
+        for offset in index.offsets() {
+            assert_eq!(trj.tell()?, *offset);
+            trj.read(&mut frame)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_index_truncated() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = NamedTempFile::new()?;
+        let frame = Frame {
+            coords: vec![[1.0; 3]; 2],
+            ..Default::default()
+        };
+        let mut trj = TRRTrajectory::open_write(tempfile.path())?;
+        trj.write(&frame)?;
+        trj.write(&frame)?;
+        trj.flush()?;
+        assert_eq!(FrameIndex::build(tempfile.path())?.len(), 2);
+
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(tempfile.path())?;
+        file.set_len(200)?;
+        assert_eq!(FrameIndex::build(tempfile.path())?.len(), 1);
+
+        let result = FrameIndex::build("README.md");
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_index_sidecar() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("traj.xtc");
+        std::fs::copy("tests/1l2y.xtc", &path)?;
+        let index_path = FrameIndex::sidecar_path(&path);
+        assert_eq!(index_path, dir.path().join("traj.xtcidx"));
+
+        let built = FrameIndex::build(&path)?;
+        assert_eq!(built.time(1), Some(2.0));
+        assert_eq!(FrameIndex::load_or_build(&path)?, built);
+        assert!(index_path.exists());
+        assert_eq!(FrameIndex::load(&path, &index_path)?, Some(built.clone()));
+
+        // the index is rebuilt after the trajectory changed
+        let mut trj = XTCTrajectory::open_append(&path)?;
+        trj.write(&Frame::with_len(304))?;
+        trj.flush()?;
+        drop(trj);
+        assert_eq!(FrameIndex::load(&path, &index_path)?, None);
+        assert_eq!(FrameIndex::load_or_build(&path)?.len(), 39);
+        assert_eq!(FrameIndex::load(&path, &index_path)?.unwrap().len(), 39);
+
+        std::fs::write(&index_path, b"garbage")?;
+        assert!(FrameIndex::load(&path, &index_path).is_err());
+        assert_eq!(FrameIndex::load_or_build(&path)?.len(), 39);
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_headers() -> Result<()> {
+        for path in &["tests/1l2y.xtc", "tests/1l2y.trr"] {
+            let index = FrameIndex::build(path)?;
+            let headers: Vec<FrameHeader> = FrameHeaders::open(path)?.collect::<Result<_>>()?;
+            let offsets: Vec<u64> = headers.iter().map(|h| h.offset).collect();
+            assert_eq!(offsets, index.offsets());
+        }
+
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(304);
+        for (xtc_header, trr_header) in xtc.headers()?.zip(trr.headers()?) {
+            let (xtc_header, trr_header) = (xtc_header?, trr_header?);
+            assert_eq!(xtc_header.step, trr_header.step);
+            assert_eq!(xtc_header.num_atoms, 304);
+            assert_approx_eq!(xtc_header.time, trr_header.time);
+        }
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let header = trr.headers()?.nth(5).unwrap()?;
+        for _ in 0..6 {
+            trr.read(&mut frame)?;
+        }
+        assert_eq!(header.step, frame.step);
+        assert_eq!(header.time, frame.time);
+        assert_eq!(header.box_vector, frame.box_vector);
+
+        let stream = File::open("tests/1l2y.xtc")?;
+        let xtc = XTCTrajectory::from_reader(stream)?;
+        assert!(matches!(xtc.headers(), Err(Error::Unsupported(_))));
+        Ok(())
+    }
+}
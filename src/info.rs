@@ -0,0 +1,150 @@
+//! Summaries of trajectory files, like `gmx check` prints them
+
+use crate::index::read_header;
+use crate::*;
+use std::fs::File;
+use std::io::BufReader;
+
+/// An overview of a trajectory file gathered from its frame headers, see
+/// `TrajectoryInfo::scan`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrajectoryInfo {
+    /// Number of atoms of the first frame, `None` if the file is empty
+    pub num_atoms: Option<usize>,
+
+    /// Number of complete frames
+    pub num_frames: usize,
+
+    /// Time of the first frame
+    pub first_time: Option<f32>,
+
+    /// Time of the last frame
+    pub last_time: Option<f32>,
+
+    /// Time between the first two frames, which is the time between all
+    /// frames of trajectories written by GROMACS
+    pub dt_estimate: Option<f32>,
+
+    /// Byte length of the file
+    pub file_size: u64,
+
+    /// Precision of the compressed coordinates of the first frame of an XTC
+    /// file. `None` for TRR files, and for XTC files with no more than 9
+    /// atoms, whose coordinates are stored uncompressed.
+    pub precision: Option<f32>,
+}
+
+impl TrajectoryInfo {
+    /// Summarize the XTC or TRR file at `path` by reading its frame headers,
+    /// without decoding any coordinates. A truncated last frame is not
+    /// counted; invalid data before it is an error.
+    ///
+    /// ```
+    /// use xdrfile::*;
+    ///
+    /// # fn main() -> Result<()> {
+    /// let info = TrajectoryInfo::scan("tests/1l2y.xtc")?;
+    /// assert_eq!(info.num_atoms, Some(304));
+    /// assert_eq!(info.num_frames, 38);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scan(path: impl AsRef<Path>) -> Result<TrajectoryInfo> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let precision = match read_header(&mut BufReader::new(file)) {
+            Ok(Some(header)) => header.precision,
+            _ => None,
+        };
+
+        let mut info = TrajectoryInfo {
+            num_atoms: None,
+            num_frames: 0,
+            first_time: None,
+            last_time: None,
+            dt_estimate: None,
+            file_size,
+            precision,
+        };
+        for header in FrameHeaders::open(path)? {
+            let header = header?;
+            info.num_atoms.get_or_insert(header.num_atoms);
+            if info.num_frames == 1 {
+                info.dt_estimate = info.first_time.map(|first| header.time - first);
+            }
+            info.first_time.get_or_insert(header.time);
+            info.last_time = Some(header.time);
+            info.num_frames += 1;
+        }
+        Ok(info)
+    }
+
+    /// Time between the first and the last frame
+    pub fn duration(&self) -> Option<f32> {
+        Some(self.last_time? - self.first_time?)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Summarize the file of this trajectory, see `TrajectoryInfo::scan`.
+    /// The file is read with a separate handle.
+    pub fn info(&self) -> Result<TrajectoryInfo> {
+        TrajectoryInfo::scan(self.handle.file_path()?)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Summarize the file of this trajectory, see `TrajectoryInfo::scan`.
+    /// The file is read with a separate handle.
+    pub fn info(&self) -> Result<TrajectoryInfo> {
+        TrajectoryInfo::scan(self.handle.file_path()?)
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_info() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?.info()?;
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?.info()?;
+        for info in &[xtc, trr] {
+            assert_eq!(info.num_atoms, Some(304));
+            assert_eq!(info.num_frames, 38);
+            assert_eq!(info.first_time, Some(1.0));
+            assert_eq!(info.last_time, Some(38.0));
+            assert_eq!(info.dt_estimate, Some(1.0));
+            assert_eq!(info.duration(), Some(37.0));
+        }
+        assert_eq!(xtc.precision, Some(10000.0));
+        assert_eq!(trr.precision, None);
+        assert_eq!(xtc.file_size, std::fs::metadata("tests/1l2y.xtc")?.len());
+
+        let dir = TempDir::new()?;
+        let path = dir.path().join("single.xtc");
+        XTCTrajectory::write_from_iter(&path, &[Frame::with_len(3)])?;
+        let info = TrajectoryInfo::scan(&path)?;
+        assert_eq!(info.num_frames, 1);
+        assert_eq!(info.first_time, Some(0.0));
+        assert_eq!(info.dt_estimate, None);
+        assert_eq!(info.precision, None);
+
+        let path = dir.path().join("empty.xtc");
+        std::fs::write(&path, b"")?;
+        let info = TrajectoryInfo::scan(&path)?;
+        assert_eq!(info.num_atoms, None);
+        assert_eq!(info.num_frames, 0);
+        assert_eq!(info.duration(), None);
+        assert!(TrajectoryInfo::scan("README.md").is_err());
+
+        let stream = File::open("tests/1l2y.trr")?;
+        let trr = TRRTrajectory::from_reader(stream)?;
+        assert!(matches!(trr.info(), Err(Error::Unsupported(_))));
+        Ok(())
+    }
+}
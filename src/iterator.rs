@@ -1,7 +1,44 @@
 use crate::*;
+use std::io::Seek;
+use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
 
-fn into_iter_inner<T: Trajectory>(mut traj: T) -> TrajectoryIterator<T> {
+/// Reference counted pointer to a frame as yielded by `TrajectoryIterator`.
+///
+/// Implemented for `Rc<Frame>` and for `Arc<Frame>`, whose frames can be sent
+/// to other threads.
+pub trait SharedFrame: Clone + Deref<Target = Frame> {
+    /// Wrap a frame into a new pointer
+    fn new(frame: Frame) -> Self;
+
+    /// Mutable access to the frame if there are no other pointers to it
+    fn get_mut(this: &mut Self) -> Option<&mut Frame>;
+}
+
+impl SharedFrame for Rc<Frame> {
+    fn new(frame: Frame) -> Self {
+        Rc::new(frame)
+    }
+
+    fn get_mut(this: &mut Self) -> Option<&mut Frame> {
+        Rc::get_mut(this)
+    }
+}
+
+impl SharedFrame for Arc<Frame> {
+    fn new(frame: Frame) -> Self {
+        Arc::new(frame)
+    }
+
+    fn get_mut(this: &mut Self) -> Option<&mut Frame> {
+        Arc::get_mut(this)
+    }
+}
+
+pub(crate) fn into_iter_inner<T: Trajectory, P: SharedFrame>(
+    mut traj: T,
+) -> TrajectoryIterator<T, P> {
     let num_atoms = traj.get_num_atoms();
     let frame = match &num_atoms {
         Ok(num_atoms) => Frame::with_len(*num_atoms),
@@ -9,11 +46,17 @@ fn into_iter_inner<T: Trajectory>(mut traj: T) -> TrajectoryIterator<T> {
     };
     TrajectoryIterator {
         trajectory: traj,
-        item: Rc::new(frame),
+        item: P::new(frame),
         has_error: false,
+        peeked: None,
+        policy: ErrorPolicy::Abort,
+        read_skipping_corrupt: None,
+        skipped: Vec::new(),
+        progress: None,
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl IntoIterator for XTCTrajectory {
     type Item = Result<Rc<Frame>>;
     type IntoIter = TrajectoryIterator<XTCTrajectory>;
@@ -23,6 +66,7 @@ impl IntoIterator for XTCTrajectory {
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl IntoIterator for TRRTrajectory {
     type Item = Result<Rc<Frame>>;
     type IntoIter = TrajectoryIterator<TRRTrajectory>;
@@ -32,17 +76,402 @@ impl IntoIterator for TRRTrajectory {
     }
 }
 
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Turn the trajectory into an iterator that yields owned frames.
+    ///
+    /// Unlike `into_iter`, every frame is read into a newly allocated `Frame`,
+    /// which is convenient for `collect()` or for sending frames elsewhere.
+    pub fn into_frames(self) -> OwnedTrajectoryIterator<XTCTrajectory> {
+        OwnedTrajectoryIterator {
+            trajectory: self,
+            has_error: false,
+        }
+    }
+
+    /// Like `into_iter`, but yields `Arc<Frame>` so that frames can be handed
+    /// to other threads
+    pub fn into_iter_arc(self) -> TrajectoryIterator<XTCTrajectory, Arc<Frame>> {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Turn the trajectory into an iterator that yields owned frames.
+    ///
+    /// Unlike `into_iter`, every frame is read into a newly allocated `Frame`,
+    /// which is convenient for `collect()` or for sending frames elsewhere.
+    pub fn into_frames(self) -> OwnedTrajectoryIterator<TRRTrajectory> {
+        OwnedTrajectoryIterator {
+            trajectory: self,
+            has_error: false,
+        }
+    }
+
+    /// Like `into_iter`, but yields `Arc<Frame>` so that frames can be handed
+    /// to other threads
+    pub fn into_iter_arc(self) -> TrajectoryIterator<TRRTrajectory, Arc<Frame>> {
+        into_iter_inner(self)
+    }
+}
+
 /// Iterator for trajectories.
 /// This iterator yields a Result<Frame, Error> for each frame in the
 /// trajectory file and stops with yielding None once the trajectory is
-/// EOF. Also yields None after the first occurrence of an error
-pub struct TrajectoryIterator<T> {
+/// EOF. Also yields None after the first occurrence of an error.
+///
+/// Frames are wrapped in `P` (`Rc<Frame>` by default, see `SharedFrame`)
+/// so that a frame can be reused if the caller does not keep it.
+pub struct TrajectoryIterator<T, P = Rc<Frame>> {
     trajectory: T,
-    item: Rc<Frame>,
+    item: P,
     has_error: bool,
+    peeked: Option<Option<Result<P>>>,
+    policy: ErrorPolicy,
+    read_skipping_corrupt: Option<ReadFn<T>>,
+    skipped: Vec<Error>,
+    progress: Option<ProgressReporter<T>>,
+}
+
+type ReadFn<T> = fn(&mut T, &mut Frame, &mut Vec<Error>) -> Result<()>;
+
+/// Progress of reading a trajectory, see `TrajectoryIterator::with_progress`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// Number of frames read so far
+    pub frames: usize,
+
+    /// Number of bytes read so far
+    pub bytes: u64,
+
+    /// Size of the trajectory file in bytes
+    pub total_bytes: u64,
+}
+
+impl Progress {
+    /// Fraction of the file that has been read, between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        if self.total_bytes == 0 {
+            1.0
+        } else {
+            self.bytes as f64 / self.total_bytes as f64
+        }
+    }
+
+    /// Estimate the total number of frames from the file size and the
+    /// average size of the frames read so far. The estimate is exact for TRR
+    /// files, whose frames all have the same size.
+    pub fn estimated_total_frames(&self) -> Option<usize> {
+        if self.frames == 0 || self.bytes == 0 {
+            return None;
+        }
+        let frame_size = self.bytes as f64 / self.frames as f64;
+        Some((self.total_bytes as f64 / frame_size).round() as usize)
+    }
+}
+
+struct ProgressReporter<T> {
+    callback: Box<dyn FnMut(&Progress) + Send>,
+    position: fn(&T) -> Result<u64>,
+    progress: Progress,
+}
+
+impl<T> ProgressReporter<T> {
+    fn report(&mut self, trajectory: &T) {
+        self.progress.frames += 1;
+        // a position that cannot be determined keeps the previous one
+        if let Ok(bytes) = (self.position)(trajectory) {
+            self.progress.bytes = bytes;
+        }
+        (self.callback)(&self.progress);
+    }
+}
+
+/// What a `TrajectoryIterator` does when reading a frame fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Yield the error and end the iteration (the default)
+    #[default]
+    Abort,
+    /// End the iteration without yielding the error, as if the trajectory
+    /// ended before the corrupt frame
+    Stop,
+    /// Skip the corrupt frame by searching for the start of the next intact
+    /// frame and continue from there
+    Skip,
+}
+
+/// Trajectories that are read from a single file
+pub trait FilePosition {
+    /// Path of the file
+    fn path(&self) -> &Path;
+
+    /// Current byte position in the file
+    fn position(&self) -> Result<u64>;
+
+    /// Size of the file in bytes
+    fn file_len(&self) -> Result<u64>;
+
+    /// Path of the file for reading it with a separate handle, or
+    /// `Error::Unsupported` if the trajectory has no file that could be
+    /// opened again, like one read from a stream
+    fn file_path(&self) -> Result<&Path> {
+        Ok(self.path())
+    }
+
+    /// The cached index of the frames of the file, or `Error::Unsupported`
+    /// if the trajectory cannot be indexed
+    fn frame_index(&self) -> Result<&FrameIndex> {
+        Err(Error::Unsupported("indexing this trajectory"))
+    }
+}
+
+/// Trajectories that can skip over corrupt data to the next intact frame
+pub trait Resynchronize: FilePosition {
+    /// Move to the first intact frame that starts after byte `after`.
+    /// Returns false if there is none.
+    fn resynchronize(&mut self, after: u64) -> Result<bool>;
+}
+
+#[cfg(feature = "c-lib")]
+fn resynchronize_xdr(handle: &mut XDRFile, after: u64, num_atoms: usize) -> Result<bool> {
+    match index::find_next_frame(handle.file_path()?, after, num_atoms)? {
+        Some(offset) => {
+            handle.seek(SeekFrom::Start(offset))?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl FilePosition for XTCTrajectory {
+    fn path(&self) -> &Path {
+        &self.handle.path
+    }
+
+    fn position(&self) -> Result<u64> {
+        self.tell()
+    }
+
+    fn file_len(&self) -> Result<u64> {
+        Ok(std::fs::metadata(self.handle.file_path()?)?.len())
+    }
+
+    fn file_path(&self) -> Result<&Path> {
+        self.handle.file_path()
+    }
+
+    fn frame_index(&self) -> Result<&FrameIndex> {
+        XTCTrajectory::frame_index(self)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl FilePosition for TRRTrajectory {
+    fn path(&self) -> &Path {
+        &self.handle.path
+    }
+
+    fn position(&self) -> Result<u64> {
+        self.tell()
+    }
+
+    fn file_len(&self) -> Result<u64> {
+        Ok(std::fs::metadata(self.handle.file_path()?)?.len())
+    }
+
+    fn file_path(&self) -> Result<&Path> {
+        self.handle.file_path()
+    }
+
+    fn frame_index(&self) -> Result<&FrameIndex> {
+        TRRTrajectory::frame_index(self)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl Resynchronize for XTCTrajectory {
+    fn resynchronize(&mut self, after: u64) -> Result<bool> {
+        let num_atoms = self.get_num_atoms()?;
+        resynchronize_xdr(&mut self.handle, after, num_atoms)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl Resynchronize for TRRTrajectory {
+    fn resynchronize(&mut self, after: u64) -> Result<bool> {
+        let num_atoms = self.get_num_atoms()?;
+        resynchronize_xdr(&mut self.handle, after, num_atoms)
+    }
+}
+
+/// Trajectories that can move past frames without decoding them
+pub trait SkipFrames: Trajectory {
+    /// Skip up to `n` frames by reading only their headers and seeking past
+    /// their coordinates. Returns the number of frames skipped, which is
+    /// less than `n` at the end of the file. After an error, the next read
+    /// must fail as well.
+    fn skip_frames(&mut self, n: usize) -> Result<usize>;
+
+    /// Read the next frame and skip the `stride - 1` frames after it, so
+    /// that repeated calls read every `stride`-th frame, starting with the
+    /// next one. A failure to skip does not discard the frame that was
+    /// read; it is reported by the next call instead. Panics if `stride`
+    /// is 0.
+    fn read_strided(&mut self, stride: usize, frame: &mut Frame) -> Result<()> {
+        assert!(stride > 0, "stride must be at least 1");
+        self.read(frame)?;
+        // Skip errors leave the trajectory failing its next read
+        let _ = self.skip_frames(stride - 1);
+        Ok(())
+    }
+
+    /// Iterate over every `stride`-th frame, starting with the next one,
+    /// without decoding the frames in between. Panics if `stride` is 0.
+    fn into_iter_strided(self, stride: usize) -> StridedTrajectoryIterator<Self>
+    where
+        Self: Sized,
+    {
+        assert!(stride > 0, "stride must be at least 1");
+        StridedTrajectoryIterator {
+            trajectory: self,
+            stride,
+            has_error: false,
+        }
+    }
+}
+
+#[cfg(feature = "c-lib")]
+fn skip_xdr_frames(handle: &mut XDRFile, n: usize) -> Result<usize> {
+    for skipped in 0..n {
+        if !handle.skip_frame()? {
+            return Ok(skipped);
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(feature = "c-lib")]
+impl SkipFrames for XTCTrajectory {
+    fn skip_frames(&mut self, n: usize) -> Result<usize> {
+        skip_xdr_frames(&mut self.handle, n)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl SkipFrames for TRRTrajectory {
+    fn skip_frames(&mut self, n: usize) -> Result<usize> {
+        skip_xdr_frames(&mut self.handle, n)
+    }
+}
+
+/// Read the next frame, skipping over frames the C API fails to read
+fn read_skipping_corrupt<T>(
+    trajectory: &mut T,
+    frame: &mut Frame,
+    skipped: &mut Vec<Error>,
+) -> Result<()>
+where
+    T: Trajectory + Resynchronize,
+{
+    loop {
+        let start = trajectory.position()?;
+        match trajectory.read(frame) {
+            Err(e) if e.code().is_some() && !e.is_eof() => {
+                skipped.push(e);
+                if !trajectory.resynchronize(start)? {
+                    return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+impl<T, P> TrajectoryIterator<T, P>
+where
+    T: Trajectory + Resynchronize,
+    P: SharedFrame,
+{
+    /// Set what happens if a frame cannot be read, see `ErrorPolicy`.
+    /// Errors of skipped frames are available from `skipped_errors`.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self.read_skipping_corrupt = match policy {
+            ErrorPolicy::Skip => Some(read_skipping_corrupt::<T>),
+            _ => None,
+        };
+        self
+    }
+}
+
+impl<T, P> TrajectoryIterator<T, P>
+where
+    T: Trajectory + FilePosition,
+    P: SharedFrame,
+{
+    /// Call `callback` after every frame that is read with the number of
+    /// frames and bytes read so far, e.g. to render a progress bar
+    pub fn with_progress<F>(mut self, callback: F) -> Result<Self>
+    where
+        F: FnMut(&Progress) + Send + 'static,
+    {
+        let progress = Progress {
+            frames: 0,
+            bytes: self.trajectory.position()?,
+            total_bytes: self.trajectory.file_len()?,
+        };
+        self.progress = Some(ProgressReporter {
+            callback: Box::new(callback),
+            position: T::position,
+            progress,
+        });
+        Ok(self)
+    }
+}
+
+impl<T, P> TrajectoryIterator<T, P>
+where
+    T: Trajectory + FilePosition + Seek,
+    P: SharedFrame,
+{
+    /// Continue the iteration at frame `frame` (counted from the start of
+    /// the file), using the index of the trajectory from
+    /// `FilePosition::frame_index`.
+    pub fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        let index = self.trajectory.frame_index()?;
+        let offset = index.offset(frame).ok_or(Error::FrameIndexOutOfBounds {
+            index: frame,
+            num_frames: index.len(),
+        })?;
+        self.seek_to_offset(offset)
+    }
+
+    /// Continue the iteration at the first frame whose time is not earlier
+    /// than `time`. If there is no such frame, the iteration ends.
+    pub fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        let mut offset = self.trajectory.file_len()?;
+        for header in FrameHeaders::open(self.trajectory.file_path()?)? {
+            let header = header?;
+            if header.time >= time {
+                offset = header.offset;
+                break;
+            }
+        }
+        self.seek_to_offset(offset)
+    }
+
+    fn seek_to_offset(&mut self, offset: u64) -> Result<()> {
+        self.trajectory.seek(SeekFrom::Start(offset))?;
+        self.peeked = None;
+        self.has_error = false;
+        Ok(())
+    }
 }
 
-impl<T: Trajectory> TrajectoryIterator<T> {
+impl<T: Trajectory, P: SharedFrame> TrajectoryIterator<T, P> {
     /// Inner function for `next()`  to seperate error handling from iteration logic
     fn next_inner(&mut self) -> <Self as Iterator>::Item {
         // If we couldn't read the number of frames when we called into_iter, return that error now
@@ -53,43 +482,205 @@ impl<T: Trajectory> TrajectoryIterator<T> {
         };
 
         // Reuse old frame
-        let item: &mut Frame = match Rc::get_mut(&mut self.item) {
+        let item: &mut Frame = match P::get_mut(&mut self.item) {
             Some(item) => item,
             None => {
                 // caller kept frame. Create new one
-                self.item = Rc::new(Frame::with_len(num_atoms as usize));
-                Rc::get_mut(&mut self.item).expect("Could not get mutable access to new Rc")
+                self.item = P::new(Frame::with_len(num_atoms));
+                P::get_mut(&mut self.item).expect("Could not get mutable access to new pointer")
             }
         };
 
-        self.trajectory.read(item)?;
-        Ok(Rc::clone(&self.item))
+        match self.read_skipping_corrupt {
+            Some(read) => read(&mut self.trajectory, item, &mut self.skipped)?,
+            None => self.trajectory.read(item)?,
+        }
+        Ok(self.item.clone())
+    }
+
+    /// Errors of frames that were skipped, or that stopped the iteration,
+    /// because of the `ErrorPolicy`
+    pub fn skipped_errors(&self) -> &[Error] {
+        &self.skipped
+    }
+
+    /// Read the next frame without consuming it, e.g. to decide whether to
+    /// stop based on its time. The peeked item is returned by the next call
+    /// to `next()`.
+    pub fn peek(&mut self) -> Option<&Result<P>> {
+        if self.peeked.is_none() {
+            let item = self.advance();
+            self.peeked = Some(item);
+        }
+        self.peeked.as_ref().and_then(Option::as_ref)
+    }
+
+    fn advance(&mut self) -> Option<Result<P>> {
+        if self.has_error {
+            return None;
+        }
+        let result = self.next_inner();
+        if let (Ok(_), Some(reporter)) = (&result, &mut self.progress) {
+            reporter.report(&self.trajectory);
+        }
+        match result {
+            Err(e) if self.policy == ErrorPolicy::Stop && !e.is_eof() => {
+                self.skipped.push(e);
+                self.has_error = true;
+                None
+            }
+            result => stop_on_error(result, &mut self.has_error),
+        }
     }
 }
 
-impl<T> Iterator for TrajectoryIterator<T>
+impl<T, P> Iterator for TrajectoryIterator<T, P>
 where
     T: Trajectory,
+    P: SharedFrame,
 {
-    type Item = Result<Rc<Frame>>;
+    type Item = Result<P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(item) => item,
+            None => self.advance(),
+        }
+    }
+}
+
+/// Convert the result of reading a frame into an iterator item: EOF ends the
+/// iteration, any other error is yielded once before the iteration ends
+pub(crate) fn stop_on_error<I>(result: Result<I>, has_error: &mut bool) -> Option<Result<I>> {
+    match result {
+        Ok(item) => Some(Ok(item)),
+        Err(e) if e.is_eof() => None,
+        Err(e) => {
+            *has_error = true;
+            Some(Err(e))
+        }
+    }
+}
+
+/// Iterator for trajectories that yields owned frames.
+/// Like `TrajectoryIterator`, it stops after EOF or the first error, but
+/// allocates a new frame for every step instead of reusing one.
+pub struct OwnedTrajectoryIterator<T> {
+    trajectory: T,
+    has_error: bool,
+}
+
+impl<T: Trajectory> OwnedTrajectoryIterator<T> {
+    fn next_inner(&mut self) -> Result<Frame> {
+        let num_atoms = self
+            .trajectory
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        let mut frame = Frame::with_len(num_atoms);
+        self.trajectory.read(&mut frame)?;
+        Ok(frame)
+    }
+}
+
+impl<T> Iterator for OwnedTrajectoryIterator<T>
+where
+    T: Trajectory,
+{
+    type Item = Result<Frame>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.has_error {
             return None;
         }
+        let result = self.next_inner();
+        stop_on_error(result, &mut self.has_error)
+    }
+}
 
-        match self.next_inner() {
-            Ok(item) => Some(Ok(item)),
-            Err(e) if e.is_eof() => None,
-            Err(e) => {
-                self.has_error = true;
-                Some(Err(e))
-            }
+/// Iterator over every n-th frame of a trajectory, see
+/// `SkipFrames::into_iter_strided`. It yields owned frames and stops after
+/// EOF or the first error.
+pub struct StridedTrajectoryIterator<T> {
+    trajectory: T,
+    stride: usize,
+    has_error: bool,
+}
+
+impl<T: SkipFrames> Iterator for StridedTrajectoryIterator<T> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.has_error {
+            return None;
         }
+        let result = self
+            .trajectory
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))
+            .and_then(|num_atoms| {
+                let mut frame = Frame::with_len(num_atoms);
+                self.trajectory.read_strided(self.stride, &mut frame)?;
+                Ok(frame)
+            });
+        stop_on_error(result, &mut self.has_error)
     }
 }
 
-#[cfg(test)]
+/// Lending-iterator style reader that reads every frame of a trajectory into
+/// the same internal buffer, without any reference counting.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut reader = FrameReader::new(XTCTrajectory::open_read("tests/1l2y.xtc")?);
+///     while let Some(frame) = reader.next_frame()? {
+///         println!("{}", frame.step);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct FrameReader<T> {
+    trajectory: T,
+    frame: Frame,
+}
+
+impl<T: Trajectory> FrameReader<T> {
+    /// Create a reader for the given trajectory
+    pub fn new(trajectory: T) -> Self {
+        FrameReader {
+            trajectory,
+            frame: Frame::new(),
+        }
+    }
+
+    /// Read the next frame into the internal buffer and return a reference to
+    /// it, or `None` once the end of the trajectory is reached
+    pub fn next_frame(&mut self) -> Result<Option<&Frame>> {
+        Ok(self.next_frame_mut()?.map(|frame| &*frame))
+    }
+
+    /// Like `next_frame`, but allows modifying the frame in place
+    pub fn next_frame_mut(&mut self) -> Result<Option<&mut Frame>> {
+        let num_atoms = self
+            .trajectory
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        self.frame.resize(num_atoms);
+        match self.trajectory.read(&mut self.frame) {
+            Ok(()) => Ok(Some(&mut self.frame)),
+            Err(e) if e.is_eof() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get back the underlying trajectory
+    pub fn into_inner(self) -> T {
+        self.trajectory
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
 mod tests {
     use super::*;
 
@@ -114,4 +705,270 @@ mod tests {
         assert!(frames[37].step == 38);
         Ok(())
     }
+
+    #[test]
+    pub fn test_owned_trajectory_iterator() -> Result<()> {
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        assert_eq!(frames[0].step, 1);
+        assert_eq!(frames[37].step, 38);
+
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        for (frame, xtc_frame) in trr.into_frames().zip(&frames) {
+            assert!(frame?.approx_eq(xtc_frame, 1e-3));
+        }
+
+        let mut frames = TRRTrajectory::open_read("README.md")?.into_frames();
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_arc_trajectory_iterator() -> Result<()> {
+        let traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let (sender, receiver) = std::sync::mpsc::channel::<Arc<Frame>>();
+        let worker = std::thread::spawn(move || receiver.iter().map(|frame| frame.step).sum());
+
+        for frame in traj.into_iter_arc() {
+            sender.send(frame?).unwrap();
+        }
+        drop(sender);
+        let sum: usize = worker.join().unwrap();
+        assert_eq!(sum, (1..=38).sum());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_frame_reader() -> Result<()> {
+        let mut reader = FrameReader::new(TRRTrajectory::open_read("tests/1l2y.trr")?);
+        let mut steps = Vec::new();
+        while let Some(frame) = reader.next_frame_mut()? {
+            frame.translate([1.0, 0.0, 0.0]);
+            steps.push(frame.step);
+        }
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+        assert!(reader.next_frame()?.is_none());
+
+        let mut reader = FrameReader::new(XTCTrajectory::open_read("README.md")?);
+        assert!(reader.next_frame().is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_peek() -> Result<()> {
+        let mut frames = XTCTrajectory::open_read("tests/1l2y.xtc")?.into_iter();
+        let mut steps = Vec::new();
+        while let Some(Ok(frame)) = frames.peek() {
+            if frame.step > 3 {
+                break;
+            }
+            steps.push(frames.next().unwrap()?.step);
+        }
+        assert_eq!(steps, vec![1, 2, 3]);
+        assert_eq!(frames.next().unwrap()?.step, 4);
+        assert_eq!(frames.count(), 34);
+
+        let mut frames = XTCTrajectory::open_read("README.md")?.into_iter();
+        assert!(matches!(frames.peek(), Some(Err(_))));
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.peek().is_none());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_error_policy() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let path = tempfile.path();
+        let mut trj = XTCTrajectory::open_write(path)?;
+        for step in 0..4 {
+            let mut frame = Frame::with_len(20);
+            frame.step = step;
+            frame.translate([step as f32; 3]);
+            trj.write(&frame)?;
+        }
+        trj.flush()?;
+
+        // break the magic number of the second frame
+        let offset = FrameIndex::build(path)?.offset(1).unwrap();
+        let mut bytes = std::fs::read(path)?;
+        bytes[offset as usize + 3] = 0;
+        std::fs::write(path, bytes)?;
+
+        let steps = |policy| -> Result<Vec<Result<usize>>> {
+            let frames = XTCTrajectory::open_read(path)?.into_iter().on_error(policy);
+            Ok(frames.map(|frame| frame.map(|f| f.step)).collect())
+        };
+        let abort = steps(ErrorPolicy::Abort)?;
+        assert_eq!(abort.len(), 2);
+        assert!(abort[1].is_err());
+        assert_eq!(steps(ErrorPolicy::Stop)?, vec![Ok(0)]);
+        assert_eq!(steps(ErrorPolicy::Skip)?, vec![Ok(0), Ok(2), Ok(3)]);
+
+        let mut frames = XTCTrajectory::open_read(path)?
+            .into_iter()
+            .on_error(ErrorPolicy::Skip);
+        assert_eq!(frames.by_ref().count(), 3);
+        assert_eq!(frames.skipped_errors().len(), 1);
+        assert_eq!(
+            frames.skipped_errors()[0].code(),
+            Some(ErrorCode::ExdrMagic)
+        );
+
+        // streams cannot be searched for the next intact frame
+        let stream = std::io::Cursor::new(std::fs::read(path)?);
+        let steps: Vec<Result<usize>> = XTCTrajectory::from_reader(stream)?
+            .into_iter()
+            .on_error(ErrorPolicy::Skip)
+            .map(|frame| frame.map(|f| f.step))
+            .collect();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0], Ok(0));
+        assert!(matches!(steps[1], Err(Error::Unsupported(_))));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_progress() -> Result<()> {
+        use std::sync::Mutex;
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let frames = TRRTrajectory::open_read("tests/1l2y.trr")?
+            .into_iter()
+            .with_progress(move |progress| sink.lock().unwrap().push(*progress))?;
+        assert_eq!(frames.count(), 38);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 38);
+        let total_bytes = std::fs::metadata("tests/1l2y.trr")?.len();
+        for (i, progress) in reports.iter().enumerate() {
+            assert_eq!(progress.frames, i + 1);
+            assert_eq!(progress.total_bytes, total_bytes);
+            assert_eq!(progress.estimated_total_frames(), Some(38));
+        }
+        assert_eq!(reports[37].bytes, total_bytes);
+        assert_eq!(reports[37].fraction(), 1.0);
+
+        let stream = std::fs::File::open("tests/1l2y.trr")?;
+        let result = TRRTrajectory::from_reader(stream)?
+            .into_iter()
+            .with_progress(|_| {});
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_seek() -> Result<()> {
+        let mut frames = XTCTrajectory::open_read("tests/1l2y.xtc")?.into_iter();
+        assert_eq!(frames.next().unwrap()?.step, 1);
+        frames.seek_to_frame(30)?;
+        assert_eq!(frames.next().unwrap()?.step, 31);
+        frames.seek_to_frame(2)?;
+        assert_eq!(frames.peek().unwrap().as_ref().unwrap().step, 3);
+        frames.seek_to_frame(5)?;
+        assert_eq!(frames.next().unwrap()?.step, 6);
+        assert!(matches!(
+            frames.seek_to_frame(38),
+            Err(Error::FrameIndexOutOfBounds {
+                index: 38,
+                num_frames: 38
+            })
+        ));
+
+        let mut frames = TRRTrajectory::open_read("tests/1l2y.trr")?.into_iter();
+        let times: Vec<f32> = frames
+            .by_ref()
+            .map(|frame| frame.map(|f| f.time))
+            .collect::<Result<_>>()?;
+        frames.seek_to_time(times[9] - 0.01)?;
+        assert_eq!(frames.next().unwrap()?.time, times[9]);
+        frames.seek_to_time(times[37] + 1.0)?;
+        assert!(frames.next().is_none());
+
+        let stream = std::fs::File::open("tests/1l2y.xtc")?;
+        let mut frames = XTCTrajectory::from_reader(stream)?.into_iter();
+        assert!(matches!(
+            frames.seek_to_time(10.0),
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(
+            frames.seek_to_frame(10),
+            Err(Error::Unsupported(_))
+        ));
+        assert_eq!(frames.next().unwrap()?.step, 1);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_seek_with_index() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("traj.xtc");
+        std::fs::copy("tests/1l2y.xtc", &path)?;
+        let xtc = XTCTrajectory::open_read(&path)?;
+        assert_eq!(xtc.frame_index()?.len(), 38);
+
+        // the file can no longer be indexed, only the index of the
+        // trajectory is left
+        std::fs::remove_file(&path)?;
+        let mut frames = xtc.into_iter();
+        frames.seek_to_frame(3)?;
+        assert_eq!(frames.next().unwrap()?.step, 4);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_strided() -> Result<()> {
+        let expected: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let strided: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_iter_strided(10)
+            .collect::<Result<_>>()?;
+        assert_eq!(strided.len(), 4);
+        for (frame, expected) in strided.iter().zip(expected.iter().step_by(10)) {
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+
+        let steps: Vec<usize> = TRRTrajectory::open_read("tests/1l2y.trr")?
+            .into_iter_strided(5)
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<_>>()?;
+        assert_eq!(steps, vec![1, 6, 11, 16, 21, 26, 31, 36]);
+
+        let bytes = std::fs::read("tests/1l2y.xtc")?;
+        let mut xtc = XTCTrajectory::from_reader(std::io::Cursor::new(bytes))?;
+        let mut frame = Frame::with_len(304);
+        assert_eq!(xtc.skip_frames(3)?, 3);
+        xtc.read_strided(30, &mut frame)?;
+        assert_eq!(frame.step, 4);
+        xtc.read(&mut frame)?;
+        assert_eq!(frame.step, 34);
+        assert_eq!(xtc.skip_frames(10)?, 4);
+        assert!(xtc.read(&mut frame).unwrap_err().is_eof());
+
+        // a frame read before a failed skip is kept, the failure comes next
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let offsets = FrameIndex::build("tests/1l2y.trr")?.offsets().to_vec();
+        let mut bytes = std::fs::read("tests/1l2y.trr")?;
+        bytes[offsets[1] as usize + 3] = 0;
+        std::fs::write(tempfile.path(), bytes)?;
+        let mut trr = TRRTrajectory::open_read(tempfile.path())?;
+        let mut frame = Frame::with_len(304);
+        trr.read_strided(2, &mut frame)?;
+        assert_eq!(frame.step, 1);
+        assert!(matches!(
+            trr.read_strided(2, &mut frame),
+            Err(Error::HandlePoisoned(_))
+        ));
+        let results: Vec<_> = TRRTrajectory::open_read(tempfile.path())?
+            .into_iter_strided(2)
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().step, 1);
+        assert!(results[1].is_err());
+        Ok(())
+    }
 }
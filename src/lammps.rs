@@ -0,0 +1,420 @@
+//! Reading LAMMPS text dump files (`dump atom` and `dump custom`)
+
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Read-only handle to a LAMMPS dump file.
+///
+/// Coordinates are read from the `x y z`, `xu yu zu`, `xs ys zs` or
+/// `xsu ysu zsu` columns, velocities from `vx vy vz` and forces from
+/// `fx fy fz`. Atoms are sorted by their `id` if the dump has one, and
+/// positions are shifted so that the box starts at the origin, as in
+/// GROMACS.
+///
+/// LAMMPS does not store units, so positions and boxes are multiplied by a
+/// length scale that converts them to nanometers (0.1 by default, for
+/// Ångström). Velocities and forces are copied as they are. The time is only
+/// known if the dump contains `ITEM: TIME` (`dump_modify time yes`) and is
+/// zero otherwise.
+pub struct LammpsDumpTrajectory {
+    dump: DumpReader,
+    num_atoms: usize,
+    length_scale: f32,
+    atoms: Vec<AtomLine>,
+}
+
+/// Line-based reader that keeps track of the line number for errors
+struct DumpReader {
+    reader: BufReader<File>,
+    line: String,
+    line_number: usize,
+}
+
+impl DumpReader {
+    fn open(path: &Path) -> Result<DumpReader> {
+        Ok(DumpReader {
+            reader: BufReader::new(File::open(path)?),
+            line: String::new(),
+            line_number: 0,
+        })
+    }
+
+    /// Read the next line. Returns false at the end of the file.
+    fn next_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        self.line_number += 1;
+        Ok(self.reader.read_line(&mut self.line)? != 0)
+    }
+
+    /// Read the next line, which must exist
+    fn expect_line(&mut self) -> Result<&str> {
+        if !self.next_line()? {
+            return Err(self.error("unexpected end of file"));
+        }
+        Ok(self.line.trim())
+    }
+
+    /// Read the next line as a single value
+    fn parse_line<T: FromStr>(&mut self) -> Result<T> {
+        self.expect_line()?;
+        self.line
+            .trim()
+            .parse()
+            .map_err(|_| self.error("invalid value"))
+    }
+
+    /// Read the next line as whitespace separated values
+    fn parse_values(&mut self, values: &mut [f32]) -> Result<()> {
+        self.expect_line()?;
+        let mut fields = self.line.split_whitespace();
+        for value in values.iter_mut() {
+            *value = match fields.next().map(str::parse) {
+                Some(Ok(value)) => value,
+                _ => return Err(self.error("invalid value")),
+            };
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidData(format!(
+            "LAMMPS dump line {}: {}",
+            self.line_number, message
+        ))
+    }
+}
+
+/// Values of one line of the ATOMS section
+#[derive(Clone, Copy, Default)]
+struct AtomLine {
+    id: i64,
+    position: [f32; 3],
+    velocity: [f32; 3],
+    force: [f32; 3],
+}
+
+/// Indices of the known columns of the ATOMS section
+struct Columns {
+    id: Option<usize>,
+    position: [usize; 3],
+    scaled: bool,
+    velocity: Option<[usize; 3]>,
+    force: Option<[usize; 3]>,
+}
+
+impl Columns {
+    fn parse(names: &[&str]) -> Option<Columns> {
+        let find = |name: &str| names.iter().position(|n| *n == name);
+        let find_xyz = |names: [&str; 3]| -> Option<[usize; 3]> {
+            Some([find(names[0])?, find(names[1])?, find(names[2])?])
+        };
+
+        let unscaled = find_xyz(["x", "y", "z"]).or_else(|| find_xyz(["xu", "yu", "zu"]));
+        let (position, scaled) = match unscaled {
+            Some(position) => (position, false),
+            None => {
+                let scaled =
+                    find_xyz(["xs", "ys", "zs"]).or_else(|| find_xyz(["xsu", "ysu", "zsu"]));
+                (scaled?, true)
+            }
+        };
+        Some(Columns {
+            id: find("id"),
+            position,
+            scaled,
+            velocity: find_xyz(["vx", "vy", "vz"]),
+            force: find_xyz(["fx", "fy", "fz"]),
+        })
+    }
+
+    /// Parse the values of the known columns of one atom line
+    fn parse_atom(&self, fields: &[&str]) -> Option<AtomLine> {
+        let parse = |i: usize| fields[i].parse::<f32>().ok();
+        let parse_xyz = |xyz: [usize; 3]| Some([parse(xyz[0])?, parse(xyz[1])?, parse(xyz[2])?]);
+        Some(AtomLine {
+            id: match self.id {
+                Some(i) => fields[i].parse().ok()?,
+                None => 0,
+            },
+            position: parse_xyz(self.position)?,
+            velocity: match self.velocity {
+                Some(xyz) => parse_xyz(xyz)?,
+                None => [0.0; 3],
+            },
+            force: match self.force {
+                Some(xyz) => parse_xyz(xyz)?,
+                None => [0.0; 3],
+            },
+        })
+    }
+}
+
+impl LammpsDumpTrajectory {
+    /// Open the dump file at `path` for reading
+    pub fn open_read(path: impl AsRef<Path>) -> Result<LammpsDumpTrajectory> {
+        let path = path.as_ref();
+
+        // the number of atoms is taken from the first frame
+        let mut first = DumpReader::open(path)?;
+        while first.next_line()? {
+            if first.line.trim() == "ITEM: NUMBER OF ATOMS" {
+                return Ok(LammpsDumpTrajectory {
+                    dump: DumpReader::open(path)?,
+                    num_atoms: first.parse_line()?,
+                    length_scale: 0.1,
+                    atoms: Vec::new(),
+                });
+            }
+        }
+        Err(Error::InvalidData(
+            "LAMMPS dump contains no frames".to_string(),
+        ))
+    }
+
+    /// Set the factor that converts lengths in the dump to nanometers, e.g.
+    /// 0.1 for Ångström (the default) or 1.0 if the dump is in nanometers
+    pub fn set_length_scale(&mut self, nm_per_unit: f32) {
+        self.length_scale = nm_per_unit;
+    }
+
+    /// Read the three lines of a BOX BOUNDS item. Returns the origin and the
+    /// box vectors in the units of the dump.
+    fn read_box(&mut self, triclinic: bool) -> Result<([f32; 3], BoxVector)> {
+        let mut bounds = [[0.0; 3]; 3];
+        for bound in bounds.iter_mut() {
+            let len = if triclinic { 3 } else { 2 };
+            self.dump.parse_values(&mut bound[..len])?;
+        }
+        let [[mut xlo, mut xhi, xy], [mut ylo, mut yhi, xz], [zlo, zhi, yz]] = bounds;
+
+        // triclinic dumps contain the bounding box of the tilted cell
+        if triclinic {
+            xlo -= 0f32.min(xy).min(xz).min(xy + xz);
+            xhi -= 0f32.max(xy).max(xz).max(xy + xz);
+            ylo -= 0f32.min(yz);
+            yhi -= 0f32.max(yz);
+        }
+        let box_vector = BoxVector([
+            [xhi - xlo, 0.0, 0.0],
+            [xy, yhi - ylo, 0.0],
+            [xz, yz, zhi - zlo],
+        ]);
+        Ok(([xlo, ylo, zlo], box_vector))
+    }
+
+    /// Read the lines of an ATOMS item with the given column names
+    fn read_atoms(&mut self, names: &[&str]) -> Result<Columns> {
+        let columns =
+            Columns::parse(names).ok_or_else(|| self.dump.error("dump contains no coordinates"))?;
+        let num_columns = names.len();
+
+        self.atoms.clear();
+        for _ in 0..self.num_atoms {
+            self.dump.expect_line()?;
+            let fields: Vec<&str> = self.dump.line.split_whitespace().collect();
+            if fields.len() != num_columns {
+                return Err(self.dump.error("wrong number of columns"));
+            }
+            let atom = columns.parse_atom(&fields);
+            match atom {
+                Some(atom) => self.atoms.push(atom),
+                None => return Err(self.dump.error("invalid value")),
+            }
+        }
+
+        // LAMMPS writes atoms in arbitrary order when running in parallel
+        if columns.id.is_some() {
+            self.atoms.sort_by_key(|atom| atom.id);
+        }
+        Ok(columns)
+    }
+}
+
+impl Trajectory for LammpsDumpTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if frame.len() != self.num_atoms {
+            return Err((&*frame, self.num_atoms).into());
+        }
+
+        // skip empty lines between frames
+        loop {
+            if !self.dump.next_line()? {
+                return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+            }
+            if !self.dump.line.trim().is_empty() {
+                break;
+            }
+        }
+        if self.dump.line.trim() != "ITEM: TIMESTEP" {
+            return Err(self.dump.error("expected ITEM: TIMESTEP"));
+        }
+        let step = self.dump.parse_line()?;
+        let mut time = 0.0;
+        let mut cell = ([0.0; 3], BoxVector::default());
+
+        let columns = loop {
+            let item = match self.dump.expect_line()?.strip_prefix("ITEM: ") {
+                Some(item) => item.to_string(),
+                None => return Err(self.dump.error("expected ITEM")),
+            };
+            match item.as_str() {
+                "TIME" => time = self.dump.parse_line::<f64>()? as f32,
+                "UNITS" => {
+                    self.dump.expect_line()?;
+                }
+                "NUMBER OF ATOMS" => {
+                    let num_atoms = self.dump.parse_line()?;
+                    if num_atoms != self.num_atoms {
+                        return Err(Error::WrongSizeFrame {
+                            expected: self.num_atoms,
+                            found: num_atoms,
+                        });
+                    }
+                }
+                item if item.starts_with("BOX BOUNDS") => {
+                    cell = self.read_box(item.contains("xy"))?;
+                }
+                item if item.starts_with("ATOMS") => {
+                    let names: Vec<&str> = item.split_whitespace().skip(1).collect();
+                    break self.read_atoms(&names)?;
+                }
+                _ => return Err(self.dump.error("unknown item")),
+            }
+        };
+
+        let (origin, box_vector) = cell;
+        let scale = self.length_scale;
+        for (xyz, atom) in frame.coords.iter_mut().zip(&self.atoms) {
+            let p = atom.position;
+            let absolute = if columns.scaled {
+                let b = &box_vector.0;
+                [
+                    p[0] * b[0][0] + p[1] * b[1][0] + p[2] * b[2][0],
+                    p[1] * b[1][1] + p[2] * b[2][1],
+                    p[2] * b[2][2],
+                ]
+            } else {
+                [p[0] - origin[0], p[1] - origin[1], p[2] - origin[2]]
+            };
+            *xyz = absolute.map(|x| x * scale);
+        }
+        frame.velocities = columns
+            .velocity
+            .map(|_| self.atoms.iter().map(|atom| atom.velocity).collect());
+        frame.forces = columns
+            .force
+            .map(|_| self.atoms.iter().map(|atom| atom.force).collect());
+        frame.box_vector = BoxVector(box_vector.0.map(|v| v.map(|x| x * scale)));
+        frame.step = step;
+        frame.time = time;
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing LAMMPS dump files"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        Ok(self.num_atoms)
+    }
+}
+
+impl IntoIterator for LammpsDumpTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<LammpsDumpTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    const DUMP: &str = "\
+ITEM: TIMESTEP
+100
+ITEM: NUMBER OF ATOMS
+2
+ITEM: BOX BOUNDS pp pp pp
+-5.0 5.0
+0.0 20.0
+0.0 30.0
+ITEM: ATOMS id type xs ys zs
+2 1 0.5 0.5 0.5
+1 1 0.0 0.25 1.0
+
+ITEM: TIMESTEP
+200
+ITEM: TIME
+0.5
+ITEM: NUMBER OF ATOMS
+2
+ITEM: BOX BOUNDS xy xz yz pp pp pp
+0.0 12.0 2.0
+0.0 10.0 0.0
+0.0 10.0 -1.0
+ITEM: ATOMS id type x y z vx vy vz
+1 1 1.0 2.0 3.0 0.1 0.2 0.3
+2 1 4.0 5.0 6.0 0.4 0.5 0.6
+";
+
+    #[test]
+    fn test_lammps_dump() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = NamedTempFile::new()?;
+        std::fs::write(tempfile.path(), DUMP)?;
+
+        let trj = LammpsDumpTrajectory::open_read(tempfile.path())?;
+        let frames: Vec<Rc<Frame>> = trj.into_iter().collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 2);
+
+        let frame = &frames[0];
+        assert_eq!(frame.step, 100);
+        assert_eq!(frame.box_vector, BoxVector::rectangular(1.0, 2.0, 3.0));
+        assert_eq!(frame.coords, vec![[0.0, 0.5, 3.0], [0.5, 1.0, 1.5]]);
+        assert!(frame.velocities.is_none());
+
+        // the x bounds include the tilt factors xy = 2 and xz = 0
+        let frame = &frames[1];
+        assert_eq!(frame.step, 200);
+        assert_eq!(frame.time, 0.5);
+        let expected = BoxVector([[1.0, 0.0, 0.0], [0.2, 0.9, 0.0], [0.0, -0.1, 1.0]]);
+        for (x, expected) in frame
+            .box_vector
+            .0
+            .iter()
+            .flatten()
+            .zip(expected.0.iter().flatten())
+        {
+            assert_approx_eq!(x, expected, 1e-6);
+        }
+        // the box starts at y = 1 because of the tilt factor yz = -1
+        for (x, expected) in frame.coords[1].iter().zip(&[0.4, 0.4, 0.6]) {
+            assert_approx_eq!(x, expected, 1e-6);
+        }
+        assert_eq!(frame.velocities.as_ref().unwrap()[0], [0.1, 0.2, 0.3]);
+
+        let mut trj = LammpsDumpTrajectory::open_read(tempfile.path())?;
+        trj.set_length_scale(1.0);
+        let mut frame = Frame::with_len(2);
+        trj.read(&mut frame)?;
+        assert_eq!(frame.box_vector, BoxVector::rectangular(10.0, 20.0, 30.0));
+
+        std::fs::write(tempfile.path(), DUMP.replace("2 1 0.5 0.5 0.5", "2 1 0.5"))?;
+        let result: Result<Vec<_>> = LammpsDumpTrajectory::open_read(tempfile.path())?
+            .into_iter()
+            .collect();
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+}
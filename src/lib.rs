@@ -61,28 +61,136 @@
 extern crate assert_approx_eq;
 extern crate lazy_init;
 
+mod adapters;
+pub mod analysis;
+mod atomic;
+mod backup;
+mod box_vector;
+#[cfg(feature = "c-lib")]
 pub mod c_abi;
+mod chain;
+mod compression;
+mod concat;
+mod convert;
+#[cfg(feature = "c-lib")]
+pub mod edr;
 mod errors;
 mod frame;
+mod gsd;
+#[cfg(feature = "hdf5")]
+mod h5md;
+mod index;
+mod info;
 mod iterator;
+mod lammps;
+mod linalg;
+#[cfg(feature = "c-lib")]
+mod memory;
+pub mod ndx;
+mod neighbors;
+#[cfg(feature = "netcdf")]
+mod netcdf;
+#[cfg(all(feature = "rayon", feature = "c-lib"))]
+mod parallel;
+pub mod pdb;
+mod pool;
+mod prefetch;
+mod recover;
+pub mod select;
+#[cfg(all(feature = "tokio", feature = "c-lib"))]
+mod stream;
+mod sync;
+mod topology;
+mod trace;
+pub mod transform;
+mod trj;
+#[cfg(feature = "c-lib")]
+mod verify;
+mod writer;
+#[cfg(feature = "c-lib")]
+pub mod xdr;
+#[cfg(feature = "c-lib")]
+mod xdr_stream;
+#[cfg(feature = "pure-rust")]
+mod xtc;
+pub mod xvg;
+pub use adapters::*;
+pub use atomic::SafeWriter;
+pub use backup::{backup_file, MAX_BACKUPS};
+pub use box_vector::BoxVector;
+pub use chain::TrajectoryChain;
+pub use concat::{concat, ConcatOptions};
+pub use convert::{convert, convert_files, ConvertOptions};
 pub use errors::*;
-pub use frame::Frame;
+pub use frame::{Frame, FrameDifference};
+pub use gsd::GsdTrajectory;
+#[cfg(feature = "hdf5")]
+pub use h5md::H5MDTrajectory;
+pub use index::{FrameHeader, FrameHeaders, FrameIndex};
+pub use info::TrajectoryInfo;
 pub use iterator::*;
-
-use c_abi::xdr_seek;
-use c_abi::xdrfile;
-use c_abi::xdrfile::XDRFILE;
-use c_abi::xdrfile_trr;
-use c_abi::xdrfile_xtc;
-
+pub use lammps::LammpsDumpTrajectory;
+#[cfg(feature = "c-lib")]
+pub use memory::{MemoryFormat, MemoryTrajectory};
+pub use neighbors::NeighborSearch;
+#[cfg(feature = "netcdf")]
+pub use netcdf::AmberNetCDFTrajectory;
+#[cfg(all(feature = "rayon", feature = "c-lib"))]
+pub use parallel::DecodedStream;
+pub use pool::{FramePool, PooledFrame, PooledTrajectoryIterator};
+pub use prefetch::PrefetchingTrajectory;
+pub use recover::{repair, TruncationReport};
+#[cfg(feature = "c-lib")]
+pub use recover::{salvage, SalvageReport};
+#[cfg(all(feature = "tokio", feature = "c-lib"))]
+pub use stream::FrameStream;
+pub use sync::SyncTrajectory;
+pub use topology::{element_mass, vdw_radius, Atom, Residue, Topology, DEFAULT_VDW_RADIUS};
+pub use trj::TrjTrajectory;
+#[cfg(feature = "c-lib")]
+pub use verify::{verify, Problem, ProblemKind, VerificationReport};
+pub use writer::TrajectoryWriter;
+#[cfg(feature = "pure-rust")]
+pub use xtc::{XtcReader, XtcWriter};
+
+#[cfg(feature = "c-lib")]
+use c_abi::{xdr_seek, xdrfile, xdrfile::XDRFILE, xdrfile_trr, xdrfile_xtc};
+
+#[cfg(feature = "c-lib")]
 use lazy_init::Lazy;
+#[cfg(feature = "c-lib")]
 use std::cell::Cell;
 use std::convert::{TryFrom, TryInto};
+#[cfg(feature = "c-lib")]
 use std::ffi::CString;
 use std::io;
 use std::io::SeekFrom;
+use std::ops::ControlFlow;
+#[cfg(feature = "c-lib")]
 use std::os::raw::{c_float, c_int};
 use std::path::{Path, PathBuf};
+#[cfg(feature = "c-lib")]
+use std::ptr::NonNull;
+#[cfg(feature = "c-lib")]
+use std::sync::Arc;
+#[cfg(feature = "c-lib")]
+use trace::operation;
+
+/// Largest number of atoms that is accepted from the header of a file by
+/// default, see `XTCTrajectory::set_max_atoms`
+pub const DEFAULT_MAX_ATOMS: usize = 1 << 28;
+
+/// Refuse a number of atoms above `max_atoms`, which most likely comes from
+/// a corrupt header and would allocate huge frames
+pub(crate) fn check_num_atoms(num_atoms: usize, max_atoms: usize) -> Result<usize> {
+    if num_atoms > max_atoms {
+        return Err(Error::TooManyAtoms {
+            num_atoms,
+            max_atoms,
+        });
+    }
+    Ok(num_atoms)
+}
 
 /// File Mode for accessing trajectories.
 #[derive(Debug, Clone, PartialEq)]
@@ -93,6 +201,7 @@ pub enum FileMode {
 }
 
 impl FileMode {
+#[cfg(feature = "c-lib")]
     /// Get a CStr slice corresponding to the file mode
     fn to_cstr(&self) -> &'static std::ffi::CStr {
         let bytes: &[u8; 2] = match *self {
@@ -105,6 +214,7 @@ impl FileMode {
     }
 }
 
+#[cfg(feature = "c-lib")]
 fn path_to_cstring(path: impl AsRef<Path>) -> Result<CString> {
     if let Some(s) = path.as_ref().to_str() {
         CString::new(s).map_err(|e| Error::InvalidOsStr(Some(e)))
@@ -125,12 +235,14 @@ where
     })
 }
 
+#[cfg(feature = "c-lib")]
 macro_rules! to {
     ($value:expr, $task:expr) => {
         to($value, $task, stringify!($value))
     };
 }
 
+#[cfg(feature = "c-lib")]
 /// Convert an error code from a C call to an Error
 ///
 /// `code` should be an integer return code returned from the C API.
@@ -145,17 +257,50 @@ fn check_code(code: impl Into<ErrorCode>, task: ErrorTask) -> Option<Error> {
     }
 }
 
+#[cfg(feature = "c-lib")]
+/// Number of the first frame that a handle opened in `filemode` reads or
+/// writes, unknown when appending
+fn first_frame(filemode: &FileMode) -> Option<usize> {
+    match filemode {
+        FileMode::Append => None,
+        _ => Some(0),
+    }
+}
+
+#[cfg(feature = "c-lib")]
 /// A safe wrapper around the c implementation of an XDRFile
 struct XDRFile {
     xdrfile: *mut XDRFILE,
-    #[allow(dead_code)]
     filemode: FileMode,
     path: PathBuf,
+    /// Decompressed copy of a compressed file, removed after closing the
+    /// handle and its clones
+    decompressed: Option<Arc<compression::TempPath>>,
+    /// Reader or writer that the C code calls back into instead of using a
+    /// file, created with `Box::into_raw` and freed after closing
+    stream: Option<NonNull<xdr_stream::XdrStream>>,
+    /// Number of the next frame, unknown after appending or seeking
+    next_frame: Option<usize>,
+    /// The error after which the position in the file is unknown, until the
+    /// handle is moved with a seek
+    poisoned: Option<Box<Error>>,
 }
 
+// SAFETY: the handle owns its C state exclusively. libxdrfile keeps the
+// buffers of a file in its XDRFILE struct and has no global state outside of
+// the Fortran interface, which is not used, and streams are `Send`. The
+// handle is not `Sync`, as every call into the C library mutates it.
+#[cfg(feature = "c-lib")]
+unsafe impl Send for XDRFile {}
+
+#[cfg(feature = "c-lib")]
 impl XDRFile {
     pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XDRFile> {
-        let path = path.as_ref();
+        let decompressed = compression::decompress_if_needed(path.as_ref(), &filemode)?;
+        let path = match &decompressed {
+            Some(temp) => temp.as_ref(),
+            None => path.as_ref(),
+        };
         unsafe {
             let path_p = path_to_cstring(path)?.into_raw();
             // SAFETY: mode_p must not be mutated by the C code
@@ -168,10 +313,15 @@ impl XDRFile {
 
             if !xdrfile.is_null() {
                 let path = path.to_owned();
+                let next_frame = first_frame(&filemode);
                 Ok(XDRFile {
                     xdrfile,
                     filemode,
                     path,
+                    decompressed: decompressed.map(Arc::new),
+                    stream: None,
+                    next_frame,
+                    poisoned: None,
                 })
             } else {
                 // Something went wrong. But the C api does not tell us what
@@ -180,41 +330,270 @@ impl XDRFile {
         }
     }
 
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
+    /// Open another handle in read mode to the file of this one, which
+    /// starts at the first frame
+    fn try_clone(&self) -> Result<XDRFile> {
+        if self.stream.is_some() {
+            return Err(Error::Unsupported("cloning a handle to a stream"));
+        }
+        if self.filemode != FileMode::Read {
+            return Err(Error::Unsupported(
+                "cloning a handle that is not in read mode",
+            ));
+        }
+        let mut handle = XDRFile::open(&self.path, FileMode::Read)?;
+        handle.decompressed = self.decompressed.clone();
+        Ok(handle)
+    }
+
+    /// Path of the file of this handle, to read it with a separate handle.
+    /// Streams have no file that could be opened again.
+    fn file_path(&self) -> Result<&Path> {
+        if self.stream.is_some() {
+            return Err(Error::Unsupported(
+                "reading a stream with a separate handle",
+            ));
+        }
+        Ok(&self.path)
+    }
+
+    /// Open a handle that reads from or writes to `stream` instead of a file
+    fn from_stream(stream: xdr_stream::Stream, filemode: FileMode) -> Result<XDRFile> {
+        let stream = NonNull::from(Box::leak(Box::new(xdr_stream::XdrStream::new(stream))));
+        // SAFETY: the stream and the callbacks outlive the C handle
+        let xdrfile = unsafe {
+            c_abi::xdr_stream::xdr_stream_open(
+                stream.as_ptr() as *mut std::os::raw::c_void,
+                &xdr_stream::OPS,
+                filemode.to_cstr().as_ptr(),
+            )
+        };
+        if xdrfile.is_null() {
+            // SAFETY: the C code did not keep the pointer
+            drop(unsafe { Box::from_raw(stream.as_ptr()) });
+            return Err(Error::Io {
+                kind: io::ErrorKind::Other,
+                message: format!("could not open a stream in {:?} mode", filemode),
+            });
+        }
+        Ok(XDRFile {
+            xdrfile,
+            next_frame: first_frame(&filemode),
+            filemode,
+            path: PathBuf::new(),
+            decompressed: None,
+            stream: Some(stream),
+            poisoned: None,
+        })
+    }
+
+    /// Count a frame that was read, written or skipped
+    fn frame_done(&mut self) {
+        if let Some(next_frame) = &mut self.next_frame {
+            *next_frame += 1;
+        }
+    }
+
+    /// Add the location of the frame starting at `offset` to an error of the
+    /// C API. The C library may have stopped anywhere in the frame, so the
+    /// handle is poisoned, see `check_poisoned`.
+    fn error_at(&mut self, offset: u64, error: Error) -> Error {
+        if error.is_eof() {
+            return error;
+        }
+        let error = Error::InFrame {
+            path: self.path.clone(),
+            offset,
+            frame: self.next_frame,
+            source: Box::new(error),
+        };
+        self.poisoned = Some(Box::new(error.clone()));
+        error
+    }
+
+    /// Refuse to read or write at an unknown position after a failed frame,
+    /// which would only produce garbage or confusing errors
+    fn check_poisoned(&self) -> Result<()> {
+        match &self.poisoned {
+            Some(cause) => Err(Error::HandlePoisoned(cause.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the number of atoms in the first frame of the file or stream.
+    ///
+    /// Handles opened for reading parse the header of the first frame and
+    /// seek back, so this also works after the file was renamed or deleted.
+    /// Files that are being written are opened again to read the frames
+    /// written so far.
+    fn read_num_atoms(
+        &self,
+        read_natoms: unsafe extern "C" fn(*const std::os::raw::c_char, *const c_int) -> c_int,
+    ) -> Result<usize> {
+        if self.filemode == FileMode::Read {
+            return self.peek_num_atoms();
+        }
+        if self.stream.is_some() {
+            return Err(Error::Unsupported(
+                "reading the number of atoms of a trajectory being written",
+            ));
+        }
+        let mut num_atoms: c_int = 0;
         unsafe {
-            xdr_seek::xdr_tell(self.xdrfile)
-                .try_into()
-                .expect("i64 could not be converted to u64")
+            let path = path_to_cstring(&self.path)?;
+            let path_p = path.into_raw();
+            let code = read_natoms(path_p, std::ptr::addr_of_mut!(num_atoms));
+            // Reconstitute the CString so it is deallocated correctly
+            let _ = CString::from_raw(path_p);
+
+            if let Some(err) = check_code(code, ErrorTask::ReadNumAtoms) {
+                Err(err)
+            } else {
+                to!(num_atoms, ErrorTask::ReadNumAtoms)
+            }
         }
     }
-}
 
-impl io::Seek for XDRFile {
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+    /// Read the number of atoms from the first frame with this handle,
+    /// leaving the position unchanged
+    fn peek_num_atoms(&self) -> Result<usize> {
+        let position = unsafe { xdr_seek::xdr_tell(self.xdrfile) };
+        let seek = |position| {
+            let code = unsafe { xdr_seek::xdr_seek(self.xdrfile, position, 0) };
+            check_code(code, ErrorTask::Seek).map_or(Ok(()), Err)
+        };
+        seek(0)?;
+        let num_atoms = index::read_num_atoms(&mut HandleReader(self));
+        seek(position)?;
+        num_atoms
+    }
+
+    /// Read the header of the next frame, leaving the position unchanged.
+    /// Returns `None` at the end of the file or if the header is invalid,
+    /// which is left for the reader of the frame to report.
+    fn peek_header(&self) -> Result<Option<index::RawHeader>> {
+        let position = unsafe { xdr_seek::xdr_tell(self.xdrfile) };
+        let header = index::read_header(&mut HandleReader(self));
+        let code = unsafe { xdr_seek::xdr_seek(self.xdrfile, position, 0) };
+        check_code(code, ErrorTask::Seek).map_or(Ok(header.ok().flatten()), Err)
+    }
+
+    /// Move past the next frame by reading only its header. Returns false at
+    /// the end of the file. A failure leaves the position unknown, so it
+    /// poisons the handle like a failed read.
+    fn skip_frame(&mut self) -> Result<bool> {
+        self.check_poisoned()?;
+        let result = self.skip_frame_inner();
+        if let Err(e) = &result {
+            self.poisoned = Some(Box::new(e.clone()));
+        }
+        result
+    }
+
+    fn skip_frame_inner(&mut self) -> Result<bool> {
+        let header = match index::read_header(&mut HandleReader(self)) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(Error::InvalidData(e.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let remaining = to(header.remaining, ErrorTask::Seek, "frame size")?;
+        let next_frame = self.next_frame;
+        self.seek_to(SeekFrom::Current(remaining))?;
+        self.next_frame = next_frame;
+        self.frame_done();
+        Ok(true)
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        let position = unsafe { xdr_seek::xdr_tell(self.xdrfile) };
+        to(position, ErrorTask::Seek, "file position")
+    }
+
+    /// Move to `pos` in the file and return the new position
+    fn seek_to(&mut self, pos: SeekFrom) -> Result<u64> {
+        let operation = operation!(TRACE, "seek", path = %self.path.display(), ?pos);
+        let result = self.seek_handle(pos);
+        operation.finish(&result);
+        result
+    }
+
+    fn seek_handle(&mut self, pos: SeekFrom) -> Result<u64> {
         let (whence, pos) = match pos {
-            SeekFrom::Start(u) => (
-                0,
-                i64::try_from(u).expect("Seek position did not fit in i64"),
-            ),
+            SeekFrom::Start(u) => (0, to(u, ErrorTask::Seek, "seek position")?),
             SeekFrom::Current(i) => (1, i),
             SeekFrom::End(i) => (2, i),
         };
-        unsafe {
-            let code = xdr_seek::xdr_seek(self.xdrfile, pos, whence);
-            match check_code(code, ErrorTask::Seek) {
-                None => Ok(self.tell()),
-                Some(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
-            }
+        let code = unsafe { xdr_seek::xdr_seek(self.xdrfile, pos, whence) };
+        if let Some(err) = check_code(code, ErrorTask::Seek) {
+            return Err(err);
+        }
+        let position = self.tell()?;
+        self.next_frame = if position == 0 { Some(0) } else { None };
+        self.poisoned = None;
+        Ok(position)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+/// Reads raw bytes through an xdr handle, in multiples of four bytes
+struct HandleReader<'a>(&'a XDRFile);
+
+#[cfg(feature = "c-lib")]
+impl io::Read for HandleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // anything else would skip the padding to four bytes
+        let len = buf.len() / 4 * 4;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "xdr handles read multiples of four bytes",
+            ));
         }
+        let len = c_int::try_from(len).unwrap_or(c_int::MAX / 4 * 4);
+        let read = unsafe {
+            xdrfile::xdrfile_read_opaque(
+                buf.as_mut_ptr() as *mut std::os::raw::c_char,
+                len,
+                self.0.xdrfile,
+            )
+        };
+        // the C library does not tell apart errors and the end of the file
+        Ok(read.max(0) as usize)
     }
 }
 
+#[cfg(feature = "c-lib")]
+impl io::Seek for XDRFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.seek_to(pos).map_err(|err| {
+            let kind = match err {
+                Error::OutOfRange { .. } => io::ErrorKind::InvalidInput,
+                _ => io::ErrorKind::Other,
+            };
+            io::Error::new(kind, err)
+        })
+    }
+}
+
+#[cfg(feature = "c-lib")]
 impl Drop for XDRFile {
     /// Close the underlying xdr file on drop
     fn drop(&mut self) {
         unsafe {
-            xdrfile::xdrfile_close(self.xdrfile);
+            match self.stream {
+                Some(stream) => {
+                    c_abi::xdr_stream::xdr_stream_close(self.xdrfile);
+                    drop(Box::from_raw(stream.as_ptr()));
+                }
+                None => {
+                    xdrfile::xdrfile_close(self.xdrfile);
+                }
+            }
         }
     }
 }
@@ -233,23 +612,167 @@ pub trait Trajectory {
     /// Get the number of atoms from the give trajectory
     fn get_num_atoms(&mut self) -> Result<usize>;
 
+    /// Call `f` with every remaining frame until the end of the trajectory
+    /// or until `f` returns `ControlFlow::Break`. A single frame buffer is
+    /// reused for all steps. Returns the value `f` broke with, if any.
+    fn for_each_frame<B, F>(&mut self, mut f: F) -> Result<Option<B>>
+    where
+        Self: Sized,
+        F: FnMut(&Frame) -> ControlFlow<B>,
+    {
+        let mut frame = Frame::with_len(self.get_num_atoms()?);
+        while read_next(self, &mut frame)? {
+            if let ControlFlow::Break(value) = f(&frame) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Read the next frame of `trajectory` into `frame`. Returns false at the
+/// end of the trajectory.
+pub(crate) fn read_next<T>(trajectory: &mut T, frame: &mut Frame) -> Result<bool>
+where
+    T: Trajectory + ?Sized,
+{
+    match trajectory.read(frame) {
+        Ok(()) => Ok(true),
+        Err(e) if e.is_eof() => Ok(false),
+        Err(e) => Err(e),
+    }
 }
 
+impl<T: Trajectory + ?Sized> Trajectory for Box<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        (**self).read(frame)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        (**self).write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        (**self).get_num_atoms()
+    }
+}
+
+#[cfg(feature = "c-lib")]
+/// Check a frame read in strict mode, and remember its step and time to
+/// compare them with the next frame
+fn validate_read(frame: &Frame, previous: &mut Option<(usize, f32)>) -> Result<()> {
+    frame.validate()?;
+    if let Some((step, time)) = *previous {
+        if frame.step <= step {
+            return Err(ValidationError::StepNotIncreasing {
+                previous: step,
+                step: frame.step,
+            }
+            .into());
+        }
+        if frame.time <= time {
+            return Err(ValidationError::TimeNotIncreasing {
+                previous: time,
+                time: frame.time,
+            }
+            .into());
+        }
+    }
+    *previous = Some((frame.step, frame.time));
+    Ok(())
+}
+
+#[cfg(feature = "c-lib")]
+/// What is known about a file in read mode, computed once and shared by a
+/// trajectory and its clones from `try_clone`
+struct FileCache {
+    num_atoms: Lazy<Result<usize>>,
+    index: Lazy<Result<FrameIndex>>,
+}
+
+#[cfg(feature = "c-lib")]
+impl FileCache {
+    fn new() -> Arc<FileCache> {
+        Arc::new(FileCache {
+            num_atoms: Lazy::new(),
+            index: Lazy::new(),
+        })
+    }
+
+    /// The index of the file of `handle`, loaded from its sidecar file or
+    /// built on the first call, see `FrameIndex::load_or_build`
+    fn index(&self, handle: &XDRFile) -> Result<&FrameIndex> {
+        if handle.stream.is_some() {
+            return Err(Error::Unsupported("indexing a stream"));
+        }
+        if handle.filemode != FileMode::Read {
+            return Err(Error::Unsupported(
+                "indexing a trajectory that is not in read mode",
+            ));
+        }
+        self.index
+            .get_or_create(|| FrameIndex::load_or_build(&handle.path))
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+}
+
+#[cfg(feature = "c-lib")]
 /// Handle to Read/Write XTC Trajectories
 pub struct XTCTrajectory {
     handle: XDRFile,
     precision: Cell<c_float>, // internal mutability required for read method
-    num_atoms: Lazy<Result<usize>>,
+    cache: Arc<FileCache>,
+    max_atoms: usize,
+    strict: bool,
+    // step and time of the last frame read in strict mode
+    last_read: Option<(usize, f32)>,
+    // reading ends after the complete frames, see `open_read_tolerant`
+    truncation: Option<TruncationReport>,
 }
 
+#[cfg(feature = "c-lib")]
 impl XTCTrajectory {
     pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XTCTrajectory> {
-        let xdr = XDRFile::open(path, filemode)?;
-        Ok(XTCTrajectory {
-            handle: xdr,
+        let operation = operation!(
+            DEBUG,
+            "open",
+            format = "xtc",
+            path = %path.as_ref().display(),
+            mode = ?filemode,
+        );
+        let result = XDRFile::open(path, filemode).map(Self::from_handle);
+        operation.finish(&result);
+        result
+    }
+
+    fn from_handle(handle: XDRFile) -> XTCTrajectory {
+        XTCTrajectory {
+            handle,
             precision: Cell::new(1000.0),
-            num_atoms: Lazy::new(),
-        })
+            cache: FileCache::new(),
+            max_atoms: DEFAULT_MAX_ATOMS,
+            strict: false,
+            last_read: None,
+            truncation: None,
+        }
+    }
+
+    /// Read a trajectory from `reader` instead of a file, e.g. from a
+    /// `Cursor` over XTC data in memory
+    pub fn from_reader(reader: impl io::Read + io::Seek + Send + 'static) -> Result<Self> {
+        let stream = xdr_stream::Stream::Reader(Box::new(reader));
+        XDRFile::from_stream(stream, FileMode::Read).map(Self::from_handle)
+    }
+
+    /// Write a trajectory to `writer` instead of a file
+    pub fn from_writer(writer: impl io::Write + io::Seek + Send + 'static) -> Result<Self> {
+        let stream = xdr_stream::Stream::Writer(Box::new(writer));
+        XDRFile::from_stream(stream, FileMode::Write).map(Self::from_handle)
     }
 
     /// Open a file in read mode
@@ -257,125 +780,297 @@ impl XTCTrajectory {
         Self::open(path, FileMode::Read)
     }
 
+    /// Open a file in read mode and check every frame that is read: it must
+    /// pass `Frame::validate` (finite coordinates and time, a box with a
+    /// volume), and its step and time must be larger than those of the
+    /// previous frame. Invalid frames are returned as
+    /// `Error::ValidationFailed` after they were read, so reading can go on
+    /// with the next frame. Seeking restarts the comparison with the previous
+    /// frame. Useful to check data from third parties.
+    pub fn open_read_strict(path: impl AsRef<Path>) -> Result<Self> {
+        let mut trajectory = Self::open_read(path)?;
+        trajectory.set_strict(true);
+        Ok(trajectory)
+    }
+
     /// Open a file in append mode
     pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
         Self::open(path, FileMode::Append)
     }
 
-    /// Open a file in write mode
+    /// Open a file in write mode. An existing file is truncated, see
+    /// `open_write_with_backup` to keep it.
     pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
         Self::open(path, FileMode::Write)
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl Trajectory for XTCTrajectory {
     fn read(&mut self, frame: &mut Frame) -> Result<()> {
-        let mut step: c_int = 0;
-
         let num_atoms = self
             .get_num_atoms()
             .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
         if num_atoms != frame.coords.len() {
             return Err((&*frame, num_atoms).into());
         }
+        self.read_unchecked_len(frame)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let operation = operation!(
+            TRACE,
+            "write",
+            format = "xtc",
+            path = %self.handle.path.display(),
+            frame = ?self.handle.next_frame,
+            offset = ?self.handle.tell().ok(),
+        );
+        let result = self.write_frame(frame);
+        operation.finish(&result);
+        result
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        unsafe {
+            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
+            if let Some(err) = check_code(code, ErrorTask::Flush) {
+                Err(err)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        let num_atoms = self
+            .cache
+            .num_atoms
+            .get_or_create(|| self.handle.read_num_atoms(xdrfile_xtc::read_xtc_natoms))
+            .clone()?;
+        check_num_atoms(num_atoms, self.max_atoms)
+    }
+}
 
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Set the largest number of atoms that `get_num_atoms` accepts from the
+    /// file, `DEFAULT_MAX_ATOMS` (2^28) by default. A corrupt header can
+    /// claim billions of atoms, and allocating frames for them would run out
+    /// of memory instead of failing with `Error::TooManyAtoms`.
+    pub fn set_max_atoms(&mut self, max_atoms: usize) {
+        self.max_atoms = max_atoms;
+    }
+
+    /// Whether a frame could not be read or written, after which the
+    /// handle refuses to read and write with `Error::HandlePoisoned`, until
+    /// it is moved with `rewind`, `seek` or `reset`
+    pub fn is_poisoned(&self) -> bool {
+        self.handle.poisoned.is_some()
+    }
+
+    /// Go back to the first frame, which also clears a poisoned handle
+    pub fn rewind(&mut self) -> Result<()> {
+        self.last_read = None;
+        self.handle.seek_to(SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    /// Clear a poisoned handle without moving it, to continue at the
+    /// position where the C library stopped. Prefer `rewind` or seeking to
+    /// a known frame offset.
+    pub fn reset(&mut self) {
+        self.handle.poisoned = None;
+    }
+
+    /// Open an independent handle to the same file, positioned at the first
+    /// frame, so that several threads can read different frames at the same
+    /// time. The number of atoms and the index of the file are shared with
+    /// the clone and only computed once. Only works in read mode.
+    pub fn try_clone(&self) -> Result<XTCTrajectory> {
+        Ok(XTCTrajectory {
+            handle: self.handle.try_clone()?,
+            precision: self.precision.clone(),
+            cache: Arc::clone(&self.cache),
+            max_atoms: self.max_atoms,
+            strict: self.strict,
+            last_read: None,
+            truncation: self.truncation,
+        })
+    }
+
+    /// The index of the frames of the file, which is shared with clones
+    /// from `try_clone`. On the first call, it is loaded from the sidecar
+    /// file next to the trajectory, or built and saved there, so that the
+    /// file is not scanned again the next time it is opened (see
+    /// `FrameIndex::load_or_build`). Only works in read mode.
+    pub fn frame_index(&self) -> Result<&FrameIndex> {
+        self.cache.index(&self.handle)
+    }
+
+    /// Read the next frame without comparing the length of `frame` with the
+    /// number of atoms of the trajectory, which `read` does. This never
+    /// allocates, so it suits tight loops over many frames.
+    ///
+    /// Frames that are too short for the next frame are still refused by the
+    /// C library. Atoms beyond the number of atoms in the trajectory are left
+    /// unchanged.
+    pub fn read_unchecked_len(&mut self, frame: &mut Frame) -> Result<()> {
+        let operation = operation!(
+            TRACE,
+            "read",
+            format = "xtc",
+            path = %self.handle.path.display(),
+            frame = ?self.handle.next_frame,
+            offset = ?self.handle.tell().ok(),
+        );
+        let result = self.read_frame(frame);
+        operation.finish(&result);
+        result
+    }
+
+    fn read_frame(&mut self, frame: &mut Frame) -> Result<()> {
+        self.handle.check_poisoned()?;
+        let offset = self.tell()?;
+        if self.truncation.is_some_and(|t| offset >= t.complete_len) {
+            return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+        }
+        let mut step: c_int = 0;
         unsafe {
             let code = xdrfile_xtc::read_xtc(
                 self.handle.xdrfile,
-                to!(num_atoms, ErrorTask::Read)?,
+                to!(frame.coords.len(), ErrorTask::Read)?,
                 &mut step,
                 &mut frame.time,
-                &mut frame.box_vector,
+                &mut frame.box_vector.0,
                 frame.coords.as_mut_ptr(),
                 &mut self.precision.get(),
             );
             if let Some(err) = check_code(code, ErrorTask::Read) {
-                return Err(err);
+                return Err(self.handle.error_at(offset, err));
             }
+            self.handle.frame_done();
             frame.step = to!(step, ErrorTask::Read)?;
+            if self.strict {
+                validate_read(frame, &mut self.last_read)?;
+            }
             Ok(())
         }
     }
 
-    fn write(&mut self, frame: &Frame) -> Result<()> {
+    fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        if self.strict {
+            frame.validate()?;
+        }
+        self.handle.check_poisoned()?;
+        let offset = self.tell()?;
         unsafe {
             let code = xdrfile_xtc::write_xtc(
                 self.handle.xdrfile,
                 to!(frame.num_atoms(), ErrorTask::Write)?,
                 to!(frame.step, ErrorTask::Write)?,
                 frame.time,
-                &frame.box_vector,
+                &frame.box_vector.0,
                 frame.coords.as_ptr(),
-                1000.0,
+                self.precision.get(),
             );
             if let Some(err) = check_code(code, ErrorTask::Write) {
-                Err(err)
+                Err(self.handle.error_at(offset, err))
             } else {
+                self.handle.frame_done();
                 Ok(())
             }
         }
     }
 
-    fn flush(&mut self) -> Result<()> {
-        unsafe {
-            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
-            if let Some(err) = check_code(code, ErrorTask::Flush) {
-                Err(err)
-            } else {
-                Ok(())
-            }
-        }
+    /// In strict mode, `write` validates every frame with `Frame::validate`
+    /// and refuses to write invalid ones, and reading checks frames like
+    /// `open_read_strict`. Disabled by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 
-    fn get_num_atoms(&mut self) -> Result<usize> {
-        self.num_atoms
-            .get_or_create(|| {
-                let mut num_atoms: c_int = 0;
-
-                unsafe {
-                    let path = path_to_cstring(&self.handle.path)?;
-                    let path_p = path.into_raw();
-                    let code = xdrfile_xtc::read_xtc_natoms(path_p, &mut num_atoms);
-                    // Reconstitute the CString so it is deallocated correctly
-                    let _ = CString::from_raw(path_p);
-
-                    if let Some(err) = check_code(code, ErrorTask::ReadNumAtoms) {
-                        Err(err)
-                    } else {
-                        to!(num_atoms, ErrorTask::ReadNumAtoms)
-                    }
-                }
-            })
-            .clone()
+    /// Set the precision that coordinates are written with, i.e. the inverse
+    /// of the smallest distance that can be stored. The default of 1000
+    /// keeps three decimals (picometers).
+    pub fn set_precision(&mut self, precision: f32) {
+        self.precision.set(precision);
     }
-}
 
-impl XTCTrajectory {
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
-        self.handle.tell()
+    /// Precision that coordinates are written with, see `set_precision`
+    pub fn precision(&self) -> f32 {
+        self.precision.get()
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl io::Seek for XTCTrajectory {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        // frames may be read out of order after a seek
+        self.last_read = None;
         self.handle.seek(pos)
     }
 }
 
+#[cfg(feature = "c-lib")]
 /// Handle to Read/Write TRR Trajectories
+///
+/// Velocities and forces are read into `Frame::velocities` and
+/// `Frame::forces` if a frame stores them, and written if the frame has
+/// them.
 pub struct TRRTrajectory {
     handle: XDRFile,
-    num_atoms: Lazy<Result<usize>>,
+    cache: Arc<FileCache>,
+    max_atoms: usize,
+    strict: bool,
+    // step and time of the last frame read in strict mode
+    last_read: Option<(usize, f32)>,
+    // reading ends after the complete frames, see `open_read_tolerant`
+    truncation: Option<TruncationReport>,
 }
 
+#[cfg(feature = "c-lib")]
 impl TRRTrajectory {
     pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<TRRTrajectory> {
-        let xdr = XDRFile::open(path, filemode)?;
-        Ok(TRRTrajectory {
-            handle: xdr,
-            num_atoms: Lazy::new(),
-        })
+        let operation = operation!(
+            DEBUG,
+            "open",
+            format = "trr",
+            path = %path.as_ref().display(),
+            mode = ?filemode,
+        );
+        let result = XDRFile::open(path, filemode).map(Self::from_handle);
+        operation.finish(&result);
+        result
+    }
+
+    fn from_handle(handle: XDRFile) -> TRRTrajectory {
+        TRRTrajectory {
+            handle,
+            cache: FileCache::new(),
+            max_atoms: DEFAULT_MAX_ATOMS,
+            strict: false,
+            last_read: None,
+            truncation: None,
+        }
+    }
+
+    /// Read a trajectory from `reader` instead of a file, e.g. from a
+    /// `Cursor` over TRR data in memory
+    pub fn from_reader(reader: impl io::Read + io::Seek + Send + 'static) -> Result<Self> {
+        let stream = xdr_stream::Stream::Reader(Box::new(reader));
+        XDRFile::from_stream(stream, FileMode::Read).map(Self::from_handle)
+    }
+
+    /// Write a trajectory to `writer` instead of a file
+    pub fn from_writer(writer: impl io::Write + io::Seek + Send + 'static) -> Result<Self> {
+        let stream = xdr_stream::Stream::Writer(Box::new(writer));
+        XDRFile::from_stream(stream, FileMode::Write).map(Self::from_handle)
     }
 
     /// Open a file in read mode
@@ -383,29 +1078,185 @@ impl TRRTrajectory {
         Self::open(path, FileMode::Read)
     }
 
+    /// Open a file in read mode and check every frame that is read: it must
+    /// pass `Frame::validate` (finite coordinates and time, a box with a
+    /// volume), and its step and time must be larger than those of the
+    /// previous frame. Invalid frames are returned as
+    /// `Error::ValidationFailed` after they were read, so reading can go on
+    /// with the next frame. Seeking restarts the comparison with the previous
+    /// frame. Useful to check data from third parties.
+    pub fn open_read_strict(path: impl AsRef<Path>) -> Result<Self> {
+        let mut trajectory = Self::open_read(path)?;
+        trajectory.set_strict(true);
+        Ok(trajectory)
+    }
+
     /// Open a file in append mode
     pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
         Self::open(path, FileMode::Append)
     }
 
-    /// Open a file in write mode
+    /// Open a file in write mode. An existing file is truncated, see
+    /// `open_write_with_backup` to keep it.
     pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
         Self::open(path, FileMode::Write)
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl Trajectory for TRRTrajectory {
     fn read(&mut self, frame: &mut Frame) -> Result<()> {
-        let mut step: c_int = 0;
-        let mut lambda: c_float = 0.0;
-
         let num_atoms = self
             .get_num_atoms()
             .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
         if num_atoms != frame.coords.len() {
             return Err((&*frame, num_atoms).into());
         }
+        self.read_unchecked_len(frame)
+    }
 
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let operation = operation!(
+            TRACE,
+            "write",
+            format = "trr",
+            path = %self.handle.path.display(),
+            frame = ?self.handle.next_frame,
+            offset = ?self.handle.tell().ok(),
+        );
+        let result = self.write_frame(frame);
+        operation.finish(&result);
+        result
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        unsafe {
+            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
+            if let Some(err) = check_code(code, ErrorTask::Flush) {
+                Err(err)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        let num_atoms = self
+            .cache
+            .num_atoms
+            .get_or_create(|| self.handle.read_num_atoms(xdrfile_trr::read_trr_natoms))
+            .clone()?;
+        check_num_atoms(num_atoms, self.max_atoms)
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Set the largest number of atoms that `get_num_atoms` accepts from the
+    /// file, `DEFAULT_MAX_ATOMS` (2^28) by default. A corrupt header can
+    /// claim billions of atoms, and allocating frames for them would run out
+    /// of memory instead of failing with `Error::TooManyAtoms`.
+    pub fn set_max_atoms(&mut self, max_atoms: usize) {
+        self.max_atoms = max_atoms;
+    }
+
+    /// Whether a frame could not be read or written, after which the
+    /// handle refuses to read and write with `Error::HandlePoisoned`, until
+    /// it is moved with `rewind`, `seek` or `reset`
+    pub fn is_poisoned(&self) -> bool {
+        self.handle.poisoned.is_some()
+    }
+
+    /// Go back to the first frame, which also clears a poisoned handle
+    pub fn rewind(&mut self) -> Result<()> {
+        self.last_read = None;
+        self.handle.seek_to(SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    /// Clear a poisoned handle without moving it, to continue at the
+    /// position where the C library stopped. Prefer `rewind` or seeking to
+    /// a known frame offset.
+    pub fn reset(&mut self) {
+        self.handle.poisoned = None;
+    }
+
+    /// Open an independent handle to the same file, positioned at the first
+    /// frame, so that several threads can read different frames at the same
+    /// time. The number of atoms and the index of the file are shared with
+    /// the clone and only computed once. Only works in read mode.
+    pub fn try_clone(&self) -> Result<TRRTrajectory> {
+        Ok(TRRTrajectory {
+            handle: self.handle.try_clone()?,
+            cache: Arc::clone(&self.cache),
+            max_atoms: self.max_atoms,
+            strict: self.strict,
+            last_read: None,
+            truncation: self.truncation,
+        })
+    }
+
+    /// The index of the frames of the file, which is shared with clones
+    /// from `try_clone`. On the first call, it is loaded from the sidecar
+    /// file next to the trajectory, or built and saved there, so that the
+    /// file is not scanned again the next time it is opened (see
+    /// `FrameIndex::load_or_build`). Only works in read mode.
+    pub fn frame_index(&self) -> Result<&FrameIndex> {
+        self.cache.index(&self.handle)
+    }
+
+    /// Read the next frame without comparing the length of `frame` with the
+    /// number of atoms of the trajectory, which `read` does. This never
+    /// allocates, so it suits tight loops over many frames.
+    ///
+    /// Frames that are too short for the next frame are still refused by the
+    /// C library. Atoms beyond the number of atoms in the trajectory are left
+    /// unchanged.
+    pub fn read_unchecked_len(&mut self, frame: &mut Frame) -> Result<()> {
+        let operation = operation!(
+            TRACE,
+            "read",
+            format = "trr",
+            path = %self.handle.path.display(),
+            frame = ?self.handle.next_frame,
+            offset = ?self.handle.tell().ok(),
+        );
+        let result = self.read_frame(frame);
+        operation.finish(&result);
+        result
+    }
+
+    fn read_frame(&mut self, frame: &mut Frame) -> Result<()> {
+        self.handle.check_poisoned()?;
+        let offset = self.tell()?;
+        if self.truncation.is_some_and(|t| offset >= t.complete_len) {
+            return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+        }
+        let mut step: c_int = 0;
+        let mut lambda: c_float = 0.0;
+        let num_atoms = frame.coords.len();
+        // only keep the buffers for vectors that the frame stores
+        let header = self.handle.peek_header()?;
+        let vectors = |buffer: &mut Option<Vec<[f32; 3]>>, present: bool| {
+            if present {
+                let buffer = buffer.get_or_insert_with(Vec::new);
+                buffer.resize(num_atoms, [0.0; 3]);
+                buffer.as_mut_ptr()
+            } else {
+                *buffer = None;
+                std::ptr::null_mut()
+            }
+        };
+        let velocities = vectors(
+            &mut frame.velocities,
+            header.as_ref().is_some_and(|h| h.velocities),
+        );
+        let forces = vectors(&mut frame.forces, header.as_ref().is_some_and(|h| h.forces));
         unsafe {
             let code = xdrfile_trr::read_trr(
                 self.handle.xdrfile,
@@ -413,20 +1264,45 @@ impl Trajectory for TRRTrajectory {
                 &mut step,
                 &mut frame.time,
                 &mut lambda,
-                &mut frame.box_vector,
+                &mut frame.box_vector.0,
                 frame.coords.as_mut_ptr(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
+                velocities,
+                forces,
             );
             if let Some(err) = check_code(code, ErrorTask::Read) {
-                return Err(err);
+                return Err(self.handle.error_at(offset, err));
             }
+            self.handle.frame_done();
             frame.step = to!(step, ErrorTask::Read)?;
+            if self.strict {
+                validate_read(frame, &mut self.last_read)?;
+            }
             Ok(())
         }
     }
 
-    fn write(&mut self, frame: &Frame) -> Result<()> {
+    fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        if self.strict {
+            frame.validate()?;
+        }
+        self.handle.check_poisoned()?;
+        for (name, vectors) in [("velocities", &frame.velocities), ("forces", &frame.forces)] {
+            match vectors {
+                Some(vectors) if vectors.len() != frame.len() => {
+                    return Err(Error::LengthMismatch {
+                        name,
+                        expected: frame.len(),
+                        found: vectors.len(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        let offset = self.tell()?;
+        let vectors_ptr = |vectors: &Option<Vec<[f32; 3]>>| match vectors {
+            Some(vectors) => vectors.as_ptr(),
+            None => std::ptr::null(),
+        };
         unsafe {
             let code = xdrfile_trr::write_trr(
                 self.handle.xdrfile,
@@ -434,66 +1310,38 @@ impl Trajectory for TRRTrajectory {
                 to!(frame.step, ErrorTask::Write)?,
                 frame.time,
                 0.0,
-                &frame.box_vector,
+                &frame.box_vector.0,
                 frame.coords[..].as_ptr(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
+                vectors_ptr(&frame.velocities),
+                vectors_ptr(&frame.forces),
             );
             if let Some(err) = check_code(code, ErrorTask::Write) {
-                Err(err)
+                Err(self.handle.error_at(offset, err))
             } else {
+                self.handle.frame_done();
                 Ok(())
             }
         }
     }
 
-    fn flush(&mut self) -> Result<()> {
-        unsafe {
-            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
-            if let Some(err) = check_code(code, ErrorTask::Flush) {
-                Err(err)
-            } else {
-                Ok(())
-            }
-        }
-    }
-
-    fn get_num_atoms(&mut self) -> Result<usize> {
-        self.num_atoms
-            .get_or_create(|| {
-                let mut num_atoms: c_int = 0;
-                unsafe {
-                    let path = path_to_cstring(&self.handle.path)?;
-                    let path_p = path.into_raw();
-                    let code = xdrfile_trr::read_trr_natoms(path_p, &mut num_atoms);
-                    // Reconstitute the CString so it is deallocated correctly
-                    let _ = CString::from_raw(path_p);
-
-                    if let Some(err) = check_code(code, ErrorTask::ReadNumAtoms) {
-                        Err(err)
-                    } else {
-                        to!(num_atoms, ErrorTask::ReadNumAtoms)
-                    }
-                }
-            })
-            .clone()
-    }
-}
-
-impl TRRTrajectory {
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
-        self.handle.tell()
+    /// In strict mode, `write` validates every frame with `Frame::validate`
+    /// and refuses to write invalid ones, and reading checks frames like
+    /// `open_read_strict`. Disabled by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 }
 
+#[cfg(feature = "c-lib")]
 impl io::Seek for TRRTrajectory {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        // frames may be read out of order after a seek
+        self.last_read = None;
         self.handle.seek(pos)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "c-lib"))]
 mod tests {
 
     use super::*;
@@ -511,8 +1359,9 @@ mod tests {
         let frame = Frame {
             step: 1,
             time: 1.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
             coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+            ..Default::default()
         };
         let mut f = XTCTrajectory::open_write(&tmp_path)?;
         let write_status = f.write(&frame);
@@ -526,8 +1375,9 @@ mod tests {
         let frame2 = Frame {
             step: 2,
             time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
             coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+            ..Default::default()
         };
         let mut f = XTCTrajectory::open_append(&tmp_path)?;
         let write_status = f.write(&frame2);
@@ -571,6 +1421,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_trr_velocities_and_forces() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame {
+            coords: vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+            velocities: Some(vec![[0.1, 0.2, 0.3], [0.4, 0.5, 0.6]]),
+            ..Default::default()
+        };
+        let mut f = TRRTrajectory::open_write(tempfile.path())?;
+        f.write(&frame)?;
+        frame.velocities = None;
+        frame.forces = Some(vec![[-1.0; 3], [1.0; 3]]);
+        f.write(&frame)?;
+        frame.forces = Some(vec![[1.0; 3]]);
+        assert!(matches!(
+            f.write(&frame),
+            Err(Error::LengthMismatch { name: "forces", .. })
+        ));
+        drop(f);
+
+        let frames: Vec<Frame> = TRRTrajectory::open_read(tempfile.path())?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            frames[0].velocities,
+            Some(vec![[0.1, 0.2, 0.3], [0.4, 0.5, 0.6]])
+        );
+        assert_eq!(frames[0].forces, None);
+        assert_eq!(frames[1].velocities, None);
+        assert_eq!(frames[1].forces, Some(vec![[-1.0; 3], [1.0; 3]]));
+        Ok(())
+    }
+
     #[test]
     fn test_write_append_read_trr() -> Result<()> {
         let tempfile = NamedTempFile::new().expect("Could not create temporary file");
@@ -581,8 +1465,9 @@ mod tests {
         let frame = Frame {
             step: 1,
             time: 1.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
             coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+            ..Default::default()
         };
         let mut f = TRRTrajectory::open_write(&tmp_path)?;
         let write_status = f.write(&frame);
@@ -596,8 +1481,9 @@ mod tests {
         let frame2 = Frame {
             step: 2,
             time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
             coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+            ..Default::default()
         };
         let mut f = TRRTrajectory::open_append(&tmp_path)?;
         let write_status = f.write(&frame2);
@@ -718,21 +1604,22 @@ mod tests {
         let frame = Frame {
             step: 5,
             time: 2.0,
-            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].into(),
             coords: vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
+            ..Default::default()
         };
         let mut f = TRRTrajectory::open_write(tmp_path)?;
-        assert_eq!(f.tell(), 0);
+        assert_eq!(f.tell()?, 0);
         f.write(&frame)?;
-        assert_eq!(f.tell(), 144);
+        assert_eq!(f.tell()?, 144);
         f.flush()?;
 
         let mut new_frame = Frame::with_len(natoms);
         let mut f = TRRTrajectory::open_read(tmp_path)?;
-        assert_eq!(f.tell(), 0);
+        assert_eq!(f.tell()?, 0);
 
         f.read(&mut new_frame)?;
-        assert_eq!(f.tell(), 144);
+        assert_eq!(f.tell()?, 144);
 
         Ok(())
     }
@@ -746,16 +1633,17 @@ mod tests {
         let mut frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].into(),
             coords: vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
+            ..Default::default()
         };
         let mut f = TRRTrajectory::open_write(tmp_path)?;
         f.write(&frame)?;
-        let after_first_frame = f.tell();
+        let after_first_frame = f.tell()?;
         frame.step += 1;
         frame.time += 10.0;
         f.write(&frame)?;
-        let after_second_frame = f.tell();
+        let after_second_frame = f.tell()?;
         f.flush()?;
 
         let mut new_frame = Frame::with_len(natoms);
@@ -764,7 +1652,7 @@ mod tests {
         assert_eq!(pos, after_first_frame);
 
         f.read(&mut new_frame)?;
-        assert_eq!(f.tell(), after_second_frame);
+        assert_eq!(f.tell()?, after_second_frame);
 
         assert_eq!(new_frame.len(), frame.len());
         assert_eq!(new_frame.step, frame.step);
@@ -828,8 +1716,9 @@ mod tests {
         let frame = Frame {
             step: 5,
             time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]].into(),
             coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+            ..Default::default()
         };
         let mut f = XTCTrajectory::open_write(&tmp_path)?;
         f.write(&frame)?;
@@ -905,8 +1794,9 @@ mod tests {
         let frame = Frame {
             step: usize::MAX,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
+            box_vector: [[0.0; 3]; 3].into(),
             coords: vec![[1.0; 3]],
+            ..Default::default()
         };
         let expected = Error::OutOfRange {
             name: "frame.step",
@@ -924,4 +1814,410 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn test_strict_write() -> Result<(), Box<dyn std::error::Error>> {
+        let tempfile = NamedTempFile::new()?;
+        let tmp_path = tempfile.path();
+
+        let mut frame = Frame {
+            box_vector: BoxVector::rectangular(1.0, 1.0, 1.0),
+            coords: vec![[0.0, 0.0, 0.0], [0.5, f32::NAN, 0.5]],
+            ..Default::default()
+        };
+        let mut f = XTCTrajectory::open_write(tmp_path)?;
+        f.write(&frame)?;
+
+        f.set_strict(true);
+        let result = f.write(&frame);
+        assert_eq!(
+            result,
+            Err(Error::ValidationFailed(
+                ValidationError::NonFiniteCoordinates { index: 1 }
+            ))
+        );
+
+        frame.coords[1][1] = 0.5;
+        let mut f = TRRTrajectory::open_write(tmp_path)?;
+        f.set_strict(true);
+        f.write(&frame)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_read() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let mut trr = TRRTrajectory::open_write(tempfile.path())?;
+        let frames = [(1, 1.0), (2, 2.0), (2, 3.0), (4, 2.0), (5, 5.0), (6, 6.0)];
+        for (i, &(step, time)) in frames.iter().enumerate() {
+            let mut frame = Frame {
+                step,
+                time,
+                box_vector: BoxVector::rectangular(1.0, 1.0, 1.0),
+                coords: vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
+                ..Default::default()
+            };
+            if i == 4 {
+                frame.coords[0][2] = f32::INFINITY;
+            }
+            trr.write(&frame)?;
+        }
+        drop(trr);
+
+        let mut trr = TRRTrajectory::open_read_strict(tempfile.path())?;
+        let mut frame = Frame::with_len(2);
+        let results: Vec<Result<()>> = (0..frames.len()).map(|_| trr.read(&mut frame)).collect();
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Ok(()),
+                Err(ValidationError::StepNotIncreasing {
+                    previous: 2,
+                    step: 2
+                }
+                .into()),
+                Err(ValidationError::TimeNotIncreasing {
+                    previous: 2.0,
+                    time: 2.0
+                }
+                .into()),
+                Err(ValidationError::NonFiniteCoordinates { index: 0 }.into()),
+                Ok(()),
+            ]
+        );
+        assert_eq!(
+            results[2].as_ref().unwrap_err().to_string(),
+            "Frame failed validation: Step 2 follows step 2"
+        );
+        assert!(trr.read(&mut frame).unwrap_err().is_eof());
+
+        // going back is fine after a seek
+        trr.rewind()?;
+        trr.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+
+        // without strict mode, all frames are read
+        let trr = TRRTrajectory::open_read(tempfile.path())?;
+        assert_eq!(trr.into_frames().collect::<Result<Vec<_>>>()?.len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_each_frame() -> Result<()> {
+        let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut steps = Vec::new();
+        let result: Option<()> = trj.for_each_frame(|frame| {
+            steps.push(frame.step);
+            ControlFlow::Continue(())
+        })?;
+        assert_eq!(result, None);
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+
+        let mut trj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let first_late = trj.for_each_frame(|frame| {
+            if frame.time > 10.0 {
+                ControlFlow::Break(frame.step)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+        assert!(first_late.is_some());
+
+        // iteration continues after the frame that ended the loop
+        let mut frame = Frame::with_len(304);
+        trj.read(&mut frame)?;
+        assert_eq!(Some(frame.step - 1), first_late);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_reader() -> Result<()> {
+        let bytes = std::fs::read("tests/1l2y.xtc")?;
+        let mut trj = XTCTrajectory::from_reader(io::Cursor::new(bytes))?;
+        assert_eq!(trj.get_num_atoms()?, 304);
+        let mut frame = Frame::with_len(304);
+        trj.read(&mut frame)?;
+        trj.read(&mut frame)?;
+        let second = trj.tell()?;
+        trj.read(&mut frame)?;
+
+        let expected = XTCTrajectory::open_read("tests/1l2y.xtc")?.into_frames();
+        let frames = XTCTrajectory::from_reader(io::Cursor::new(std::fs::read("tests/1l2y.xtc")?))?;
+        let mut num_frames = 0;
+        for (expected, frame) in expected.zip(frames) {
+            assert_eq!(expected?.coords, frame?.coords);
+            num_frames += 1;
+        }
+        assert_eq!(num_frames, 38);
+
+        // seeking back to the second frame
+        trj.seek(SeekFrom::Start(second))?;
+        trj.read(&mut frame)?;
+        assert_eq!(frame.step, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_atoms_from_open_handle() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        for name in &["1l2y.xtc", "1l2y.trr"] {
+            let path = dir.path().join(name);
+            std::fs::copy(Path::new("tests").join(name), &path)?;
+            let mut trj: Box<dyn Trajectory> = if name.ends_with("xtc") {
+                Box::new(XTCTrajectory::open_read(&path)?)
+            } else {
+                Box::new(TRRTrajectory::open_read(&path)?)
+            };
+            let mut frame = Frame::with_len(304);
+            trj.read(&mut frame)?;
+            std::fs::remove_file(&path)?;
+
+            // the position is kept
+            assert_eq!(trj.get_num_atoms()?, 304);
+            trj.read(&mut frame)?;
+            assert_eq!(frame.step, 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_writer() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let frames: Vec<_> = TRRTrajectory::open_read("tests/1l2y.trr")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let mut trj = TRRTrajectory::from_writer(std::fs::File::create(tempfile.path())?)?;
+        for frame in &frames {
+            trj.write(frame)?;
+        }
+        assert!(matches!(trj.get_num_atoms(), Err(Error::Unsupported(_))));
+        drop(trj);
+
+        let written = TRRTrajectory::open_read(tempfile.path())?.into_frames();
+        for (frame, written) in frames.iter().zip(written) {
+            assert_eq!(frame.coords, written?.coords);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_offsets_beyond_2gb() -> Result<()> {
+        // a sparse file with a frame behind a gap of 3 GB
+        let tempfile = NamedTempFile::new()?;
+        let offset = 3 << 30;
+        let mut file = std::fs::File::create(tempfile.path())?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&std::fs::read("tests/1l2y.xtc")?)?;
+        drop(file);
+
+        let mut expected = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut expected)?;
+
+        let mut xtc = XTCTrajectory::open_read(tempfile.path())?;
+        let from_file = std::fs::File::open(tempfile.path())?;
+        let mut stream = XTCTrajectory::from_reader(from_file)?;
+        for trj in [&mut xtc, &mut stream] {
+            assert_eq!(trj.seek(SeekFrom::Start(offset))?, offset);
+            assert_eq!(trj.tell()?, offset);
+            let mut frame = Frame::with_len(304);
+            trj.read_unchecked_len(&mut frame)?;
+            assert!(frame.approx_eq(&expected, 0.0));
+            let end = trj.tell()?;
+            assert!(end > offset);
+            let back = -i64::try_from(end - offset).unwrap();
+            assert_eq!(trj.seek(SeekFrom::Current(back))?, offset);
+        }
+        let err = xtc.seek(SeekFrom::Start(u64::MAX)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            Error::from(err),
+            Error::OutOfRange {
+                task: ErrorTask::Seek,
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_clone() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let expected: Vec<Frame> = xtc.try_clone()?.into_frames().collect::<Result<_>>()?;
+        assert_eq!(xtc.frame_index()?.len(), 38);
+
+        // each clone reads a different half of the frames
+        let halves: Vec<_> = [0, 19]
+            .iter()
+            .map(|&start| {
+                let mut clone = xtc.try_clone()?;
+                Ok(std::thread::spawn(move || -> Result<Vec<Frame>> {
+                    let offset = clone.frame_index()?.offset(start).unwrap();
+                    clone.seek(SeekFrom::Start(offset))?;
+                    clone.into_frames().take(19).collect()
+                }))
+            })
+            .collect::<Result<_>>()?;
+        let mut frames = Vec::new();
+        for half in halves {
+            frames.extend(half.join().unwrap()?);
+        }
+        assert_eq!(frames.len(), 38);
+        for (frame, expected) in frames.iter().zip(&expected) {
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+
+        // the index is only built once
+        let clone = xtc.try_clone()?;
+        assert!(std::ptr::eq(xtc.frame_index()?, clone.frame_index()?));
+        drop(xtc);
+        assert_eq!(clone.try_clone()?.into_frames().count(), 38);
+
+        let tempfile = NamedTempFile::new()?;
+        let trr = TRRTrajectory::open_write(tempfile.path())?;
+        assert!(matches!(trr.try_clone(), Err(Error::Unsupported(_))));
+        assert!(matches!(trr.frame_index(), Err(Error::Unsupported(_))));
+        let bytes = std::fs::read("tests/1l2y.trr")?;
+        let trr = TRRTrajectory::from_reader(io::Cursor::new(bytes))?;
+        assert!(matches!(trr.try_clone(), Err(Error::Unsupported(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_index_sidecar() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("traj.xtc");
+        std::fs::copy("tests/1l2y.xtc", &path)?;
+        let index_path = FrameIndex::sidecar_path(&path);
+
+        let xtc = XTCTrajectory::open_read(&path)?;
+        assert_eq!(xtc.frame_index()?.time(1), Some(2.0));
+        assert!(index_path.exists());
+
+        // change the time of the second frame in the sidecar file, which
+        // only shows up if the file is not scanned again
+        let mut bytes = std::fs::read(&index_path)?;
+        bytes[68..72].copy_from_slice(&99.0f32.to_le_bytes());
+        std::fs::write(&index_path, bytes)?;
+        let xtc = XTCTrajectory::open_read(&path)?;
+        assert_eq!(xtc.frame_index()?.time(1), Some(99.0));
+        assert_eq!(xtc.frame_index()?.len(), 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_location() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let offsets = FrameIndex::build("tests/1l2y.xtc")?.offsets().to_vec();
+        let mut bytes = std::fs::read("tests/1l2y.xtc")?;
+        bytes[offsets[2] as usize + 3] = 0;
+        std::fs::write(tempfile.path(), bytes)?;
+
+        let mut xtc = XTCTrajectory::open_read(tempfile.path())?;
+        let mut frame = Frame::with_len(304);
+        xtc.read(&mut frame)?;
+        xtc.read(&mut frame)?;
+        let e = xtc.read(&mut frame).unwrap_err();
+        assert_eq!(e.code(), Some(ErrorCode::ExdrMagic));
+        match &e {
+            Error::InFrame {
+                path,
+                offset,
+                frame,
+                ..
+            } => {
+                assert_eq!(path, tempfile.path());
+                assert_eq!(*offset, offsets[2]);
+                assert_eq!(*frame, Some(2));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(e
+            .to_string()
+            .contains(&format!("in frame 2 at byte {}", offsets[2])));
+
+        // the frame number is unknown after seeking
+        xtc.seek(SeekFrom::Start(offsets[1]))?;
+        xtc.read(&mut frame)?;
+        let e = xtc.read(&mut frame).unwrap_err();
+        assert!(matches!(e, Error::InFrame { frame: None, .. }));
+        xtc.seek(SeekFrom::Start(offsets[3]))?;
+        let remaining = xtc.into_iter().count();
+        assert_eq!(remaining, 35);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_atoms() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let mut bytes = std::fs::read("tests/1l2y.xtc")?;
+        // the number of atoms is stored before and after the box
+        for start in &[4, 52] {
+            bytes[*start..start + 4].copy_from_slice(&2_000_000_000i32.to_be_bytes());
+        }
+        std::fs::write(tempfile.path(), &bytes)?;
+
+        let mut xtc = XTCTrajectory::open_read(tempfile.path())?;
+        assert_eq!(
+            xtc.get_num_atoms(),
+            Err(Error::TooManyAtoms {
+                num_atoms: 2_000_000_000,
+                max_atoms: DEFAULT_MAX_ATOMS
+            })
+        );
+        assert!(xtc.into_frames().next().unwrap().is_err());
+        let mut memory = MemoryTrajectory::from_bytes(MemoryFormat::Xtc, bytes);
+        assert!(matches!(
+            memory.get_num_atoms(),
+            Err(Error::TooManyAtoms { .. })
+        ));
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        trr.set_max_atoms(100);
+        assert!(matches!(
+            trr.get_num_atoms(),
+            Err(Error::TooManyAtoms { num_atoms: 304, .. })
+        ));
+        trr.set_max_atoms(304);
+        assert_eq!(trr.get_num_atoms()?, 304);
+        Ok(())
+    }
+
+    #[test]
+    fn test_poisoned_handle() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let offsets = FrameIndex::build("tests/1l2y.trr")?.offsets().to_vec();
+        let mut bytes = std::fs::read("tests/1l2y.trr")?;
+        bytes[offsets[1] as usize + 3] = 0;
+        std::fs::write(tempfile.path(), bytes)?;
+
+        let mut trr = TRRTrajectory::open_read(tempfile.path())?;
+        let mut frame = Frame::with_len(304);
+        trr.read(&mut frame)?;
+        let cause = trr.read(&mut frame).unwrap_err();
+        assert!(trr.is_poisoned());
+        let e = trr.read(&mut frame).unwrap_err();
+        assert_eq!(e, Error::HandlePoisoned(Box::new(cause.clone())));
+        assert_eq!(e.code(), cause.code());
+        assert!(e.to_string().contains(&cause.to_string()));
+
+        trr.rewind()?;
+        assert!(!trr.is_poisoned());
+        trr.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+        assert!(trr.read(&mut frame).is_err());
+        trr.seek(SeekFrom::Start(offsets[2]))?;
+        trr.read(&mut frame)?;
+        assert_eq!(frame.step, 3);
+
+        // after a reset, reading continues wherever the C library stopped
+        trr.rewind()?;
+        trr.read(&mut frame)?;
+        assert!(trr.read(&mut frame).is_err());
+        trr.reset();
+        assert!(!matches!(
+            trr.read(&mut frame),
+            Err(Error::HandlePoisoned(_))
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,240 @@
+//! Small dense linear algebra helpers used by the geometry routines
+
+/// Eigen decomposition of a real symmetric `n x n` matrix (row major).
+///
+/// Uses the cyclic Jacobi method. Returns the eigenvalues in descending order
+/// and the matching eigenvectors as the columns of a row major `n x n` matrix.
+pub(crate) fn symmetric_eigen(matrix: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    assert_eq!(matrix.len(), n * n, "matrix must be n x n");
+    let mut a = matrix.to_vec();
+    let mut v = vec![0.0; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.0;
+    }
+
+    for _sweep in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|p| ((p + 1)..n).map(move |q| (p, q)))
+            .map(|(p, q)| a[p * n + q].powi(2))
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[p * n + q];
+                if apq == 0.0 {
+                    continue;
+                }
+                let app = a[p * n + p];
+                let aqq = a[q * n + q];
+                let theta = (aqq - app) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for k in 0..n {
+                    let akp = a[k * n + p];
+                    let akq = a[k * n + q];
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let apk = a[p * n + k];
+                    let aqk = a[q * n + k];
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[j * n + j].total_cmp(&a[i * n + i]));
+    let values = order.iter().map(|&i| a[i * n + i]).collect();
+    let mut vectors = vec![0.0; n * n];
+    for (col, &i) in order.iter().enumerate() {
+        for row in 0..n {
+            vectors[row * n + col] = v[row * n + i];
+        }
+    }
+    (values, vectors)
+}
+
+/// Rotation matrix that optimally superimposes centered coordinates with
+/// correlation matrix `s[a][b] = sum(x_a * y_b)` onto the reference `y`.
+///
+/// Implements the quaternion formulation of the Kabsch problem (Horn, 1987),
+/// which always yields a proper rotation.
+pub(crate) fn optimal_rotation(s: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let [[sxx, sxy, sxz], [syx, syy, syz], [szx, szy, szz]] = *s;
+    #[rustfmt::skip]
+    let n = [
+        sxx + syy + szz, syz - szy,        szx - sxz,        sxy - syx,
+        syz - szy,       sxx - syy - szz,  sxy + syx,        szx + sxz,
+        szx - sxz,       sxy + syx,        -sxx + syy - szz, syz + szy,
+        sxy - syx,       szx + sxz,        syz + szy,        -sxx - syy + szz,
+    ];
+    let (_, vectors) = symmetric_eigen(&n, 4);
+    let (q0, q1, q2, q3) = (vectors[0], vectors[4], vectors[8], vectors[12]);
+
+    [
+        [
+            q0 * q0 + q1 * q1 - q2 * q2 - q3 * q3,
+            2.0 * (q1 * q2 - q0 * q3),
+            2.0 * (q1 * q3 + q0 * q2),
+        ],
+        [
+            2.0 * (q1 * q2 + q0 * q3),
+            q0 * q0 - q1 * q1 + q2 * q2 - q3 * q3,
+            2.0 * (q2 * q3 - q0 * q1),
+        ],
+        [
+            2.0 * (q1 * q3 - q0 * q2),
+            2.0 * (q2 * q3 + q0 * q1),
+            q0 * q0 - q1 * q1 - q2 * q2 + q3 * q3,
+        ],
+    ]
+}
+
+/// Unnormalized autocorrelation `sum_k x[k] * x[k + m]` of `x` for every lag
+/// `m` from 0 to `x.len() - 1`, computed with a zero padded FFT in
+/// `O(n log n)`.
+pub(crate) fn autocorrelation(x: &[f64]) -> Vec<f64> {
+    let n = x.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let size = (2 * n).next_power_of_two();
+    let mut data: Vec<(f64, f64)> = x.iter().map(|&x| (x, 0.0)).collect();
+    data.resize(size, (0.0, 0.0));
+    fft(&mut data, false);
+    for value in data.iter_mut() {
+        *value = (value.0 * value.0 + value.1 * value.1, 0.0);
+    }
+    fft(&mut data, true);
+    data[..n]
+        .iter()
+        .map(|value| value.0 / size as f64)
+        .collect()
+}
+
+/// Cosine transform `x[0] + 2 * sum_j x[j] * cos(2 pi j k / m)` of the one
+/// sided even function `x` for every `k` from 0 to `m / 2`, where `m` is the
+/// zero padded length `(2 * x.len()).next_power_of_two()`, computed with an
+/// FFT.
+pub(crate) fn cosine_transform(x: &[f64]) -> Vec<f64> {
+    if x.is_empty() {
+        return Vec::new();
+    }
+    let size = (2 * x.len()).next_power_of_two();
+    let mut data: Vec<(f64, f64)> = x.iter().map(|&x| (x, 0.0)).collect();
+    data.resize(size, (0.0, 0.0));
+    fft(&mut data, false);
+    data[..=size / 2]
+        .iter()
+        .map(|value| 2.0 * value.0 - x[0])
+        .collect()
+}
+
+/// In-place radix-2 FFT of complex numbers given as (real, imaginary)
+/// pairs, whose length must be a power of two. The inverse transform is not
+/// normalized.
+fn fft(data: &mut [(f64, f64)], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "FFT length must be a power of two");
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f64::consts::PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (ar, ai) = data[start + k];
+                let (br, bi) = data[start + k + len / 2];
+                let (tr, ti) = (br * cos - bi * sin, br * sin + bi * cos);
+                data[start + k] = (ar + tr, ai + ti);
+                data[start + k + len / 2] = (ar - tr, ai - ti);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let matrix = [2.0, 1.0, 0.0, 1.0, 2.0, 0.0, 0.0, 0.0, 5.0];
+        let (values, vectors) = symmetric_eigen(&matrix, 3);
+        assert_approx_eq!(values[0], 5.0);
+        assert_approx_eq!(values[1], 3.0);
+        assert_approx_eq!(values[2], 1.0);
+
+        // A v = lambda v for every column
+        for col in 0..3 {
+            for row in 0..3 {
+                let av: f64 = (0..3)
+                    .map(|k| matrix[row * 3 + k] * vectors[k * 3 + col])
+                    .sum();
+                assert_approx_eq!(av, values[col] * vectors[row * 3 + col]);
+            }
+        }
+
+        // NaN propagates instead of panicking while sorting
+        let (values, _) = symmetric_eigen(&[f64::NAN, 0.0, 0.0, 1.0], 2);
+        assert!(values.iter().any(|x| x.is_nan()));
+    }
+
+    #[test]
+    fn test_autocorrelation() {
+        let x = [1.0, -2.0, 0.5, 3.0, 4.0, -1.5, 2.0];
+        let result = autocorrelation(&x);
+        assert_eq!(result.len(), x.len());
+        for (m, value) in result.iter().enumerate() {
+            let direct: f64 = (0..x.len() - m).map(|k| x[k] * x[k + m]).sum();
+            assert_approx_eq!(*value, direct, 1e-9);
+        }
+        assert!(autocorrelation(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_cosine_transform() {
+        let x = [3.0, 1.0, -0.5];
+        let transform = cosine_transform(&x);
+        assert_eq!(transform.len(), 5);
+        for (k, value) in transform.iter().enumerate() {
+            let expected: f64 = x[0]
+                + 2.0
+                    * (1..3)
+                        .map(|j| x[j] * (std::f64::consts::PI * (j * k) as f64 / 4.0).cos())
+                        .sum::<f64>();
+            assert_approx_eq!(value, expected);
+        }
+        assert!(cosine_transform(&[]).is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! Trajectories that are kept in memory instead of a file
+
+use crate::index::read_num_atoms;
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::io::{Cursor, Read, Seek, Write};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Encoding of the frames of a `MemoryTrajectory`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryFormat {
+    Xtc,
+    Trr,
+}
+
+/// A view of the bytes of a `MemoryTrajectory` with its own position, so
+/// that reading and writing do not interfere
+struct SharedCursor {
+    data: Arc<Mutex<Vec<u8>>>,
+    position: usize,
+}
+
+impl Read for SharedCursor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = lock(&self.data);
+        let available = data.get(self.position..).unwrap_or_default();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl Write for SharedCursor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = lock(&self.data);
+        let end = self.position + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[self.position..end].copy_from_slice(buf);
+        self.position = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SharedCursor {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = lock(&self.data).len();
+        let position = match pos {
+            SeekFrom::Start(offset) => usize::try_from(offset).ok(),
+            SeekFrom::Current(offset) => offset_by(self.position, offset),
+            SeekFrom::End(offset) => offset_by(len, offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position as u64)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn lock(data: &Mutex<Vec<u8>>) -> MutexGuard<'_, Vec<u8>> {
+    data.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn offset_by(position: usize, offset: i64) -> Option<usize> {
+    let offset = isize::try_from(offset).ok()?;
+    position.checked_add_signed(offset)
+}
+
+/// An XTC or TRR trajectory whose encoded bytes are kept in a `Vec<u8>`.
+///
+/// Frames are written to the end of the data and read from the start, so a
+/// trajectory can be built up, read back and finally written to disk in one
+/// go, without any temporary files.
+///
+/// ```
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = MemoryTrajectory::new(MemoryFormat::Xtc);
+/// trajectory.write(&Frame::with_len(10))?;
+///
+/// let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+/// trajectory.read(&mut frame)?;
+/// let bytes = trajectory.into_bytes();
+/// # Ok(())
+/// # }
+/// ```
+pub struct MemoryTrajectory {
+    format: MemoryFormat,
+    data: Arc<Mutex<Vec<u8>>>,
+    precision: f32,
+    reader: Option<Box<dyn Trajectory>>,
+    writer: Option<Box<dyn Trajectory>>,
+    // frames are only appended, so the header of the first frame never changes
+    num_atoms: Option<usize>,
+}
+
+impl MemoryTrajectory {
+    /// Create an empty trajectory
+    pub fn new(format: MemoryFormat) -> MemoryTrajectory {
+        MemoryTrajectory::from_bytes(format, Vec::new())
+    }
+
+    /// Create a trajectory from encoded frames, e.g. the contents of a file
+    pub fn from_bytes(format: MemoryFormat, bytes: Vec<u8>) -> MemoryTrajectory {
+        MemoryTrajectory {
+            format,
+            data: Arc::new(Mutex::new(bytes)),
+            precision: 1000.0,
+            reader: None,
+            writer: None,
+            num_atoms: None,
+        }
+    }
+
+    /// Read a whole trajectory file into memory, choosing the format by
+    /// file extension (xtc or trr)
+    pub fn load(path: impl AsRef<Path>) -> Result<MemoryTrajectory> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let format = match extension.to_ascii_lowercase().as_str() {
+            "xtc" => MemoryFormat::Xtc,
+            "trr" => MemoryFormat::Trr,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "Unknown trajectory format of {:?}",
+                    path
+                )))
+            }
+        };
+        Ok(MemoryTrajectory::from_bytes(format, std::fs::read(path)?))
+    }
+
+    /// Format of the encoded frames
+    pub fn format(&self) -> MemoryFormat {
+        self.format
+    }
+
+    /// Set the precision of coordinates written to XTC trajectories, see
+    /// `XTCTrajectory::set_precision`. Only affects frames written afterwards.
+    pub fn set_precision(&mut self, precision: f32) {
+        self.writer = None;
+        self.precision = precision;
+    }
+
+    /// Number of encoded bytes
+    pub fn len_bytes(&self) -> usize {
+        lock(&self.data).len()
+    }
+
+    /// Copy of the encoded frames
+    pub fn to_bytes(&self) -> Vec<u8> {
+        lock(&self.data).clone()
+    }
+
+    /// Take the encoded frames
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        // the handles share the data
+        self.reader = None;
+        self.writer = None;
+        std::mem::take(&mut *lock(&self.data))
+    }
+
+    /// Write the encoded frames to a file at `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, &*lock(&self.data))?;
+        Ok(())
+    }
+
+    /// Continue reading at the first frame
+    pub fn rewind(&mut self) {
+        self.reader = None;
+    }
+
+    fn cursor(&self, position: usize) -> SharedCursor {
+        SharedCursor {
+            data: Arc::clone(&self.data),
+            position,
+        }
+    }
+}
+
+impl Trajectory for MemoryTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if self.reader.is_none() {
+            // an empty reader would remember that it has no atoms
+            if lock(&self.data).is_empty() {
+                return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+            }
+            let cursor = self.cursor(0);
+            self.reader = Some(match self.format {
+                MemoryFormat::Xtc => Box::new(XTCTrajectory::from_reader(cursor)?),
+                MemoryFormat::Trr => Box::new(TRRTrajectory::from_reader(cursor)?),
+            });
+        }
+        self.reader
+            .as_mut()
+            .expect("reader was created")
+            .read(frame)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let first_frame = self.num_atoms.is_none() && self.len_bytes() == 0;
+        if self.writer.is_none() {
+            let cursor = self.cursor(self.len_bytes());
+            self.writer = Some(match self.format {
+                MemoryFormat::Xtc => {
+                    let mut xtc = XTCTrajectory::from_writer(cursor)?;
+                    xtc.set_precision(self.precision);
+                    Box::new(xtc)
+                }
+                MemoryFormat::Trr => Box::new(TRRTrajectory::from_writer(cursor)?),
+            });
+        }
+        self.writer
+            .as_mut()
+            .expect("writer was created")
+            .write(frame)?;
+        if first_frame {
+            self.num_atoms = Some(frame.len());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        if let Some(num_atoms) = self.num_atoms {
+            return Ok(num_atoms);
+        }
+        let num_atoms = read_num_atoms(&mut Cursor::new(&lock(&self.data)[..]))?;
+        let num_atoms = check_num_atoms(num_atoms, DEFAULT_MAX_ATOMS)?;
+        self.num_atoms = Some(num_atoms);
+        Ok(num_atoms)
+    }
+}
+
+impl IntoIterator for MemoryTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<MemoryTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_memory_trajectory() -> Result<()> {
+        let frames: Vec<Frame> = TRRTrajectory::open_read("tests/1l2y.trr")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Trr);
+        let mut frame = Frame::with_len(304);
+        assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+
+        // reading and writing can be interleaved
+        trajectory.write(&frames[0])?;
+        assert_eq!(trajectory.get_num_atoms()?, 304);
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.coords, frames[0].coords);
+        assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        for frame in &frames[1..] {
+            trajectory.write(frame)?;
+        }
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, frames[1].step);
+
+        // the bytes are the same as those of a file
+        let dir = TempDir::new()?;
+        let path = dir.path().join("copy.trr");
+        trajectory.save(&path)?;
+        assert_eq!(std::fs::read(&path)?, std::fs::read("tests/1l2y.trr")?);
+
+        let loaded = MemoryTrajectory::load(&path)?;
+        assert_eq!(loaded.format(), MemoryFormat::Trr);
+        assert_eq!(loaded.into_iter().count(), 38);
+        assert_eq!(
+            trajectory.into_bytes().len(),
+            std::fs::metadata(&path)?.len() as usize
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_xtc() -> Result<()> {
+        let mut trajectory = MemoryTrajectory::new(MemoryFormat::Xtc);
+        trajectory.set_precision(10.0);
+        let frame = Frame {
+            coords: vec![[0.123, 0.456, 0.789]; 20],
+            ..Frame::default()
+        };
+        trajectory.write(&frame)?;
+        let read: Vec<_> = trajectory.into_iter().collect::<Result<_>>()?;
+        assert_eq!(read.len(), 1);
+        assert_approx_eq!(read[0].coords[0][0], 0.1, 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_atoms_is_cached() -> Result<()> {
+        let mut loaded = MemoryTrajectory::load("tests/1l2y.xtc")?;
+        assert_eq!(loaded.get_num_atoms()?, 304);
+        let mut written = MemoryTrajectory::new(MemoryFormat::Trr);
+        assert!(written.get_num_atoms().is_err());
+        written.write(&Frame::with_len(20))?;
+        written.write(&Frame::with_len(20))?;
+
+        // the headers are not parsed again
+        for trajectory in [&mut loaded, &mut written] {
+            lock(&trajectory.data)[..8].fill(0);
+        }
+        assert_eq!(loaded.get_num_atoms()?, 304);
+        assert_eq!(written.get_num_atoms()?, 20);
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Reading and writing GROMACS index (.ndx) files
+//!
+//! Index files store named groups of atoms, e.g. as created by `gmx
+//! make_ndx`. The atom numbers in the file start at 1; the groups returned
+//! here hold zero-based indices that can be passed to `Frame::subset`
+//! directly.
+//!
+//! ```no_run
+//! use xdrfile::*;
+//!
+//! # fn main() -> Result<()> {
+//! let groups = ndx::read("index.ndx")?;
+//! let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+//! let mut frame = Frame::with_len(trj.get_num_atoms()?);
+//! trj.read(&mut frame)?;
+//! let backbone = frame.subset(&groups["Backbone"])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Named groups of zero-based atom indices
+pub type IndexGroups = HashMap<String, Vec<usize>>;
+
+/// Number of atom numbers per line when writing, as in GROMACS
+const NUMBERS_PER_LINE: usize = 15;
+
+/// Read the index file at `path`
+pub fn read(path: impl AsRef<Path>) -> Result<IndexGroups> {
+    parse(BufReader::new(File::open(path)?))
+}
+
+/// Parse index groups from `reader`. Group names may not repeat.
+pub fn parse<R: BufRead>(reader: R) -> Result<IndexGroups> {
+    let mut groups = IndexGroups::new();
+    let mut current = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if groups.contains_key(name) {
+                return Err(Error::InvalidData(format!(
+                    "duplicate index group '{}' in line {}",
+                    name,
+                    i + 1
+                )));
+            }
+            groups.insert(name.to_string(), Vec::new());
+            current = Some(name.to_string());
+            continue;
+        }
+        let indices = current
+            .as_ref()
+            .and_then(|name| groups.get_mut(name))
+            .ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "atom numbers before the first group in line {}",
+                    i + 1
+                ))
+            })?;
+        for number in line.split_whitespace() {
+            match number.parse::<usize>() {
+                Ok(number) if number > 0 => indices.push(number - 1),
+                _ => {
+                    return Err(Error::InvalidData(format!(
+                        "invalid atom number '{}' in line {}",
+                        number,
+                        i + 1
+                    )))
+                }
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// Write `groups` in index file format, sorted by name so that the output
+/// does not depend on the iteration order of the map
+pub fn write<W: Write>(writer: &mut W, groups: &IndexGroups) -> Result<()> {
+    let mut names: Vec<&String> = groups.keys().collect();
+    names.sort();
+    for name in names {
+        writeln!(writer, "[ {} ]", name)?;
+        for line in groups[name].chunks(NUMBERS_PER_LINE) {
+            let numbers: Vec<String> = line.iter().map(|i| format!("{:>4}", i + 1)).collect();
+            writeln!(writer, "{}", numbers.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let ndx = "[ System ]\n   1    2    3\n   4\n\n[ Backbone]\n2 4\n[Empty]\n";
+        let groups = parse(ndx.as_bytes())?;
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups["System"], vec![0, 1, 2, 3]);
+        assert_eq!(groups["Backbone"], vec![1, 3]);
+        assert!(groups["Empty"].is_empty());
+
+        let frame = Frame {
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3], [3.0; 3]],
+            ..Frame::new()
+        };
+        assert_eq!(
+            frame.subset(&groups["Backbone"])?.coords,
+            vec![[1.0; 3], [3.0; 3]]
+        );
+
+        for invalid in &[
+            "1 2\n",
+            "[ A ]\n1 0\n",
+            "[ A ]\n1 x\n",
+            "[ A ]\n1\n[ A ]\n2\n",
+        ] {
+            let result = parse(invalid.as_bytes());
+            assert!(matches!(result, Err(Error::InvalidData(_))), "{}", invalid);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> Result<()> {
+        let mut groups = IndexGroups::new();
+        groups.insert("b".to_string(), (0..20).collect());
+        groups.insert("a".to_string(), vec![4]);
+        let mut out = Vec::new();
+        write(&mut out, &groups)?;
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("[ a ]\n   5\n[ b ]\n   1    2"));
+        assert_eq!(text.lines().count(), 5);
+        assert_eq!(parse(text.as_bytes())?, groups);
+        Ok(())
+    }
+}
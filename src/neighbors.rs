@@ -0,0 +1,315 @@
+//! Finding the atoms within a cutoff of each other with a cell list
+
+use crate::frame::sub;
+use crate::*;
+
+/// The atoms of a frame sorted into cells at least as wide as a cutoff, to
+/// find all atoms within the cutoff of a position by only looking at the
+/// neighboring cells. Building the cells takes linear time in the number of
+/// atoms, and each query takes time proportional to the number of atoms
+/// nearby, so this scales to large systems.
+///
+/// If the frame has a box, distances follow the minimum image convention
+/// of the (possibly triclinic) box, and the cutoff may be at most half of
+/// its smallest width. Without a box, periodic boundaries are ignored.
+///
+/// ```
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let frame = Frame {
+///     box_vector: BoxVector::rectangular(3.0, 3.0, 3.0),
+///     coords: vec![[0.1, 0.1, 0.1], [2.9, 0.1, 0.1], [1.5, 1.5, 1.5]],
+///     ..Default::default()
+/// };
+/// let search = NeighborSearch::new(&frame, None, 1.0)?;
+/// // the second atom is close to the first one across the boundary
+/// let neighbors = search.neighbors_of(0, 0.5)?;
+/// assert_eq!(neighbors.len(), 1);
+/// assert_eq!(neighbors[0].0, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct NeighborSearch<'a> {
+    frame: &'a Frame,
+    cutoff: f32,
+    periodic: bool,
+    /// Lower corner and size of the cells without periodic boundaries
+    origin: [f32; 3],
+    extent: [f64; 3],
+    shape: [usize; 3],
+    cells: Vec<Vec<usize>>,
+}
+
+impl<'a> NeighborSearch<'a> {
+    /// Sort the atoms of `frame` in `atoms` (or all atoms if `None`) into
+    /// cells for queries with a cutoff of up to `cutoff` nm
+    pub fn new(
+        frame: &'a Frame,
+        atoms: Option<&[usize]>,
+        cutoff: f32,
+    ) -> Result<NeighborSearch<'a>> {
+        if cutoff.is_nan() || cutoff <= 0.0 {
+            return Err(Error::Unsupported(
+                "neighbor searches without a positive cutoff",
+            ));
+        }
+        let all: Vec<usize>;
+        let atoms = match atoms {
+            Some(atoms) => {
+                frame.check_indices(atoms)?;
+                atoms
+            }
+            None => {
+                all = (0..frame.len()).collect();
+                &all
+            }
+        };
+
+        let periodic = frame.box_vector.volume() > 0.0;
+        let mut origin = [0.0; 3];
+        let widths = if periodic {
+            let widths = frame.box_vector.face_distances();
+            let max = widths.iter().copied().fold(f64::INFINITY, f64::min) / 2.0;
+            if f64::from(cutoff) > max {
+                return Err(Error::CutoffTooLarge {
+                    cutoff,
+                    max: max as f32,
+                });
+            }
+            widths
+        } else {
+            let mut upper = [f32::NEG_INFINITY; 3];
+            origin = [f32::INFINITY; 3];
+            for &i in atoms {
+                for k in 0..3 {
+                    origin[k] = origin[k].min(frame.coords[i][k]);
+                    upper[k] = upper[k].max(frame.coords[i][k]);
+                }
+            }
+            if atoms.is_empty() {
+                origin = [0.0; 3];
+                upper = [0.0; 3];
+            }
+            [0, 1, 2].map(|k| f64::from(upper[k] - origin[k]))
+        };
+
+        let shape = cell_shape(widths, cutoff, atoms.len());
+        let mut search = NeighborSearch {
+            frame,
+            cutoff,
+            periodic,
+            origin,
+            extent: [0, 1, 2].map(|k| widths[k].max(shape[k] as f64 * f64::from(cutoff))),
+            shape,
+            cells: Vec::new(),
+        };
+        search.cells = vec![Vec::new(); search.shape.iter().product()];
+        for &i in atoms {
+            let cell = search.cell(frame.coords[i]);
+            let index = search.index(cell);
+            search.cells[index].push(i);
+        }
+        Ok(search)
+    }
+
+    /// The largest cutoff of queries
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// The atoms within `cutoff` of `position` and their distances from it,
+    /// in no particular order
+    pub fn neighbors_within(&self, position: [f32; 3], cutoff: f32) -> Result<Vec<(usize, f32)>> {
+        self.check_cutoff(cutoff)?;
+        let mut neighbors = Vec::new();
+        self.for_each_within(position, cutoff, |j, r| neighbors.push((j, r)));
+        Ok(neighbors)
+    }
+
+    /// The atoms within `cutoff` of atom `i` and their distances from it,
+    /// without `i` itself. `i` does not need to be one of the atoms the
+    /// search was built for.
+    pub fn neighbors_of(&self, i: usize, cutoff: f32) -> Result<Vec<(usize, f32)>> {
+        self.frame.check_indices(&[i])?;
+        let mut neighbors = self.neighbors_within(self.frame.coords[i], cutoff)?;
+        neighbors.retain(|&(j, _)| j != i);
+        Ok(neighbors)
+    }
+
+    /// All pairs `(i, j, distance)` of the atoms of the search within
+    /// `cutoff` of each other, each pair once with `i < j`
+    pub fn pairs_within(&self, cutoff: f32) -> Result<Vec<(usize, usize, f32)>> {
+        self.check_cutoff(cutoff)?;
+        let mut pairs = Vec::new();
+        for &i in self.cells.iter().flatten() {
+            self.for_each_within(self.frame.coords[i], cutoff, |j, r| {
+                if i < j {
+                    pairs.push((i, j, r));
+                }
+            });
+        }
+        Ok(pairs)
+    }
+
+    /// Call `f` with every atom within `cutoff` of `position` and its
+    /// distance, which must not be longer than the cutoff of the search
+    pub(crate) fn for_each_within(
+        &self,
+        position: [f32; 3],
+        cutoff: f32,
+        mut f: impl FnMut(usize, f32),
+    ) {
+        let cell = self.cell(position);
+        let neighbors = |dim: usize| {
+            let n = self.shape[dim];
+            let c = cell[dim];
+            let mut indices = if self.periodic {
+                vec![(c + n - 1) % n, c, (c + 1) % n]
+            } else {
+                (c.saturating_sub(1)..=(c + 1).min(n - 1)).collect()
+            };
+            indices.sort_unstable();
+            indices.dedup();
+            indices
+        };
+        let (xs, ys, zs) = (neighbors(0), neighbors(1), neighbors(2));
+        for &z in &zs {
+            for &y in &ys {
+                for &x in &xs {
+                    for &j in &self.cells[self.index([x, y, z])] {
+                        let mut d = sub(self.frame.coords[j], position);
+                        if self.periodic {
+                            d = self.frame.box_vector.minimum_image(d);
+                        }
+                        let r = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                        if r <= cutoff {
+                            f(j, r);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_cutoff(&self, cutoff: f32) -> Result<()> {
+        if cutoff > self.cutoff {
+            return Err(Error::CutoffTooLarge {
+                cutoff,
+                max: self.cutoff,
+            });
+        }
+        Ok(())
+    }
+
+    /// Index of the cell of `position` along each axis
+    fn cell(&self, position: [f32; 3]) -> [usize; 3] {
+        let fractional = if self.periodic {
+            self.frame
+                .box_vector
+                .fractional(position)
+                .map(|s| s.rem_euclid(1.0))
+        } else {
+            // positions outside of the cells belong to the nearest cell
+            [0, 1, 2].map(|k| (f64::from(position[k] - self.origin[k]) / self.extent[k]).max(0.0))
+        };
+        let mut cell = [0; 3];
+        for ((c, s), n) in cell.iter_mut().zip(&fractional).zip(&self.shape) {
+            *c = ((s * *n as f64) as usize).min(n - 1);
+        }
+        cell
+    }
+
+    fn index(&self, cell: [usize; 3]) -> usize {
+        cell[0] + self.shape[0] * (cell[1] + self.shape[1] * cell[2])
+    }
+}
+
+/// Number of cells along each axis for a box with the given widths: as many
+/// as fit with at least `cutoff` width, but not many more than there are
+/// atoms, as wider cells work just as well
+fn cell_shape(widths: [f64; 3], cutoff: f32, num_atoms: usize) -> [usize; 3] {
+    let mut shape = widths.map(|width| (width / f64::from(cutoff)).floor().max(1.0));
+    let limit = 2.0 * num_atoms.max(1) as f64;
+    let cells: f64 = shape.iter().product();
+    if cells > limit {
+        let factor = (cells / limit).cbrt();
+        shape = shape.map(|n| (n / factor).floor().max(1.0));
+    }
+    shape.map(|n| n as usize)
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+
+    fn brute_force(frame: &Frame, i: usize, cutoff: f32, pbc: bool) -> Vec<usize> {
+        (0..frame.len())
+            .filter(|&j| {
+                let r = if pbc {
+                    frame.distance_pbc(i, j).unwrap()
+                } else {
+                    frame.distance(i, j).unwrap()
+                };
+                j != i && r <= cutoff
+            })
+            .collect()
+    }
+
+    fn sorted(neighbors: Vec<(usize, f32)>) -> Vec<usize> {
+        let mut atoms: Vec<usize> = neighbors.into_iter().map(|(j, _)| j).collect();
+        atoms.sort_unstable();
+        atoms
+    }
+
+    #[test]
+    fn test_neighbor_search() -> Result<()> {
+        // without a box
+        let mut frame = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut frame)?;
+        frame.box_vector = BoxVector::default();
+        let search = NeighborSearch::new(&frame, None, 0.4)?;
+        for i in (0..304).step_by(7) {
+            assert_eq!(
+                sorted(search.neighbors_of(i, 0.4)?),
+                brute_force(&frame, i, 0.4, false)
+            );
+        }
+        let pairs = search.pairs_within(0.25)?;
+        let expected: usize = (0..304)
+            .map(|i| brute_force(&frame, i, 0.25, false).len())
+            .sum();
+        assert_eq!(pairs.len() * 2, expected);
+        assert!(pairs.iter().all(|&(i, j, r)| i < j && r <= 0.25));
+        assert!(matches!(
+            search.neighbors_of(0, 0.5),
+            Err(Error::CutoffTooLarge { .. })
+        ));
+        assert!(search.neighbors_within([100.0; 3], 0.4)?.is_empty());
+
+        // in a triclinic box, with atoms wrapped into it
+        frame.box_vector = BoxVector::from_lengths_angles([2.0, 2.2, 2.4], [70.0, 80.0, 75.0]);
+        for xyz in frame.coords.iter_mut() {
+            *xyz = xyz.map(|x| x.rem_euclid(1.8));
+        }
+        let search = NeighborSearch::new(&frame, None, 0.6)?;
+        for i in (0..304).step_by(5) {
+            assert_eq!(
+                sorted(search.neighbors_of(i, 0.6)?),
+                brute_force(&frame, i, 0.6, true)
+            );
+        }
+
+        // only some atoms are searched
+        let atoms = [1, 2, 3];
+        let search = NeighborSearch::new(&frame, Some(&atoms), 0.6)?;
+        assert!(search
+            .neighbors_of(0, 0.6)?
+            .iter()
+            .all(|(j, _)| atoms.contains(j)));
+        assert!(NeighborSearch::new(&frame, Some(&[304]), 0.6).is_err());
+        assert!(NeighborSearch::new(&frame, None, 1.0).is_err());
+        assert!(NeighborSearch::new(&frame, None, 0.0).is_err());
+        Ok(())
+    }
+}
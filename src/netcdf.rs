@@ -0,0 +1,635 @@
+//! Reading Amber NetCDF trajectories (.nc)
+//!
+//! Amber stores trajectories in the classic NetCDF format (usually with
+//! 64-bit offsets), which is parsed directly without linking libnetcdf.
+
+use crate::index::{read_f32, read_f64, read_i32};
+use crate::*;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+
+const NC_DIMENSION: i32 = 0x0A;
+const NC_VARIABLE: i32 = 0x0B;
+const NC_ATTRIBUTE: i32 = 0x0C;
+/// Number of records of a file that is still being written
+const STREAMING: i32 = -1;
+
+/// Amber stores lengths in Ångström, frames use nanometers
+const NM_PER_ANGSTROM: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NcType {
+    Byte,
+    Char,
+    Short,
+    Int,
+    Float,
+    Double,
+}
+
+impl NcType {
+    fn from_code(code: i32) -> io::Result<NcType> {
+        match code {
+            1 => Ok(NcType::Byte),
+            2 => Ok(NcType::Char),
+            3 => Ok(NcType::Short),
+            4 => Ok(NcType::Int),
+            5 => Ok(NcType::Float),
+            6 => Ok(NcType::Double),
+            _ => Err(invalid_data(format!("unknown NetCDF type {}", code))),
+        }
+    }
+
+    fn size(self) -> u64 {
+        match self {
+            NcType::Byte | NcType::Char => 1,
+            NcType::Short => 2,
+            NcType::Int | NcType::Float => 4,
+            NcType::Double => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Dimension {
+    name: String,
+    /// Zero for the record (unlimited) dimension
+    len: u64,
+}
+
+#[derive(Debug)]
+enum AttributeValue {
+    Text(String),
+    Numbers(Vec<f64>),
+}
+
+#[derive(Debug)]
+struct Attribute {
+    name: String,
+    value: AttributeValue,
+}
+
+#[derive(Debug)]
+struct Variable {
+    name: String,
+    dims: Vec<usize>,
+    attributes: Vec<Attribute>,
+    nc_type: NcType,
+    vsize: u64,
+    begin: u64,
+}
+
+/// Header of a classic NetCDF file
+#[derive(Debug)]
+struct Header {
+    num_records: Option<u64>,
+    dims: Vec<Dimension>,
+    attributes: Vec<Attribute>,
+    variables: Vec<Variable>,
+}
+
+impl Header {
+    fn read(reader: &mut impl Read) -> io::Result<Header> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let large_offsets = match magic {
+            [b'C', b'D', b'F', 1] => false,
+            [b'C', b'D', b'F', 2] => true,
+            [b'C', b'D', b'F', _] => {
+                return Err(invalid_data("only classic NetCDF files are supported"))
+            }
+            _ => return Err(invalid_data("not a NetCDF file")),
+        };
+        let num_records = match read_i32(reader)? {
+            STREAMING => None,
+            n => Some(to_u64(n)?),
+        };
+
+        let mut dims = Vec::new();
+        for _ in 0..read_list_len(reader, NC_DIMENSION)? {
+            let name = read_name(reader)?;
+            let len = to_u64(read_i32(reader)?)?;
+            dims.push(Dimension { name, len });
+        }
+        let attributes = read_attributes(reader)?;
+
+        let mut variables = Vec::new();
+        for _ in 0..read_list_len(reader, NC_VARIABLE)? {
+            let name = read_name(reader)?;
+            let mut var_dims = Vec::new();
+            for _ in 0..to_u64(read_i32(reader)?)? {
+                let dim = to_u64(read_i32(reader)?)? as usize;
+                if dim >= dims.len() {
+                    return Err(invalid_data("variable refers to unknown dimension"));
+                }
+                var_dims.push(dim);
+            }
+            let attributes = read_attributes(reader)?;
+            let nc_type = NcType::from_code(read_i32(reader)?)?;
+            let vsize = u64::from(read_i32(reader)? as u32);
+            let begin = if large_offsets {
+                let mut bytes = [0; 8];
+                reader.read_exact(&mut bytes)?;
+                u64::from_be_bytes(bytes)
+            } else {
+                u64::from(read_i32(reader)? as u32)
+            };
+            variables.push(Variable {
+                name,
+                dims: var_dims,
+                attributes,
+                nc_type,
+                vsize,
+                begin,
+            });
+        }
+
+        Ok(Header {
+            num_records,
+            dims,
+            attributes,
+            variables,
+        })
+    }
+
+    fn dim_len(&self, name: &str) -> Option<u64> {
+        self.dims.iter().find(|d| d.name == name).map(|d| d.len)
+    }
+
+    fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+
+    fn is_record_variable(&self, variable: &Variable) -> bool {
+        variable
+            .dims
+            .first()
+            .is_some_and(|&dim| self.dims[dim].len == 0)
+    }
+
+    /// Byte offset of the first record
+    fn records_begin(&self) -> u64 {
+        self.variables
+            .iter()
+            .filter(|v| self.is_record_variable(v))
+            .map(|v| v.begin)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Number of bytes between two records of the same variable
+    fn record_size(&self) -> u64 {
+        let record_variables: Vec<&Variable> = self
+            .variables
+            .iter()
+            .filter(|v| self.is_record_variable(v))
+            .collect();
+        match record_variables.as_slice() {
+            // a single record variable is not padded
+            [variable] => {
+                let len: u64 = variable.dims[1..]
+                    .iter()
+                    .map(|&dim| self.dims[dim].len)
+                    .product();
+                len * variable.nc_type.size()
+            }
+            variables => variables.iter().map(|v| v.vsize).sum(),
+        }
+    }
+}
+
+fn read_list_len(reader: &mut impl Read, tag: i32) -> io::Result<u64> {
+    let found = read_i32(reader)?;
+    let len = to_u64(read_i32(reader)?)?;
+    match found {
+        0 if len == 0 => Ok(0),
+        found if found == tag => Ok(len),
+        _ => Err(invalid_data("malformed NetCDF header")),
+    }
+}
+
+fn read_attributes(reader: &mut impl Read) -> io::Result<Vec<Attribute>> {
+    let mut attributes = Vec::new();
+    for _ in 0..read_list_len(reader, NC_ATTRIBUTE)? {
+        let name = read_name(reader)?;
+        let nc_type = NcType::from_code(read_i32(reader)?)?;
+        let len = to_u64(read_i32(reader)?)?;
+        let value = match nc_type {
+            NcType::Char => {
+                let bytes = read_padded(reader, len)?;
+                AttributeValue::Text(String::from_utf8_lossy(&bytes).into_owned())
+            }
+            nc_type => {
+                let bytes = read_padded(reader, len * nc_type.size())?;
+                let mut numbers = vec![0.0; len as usize];
+                decode(&bytes, nc_type, &mut numbers)?;
+                AttributeValue::Numbers(numbers)
+            }
+        };
+        attributes.push(Attribute { name, value });
+    }
+    Ok(attributes)
+}
+
+fn read_name(reader: &mut impl Read) -> io::Result<String> {
+    let len = to_u64(read_i32(reader)?)?;
+    let bytes = read_padded(reader, len)?;
+    String::from_utf8(bytes).map_err(|_| invalid_data("NetCDF name is not UTF-8"))
+}
+
+/// Read `len` bytes followed by padding to a multiple of four bytes
+fn read_padded(reader: &mut impl Read, len: u64) -> io::Result<Vec<u8>> {
+    let padded = len.div_ceil(4) * 4;
+    let mut bytes = Vec::new();
+    reader.take(padded).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != padded {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    bytes.truncate(len as usize);
+    Ok(bytes)
+}
+
+/// Convert big-endian values of type `nc_type` into `out`
+fn decode<T: FromF64>(bytes: &[u8], nc_type: NcType, out: &mut [T]) -> io::Result<()> {
+    let mut reader = bytes;
+    for value in out.iter_mut() {
+        *value = T::from_f64(match nc_type {
+            NcType::Float => f64::from(read_f32(&mut reader)?),
+            NcType::Double => read_f64(&mut reader)?,
+            NcType::Int => f64::from(read_i32(&mut reader)?),
+            NcType::Short => {
+                let mut short = [0; 2];
+                reader.read_exact(&mut short)?;
+                f64::from(i16::from_be_bytes(short))
+            }
+            NcType::Byte => {
+                let mut byte = [0; 1];
+                reader.read_exact(&mut byte)?;
+                f64::from(byte[0] as i8)
+            }
+            NcType::Char => return Err(invalid_data("expected numbers, found text")),
+        });
+    }
+    Ok(())
+}
+
+trait FromF64 {
+    fn from_f64(value: f64) -> Self;
+}
+
+impl FromF64 for f32 {
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+}
+
+impl FromF64 for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+}
+
+fn to_u64(value: i32) -> io::Result<u64> {
+    u64::try_from(value).map_err(|_| invalid_data("negative size in NetCDF header"))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::InvalidData => Error::InvalidData(err.to_string()),
+        io::ErrorKind::UnexpectedEof => Error::InvalidData("unexpected end of file".to_string()),
+        _ => err.into(),
+    }
+}
+
+/// Location of a per-frame variable in the file
+#[derive(Clone, Copy, Debug)]
+struct Field {
+    begin: u64,
+    nc_type: NcType,
+}
+
+/// Read-only handle to an Amber NetCDF trajectory.
+///
+/// Coordinates, velocities and boxes are converted from Amber's units
+/// (Ångström, Ångström/ps) to nanometers. Amber does not store steps, so
+/// the step of a frame is its index in the file.
+pub struct AmberNetCDFTrajectory {
+    reader: BufReader<File>,
+    num_atoms: usize,
+    num_frames: usize,
+    record_size: u64,
+    next_frame: usize,
+    time: Option<Field>,
+    coordinates: Field,
+    velocities: Option<(Field, f32)>,
+    cell: Option<(Field, Field)>,
+    buffer: Vec<f32>,
+}
+
+impl AmberNetCDFTrajectory {
+    /// Open the trajectory at `path` for reading
+    pub fn open_read(path: impl AsRef<Path>) -> Result<AmberNetCDFTrajectory> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let header = Header::read(&mut reader).map_err(to_error)?;
+
+        let conventions = header.attributes.iter().find(|a| a.name == "Conventions");
+        match conventions.map(|a| &a.value) {
+            Some(AttributeValue::Text(text)) if text.contains("AMBER") => {}
+            _ => {
+                return Err(Error::InvalidData(
+                    "NetCDF file does not follow the AMBER conventions".to_string(),
+                ))
+            }
+        }
+
+        let num_atoms = header
+            .dim_len("atom")
+            .ok_or_else(|| Error::InvalidData("missing atom dimension".to_string()))?;
+        let num_atoms = crate::to(num_atoms, ErrorTask::Read, "atom")?;
+
+        // all frame data are record variables with a fixed trailing shape
+        let field = |name: &str, shape: &[u64]| -> Result<Option<Field>> {
+            let variable = match header.variable(name) {
+                Some(variable) => variable,
+                None => return Ok(None),
+            };
+            let var_shape: Vec<u64> = variable.dims.iter().map(|&d| header.dims[d].len).collect();
+            if !header.is_record_variable(variable) || var_shape[1..] != *shape {
+                return Err(Error::InvalidData(format!(
+                    "unexpected shape of variable {}",
+                    name
+                )));
+            }
+            Ok(Some(Field {
+                begin: variable.begin,
+                nc_type: variable.nc_type,
+            }))
+        };
+        let atoms = num_atoms as u64;
+        let coordinates = field("coordinates", &[atoms, 3])?
+            .ok_or_else(|| Error::InvalidData("missing coordinates".to_string()))?;
+        let velocities = match field("velocities", &[atoms, 3])? {
+            Some(velocities) => {
+                let scale = header
+                    .variable("velocities")
+                    .and_then(|v| v.attributes.iter().find(|a| a.name == "scale_factor"))
+                    .and_then(|a| match &a.value {
+                        AttributeValue::Numbers(numbers) => numbers.first().copied(),
+                        AttributeValue::Text(_) => None,
+                    })
+                    .unwrap_or(1.0);
+                Some((velocities, scale as f32))
+            }
+            None => None,
+        };
+        let cell = match (field("cell_lengths", &[3])?, field("cell_angles", &[3])?) {
+            (Some(lengths), Some(angles)) => Some((lengths, angles)),
+            _ => None,
+        };
+
+        let record_size = header.record_size();
+        let num_records = match header.num_records {
+            Some(n) => n,
+            None if record_size == 0 => 0,
+            None => file_len.saturating_sub(header.records_begin()) / record_size,
+        };
+
+        Ok(AmberNetCDFTrajectory {
+            reader,
+            num_atoms,
+            num_frames: crate::to(num_records, ErrorTask::Read, "frame")?,
+            record_size,
+            next_frame: 0,
+            time: field("time", &[])?,
+            coordinates,
+            velocities,
+            cell,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Number of frames in the file
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Read `len` values of `field` of the next frame into the buffer
+    fn read_field(&mut self, field: Field, len: usize) -> Result<&[f32]> {
+        let offset = field.begin + self.next_frame as u64 * self.record_size;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len * field.nc_type.size() as usize];
+        self.reader.read_exact(&mut bytes).map_err(to_error)?;
+        self.buffer.resize(len, 0.0);
+        decode(&bytes, field.nc_type, &mut self.buffer).map_err(to_error)?;
+        Ok(&self.buffer)
+    }
+}
+
+fn copy_xyz(values: &[f32], scale: f32, out: &mut [[f32; 3]]) {
+    for (xyz, values) in out.iter_mut().zip(values.chunks_exact(3)) {
+        for (x, value) in xyz.iter_mut().zip(values) {
+            *x = value * scale;
+        }
+    }
+}
+
+impl Trajectory for AmberNetCDFTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if frame.len() != self.num_atoms {
+            return Err((&*frame, self.num_atoms).into());
+        }
+        if self.next_frame >= self.num_frames {
+            return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into());
+        }
+
+        let num_values = 3 * self.num_atoms;
+        let coords = self.read_field(self.coordinates, num_values)?;
+        copy_xyz(coords, NM_PER_ANGSTROM, &mut frame.coords);
+
+        if let Some((field, scale)) = self.velocities {
+            let velocities = self.read_field(field, num_values)?;
+            let out = frame.velocities.get_or_insert_with(Vec::new);
+            out.resize(velocities.len() / 3, [0.0; 3]);
+            copy_xyz(velocities, scale * NM_PER_ANGSTROM, out);
+        } else {
+            frame.velocities = None;
+        }
+
+        frame.box_vector = match self.cell {
+            Some((lengths, angles)) => {
+                let lengths = self.read_field(lengths, 3)?;
+                let lengths = [lengths[0], lengths[1], lengths[2]].map(|l| l * NM_PER_ANGSTROM);
+                let angles = self.read_field(angles, 3)?;
+                BoxVector::from_lengths_angles(lengths, [angles[0], angles[1], angles[2]])
+            }
+            None => BoxVector::default(),
+        };
+        frame.time = match self.time {
+            Some(time) => self.read_field(time, 1)?[0],
+            None => 0.0,
+        };
+        frame.step = self.next_frame;
+        frame.forces = None;
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing Amber NetCDF trajectories"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        Ok(self.num_atoms)
+    }
+}
+
+impl IntoIterator for AmberNetCDFTrajectory {
+    type Item = Result<std::rc::Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<AmberNetCDFTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        crate::iterator::into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn put_i32(out: &mut Vec<u8>, value: i32) {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_name(out: &mut Vec<u8>, name: &str) {
+        put_i32(out, name.len() as i32);
+        out.extend_from_slice(name.as_bytes());
+        out.resize(out.len().div_ceil(4) * 4, 0);
+    }
+
+    /// Header of an Amber trajectory with velocities and a box, with the
+    /// variables starting at `begins`
+    fn header(num_frames: i32, num_atoms: i32, begins: &[u64; 5]) -> Vec<u8> {
+        let mut out = b"CDF\x02".to_vec();
+        put_i32(&mut out, num_frames);
+
+        let dims = [
+            ("frame", 0),
+            ("spatial", 3),
+            ("atom", num_atoms),
+            ("cell_spatial", 3),
+            ("cell_angular", 3),
+        ];
+        put_i32(&mut out, NC_DIMENSION);
+        put_i32(&mut out, dims.len() as i32);
+        for (name, len) in &dims {
+            put_name(&mut out, name);
+            put_i32(&mut out, *len);
+        }
+
+        put_i32(&mut out, NC_ATTRIBUTE);
+        put_i32(&mut out, 1);
+        put_name(&mut out, "Conventions");
+        put_i32(&mut out, 2);
+        put_name(&mut out, "AMBER");
+
+        let variables: [(&str, &[i32], i32, i32); 5] = [
+            ("time", &[0], 5, 4),
+            ("coordinates", &[0, 2, 1], 5, 12 * num_atoms),
+            ("velocities", &[0, 2, 1], 5, 12 * num_atoms),
+            ("cell_lengths", &[0, 3], 6, 24),
+            ("cell_angles", &[0, 4], 6, 24),
+        ];
+        put_i32(&mut out, NC_VARIABLE);
+        put_i32(&mut out, variables.len() as i32);
+        for ((name, dims, nc_type, vsize), begin) in variables.iter().zip(begins) {
+            put_name(&mut out, name);
+            put_i32(&mut out, dims.len() as i32);
+            for dim in dims.iter() {
+                put_i32(&mut out, *dim);
+            }
+            if *name == "velocities" {
+                put_i32(&mut out, NC_ATTRIBUTE);
+                put_i32(&mut out, 1);
+                put_name(&mut out, "scale_factor");
+                put_i32(&mut out, 5);
+                put_i32(&mut out, 1);
+                out.extend_from_slice(&20.455f32.to_be_bytes());
+            } else {
+                put_i32(&mut out, 0);
+                put_i32(&mut out, 0);
+            }
+            put_i32(&mut out, *nc_type);
+            put_i32(&mut out, *vsize);
+            out.extend_from_slice(&begin.to_be_bytes());
+        }
+        out
+    }
+
+    fn write_test_file(num_frames: usize, num_atoms: usize) -> Vec<u8> {
+        let len = header(0, 0, &[0; 5]).len() as u64;
+        let n = num_atoms as u64;
+        let begins = [
+            len,
+            len + 4,
+            len + 4 + 12 * n,
+            len + 4 + 24 * n,
+            len + 28 + 24 * n,
+        ];
+        let mut out = header(num_frames as i32, num_atoms as i32, &begins);
+        for frame in 0..num_frames {
+            out.extend_from_slice(&(frame as f32 * 2.0).to_be_bytes());
+            for value in 0..3 * num_atoms {
+                out.extend_from_slice(&((frame * 100 + value) as f32).to_be_bytes());
+            }
+            for _ in 0..3 * num_atoms {
+                out.extend_from_slice(&1.0f32.to_be_bytes());
+            }
+            for value in &[30.0f64, 40.0, 50.0, 90.0, 90.0, 90.0] {
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_amber_netcdf() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = NamedTempFile::new()?;
+        std::fs::write(tempfile.path(), write_test_file(3, 2))?;
+
+        let mut trj = AmberNetCDFTrajectory::open_read(tempfile.path())?;
+        assert_eq!(trj.get_num_atoms()?, 2);
+        assert_eq!(trj.num_frames(), 3);
+        let frames: Vec<Frame> = trj
+            .into_iter()
+            .map(|f| f.map(|f| (*f).clone()))
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 3);
+
+        let frame = &frames[1];
+        assert_eq!(frame.step, 1);
+        assert_eq!(frame.time, 2.0);
+        for (x, expected) in frame.coords[1].iter().zip(&[10.3, 10.4, 10.5]) {
+            assert_approx_eq!(x, expected, 1e-5);
+        }
+        assert_eq!(frame.box_vector, BoxVector::rectangular(3.0, 4.0, 5.0));
+        let velocities = frame.velocities.as_ref().unwrap();
+        assert_approx_eq!(velocities[0][0], 2.0455, 1e-5);
+
+        std::fs::write(tempfile.path(), b"CDF\x05")?;
+        let result = AmberNetCDFTrajectory::open_read(tempfile.path());
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Parallel decoding of trajectories with rayon
+
+use crate::*;
+use rayon::prelude::*;
+use std::borrow::BorrowMut;
+use std::io::Seek;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// Number of parts per rayon thread, so that threads which finish early can
+/// pick up more work
+const PARTS_PER_THREAD: usize = 4;
+
+/// Read the frames `start..end` with `trajectory`, a handle of their own,
+/// into frames from `new_frame`, stopping after the first error
+fn read_part<T, H, N, G>(
+    trajectory: Result<H>,
+    new_frame: N,
+    index: &FrameIndex,
+    start: usize,
+    end: usize,
+) -> impl Iterator<Item = Result<G>>
+where
+    T: Trajectory + Seek,
+    H: BorrowMut<T>,
+    N: Fn() -> G,
+    G: BorrowMut<Frame>,
+{
+    let mut trajectory = trajectory.and_then(|mut trajectory| {
+        let offset = index.offset(start).expect("part starts within index");
+        trajectory.borrow_mut().seek(SeekFrom::Start(offset))?;
+        Ok(trajectory)
+    });
+    let mut failed = false;
+    (start..end).map_while(move |_| {
+        if failed {
+            return None;
+        }
+        let result = trajectory
+            .as_mut()
+            .map_err(|e| e.clone())
+            .and_then(|trajectory| {
+                let mut frame = new_frame();
+                trajectory.borrow_mut().read(frame.borrow_mut())?;
+                Ok(frame)
+            });
+        failed = result.is_err();
+        Some(result)
+    })
+}
+
+fn par_frames_inner<T, F>(
+    path: &Path,
+    index: &FrameIndex,
+    open: F,
+    max_atoms: usize,
+) -> Result<impl ParallelIterator<Item = Result<Frame>>>
+where
+    T: Trajectory + Seek,
+    F: Fn(&Path) -> Result<T> + Send + Sync,
+{
+    let index = Arc::new(index.clone());
+    let path = path.to_owned();
+    let num_atoms = check_num_atoms(index.num_atoms(), max_atoms)?;
+    let num_frames = index.len();
+    let part_size = num_frames
+        .div_ceil(rayon::current_num_threads() * PARTS_PER_THREAD)
+        .max(1);
+
+    let parts = num_frames.div_ceil(part_size);
+    Ok((0..parts).into_par_iter().flat_map_iter(move |part| {
+        // every part reads a contiguous range of frames with its own handle
+        let start = part * part_size;
+        let end = num_frames.min(start + part_size);
+        let new_frame = move || Frame::with_len(num_atoms);
+        read_part::<T, _, _, _>(open(&path), new_frame, &index, start, end)
+    }))
+}
+
+/// Frames that are decoded ahead of time on the rayon thread pool and
+/// delivered in order, see `XTCTrajectory::decoded_stream`.
+///
+/// Iteration stops after the first error. Dropping the stream stops the
+/// decoding once the frames in flight are done. With
+/// `XTCTrajectory::decoded_stream_pooled`, the frames come from a
+/// `FramePool` instead of being allocated one by one.
+pub struct DecodedStream<F = Frame> {
+    receiver: Receiver<Result<F>>,
+    remaining: usize,
+}
+
+impl<F> Iterator for DecodedStream<F> {
+    type Item = Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.receiver.recv().ok()?;
+        self.remaining -= 1;
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+fn decoded_stream_inner<T, F, N, G>(
+    path: &Path,
+    index: &FrameIndex,
+    open: F,
+    new_frame: N,
+    window: usize,
+    max_atoms: usize,
+) -> Result<DecodedStream<G>>
+where
+    T: Trajectory + Seek + Send,
+    F: Fn(&Path) -> Result<T> + Send + Sync + 'static,
+    N: Fn(usize) -> G + Send + Sync + 'static,
+    G: BorrowMut<Frame> + Send + 'static,
+{
+    let index = index.clone();
+    let num_atoms = check_num_atoms(index.num_atoms(), max_atoms)?;
+    let new_frame = move || new_frame(num_atoms);
+    let path = path.to_owned();
+    let num_frames = index.len();
+    let window = window.max(1);
+    // one window is buffered while the next one is decoded
+    let (sender, receiver) = mpsc::sync_channel(window);
+    thread::spawn(move || {
+        let part_size = window.div_ceil(rayon::current_num_threads()).max(1);
+        // handles are kept across windows instead of reopening the file for
+        // every part, which matters for small windows
+        let handles = Mutex::new(Vec::new());
+        for first in (0..num_frames).step_by(window) {
+            let last = num_frames.min(first + window);
+            let parts: Vec<Vec<Result<G>>> = (0..(last - first).div_ceil(part_size))
+                .into_par_iter()
+                .map(|part| {
+                    let start = first + part * part_size;
+                    let end = last.min(start + part_size);
+                    let handle = handles.lock().unwrap_or_else(PoisonError::into_inner).pop();
+                    let mut trajectory = handle.map_or_else(|| open(&path), Ok);
+                    let frames = read_part::<T, _, _, _>(
+                        trajectory.as_mut().map_err(|e| e.clone()),
+                        &new_frame,
+                        &index,
+                        start,
+                        end,
+                    )
+                    .collect();
+                    if let Ok(trajectory) = trajectory {
+                        handles
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(trajectory);
+                    }
+                    frames
+                })
+                .collect();
+            for result in parts.into_iter().flatten() {
+                let failed = result.is_err();
+                // the stream was dropped
+                if sender.send(result).is_err() || failed {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(DecodedStream {
+        receiver,
+        remaining: num_frames,
+    })
+}
+
+/// Open the handles of the parts with the limit on the number of atoms of
+/// the trajectory
+fn open_xtc(max_atoms: usize) -> impl Fn(&Path) -> Result<XTCTrajectory> + Send + Sync {
+    move |path| {
+        let mut xtc = XTCTrajectory::open_read(path)?;
+        xtc.set_max_atoms(max_atoms);
+        Ok(xtc)
+    }
+}
+
+fn open_trr(max_atoms: usize) -> impl Fn(&Path) -> Result<TRRTrajectory> + Send + Sync {
+    move |path| {
+        let mut trr = TRRTrajectory::open_read(path)?;
+        trr.set_max_atoms(max_atoms);
+        Ok(trr)
+    }
+}
+
+impl XTCTrajectory {
+    /// Decode all frames of the trajectory in parallel.
+    ///
+    /// The file is indexed with `frame_index` and split into contiguous ranges of frames, each
+    /// of which is read with a separate file handle. Collecting the iterator
+    /// keeps the frames in order.
+    pub fn par_frames(&self) -> Result<impl ParallelIterator<Item = Result<Frame>>> {
+        par_frames_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_xtc(self.max_atoms),
+            self.max_atoms,
+        )
+    }
+
+    /// Decode the frames of the trajectory `window` at a time on the rayon
+    /// thread pool, while the previous ones are being consumed.
+    ///
+    /// The file is indexed with `frame_index` first. Frames arrive in order, and at most two
+    /// windows of frames are held in memory.
+    pub fn decoded_stream(&self, window: usize) -> Result<DecodedStream> {
+        decoded_stream_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_xtc(self.max_atoms),
+            Frame::with_len,
+            window,
+            self.max_atoms,
+        )
+    }
+
+    /// Like `decoded_stream`, but decode into frames taken from `pool`.
+    /// The pool should keep at least two windows of frames for all of them
+    /// to be reused.
+    pub fn decoded_stream_pooled(
+        &self,
+        window: usize,
+        pool: &FramePool,
+    ) -> Result<DecodedStream<PooledFrame>> {
+        let pool = pool.clone();
+        decoded_stream_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_xtc(self.max_atoms),
+            move |_| pool.get(),
+            window,
+            self.max_atoms,
+        )
+    }
+}
+
+impl TRRTrajectory {
+    /// Decode all frames of the trajectory in parallel.
+    ///
+    /// The file is indexed with `frame_index` and split into contiguous ranges of frames, each
+    /// of which is read with a separate file handle. Collecting the iterator
+    /// keeps the frames in order.
+    pub fn par_frames(&self) -> Result<impl ParallelIterator<Item = Result<Frame>>> {
+        par_frames_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_trr(self.max_atoms),
+            self.max_atoms,
+        )
+    }
+
+    /// Decode the frames of the trajectory `window` at a time on the rayon
+    /// thread pool, while the previous ones are being consumed.
+    ///
+    /// The file is indexed with `frame_index` first. Frames arrive in order, and at most two
+    /// windows of frames are held in memory.
+    pub fn decoded_stream(&self, window: usize) -> Result<DecodedStream> {
+        decoded_stream_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_trr(self.max_atoms),
+            Frame::with_len,
+            window,
+            self.max_atoms,
+        )
+    }
+
+    /// Like `decoded_stream`, but decode into frames taken from `pool`.
+    /// The pool should keep at least two windows of frames for all of them
+    /// to be reused.
+    pub fn decoded_stream_pooled(
+        &self,
+        window: usize,
+        pool: &FramePool,
+    ) -> Result<DecodedStream<PooledFrame>> {
+        let pool = pool.clone();
+        decoded_stream_inner(
+            self.handle.file_path()?,
+            self.frame_index()?,
+            open_trr(self.max_atoms),
+            move |_| pool.get(),
+            window,
+            self.max_atoms,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_frames() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let frames: Vec<Frame> = xtc.par_frames()?.collect::<Result<_>>()?;
+        let expected: Vec<Frame> = xtc.into_frames().collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        for (frame, expected) in frames.iter().zip(&expected) {
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let steps: Vec<usize> = trr
+            .par_frames()?
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect::<Result<_>>()?;
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoded_stream() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let expected: Vec<Frame> = xtc.par_frames()?.collect::<Result<_>>()?;
+        let frames: Vec<Frame> = xtc.decoded_stream(5)?.collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        for (frame, expected) in frames.iter().zip(&expected) {
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut stream = trr.decoded_stream(1)?;
+        assert_eq!(stream.size_hint(), (0, Some(38)));
+        assert_eq!(stream.next().unwrap()?.step, 1);
+        assert_eq!(stream.next().unwrap()?.step, 2);
+        drop(stream);
+
+        let pool = FramePool::new(304, 10);
+        let frames: Vec<PooledFrame> = xtc
+            .decoded_stream_pooled(5, &pool)?
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        for (frame, expected) in frames.iter().zip(&expected) {
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+        drop(frames);
+        assert_eq!(pool.num_free(), 10);
+        let mut stream = xtc.decoded_stream_pooled(5, &pool)?;
+        stream.next().unwrap()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Writing frames as PDB files for visualization
+//!
+//! Coordinates and boxes are converted from nanometers to Ångström. Without
+//! a list of `Atom`s, every atom is written as atom "X" of residue "UNK".
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//! use xdrfile::*;
+//!
+//! # fn main() -> Result<()> {
+//! let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+//! let mut out = BufWriter::new(File::create("1l2y.pdb")?);
+//! pdb::write_models(&mut out, trj, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::*;
+use std::borrow::Borrow;
+use std::io::Write;
+
+/// Ångström per nanometer
+const ANGSTROM: f32 = 10.0;
+
+/// Write `frame` as a single structure, optionally naming the atoms after
+/// `atoms`, which must have one entry per atom
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame, atoms: Option<&[Atom]>) -> Result<()> {
+    check_atoms(frame, atoms)?;
+    write_cryst1(writer, &frame.box_vector)?;
+    write_atoms(writer, frame, atoms, None)?;
+    writeln!(writer, "END")?;
+    Ok(())
+}
+
+/// Like `write_frame`, but with one B-factor per atom in the temperature
+/// factor column, e.g. from `analysis::bfactors`, to color structures by
+/// their flexibility
+pub fn write_frame_with_bfactors<W: Write>(
+    writer: &mut W,
+    frame: &Frame,
+    atoms: Option<&[Atom]>,
+    bfactors: &[f32],
+) -> Result<()> {
+    check_atoms(frame, atoms)?;
+    if bfactors.len() != frame.len() {
+        return Err(Error::LengthMismatch {
+            name: "B-factors",
+            expected: frame.len(),
+            found: bfactors.len(),
+        });
+    }
+    write_cryst1(writer, &frame.box_vector)?;
+    write_atoms(writer, frame, atoms, Some(bfactors))?;
+    writeln!(writer, "END")?;
+    Ok(())
+}
+
+/// Write all frames as the models of a multi-model PDB file. Returns the
+/// number of models written; reading stops at the first error.
+pub fn write_models<W, I, F>(writer: &mut W, frames: I, atoms: Option<&[Atom]>) -> Result<usize>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<F>>,
+    F: Borrow<Frame>,
+{
+    let mut num_models = 0;
+    for frame in frames {
+        let frame = frame?;
+        let frame = frame.borrow();
+        check_atoms(frame, atoms)?;
+        if num_models == 0 {
+            write_cryst1(writer, &frame.box_vector)?;
+        }
+        num_models += 1;
+        writeln!(writer, "MODEL     {:>4}", num_models % 10000)?;
+        write_atoms(writer, frame, atoms, None)?;
+        writeln!(writer, "ENDMDL")?;
+    }
+    writeln!(writer, "END")?;
+    Ok(num_models)
+}
+
+fn check_atoms(frame: &Frame, atoms: Option<&[Atom]>) -> Result<()> {
+    match atoms {
+        Some(atoms) if atoms.len() != frame.len() => Err(Error::LengthMismatch {
+            name: "atom names",
+            expected: frame.len(),
+            found: atoms.len(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn write_cryst1<W: Write>(writer: &mut W, box_vector: &BoxVector) -> Result<()> {
+    if box_vector.is_zero() {
+        return Ok(());
+    }
+    let ([a, b, c], [alpha, beta, gamma]) = box_vector.lengths_and_angles();
+    writeln!(
+        writer,
+        "CRYST1{:>9.3}{:>9.3}{:>9.3}{:>7.2}{:>7.2}{:>7.2} P 1           1",
+        a * ANGSTROM,
+        b * ANGSTROM,
+        c * ANGSTROM,
+        alpha,
+        beta,
+        gamma
+    )?;
+    Ok(())
+}
+
+fn write_atoms<W: Write>(
+    writer: &mut W,
+    frame: &Frame,
+    atoms: Option<&[Atom]>,
+    bfactors: Option<&[f32]>,
+) -> Result<()> {
+    let unknown = Atom::new("X", "UNK", 1);
+    for (i, [x, y, z]) in frame.iter_atoms() {
+        let atom = atoms.map_or(&unknown, |atoms| &atoms[i]);
+        // names shorter than four characters start in the second column
+        let name = if atom.name.len() < 4 {
+            format!(" {:<3}", atom.name)
+        } else {
+            atom.name.chars().take(4).collect()
+        };
+        writeln!(
+            writer,
+            "ATOM  {:>5} {}{:>4} A{:>4}    {:>8.3}{:>8.3}{:>8.3}{:>6.2}{:>6.2}          {:>2}",
+            (i + 1) % 100_000,
+            name,
+            atom.residue_name.chars().take(3).collect::<String>(),
+            atom.residue_number % 10000,
+            x * ANGSTROM,
+            y * ANGSTROM,
+            z * ANGSTROM,
+            1.0,
+            bfactors.map_or(0.0, |b| b[i]),
+            atom.element,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_frame() -> Frame {
+        Frame {
+            box_vector: BoxVector::rectangular(2.0, 3.0, 4.0),
+            coords: vec![[0.1, 0.2, 0.3], [1.0, -1.5, 2.25]],
+            ..Frame::new()
+        }
+    }
+
+    #[test]
+    fn test_write_frame() -> Result<()> {
+        let mut atoms = vec![Atom::new("N", "ALA", 1), Atom::new("HD21", "ASN", 2)];
+        atoms[0].element = "N".to_string();
+        let mut out = Vec::new();
+        write_frame(&mut out, &test_frame(), Some(&atoms))?;
+        let pdb = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = pdb.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "CRYST1   20.000   30.000   40.000  90.00  90.00  90.00 P 1           1",
+                "ATOM      1  N   ALA A   1       1.000   2.000   3.000  1.00  0.00           N",
+                "ATOM      2 HD21 ASN A   2      10.000 -15.000  22.500  1.00  0.00            ",
+                "END",
+            ]
+        );
+        for line in &lines[1..3] {
+            assert_eq!(line.len(), 78);
+        }
+
+        let result = write_frame(&mut Vec::new(), &test_frame(), Some(&atoms[..1]));
+        assert!(matches!(result, Err(Error::LengthMismatch { .. })));
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_write_models() -> Result<()> {
+        let mut out = Vec::new();
+        let frames = vec![Ok(test_frame()), Ok(test_frame())];
+        assert_eq!(write_models(&mut out, frames, None)?, 2);
+        let pdb = String::from_utf8(out).unwrap();
+        assert_eq!(pdb.matches("CRYST1").count(), 1);
+        assert_eq!(pdb.matches("ATOM  ").count(), 4);
+        assert!(pdb.contains("MODEL        2\n"));
+        assert!(pdb.contains(" X   UNK A   1"));
+        assert!(pdb.ends_with("ENDMDL\nEND\n"));
+
+        let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut out = Vec::new();
+        assert_eq!(write_models(&mut out, trj, None)?, 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_frame_with_bfactors() -> Result<()> {
+        let mut out = Vec::new();
+        write_frame_with_bfactors(&mut out, &test_frame(), None, &[12.5, 101.25])?;
+        let pdb = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = pdb.lines().collect();
+        assert_eq!(&lines[1][54..66], "  1.00 12.50");
+        assert_eq!(&lines[2][54..66], "  1.00101.25");
+
+        let result = write_frame_with_bfactors(&mut Vec::new(), &test_frame(), None, &[1.0]);
+        assert!(matches!(
+            result,
+            Err(Error::LengthMismatch {
+                name: "B-factors",
+                ..
+            })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Reusable frames that return to a pool when dropped
+
+use crate::iterator::stop_on_error;
+use crate::*;
+use std::borrow::{Borrow, BorrowMut};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, PoisonError};
+
+struct PoolInner {
+    num_atoms: usize,
+    capacity: usize,
+    free: Mutex<Vec<Frame>>,
+}
+
+/// A pool of frames with a fixed number of atoms that are handed out as
+/// `PooledFrame`s and taken back when those are dropped.
+///
+/// Unlike the single buffer of `TrajectoryIterator`, frames that are kept
+/// alive for a while do not force a new allocation for every following
+/// frame, and at most `capacity` unused frames are kept around. The pool can
+/// be cloned and shared between threads; clones use the same frames.
+///
+/// ```
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let pool = FramePool::new(304, 4);
+/// let mut kept = Vec::new();
+/// for frame in pool.frames(XTCTrajectory::open_read("tests/1l2y.xtc")?) {
+///     let frame = frame?;
+///     if frame.step % 10 == 0 {
+///         kept.push(frame);
+///     }
+/// }
+/// assert_eq!(kept.len(), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FramePool {
+    inner: Arc<PoolInner>,
+}
+
+impl FramePool {
+    /// Create a pool of frames with `num_atoms` atoms that keeps up to
+    /// `capacity` unused frames
+    pub fn new(num_atoms: usize, capacity: usize) -> FramePool {
+        FramePool {
+            inner: Arc::new(PoolInner {
+                num_atoms,
+                capacity,
+                free: Mutex::new(Vec::with_capacity(capacity)),
+            }),
+        }
+    }
+
+    /// Number of atoms of the frames in the pool
+    pub fn num_atoms(&self) -> usize {
+        self.inner.num_atoms
+    }
+
+    /// Number of unused frames that are kept
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Number of unused frames currently in the pool
+    pub fn num_free(&self) -> usize {
+        self.free().len()
+    }
+
+    /// Take an unused frame from the pool, or allocate a new one if there is
+    /// none. The contents of a reused frame are those it was dropped with.
+    pub fn get(&self) -> PooledFrame {
+        let frame = self
+            .free()
+            .pop()
+            .unwrap_or_else(|| Frame::with_len(self.inner.num_atoms));
+        PooledFrame {
+            frame: Some(frame),
+            pool: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Read all remaining frames of `trajectory` into frames of the pool
+    pub fn frames<T: Trajectory>(&self, trajectory: T) -> PooledTrajectoryIterator<T> {
+        PooledTrajectoryIterator {
+            trajectory,
+            pool: self.clone(),
+            has_error: false,
+        }
+    }
+
+    fn free(&self) -> std::sync::MutexGuard<'_, Vec<Frame>> {
+        self.inner
+            .free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for FramePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramePool")
+            .field("num_atoms", &self.num_atoms())
+            .field("capacity", &self.capacity())
+            .field("num_free", &self.num_free())
+            .finish()
+    }
+}
+
+/// A frame borrowed from a `FramePool`, which it returns to when dropped
+pub struct PooledFrame {
+    // only taken when dropped or detached
+    frame: Option<Frame>,
+    pool: Arc<PoolInner>,
+}
+
+impl PooledFrame {
+    /// Take the frame out of the pool for good
+    pub fn into_inner(mut self) -> Frame {
+        self.frame.take().expect("frame is present until dropped")
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        self.frame.as_ref().expect("frame is present until dropped")
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        self.frame.as_mut().expect("frame is present until dropped")
+    }
+}
+
+impl Borrow<Frame> for PooledFrame {
+    fn borrow(&self) -> &Frame {
+        self
+    }
+}
+
+impl BorrowMut<Frame> for PooledFrame {
+    fn borrow_mut(&mut self) -> &mut Frame {
+        self
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        let frame = match self.frame.take() {
+            Some(frame) => frame,
+            None => return,
+        };
+        // frames that were resized do not fit into the pool anymore
+        if frame.len() != self.pool.num_atoms {
+            return;
+        }
+        let mut free = self
+            .pool
+            .free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if free.len() < self.pool.capacity {
+            free.push(frame);
+        }
+    }
+}
+
+impl fmt::Debug for PooledFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Iterator that reads frames into frames of a `FramePool`, see
+/// `FramePool::frames`. Like `TrajectoryIterator`, it stops after EOF or the
+/// first error.
+pub struct PooledTrajectoryIterator<T> {
+    trajectory: T,
+    pool: FramePool,
+    has_error: bool,
+}
+
+impl<T: Trajectory> Iterator for PooledTrajectoryIterator<T> {
+    type Item = Result<PooledFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.has_error {
+            return None;
+        }
+        let mut frame = self.pool.get();
+        let result = self.trajectory.read(&mut frame).map(|()| frame);
+        stop_on_error(result, &mut self.has_error)
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_pool() -> Result<()> {
+        let pool = FramePool::new(304, 2);
+        let frame = pool.get();
+        let coords = frame.coords.as_ptr();
+        drop(frame);
+        assert_eq!(pool.num_free(), 1);
+        // the same frame is handed out again
+        assert_eq!(pool.get().coords.as_ptr(), coords);
+
+        // at most `capacity` frames are kept
+        let frames: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        drop(frames);
+        assert_eq!(pool.num_free(), 2);
+        let detached = pool.get().into_inner();
+        assert_eq!(detached.len(), 304);
+        assert_eq!(pool.num_free(), 1);
+
+        let mut frames = pool.frames(TRRTrajectory::open_read("tests/1l2y.trr")?);
+        let first = frames.next().unwrap()?;
+        let steps: Vec<usize> = frames.map(|frame| Ok(frame?.step)).collect::<Result<_>>()?;
+        assert_eq!(first.step, 1);
+        assert_eq!(steps, (2..=38).collect::<Vec<_>>());
+
+        // frames of the wrong size are an error
+        let mut frames = FramePool::new(10, 1).frames(XTCTrajectory::open_read("tests/1l2y.xtc")?);
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.next().is_none());
+        Ok(())
+    }
+}
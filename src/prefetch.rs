@@ -0,0 +1,155 @@
+//! Reading frames ahead of time on a background thread
+
+use crate::chain::open_by_extension;
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// A trajectory whose frames are decoded on a background thread while the
+/// previous ones are being processed.
+///
+/// Up to `depth` decoded frames wait in a queue, so reading only blocks if
+/// the consumer is faster than the decoding. Frames passed to `read` are
+/// handed back to the background thread and reused.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = PrefetchingTrajectory::open("traj.xtc", 4)?;
+/// let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+/// while trajectory.read(&mut frame).is_ok() {
+///     println!("{}", frame.time);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct PrefetchingTrajectory {
+    frames: Receiver<Result<Frame>>,
+    recycled: Sender<Frame>,
+    num_atoms: usize,
+}
+
+impl PrefetchingTrajectory {
+    /// Read the XTC, TRR or trj file at `path` with up to `depth` frames
+    /// read ahead, choosing the format by file extension
+    pub fn open(path: impl AsRef<Path>, depth: usize) -> Result<PrefetchingTrajectory> {
+        let path = path.as_ref().to_owned();
+        PrefetchingTrajectory::new(move || open_by_extension(&path, FileMode::Read), depth)
+    }
+
+    /// Read the trajectory returned by `open` with up to `depth` frames read
+    /// ahead. The trajectory is opened on the background thread, so it does
+    /// not need to be `Send`.
+    pub fn new<T, F>(open: F, depth: usize) -> Result<PrefetchingTrajectory>
+    where
+        T: Trajectory,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let (num_atoms_sender, num_atoms) = mpsc::sync_channel(1);
+        let (sender, frames) = mpsc::sync_channel(depth);
+        let (recycled, recycled_frames) = mpsc::channel::<Frame>();
+        thread::spawn(move || {
+            let (num_atoms, mut trajectory) =
+                match open().and_then(|mut t| Ok((t.get_num_atoms()?, t))) {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        let _ = num_atoms_sender.send(Err(e));
+                        return;
+                    }
+                };
+            let _ = num_atoms_sender.send(Ok(num_atoms));
+            loop {
+                let mut frame = recycled_frames
+                    .try_recv()
+                    .unwrap_or_else(|_| Frame::with_len(num_atoms));
+                let result = trajectory.read(&mut frame).map(|()| frame);
+                let failed = result.is_err();
+                // stop after EOF, an error or when the trajectory was dropped
+                if sender.send(result).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        let num_atoms = num_atoms.recv().unwrap_or_else(|_| {
+            Err(Error::Io {
+                kind: io::ErrorKind::Other,
+                message: "the background thread panicked".to_string(),
+            })
+        })?;
+        Ok(PrefetchingTrajectory {
+            frames,
+            recycled,
+            num_atoms,
+        })
+    }
+}
+
+impl Trajectory for PrefetchingTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if frame.len() != self.num_atoms {
+            return Err((&*frame, self.num_atoms).into());
+        }
+        match self.frames.recv() {
+            Ok(Ok(next)) => {
+                let previous = std::mem::replace(frame, next);
+                let _ = self.recycled.send(previous);
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            // the background thread is done
+            Err(_) => Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into()),
+        }
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing to a PrefetchingTrajectory"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        Ok(self.num_atoms)
+    }
+}
+
+impl IntoIterator for PrefetchingTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<PrefetchingTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefetching() -> Result<()> {
+        let expected: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let mut trajectory = PrefetchingTrajectory::open("tests/1l2y.xtc", 4)?;
+        let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+        for expected in &expected {
+            trajectory.read(&mut frame)?;
+            assert!(frame.approx_eq(expected, 0.0));
+        }
+        assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        assert!(trajectory.read(&mut Frame::with_len(1)).is_err());
+
+        let trajectory =
+            PrefetchingTrajectory::new(|| TRRTrajectory::open_read("tests/1l2y.trr"), 0)?;
+        assert_eq!(trajectory.into_iter().count(), 38);
+
+        assert!(PrefetchingTrajectory::open("tests/missing.xtc", 4).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+//! Recovering trajectories whose last frame was cut off, e.g. by a crash
+
+#[cfg(feature = "c-lib")]
+use crate::chain::open_by_extension;
+#[cfg(feature = "c-lib")]
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::fs::OpenOptions;
+#[cfg(feature = "c-lib")]
+use std::rc::Rc;
+
+/// The complete frames of an XTC or TRR file, and whether it ends with a
+/// partially written frame, see `TruncationReport::scan`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TruncationReport {
+    /// Number of complete frames
+    pub complete_frames: usize,
+
+    /// Byte length of the complete frames, i.e. where the truncated frame
+    /// starts
+    pub complete_len: u64,
+
+    /// Byte length of the file
+    pub file_len: u64,
+}
+
+impl TruncationReport {
+    /// Find the complete frames of the XTC or TRR file at `path` by reading
+    /// the frame headers. Only an incomplete last frame counts as truncated;
+    /// invalid data before it is an error.
+    pub fn scan(path: impl AsRef<Path>) -> Result<TruncationReport> {
+        let path = path.as_ref();
+        let mut headers = FrameHeaders::open(path)?;
+        let mut complete_frames = 0;
+        for header in &mut headers {
+            header?;
+            complete_frames += 1;
+        }
+        Ok(TruncationReport {
+            complete_frames,
+            complete_len: headers.end_offset(),
+            file_len: std::fs::metadata(path)?.len(),
+        })
+    }
+
+    /// Whether the file ends with an incomplete frame
+    pub fn is_truncated(&self) -> bool {
+        self.complete_len < self.file_len
+    }
+
+    /// Number of bytes of the incomplete last frame
+    pub fn truncated_bytes(&self) -> u64 {
+        self.file_len - self.complete_len
+    }
+}
+
+/// Cut off an incomplete last frame of the XTC or TRR file at `path`, so
+/// that it can be read and appended to again. Returns what was found before
+/// the file was changed; files without a truncated frame are left as they
+/// are.
+pub fn repair(path: impl AsRef<Path>) -> Result<TruncationReport> {
+    let path = path.as_ref();
+    let report = TruncationReport::scan(path)?;
+    if report.is_truncated() {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(report.complete_len)?;
+    }
+    Ok(report)
+}
+
+/// What `salvage` recovered from a damaged trajectory
+#[cfg(feature = "c-lib")]
+#[derive(Clone, Debug)]
+pub struct SalvageReport {
+    /// Number of intact frames that were copied
+    pub frames: usize,
+
+    /// Errors of the damaged frames that were skipped
+    pub skipped: Vec<Error>,
+}
+
+/// Copy all intact frames of the damaged XTC or TRR file at `src` to a new
+/// trajectory at `dst`, choosing the formats by file extension.
+///
+/// When a frame cannot be decoded, the file is scanned forward for the next
+/// frame header with the magic number and number of atoms of the
+/// trajectory, and reading resumes there (see `ErrorPolicy::Skip`). A
+/// truncated last frame is dropped.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let report = salvage("damaged.xtc", "salvaged.xtc")?;
+/// println!("{} frames, {} skipped", report.frames, report.skipped.len());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "c-lib")]
+pub fn salvage(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<SalvageReport> {
+    let src = src.as_ref();
+    let is_xtc = src
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xtc"));
+    let is_trr = src
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("trr"));
+    let mut dst = open_by_extension(dst.as_ref(), FileMode::Write)?;
+    if is_xtc {
+        salvage_from(XTCTrajectory::open_read(src)?, &mut dst)
+    } else if is_trr {
+        salvage_from(TRRTrajectory::open_read(src)?, &mut dst)
+    } else {
+        Err(Error::InvalidData(format!(
+            "Only XTC and TRR files can be salvaged, not {:?}",
+            src
+        )))
+    }
+}
+
+#[cfg(feature = "c-lib")]
+fn salvage_from<T>(src: T, dst: &mut impl Trajectory) -> Result<SalvageReport>
+where
+    T: Trajectory + Resynchronize,
+{
+    let mut frames = into_iter_inner::<T, Rc<Frame>>(src).on_error(ErrorPolicy::Skip);
+    let mut written = 0;
+    for frame in &mut frames {
+        dst.write(&*frame?)?;
+        written += 1;
+    }
+    dst.flush()?;
+    Ok(SalvageReport {
+        frames: written,
+        skipped: frames.skipped_errors().to_vec(),
+    })
+}
+
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Open a file in read mode that may end with a truncated frame, like
+    /// the trajectory of a crashed simulation. Reading ends cleanly after
+    /// the last complete frame instead of failing on the truncated one. The
+    /// file is scanned once when it is opened, see `truncation`.
+    pub fn open_read_tolerant(path: impl AsRef<Path>) -> Result<Self> {
+        let report = TruncationReport::scan(path.as_ref())?;
+        let mut trajectory = XTCTrajectory::open_read(path)?;
+        trajectory.truncation = Some(report);
+        Ok(trajectory)
+    }
+
+    /// The complete frames found by `open_read_tolerant`
+    pub fn truncation(&self) -> Option<TruncationReport> {
+        self.truncation
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Open a file in read mode that may end with a truncated frame, like
+    /// the trajectory of a crashed simulation. Reading ends cleanly after
+    /// the last complete frame instead of failing on the truncated one. The
+    /// file is scanned once when it is opened, see `truncation`.
+    pub fn open_read_tolerant(path: impl AsRef<Path>) -> Result<Self> {
+        let report = TruncationReport::scan(path.as_ref())?;
+        let mut trajectory = TRRTrajectory::open_read(path)?;
+        trajectory.truncation = Some(report);
+        Ok(trajectory)
+    }
+
+    /// The complete frames found by `open_read_tolerant`
+    pub fn truncation(&self) -> Option<TruncationReport> {
+        self.truncation
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_truncated_trajectories() -> Result<()> {
+        let dir = TempDir::new()?;
+        for name in &["1l2y.xtc", "1l2y.trr"] {
+            let path = dir.path().join(name);
+            std::fs::copy(Path::new("tests").join(name), &path)?;
+            let intact = TruncationReport::scan(&path)?;
+            assert_eq!(intact.complete_frames, 38);
+            assert!(!intact.is_truncated());
+
+            // cut the last frame in half
+            let complete_len = FrameIndex::build(&path)?.offset(37).unwrap();
+            let file_len = intact.file_len - (intact.file_len - complete_len) / 2;
+            OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(file_len)?;
+
+            let report = TruncationReport::scan(&path)?;
+            assert_eq!(report.complete_frames, 37);
+            assert_eq!(report.complete_len, complete_len);
+            assert_eq!(report.truncated_bytes(), file_len - complete_len);
+
+            let frames: Vec<_> = if name.ends_with("xtc") {
+                let xtc = XTCTrajectory::open_read_tolerant(&path)?;
+                assert_eq!(xtc.truncation(), Some(report));
+                xtc.into_frames().collect::<Result<_>>()?
+            } else {
+                TRRTrajectory::open_read_tolerant(&path)?
+                    .into_frames()
+                    .collect::<Result<_>>()?
+            };
+            assert_eq!(frames.len(), 37);
+
+            assert_eq!(repair(&path)?, report);
+            assert_eq!(std::fs::metadata(&path)?.len(), complete_len);
+            assert!(!repair(&path)?.is_truncated());
+        }
+        assert!(TruncationReport::scan("README.md").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_salvage() -> Result<()> {
+        let dir = TempDir::new()?;
+        let damaged = dir.path().join("damaged.trr");
+        let salvaged = dir.path().join("salvaged.xtc");
+        std::fs::copy("tests/1l2y.trr", &damaged)?;
+
+        // claim far too many atoms in the header of frame 10, and cut the
+        // last frame short
+        let index = FrameIndex::build(&damaged)?;
+        let mut bytes = std::fs::read(&damaged)?;
+        let header = index.offset(10).unwrap() as usize;
+        let natoms = header + 4 * 16;
+        assert_eq!(bytes[natoms..natoms + 4], 304i32.to_be_bytes());
+        bytes[natoms..natoms + 4].copy_from_slice(&1_000_000i32.to_be_bytes());
+        bytes.truncate(bytes.len() - 100);
+        std::fs::write(&damaged, bytes)?;
+
+        let report = salvage(&damaged, &salvaged)?;
+        assert_eq!(report.frames, 36);
+        assert_eq!(report.skipped.len(), 2);
+        let steps: Vec<usize> = XTCTrajectory::open_read(&salvaged)?
+            .into_frames()
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect::<Result<_>>()?;
+        let expected: Vec<usize> = (1..=37).filter(|&step| step != 11).collect();
+        assert_eq!(steps, expected);
+
+        assert!(salvage("README.md", &salvaged).is_err());
+        Ok(())
+    }
+}
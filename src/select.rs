@@ -0,0 +1,505 @@
+//! Selecting atoms of a topology with expressions like
+//! `"name CA and resid 1 to 20 and not water"`
+//!
+//! The selected atoms are returned as sorted, zero-based indices that can be
+//! passed to `Frame::subset`, subset reads and the functions of `analysis`.
+//!
+//! ```no_run
+//! use xdrfile::*;
+//!
+//! # fn main() -> Result<()> {
+//! let topology = Topology::read_top("topol.top")?;
+//! let calphas = topology.select("name CA and resid 1 to 20")?;
+//! let mut trj = XTCTrajectory::open_read("traj.xtc")?;
+//! let mut frame = Frame::with_len(trj.get_num_atoms()?);
+//! trj.read(&mut frame)?;
+//! let calphas = frame.subset(&calphas)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The language has these parts, from the loosest to the tightest binding:
+//!
+//! - `a or b`, `a and b`, `not a` and parentheses
+//! - `all`, `none`, `protein`, `backbone` (`N`, `CA`, `C` and `O` of the
+//!   protein), `water` and `hydrogen`
+//! - `name`, `resname` and `element` followed by one or more names, which
+//!   may contain the wildcards `*` and `?`
+//! - `resid` followed by residue numbers, and `index` followed by zero-based
+//!   atom indices, both also as ranges `1 to 20`, `1-20` or `1:20`
+//!
+//! Names end at the next keyword or parenthesis, so atoms named like a
+//! keyword cannot be selected by name.
+
+use crate::*;
+use std::str::FromStr;
+
+/// Residue names of the standard amino acids and their common variants
+const PROTEIN_RESIDUES: &[&str] = &[
+    "ALA", "ARG", "ASN", "ASP", "ASH", "CYS", "CYX", "GLN", "GLU", "GLH", "GLY", "HIS", "HID",
+    "HIE", "HIP", "HSD", "HSE", "HSP", "ILE", "LEU", "LYS", "LYN", "MET", "PHE", "PRO", "SER",
+    "THR", "TRP", "TYR", "VAL", "ACE", "NME", "NH2",
+];
+
+/// Residue names of common water models
+const WATER_RESIDUES: &[&str] = &[
+    "SOL", "WAT", "HOH", "TIP3", "TIP4", "TIP5", "SPC", "SPCE", "T3P", "T4P",
+];
+
+/// Atom names of the protein backbone
+const BACKBONE_NAMES: &[&str] = &["N", "CA", "C", "O"];
+
+/// Words that end a list of names or numbers
+const KEYWORDS: &[&str] = &[
+    "and", "or", "not", "to", "all", "none", "protein", "backbone", "water", "hydrogen", "name",
+    "resname", "element", "resid", "index",
+];
+
+/// A parsed selection expression, see the module documentation
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    All,
+    None,
+    Protein,
+    Backbone,
+    Water,
+    Hydrogen,
+    /// Atom names, possibly with wildcards
+    Name(Vec<String>),
+    /// Residue names, possibly with wildcards
+    ResidueName(Vec<String>),
+    /// Element symbols, possibly with wildcards
+    Element(Vec<String>),
+    /// Inclusive ranges of residue numbers
+    ResidueNumber(Vec<(usize, usize)>),
+    /// Inclusive ranges of zero-based atom indices
+    Index(Vec<(usize, usize)>),
+    Not(Box<Selection>),
+    And(Box<Selection>, Box<Selection>),
+    Or(Box<Selection>, Box<Selection>),
+}
+
+impl Selection {
+    /// Parse a selection expression
+    pub fn parse(expression: &str) -> Result<Selection> {
+        let mut parser = Parser {
+            tokens: tokenize(expression),
+            next: 0,
+            end: expression.len(),
+        };
+        let selection = parser.or()?;
+        match parser.peek() {
+            None => Ok(selection),
+            Some(token) => Err(token.error("unexpected")),
+        }
+    }
+
+    /// Zero-based indices of the atoms of `topology` that are selected, in
+    /// increasing order
+    pub fn evaluate(&self, topology: &Topology) -> Vec<usize> {
+        (0..topology.len())
+            .filter(|&i| self.matches(i, &topology.atoms[i]))
+            .collect()
+    }
+
+    /// Whether the atom at index `i` is selected
+    pub fn matches(&self, i: usize, atom: &Atom) -> bool {
+        let any = |patterns: &[String], text: &str| patterns.iter().any(|p| glob(p, text));
+        let within = |ranges: &[(usize, usize)], n: usize| {
+            ranges.iter().any(|&(first, last)| first <= n && n <= last)
+        };
+        match self {
+            Selection::All => true,
+            Selection::None => false,
+            Selection::Protein => PROTEIN_RESIDUES.contains(&atom.residue_name.as_str()),
+            Selection::Backbone => {
+                Selection::Protein.matches(i, atom) && BACKBONE_NAMES.contains(&atom.name.as_str())
+            }
+            Selection::Water => WATER_RESIDUES.contains(&atom.residue_name.as_str()),
+            Selection::Hydrogen => atom.element_or_guess() == "H",
+            Selection::Name(names) => any(names, &atom.name),
+            Selection::ResidueName(names) => any(names, &atom.residue_name),
+            Selection::Element(names) => any(names, atom.element_or_guess()),
+            Selection::ResidueNumber(ranges) => within(ranges, atom.residue_number),
+            Selection::Index(ranges) => within(ranges, i),
+            Selection::Not(a) => !a.matches(i, atom),
+            Selection::And(a, b) => a.matches(i, atom) && b.matches(i, atom),
+            Selection::Or(a, b) => a.matches(i, atom) || b.matches(i, atom),
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Selection> {
+        Selection::parse(expression)
+    }
+}
+
+impl Topology {
+    /// Zero-based indices of the atoms selected by `expression`, see the
+    /// `select` module
+    ///
+    /// ```
+    /// use xdrfile::*;
+    ///
+    /// # fn main() -> Result<()> {
+    /// let topology = Topology::new(vec![
+    ///     Atom::new("N", "GLY", 1),
+    ///     Atom::new("CA", "GLY", 1),
+    ///     Atom::new("OW", "SOL", 2),
+    /// ]);
+    /// assert_eq!(topology.select("name CA or water")?, [1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn select(&self, expression: &str) -> Result<Vec<usize>> {
+        Ok(Selection::parse(expression)?.evaluate(self))
+    }
+}
+
+/// A word or parenthesis of an expression and its byte offset
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Token<'_> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidSelection {
+            position: self.position,
+            message: format!("{} '{}'", message, self.text),
+        }
+    }
+
+    fn is_value(&self) -> bool {
+        self.text != "(" && self.text != ")" && !KEYWORDS.contains(&self.text)
+    }
+}
+
+fn tokenize(expression: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in expression.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(s) = start.take() {
+                tokens.push(Token {
+                    text: &expression[s..i],
+                    position: s,
+                });
+            }
+            if !c.is_whitespace() {
+                tokens.push(Token {
+                    text: &expression[i..i + 1],
+                    position: i,
+                });
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(Token {
+            text: &expression[s..],
+            position: s,
+        });
+    }
+    tokens
+}
+
+/// A recursive descent parser with `or` binding looser than `and`, and
+/// `and` looser than `not`
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    /// Length of the expression, the position of errors at its end
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.next).copied()
+    }
+
+    fn advance(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    /// Consume the next token if it is `text`
+    fn accept(&mut self, text: &str) -> bool {
+        let found = self.peek().map(|token| token.text) == Some(text);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn end_error(&self, message: &str) -> Error {
+        Error::InvalidSelection {
+            position: self.end,
+            message: message.to_string(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Selection> {
+        let mut selection = self.and()?;
+        while self.accept("or") {
+            selection = Selection::Or(Box::new(selection), Box::new(self.and()?));
+        }
+        Ok(selection)
+    }
+
+    fn and(&mut self) -> Result<Selection> {
+        let mut selection = self.not()?;
+        while self.accept("and") {
+            selection = Selection::And(Box::new(selection), Box::new(self.not()?));
+        }
+        Ok(selection)
+    }
+
+    fn not(&mut self) -> Result<Selection> {
+        if self.accept("not") {
+            Ok(Selection::Not(Box::new(self.not()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Selection> {
+        let token = self
+            .advance()
+            .ok_or_else(|| self.end_error("expected a selection"))?;
+        Ok(match token.text {
+            "(" => {
+                let selection = self.or()?;
+                match self.advance() {
+                    Some(close) if close.text == ")" => selection,
+                    Some(other) => return Err(other.error("expected ')' instead of")),
+                    None => return Err(token.error("unclosed")),
+                }
+            }
+            "all" => Selection::All,
+            "none" => Selection::None,
+            "protein" => Selection::Protein,
+            "backbone" => Selection::Backbone,
+            "water" => Selection::Water,
+            "hydrogen" => Selection::Hydrogen,
+            "name" => Selection::Name(self.names(token)?),
+            "resname" => Selection::ResidueName(self.names(token)?),
+            "element" => Selection::Element(self.names(token)?),
+            "resid" => Selection::ResidueNumber(self.ranges(token)?),
+            "index" => Selection::Index(self.ranges(token)?),
+            _ => return Err(token.error("unknown keyword")),
+        })
+    }
+
+    /// The names following `keyword`, at least one
+    fn names(&mut self, keyword: Token) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        while let Some(token) = self.peek().filter(Token::is_value) {
+            names.push(token.text.to_string());
+            self.next += 1;
+        }
+        if names.is_empty() {
+            return Err(keyword.error("no names after"));
+        }
+        Ok(names)
+    }
+
+    /// The numbers and ranges following `keyword`, at least one
+    fn ranges(&mut self, keyword: Token) -> Result<Vec<(usize, usize)>> {
+        let number = |token: Token, text: &str| {
+            text.parse::<usize>()
+                .map_err(|_| token.error("invalid number in"))
+        };
+        let mut ranges = Vec::new();
+        while let Some(token) = self.peek().filter(Token::is_value) {
+            self.next += 1;
+            let range = match token.text.find(['-', ':']) {
+                Some(split) => (
+                    number(token, &token.text[..split])?,
+                    number(token, &token.text[split + 1..])?,
+                ),
+                None => {
+                    let first = number(token, token.text)?;
+                    if self.accept("to") {
+                        let last = self
+                            .advance()
+                            .ok_or_else(|| self.end_error("expected a number after 'to'"))?;
+                        (first, number(last, last.text)?)
+                    } else {
+                        (first, first)
+                    }
+                }
+            };
+            if range.0 > range.1 {
+                return Err(token.error("empty range"));
+            }
+            ranges.push(range);
+        }
+        if ranges.is_empty() {
+            return Err(keyword.error("no numbers after"));
+        }
+        Ok(ranges)
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` stands for any number of
+/// characters and `?` for a single one
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // position after the last `*` and the text it was matched up to
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((after, matched)) = star {
+            // let the last `*` match one more character
+            p = after;
+            t = matched + 1;
+            star = Some((after, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topology() -> Topology {
+        let mut atoms = Vec::new();
+        for (resnum, resname) in [(1, "MET"), (2, "GLY"), (3, "LIG")].iter() {
+            for name in &["N", "H", "CA", "C", "O"] {
+                atoms.push(Atom::new(name, resname, *resnum));
+            }
+        }
+        for resnum in 4..6 {
+            for name in &["OW", "HW1", "HW2"] {
+                atoms.push(Atom::new(name, "SOL", resnum));
+            }
+        }
+        let mut ion = Atom::new("NA", "NA", 6);
+        ion.element = "Na".to_string();
+        atoms.push(ion);
+        Topology::new(atoms)
+    }
+
+    fn invalid_at(expression: &str) -> usize {
+        match Selection::parse(expression) {
+            Err(Error::InvalidSelection { position, .. }) => position,
+            other => panic!("{:?} parsed as {:?}", expression, other),
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("CA", "CA"));
+        assert!(!glob("CA", "CB"));
+        assert!(glob("C?", "CB"));
+        assert!(!glob("C?", "C"));
+        assert!(glob("H*", "H"));
+        assert!(glob("H*", "HW1"));
+        assert!(glob("*W*", "HW1"));
+        assert!(glob("*1", "HW1"));
+        assert!(!glob("*2", "HW1"));
+        assert!(glob("*", ""));
+        assert!(glob("a*b*c", "abxbxc"));
+        assert!(!glob("a*b*c", "abxbx"));
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let names = |names: &[&str]| names.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            Selection::parse("name CA C and not resid 1 to 3 5-7 9:9")?,
+            Selection::And(
+                Box::new(Selection::Name(names(&["CA", "C"]))),
+                Box::new(Selection::Not(Box::new(Selection::ResidueNumber(vec![
+                    (1, 3),
+                    (5, 7),
+                    (9, 9)
+                ])))),
+            )
+        );
+        // `and` binds tighter than `or`
+        assert_eq!(
+            "all or none and water".parse::<Selection>()?,
+            Selection::Or(
+                Box::new(Selection::All),
+                Box::new(Selection::And(
+                    Box::new(Selection::None),
+                    Box::new(Selection::Water)
+                )),
+            )
+        );
+        assert_eq!(
+            Selection::parse("(all or none) and water")?,
+            Selection::And(
+                Box::new(Selection::Or(
+                    Box::new(Selection::All),
+                    Box::new(Selection::None)
+                )),
+                Box::new(Selection::Water),
+            )
+        );
+        assert_eq!(
+            Selection::parse("not not(hydrogen)")?,
+            Selection::Not(Box::new(Selection::Not(Box::new(Selection::Hydrogen))))
+        );
+
+        assert_eq!(invalid_at(""), 0);
+        assert_eq!(invalid_at("name"), 0);
+        assert_eq!(invalid_at("name CA and"), 11);
+        assert_eq!(invalid_at("protein water"), 8);
+        assert_eq!(invalid_at("resid 1 to"), 10);
+        assert_eq!(invalid_at("resid one"), 6);
+        assert_eq!(invalid_at("index 5-2"), 6);
+        assert_eq!(invalid_at("(water or protein"), 0);
+        assert_eq!(invalid_at("water)"), 5);
+        assert_eq!(invalid_at("atoms 1"), 0);
+        assert!(Selection::parse("name").unwrap_err().is_usage_error());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate() -> Result<()> {
+        let topology = topology();
+        assert_eq!(topology.select("all")?.len(), 22);
+        assert!(topology.select("none")?.is_empty());
+        assert_eq!(topology.select("protein")?, (0..10).collect::<Vec<_>>());
+        assert_eq!(topology.select("backbone")?, [0, 2, 3, 4, 5, 7, 8, 9]);
+        assert_eq!(topology.select("water")?, (15..21).collect::<Vec<_>>());
+        assert_eq!(topology.select("hydrogen")?, [1, 6, 11, 16, 17, 19, 20]);
+        assert_eq!(topology.select("element O")?, [4, 9, 14, 15, 18]);
+        assert_eq!(topology.select("element Na")?, [21]);
+        assert_eq!(topology.select("name CA and resid 1 to 2")?, [2, 7]);
+        assert_eq!(topology.select("name H* and not water")?, [1, 6, 11]);
+        assert_eq!(
+            topology.select("resname LIG SOL and name O*")?,
+            [14, 15, 18]
+        );
+        assert_eq!(topology.select("index 0 20:30")?, [0, 20, 21]);
+        assert_eq!(
+            topology.select("not (protein or water) and not name NA")?,
+            (10..15).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            topology.select("name CA or resid 6")?,
+            topology.select("resid 6 or name CA")?
+        );
+        Ok(())
+    }
+}
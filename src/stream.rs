@@ -0,0 +1,110 @@
+//! Asynchronous reading of trajectories with tokio
+
+use crate::*;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// Number of frames that are read ahead of the consumer of the stream
+const READ_AHEAD: usize = 16;
+
+/// Stream of frames that are read on tokio's blocking thread pool, see
+/// `XTCTrajectory::stream` and `TRRTrajectory::stream`.
+///
+/// Like `OwnedTrajectoryIterator`, it ends after EOF or the first error.
+/// Dropping the stream stops the reading thread.
+pub struct FrameStream {
+    receiver: mpsc::Receiver<Result<Frame>>,
+}
+
+impl Stream for FrameStream {
+    type Item = Result<Frame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+fn stream_inner<T, F>(path: &Path, open: F) -> FrameStream
+where
+    T: Trajectory,
+    F: FnOnce(&Path) -> Result<OwnedTrajectoryIterator<T>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(READ_AHEAD);
+    let path = path.to_owned();
+    // the trajectory is only borrowed, so the file is opened again on the
+    // blocking thread
+    tokio::task::spawn_blocking(move || {
+        let frames = match open(&path) {
+            Ok(frames) => frames,
+            Err(e) => {
+                let _ = sender.blocking_send(Err(e));
+                return;
+            }
+        };
+        for frame in frames {
+            if sender.blocking_send(frame).is_err() {
+                // the stream was dropped
+                break;
+            }
+        }
+    });
+    FrameStream { receiver }
+}
+
+impl XTCTrajectory {
+    /// Read all frames of the trajectory from the start of the file as an
+    /// async stream, without blocking the runtime.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn stream(&self) -> FrameStream {
+        stream_inner(&self.handle.path, |path| {
+            Ok(XTCTrajectory::open_read(path)?.into_frames())
+        })
+    }
+}
+
+impl TRRTrajectory {
+    /// Read all frames of the trajectory from the start of the file as an
+    /// async stream, without blocking the runtime.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn stream(&self) -> FrameStream {
+        stream_inner(&self.handle.path, |path| {
+            Ok(TRRTrajectory::open_read(path)?.into_frames())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+
+    async fn collect_steps(mut stream: FrameStream) -> Result<Vec<usize>> {
+        let mut steps = Vec::new();
+        while let Some(frame) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            steps.push(frame?.step);
+        }
+        Ok(steps)
+    }
+
+    #[test]
+    fn test_stream() -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("could not start runtime");
+
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let steps = runtime.block_on(async { collect_steps(xtc.stream()).await })?;
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let steps = runtime.block_on(async { collect_steps(trr.stream()).await })?;
+        assert_eq!(steps.len(), 38);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Sharing a trajectory between threads
+
+use crate::*;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A trajectory behind a mutex, which can be shared between threads, e.g.
+/// with an `Arc`, and used through `&self`.
+///
+/// `XTCTrajectory` and `TRRTrajectory` are `Send`, so they can be moved into
+/// a worker thread or async task on their own, but not `Sync`, as every call
+/// into the C library changes the handle. Each method locks the trajectory
+/// for the duration of the call; use `lock` to read several frames in a row
+/// without other threads reading in between.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let trajectory = Arc::new(SyncTrajectory::new(XTCTrajectory::open_read(
+///     "tests/1l2y.xtc",
+/// )?));
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let trajectory = Arc::clone(&trajectory);
+///         thread::spawn(move || -> Result<usize> {
+///             let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+///             let mut frames = 0;
+///             while trajectory.read(&mut frame).is_ok() {
+///                 frames += 1;
+///             }
+///             Ok(frames)
+///         })
+///     })
+///     .collect();
+/// let mut frames = 0;
+/// for worker in workers {
+///     frames += worker.join().unwrap()?;
+/// }
+/// assert_eq!(frames, 38);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyncTrajectory<T> {
+    inner: Mutex<T>,
+}
+
+impl<T: Trajectory> SyncTrajectory<T> {
+    /// Wrap `trajectory` for use from several threads
+    pub fn new(trajectory: T) -> SyncTrajectory<T> {
+        SyncTrajectory {
+            inner: Mutex::new(trajectory),
+        }
+    }
+
+    /// Lock the trajectory until the guard is dropped. A trajectory whose
+    /// lock was poisoned by a panicking thread is still handed out, as the
+    /// handle stays valid even if a frame was only partly read.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Read the next frame into `frame`, see `Trajectory::read`
+    pub fn read(&self, frame: &mut Frame) -> Result<()> {
+        self.lock().read(frame)
+    }
+
+    /// Write `frame` to the trajectory, see `Trajectory::write`
+    pub fn write(&self, frame: &Frame) -> Result<()> {
+        self.lock().write(frame)
+    }
+
+    /// Flush the trajectory, see `Trajectory::flush`
+    pub fn flush(&self) -> Result<()> {
+        self.lock().flush()
+    }
+
+    /// Get the number of atoms, see `Trajectory::get_num_atoms`
+    pub fn get_num_atoms(&self) -> Result<usize> {
+        self.lock().get_num_atoms()
+    }
+
+    /// Take the trajectory out of the wrapper
+    pub fn into_inner(self) -> T {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Trajectory> From<T> for SyncTrajectory<T> {
+    fn from(trajectory: T) -> Self {
+        SyncTrajectory::new(trajectory)
+    }
+}
+
+/// Exclusive access does not need to lock, so a `SyncTrajectory` can also be
+/// passed to functions that take any trajectory
+impl<T: Trajectory> Trajectory for SyncTrajectory<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        self.get_mut().read(frame)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.get_mut().write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.get_mut().flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.get_mut().get_num_atoms()
+    }
+}
+
+impl<T> fmt::Debug for SyncTrajectory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncTrajectory").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_send_and_sync() -> Result<()> {
+        assert_send::<XTCTrajectory>();
+        assert_send::<TRRTrajectory>();
+        assert_sync::<SyncTrajectory<XTCTrajectory>>();
+        assert_sync::<SyncTrajectory<TRRTrajectory>>();
+
+        // a trajectory opened on one thread is read on another
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(trr.get_num_atoms()?);
+        trr.read(&mut frame)?;
+        let steps = thread::spawn(move || {
+            trr.into_frames()
+                .map(|frame| frame.map(|frame| frame.step))
+                .collect::<Result<Vec<_>>>()
+        })
+        .join()
+        .unwrap()?;
+        assert_eq!(steps, (2..=38).collect::<Vec<_>>());
+
+        let xtc = Arc::new(SyncTrajectory::new(XTCTrajectory::open_read(
+            "tests/1l2y.xtc",
+        )?));
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let xtc = Arc::clone(&xtc);
+                thread::spawn(move || -> Result<Vec<usize>> {
+                    let mut frame = Frame::with_len(xtc.get_num_atoms()?);
+                    let mut steps = Vec::new();
+                    while xtc.read(&mut frame).is_ok() {
+                        steps.push(frame.step);
+                    }
+                    Ok(steps)
+                })
+            })
+            .collect();
+        let mut steps = Vec::new();
+        for worker in workers {
+            steps.extend(worker.join().unwrap()?);
+        }
+        // every frame is read exactly once
+        steps.sort_unstable();
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+
+        let mut xtc = Arc::try_unwrap(xtc).unwrap();
+        assert_eq!(Trajectory::get_num_atoms(&mut xtc)?, 304);
+        assert!(xtc.into_inner().read(&mut Frame::with_len(304)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,883 @@
+//! Per-atom metadata that trajectory files do not store
+
+use crate::*;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Names of an atom and its residue, e.g. for writing structure files
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Atom {
+    /// Atom name, e.g. "CA"
+    pub name: String,
+
+    /// Residue name, e.g. "ALA"
+    pub residue_name: String,
+
+    /// Residue number as used in the structure file (usually starting at 1)
+    pub residue_number: usize,
+
+    /// Chemical element symbol, e.g. "C" (may be empty if unknown)
+    pub element: String,
+
+    /// Mass in atomic mass units (0 if unknown)
+    pub mass: f32,
+
+    /// Partial charge in elementary charges
+    pub charge: f32,
+}
+
+impl Atom {
+    /// Create an atom with the given names and an unknown element
+    pub fn new(name: &str, residue_name: &str, residue_number: usize) -> Atom {
+        Atom {
+            name: name.to_string(),
+            residue_name: residue_name.to_string(),
+            residue_number,
+            ..Atom::default()
+        }
+    }
+
+    /// Element symbol of the atom, guessed from the first letter of its name
+    /// if the topology does not give it
+    pub(crate) fn element_or_guess(&self) -> &str {
+        if self.element.is_empty() {
+            self.name.get(..1).unwrap_or("")
+        } else {
+            &self.element
+        }
+    }
+}
+
+/// Consecutive atoms with the same residue name and number
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Residue {
+    /// Residue name, e.g. "ALA"
+    pub name: String,
+
+    /// Residue number as used in the structure file
+    pub number: usize,
+
+    /// Indices of the atoms of the residue
+    pub atoms: Range<usize>,
+}
+
+/// The atoms of a simulated system in the same order as in its trajectories
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let topology = Topology::read_top("topol.top")?;
+/// let mut trj = XTCTrajectory::open_read("traj.xtc")?;
+/// let mut frame = Frame::with_len(trj.get_num_atoms()?);
+/// trj.read(&mut frame)?;
+/// let com = frame.center_of_mass(&topology.masses())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Topology {
+    /// One entry per atom
+    pub atoms: Vec<Atom>,
+
+    /// The residues of `atoms` in order, as found by `Topology::new`
+    pub residues: Vec<Residue>,
+
+    /// Pairs of bonded atom indices
+    pub bonds: Vec<(usize, usize)>,
+}
+
+impl Topology {
+    /// Create a topology of `atoms` without bonds, grouping consecutive
+    /// atoms with the same residue name and number into residues
+    pub fn new(atoms: Vec<Atom>) -> Topology {
+        let mut residues: Vec<Residue> = Vec::new();
+        for (i, atom) in atoms.iter().enumerate() {
+            match residues.last_mut() {
+                Some(residue)
+                    if residue.number == atom.residue_number
+                        && residue.name == atom.residue_name =>
+                {
+                    residue.atoms.end = i + 1
+                }
+                _ => residues.push(Residue {
+                    name: atom.residue_name.clone(),
+                    number: atom.residue_number,
+                    atoms: i..i + 1,
+                }),
+            }
+        }
+        Topology {
+            atoms,
+            residues,
+            bonds: Vec::new(),
+        }
+    }
+
+    /// Read a GROMACS topology (.top) file.
+    ///
+    /// Only the `[ atomtypes ]`, `[ moleculetype ]`, `[ atoms ]` and
+    /// `[ molecules ]` sections are used. Included files are searched next
+    /// to the including file and in `$GMXLIB`; includes that cannot be found
+    /// are skipped. Masses and charges missing from `[ atoms ]` are taken
+    /// from the atom type, or 0 if the type is unknown. Conditional
+    /// directives such as `#ifdef` are ignored, so all branches are read.
+    pub fn read_top(path: impl AsRef<Path>) -> Result<Topology> {
+        let mut parser = TopParser::default();
+        parser.parse_file(path.as_ref(), 0)?;
+        parser.into_topology()
+    }
+
+    /// Read the atoms of a GROMACS structure (.gro) file, which has no
+    /// bonds. Elements and masses are guessed from the atom names, see
+    /// `guess_elements` and `guess_masses`.
+    pub fn read_gro(path: impl AsRef<Path>) -> Result<Topology> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let invalid = |line: usize, message: &str| {
+            Error::InvalidData(format!(
+                "{} in line {} of {}",
+                message,
+                line,
+                path.display()
+            ))
+        };
+        let mut lines = contents.lines();
+        let num_atoms = lines
+            .nth(1)
+            .and_then(|line| line.trim().parse::<usize>().ok())
+            .ok_or_else(|| invalid(2, "invalid number of atoms"))?;
+        let mut atoms = Vec::with_capacity(num_atoms);
+        for i in 0..num_atoms {
+            let line = lines.next().ok_or_else(|| invalid(i + 3, "missing atom"))?;
+            let field = |range: Range<usize>| line.get(range).map(str::trim);
+            let residue_number = field(0..5).and_then(|number| number.parse().ok());
+            match (residue_number, field(5..10), field(10..15)) {
+                (Some(number), Some(residue_name), Some(name)) => {
+                    atoms.push(Atom::new(name, residue_name, number))
+                }
+                _ => return Err(invalid(i + 3, "invalid atom")),
+            }
+        }
+        let mut topology = Topology::new(atoms);
+        topology.guess_elements();
+        topology.guess_masses();
+        Ok(topology)
+    }
+
+    /// Read the atoms of the first model of a PDB file, and the bonds of
+    /// its `CONECT` records. Elements are taken from the element column, or
+    /// guessed from the atom names if it is empty, and masses are guessed
+    /// from the elements.
+    pub fn read_pdb(path: impl AsRef<Path>) -> Result<Topology> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let invalid = |line: usize, message: &str| {
+            Error::InvalidData(format!(
+                "{} in line {} of {}",
+                message,
+                line,
+                path.display()
+            ))
+        };
+        let mut atoms = Vec::new();
+        let mut serials = HashMap::new();
+        let mut connections = Vec::new();
+        let mut first_model = true;
+        for (i, line) in contents.lines().enumerate() {
+            let field = |range: Range<usize>| line.get(range).unwrap_or_default().trim();
+            match field(0..6) {
+                "ATOM" | "HETATM" if first_model => {
+                    let residue_number = field(22..26)
+                        .parse()
+                        .map_err(|_| invalid(i + 1, "invalid residue number"))?;
+                    let mut atom = Atom::new(field(12..16), field(17..21), residue_number);
+                    if let Some(number) = element_number(field(76..78)) {
+                        atom.element = ELEMENTS[number].to_string();
+                    }
+                    if let Ok(serial) = field(6..11).parse::<usize>() {
+                        serials.insert(serial, atoms.len());
+                    }
+                    atoms.push(atom);
+                }
+                "CONECT" => {
+                    let numbers: Vec<usize> = [6..11, 11..16, 16..21, 21..26, 26..31]
+                        .iter()
+                        .map(|range| field(range.clone()))
+                        .take_while(|number| !number.is_empty())
+                        .map(|number| number.parse())
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| invalid(i + 1, "invalid atom number"))?;
+                    connections.push((i + 1, numbers));
+                }
+                "ENDMDL" => first_model = false,
+                _ => {}
+            }
+        }
+
+        let mut bonds = Vec::new();
+        for (line, numbers) in connections {
+            let mut indices = numbers.iter().map(|serial| {
+                serials
+                    .get(serial)
+                    .copied()
+                    .ok_or_else(|| invalid(line, "bond to a missing atom"))
+            });
+            if let Some(i) = indices.next() {
+                let i = i?;
+                for j in indices {
+                    let j = j?;
+                    bonds.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+        bonds.sort_unstable();
+        bonds.dedup();
+
+        let mut topology = Topology::new(atoms);
+        topology.bonds = bonds;
+        topology.guess_elements();
+        topology.guess_masses();
+        Ok(topology)
+    }
+
+    /// Number of atoms
+    pub fn len(&self) -> usize {
+        self.atoms.len()
+    }
+
+    /// Whether the topology has no atoms
+    pub fn is_empty(&self) -> bool {
+        self.atoms.is_empty()
+    }
+
+    /// Masses of all atoms, e.g. for `Frame::center_of_mass`
+    pub fn masses(&self) -> Vec<f32> {
+        self.atoms.iter().map(|atom| atom.mass).collect()
+    }
+
+    /// Partial charges of all atoms
+    pub fn charges(&self) -> Vec<f32> {
+        self.atoms.iter().map(|atom| atom.charge).collect()
+    }
+
+    /// Index of the atom called `name` in the residue numbered
+    /// `residue_number`, the first one if the number repeats
+    pub fn find_atom(&self, residue_number: usize, name: &str) -> Option<usize> {
+        self.residues
+            .iter()
+            .filter(|residue| residue.number == residue_number)
+            .flat_map(|residue| residue.atoms.clone())
+            .find(|&i| self.atoms[i].name == name)
+    }
+
+    /// Indices of all atoms called `name`
+    pub fn atoms_named(&self, name: &str) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| self.atoms[i].name == name)
+            .collect()
+    }
+
+    /// The residue of atom `i`
+    pub fn residue_of(&self, i: usize) -> Option<&Residue> {
+        let index = self
+            .residues
+            .partition_point(|residue| residue.atoms.end <= i);
+        self.residues
+            .get(index)
+            .filter(|residue| residue.atoms.contains(&i))
+    }
+
+    /// Fill in the elements of atoms that have none, guessed from the atom
+    /// names: single-atom residues named after an element, like `NA` or
+    /// `CL` ions, are that element, other atoms the element of the first
+    /// letter of their name.
+    pub fn guess_elements(&mut self) {
+        for residue in &self.residues {
+            for atom in &mut self.atoms[residue.atoms.clone()] {
+                if !atom.element.is_empty() {
+                    continue;
+                }
+                let name = atom.name.trim_start_matches(|c: char| c.is_ascii_digit());
+                let ion = residue.atoms.len() == 1 && name.len() == 2;
+                let symbol = if ion {
+                    name
+                } else {
+                    name.get(..1).unwrap_or("")
+                };
+                if let Some(i) = element_number(symbol) {
+                    atom.element = ELEMENTS[i].to_string();
+                }
+            }
+        }
+    }
+
+    /// Fill in the masses of atoms with a mass of 0 with the standard
+    /// atomic weight of their element, guessed from the name if unknown
+    pub fn guess_masses(&mut self) {
+        for atom in &mut self.atoms {
+            if atom.mass == 0.0 {
+                atom.mass = element_mass(atom.element_or_guess()).unwrap_or_default();
+            }
+        }
+    }
+
+    /// Van der Waals radii of all atoms, see `vdw_radius`, with elements
+    /// guessed from the names if unknown and `DEFAULT_VDW_RADIUS` for
+    /// elements without a radius
+    pub fn vdw_radii(&self) -> Vec<f32> {
+        self.atoms
+            .iter()
+            .map(|atom| vdw_radius(atom.element_or_guess()).unwrap_or(DEFAULT_VDW_RADIUS))
+            .collect()
+    }
+
+    /// The topology of the atoms at `indices`, e.g. of an index group, with
+    /// the bonds between them
+    pub fn subset(&self, indices: &[usize]) -> Result<Topology> {
+        let mut new_index = vec![None; self.len()];
+        for (new, &i) in indices.iter().enumerate() {
+            *new_index.get_mut(i).ok_or(Error::AtomIndexOutOfBounds {
+                index: i,
+                num_atoms: self.len(),
+            })? = Some(new);
+        }
+        let mut topology = Topology::new(indices.iter().map(|&i| self.atoms[i].clone()).collect());
+        topology.bonds = self
+            .bonds
+            .iter()
+            .filter_map(|&(i, j)| Some((new_index[i]?, new_index[j]?)))
+            .collect();
+        Ok(topology)
+    }
+}
+
+/// Standard atomic weight of the element with the symbol `symbol`, ignoring
+/// case
+///
+/// ```
+/// use xdrfile::*;
+///
+/// assert_eq!(element_mass("C"), Some(12.011));
+/// assert_eq!(element_mass("CL"), Some(35.45));
+/// assert_eq!(element_mass("X"), None);
+/// ```
+pub fn element_mass(symbol: &str) -> Option<f32> {
+    element_number(symbol).map(|i| MASSES[i])
+}
+
+/// Van der Waals radius in nm used by `Topology::vdw_radii` for elements
+/// whose radius is unknown
+pub const DEFAULT_VDW_RADIUS: f32 = 0.2;
+
+/// Van der Waals radius in nm of the element with the symbol `symbol`,
+/// ignoring case, as tabulated by Bondi (1964)
+///
+/// ```
+/// use xdrfile::*;
+///
+/// assert_eq!(vdw_radius("C"), Some(0.17));
+/// assert_eq!(vdw_radius("Fe"), None);
+/// ```
+pub fn vdw_radius(symbol: &str) -> Option<f32> {
+    let radius = match ELEMENTS[element_number(symbol)?] {
+        "H" => 0.12,
+        "He" => 0.14,
+        "Li" => 0.182,
+        "C" => 0.17,
+        "N" => 0.155,
+        "O" => 0.152,
+        "F" => 0.147,
+        "Ne" => 0.154,
+        "Na" => 0.227,
+        "Mg" => 0.173,
+        "Si" => 0.21,
+        "P" => 0.18,
+        "S" => 0.18,
+        "Cl" => 0.175,
+        "Ar" => 0.188,
+        "K" => 0.275,
+        "Ni" => 0.163,
+        "Cu" => 0.14,
+        "Zn" => 0.139,
+        "Ga" => 0.187,
+        "As" => 0.185,
+        "Se" => 0.19,
+        "Br" => 0.185,
+        "Kr" => 0.202,
+        "Pd" => 0.163,
+        "Ag" => 0.172,
+        "Cd" => 0.158,
+        "In" => 0.193,
+        "Sn" => 0.217,
+        "Te" => 0.206,
+        "I" => 0.198,
+        "Xe" => 0.216,
+        _ => return None,
+    };
+    Some(radius)
+}
+
+/// Index of an element in `ELEMENTS`
+fn element_number(symbol: &str) -> Option<usize> {
+    ELEMENTS
+        .iter()
+        .position(|element| element.eq_ignore_ascii_case(symbol))
+}
+
+/// Maximum nesting of `#include` directives, to stop include cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Element symbols by atomic number, starting at hydrogen
+const ELEMENTS: [&str; 54] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe",
+];
+
+/// Standard atomic weights of `ELEMENTS`
+const MASSES: [f32; 54] = [
+    1.008, 4.0026, 6.94, 9.0122, 10.81, 12.011, 14.007, 15.999, 18.998, 20.18, 22.99, 24.305,
+    26.982, 28.085, 30.974, 32.06, 35.45, 39.948, 39.098, 40.078, 44.956, 47.867, 50.942, 51.996,
+    54.938, 55.845, 58.933, 58.693, 63.546, 65.38, 69.723, 72.63, 74.922, 78.971, 79.904, 83.798,
+    85.468, 87.62, 88.906, 91.224, 92.906, 95.95, 98.0, 101.07, 102.91, 106.42, 107.87, 112.41,
+    114.82, 118.71, 121.76, 127.6, 126.9, 131.29,
+];
+
+/// Mass, charge and element of an atom type
+#[derive(Clone, Debug, Default)]
+struct AtomType {
+    mass: f32,
+    charge: f32,
+    element: String,
+}
+
+/// The atoms and bonds of a `[ moleculetype ]`, with atom indices starting
+/// at 0
+#[derive(Default)]
+struct MoleculeType {
+    atoms: Vec<TopAtom>,
+    bonds: Vec<(usize, usize)>,
+}
+
+/// An atom in a `[ atoms ]` section before atom type defaults are applied
+struct TopAtom {
+    atom: Atom,
+    atom_type: String,
+    has_charge: bool,
+    has_mass: bool,
+}
+
+#[derive(Default)]
+struct TopParser {
+    section: String,
+    atom_types: HashMap<String, AtomType>,
+    molecule_types: HashMap<String, MoleculeType>,
+    current_molecule: Option<String>,
+    molecules: Vec<(String, usize)>,
+}
+
+impl TopParser {
+    fn parse_file(&mut self, path: &Path, depth: usize) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(Error::InvalidData(format!(
+                "includes nested too deeply in {}",
+                path.display()
+            )));
+        }
+        let contents = fs::read_to_string(path)?;
+        let invalid = |line: usize, message: &str| {
+            Error::InvalidData(format!(
+                "{} in line {} of {}",
+                message,
+                line,
+                path.display()
+            ))
+        };
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(directive) = line.strip_prefix('#') {
+                if let Some(include) = directive.trim().strip_prefix("include") {
+                    let name = include
+                        .trim()
+                        .trim_matches(|c| c == '"' || c == '<' || c == '>');
+                    if let Some(include) = find_include(path, name) {
+                        self.parse_file(&include, depth + 1)?;
+                    }
+                }
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                self.section = section.trim().to_string();
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match self.section.as_str() {
+                "atomtypes" => self.parse_atom_type(&fields),
+                "moleculetype" => {
+                    let name = fields[0].to_string();
+                    self.molecule_types
+                        .insert(name.clone(), MoleculeType::default());
+                    self.current_molecule = Some(name);
+                }
+                "atoms" => {
+                    let atom = parse_atom(&fields).ok_or_else(|| invalid(i + 1, "invalid atom"))?;
+                    self.current_molecule()
+                        .ok_or_else(|| invalid(i + 1, "atoms outside of a molecule type"))?
+                        .atoms
+                        .push(atom);
+                }
+                "bonds" => {
+                    let bond = match fields[..] {
+                        [i, j, ..] => i.parse::<usize>().ok().zip(j.parse::<usize>().ok()),
+                        _ => None,
+                    };
+                    let bond = bond
+                        .filter(|&(i, j)| i > 0 && j > 0)
+                        .ok_or_else(|| invalid(i + 1, "invalid bond"))?;
+                    self.current_molecule()
+                        .ok_or_else(|| invalid(i + 1, "bonds outside of a molecule type"))?
+                        .bonds
+                        .push((bond.0 - 1, bond.1 - 1));
+                }
+                "molecules" => match fields[..] {
+                    [name, count] => {
+                        let count = count
+                            .parse()
+                            .map_err(|_| invalid(i + 1, "invalid molecule count"))?;
+                        self.molecules.push((name.to_string(), count));
+                    }
+                    _ => return Err(invalid(i + 1, "invalid molecule entry")),
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn current_molecule(&mut self) -> Option<&mut MoleculeType> {
+        let name = self.current_molecule.as_ref()?;
+        self.molecule_types.get_mut(name)
+    }
+
+    /// Parse an atom type line. Depending on the force field, the mass,
+    /// charge and particle type may be preceded by a bonded type and an
+    /// atomic number, so the fields are found relative to the particle type.
+    fn parse_atom_type(&mut self, fields: &[&str]) {
+        let ptype = match (3..fields.len()).find(|&i| matches!(fields[i], "A" | "D" | "S" | "V")) {
+            Some(ptype) => ptype,
+            None => return,
+        };
+        let element = if ptype >= 4 {
+            fields[ptype - 3]
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| ELEMENTS.get(number.wrapping_sub(1)))
+                .map_or_else(String::new, |symbol| symbol.to_string())
+        } else {
+            String::new()
+        };
+        let atom_type = AtomType {
+            mass: fields[ptype - 2].parse().unwrap_or_default(),
+            charge: fields[ptype - 1].parse().unwrap_or_default(),
+            element,
+        };
+        self.atom_types.insert(fields[0].to_string(), atom_type);
+    }
+
+    fn into_topology(self) -> Result<Topology> {
+        let mut atoms = Vec::new();
+        let mut bonds = Vec::new();
+        let mut last_residue = 0;
+        for (name, count) in &self.molecules {
+            let molecule = self.molecule_types.get(name).ok_or_else(|| {
+                Error::InvalidData(format!("molecule type '{}' is not defined", name))
+            })?;
+            if let Some(&(i, j)) = molecule
+                .bonds
+                .iter()
+                .find(|&&(i, j)| i.max(j) >= molecule.atoms.len())
+            {
+                return Err(Error::InvalidData(format!(
+                    "bond {}-{} of molecule type '{}' refers to a missing atom",
+                    i + 1,
+                    j + 1,
+                    name
+                )));
+            }
+            let first_residue = molecule
+                .atoms
+                .iter()
+                .map(|top_atom| top_atom.atom.residue_number)
+                .min()
+                .unwrap_or_default();
+            for _ in 0..*count {
+                let offset = atoms.len();
+                bonds.extend(
+                    molecule
+                        .bonds
+                        .iter()
+                        .map(|&(i, j)| (offset + i, offset + j)),
+                );
+                // residues are numbered consecutively over all molecules
+                let first = last_residue + 1;
+                for top_atom in &molecule.atoms {
+                    let mut atom = top_atom.atom.clone();
+                    atom.residue_number = atom.residue_number - first_residue + first;
+                    last_residue = last_residue.max(atom.residue_number);
+                    if let Some(atom_type) = self.atom_types.get(&top_atom.atom_type) {
+                        if !top_atom.has_mass {
+                            atom.mass = atom_type.mass;
+                        }
+                        if !top_atom.has_charge {
+                            atom.charge = atom_type.charge;
+                        }
+                        atom.element = atom_type.element.clone();
+                    }
+                    atoms.push(atom);
+                }
+            }
+        }
+        let mut topology = Topology::new(atoms);
+        topology.bonds = bonds;
+        Ok(topology)
+    }
+}
+
+/// Parse a line of the form `nr type resnr residue atom cgnr [charge [mass]]`
+fn parse_atom(fields: &[&str]) -> Option<TopAtom> {
+    if fields.len() < 5 {
+        return None;
+    }
+    let mut atom = Atom::new(fields[4], fields[3], fields[2].parse().ok()?);
+    let charge = fields
+        .get(6)
+        .map(|charge| charge.parse())
+        .transpose()
+        .ok()?;
+    let mass = fields.get(7).map(|mass| mass.parse()).transpose().ok()?;
+    atom.charge = charge.unwrap_or_default();
+    atom.mass = mass.unwrap_or_default();
+    Some(TopAtom {
+        atom,
+        atom_type: fields[1].to_string(),
+        has_charge: charge.is_some(),
+        has_mass: mass.is_some(),
+    })
+}
+
+/// Look up an included file next to `including` and in `$GMXLIB`
+fn find_include(including: &Path, name: &str) -> Option<PathBuf> {
+    let local = including.parent().map(|dir| dir.join(name));
+    let gmxlib = std::env::var_os("GMXLIB").map(|dir| Path::new(&dir).join(name));
+    local.into_iter().chain(gmxlib).find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOP: &str = "\
+; a comment
+#include \"missing.ff/forcefield.itp\"
+#include \"water.itp\"
+
+[ atomtypes ]
+; name at.num mass charge ptype sigma epsilon
+  NT   7   14.0070  0.0  A  0.3  0.7
+  CT   12.0110  0.0  A  0.3  0.4
+
+[ moleculetype ]
+; name nrexcl
+Peptide  3
+
+[ atoms ]
+   1  NT   5  ALA  N   1  -0.3
+   2  CT   5  ALA  CA  1   0.1  13.019
+   3  XX   6  GLY  C   2   0.2
+
+[ bonds ]
+   1  2  1
+   2  3  1
+
+[ system ]
+Test
+
+[ molecules ]
+Peptide  2
+SOL      1
+";
+
+    const WATER: &str = "\
+[ moleculetype ]
+SOL  2
+[ atoms ]
+1  OW  1  SOL  OW  1  -0.834  15.9994
+2  HW  1  SOL  HW1 1   0.417   1.008
+";
+
+    #[test]
+    fn test_read_top() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("topol.top"), TOP)?;
+        fs::write(dir.path().join("water.itp"), WATER)?;
+        let topology = Topology::read_top(dir.path().join("topol.top"))?;
+        assert_eq!(topology.len(), 8);
+
+        let names: Vec<&str> = topology.atoms.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["N", "CA", "C", "N", "CA", "C", "OW", "HW1"]);
+        let residues: Vec<usize> = topology.atoms.iter().map(|a| a.residue_number).collect();
+        assert_eq!(residues, vec![1, 1, 2, 3, 3, 4, 5, 5]);
+        assert_eq!(topology.atoms[0].element, "N");
+        assert_eq!(topology.atoms[1].element, "");
+        assert_eq!(topology.atoms[6].residue_name, "SOL");
+
+        // masses fall back to the atom type, then to 0
+        assert_eq!(topology.masses()[..3], [14.007, 13.019, 0.0]);
+        assert_eq!(topology.charges()[..3], [-0.3, 0.1, 0.2]);
+        assert_eq!(topology.atoms[7].mass, 1.008);
+        assert_eq!(topology.bonds, [(0, 1), (1, 2), (3, 4), (4, 5)]);
+        assert_eq!(topology.residues.len(), 5);
+        assert_eq!(topology.residues[4].atoms, 6..8);
+
+        fs::write(
+            dir.path().join("topol.top"),
+            TOP.replace("SOL      1", "NA 1"),
+        )?;
+        let result = Topology::read_top(dir.path().join("topol.top"));
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+
+        fs::write(
+            dir.path().join("topol.top"),
+            TOP.replace("2  3  1", "2  4  1"),
+        )?;
+        let result = Topology::read_top(dir.path().join("topol.top"));
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_residues() -> Result<()> {
+        let mut atoms = vec![
+            Atom::new("N", "ALA", 1),
+            Atom::new("CA", "ALA", 1),
+            Atom::new("N", "GLY", 2),
+            Atom::new("CA", "GLY", 2),
+            Atom::new("NA", "NA", 3),
+            Atom::new("CL", "CL", 4),
+            Atom::new("1HD1", "LEU", 5),
+        ];
+        atoms[1].mass = 13.0;
+        let mut topology = Topology::new(atoms);
+        let ranges: Vec<_> = topology.residues.iter().map(|r| r.atoms.clone()).collect();
+        assert_eq!(ranges, [0..2, 2..4, 4..5, 5..6, 6..7]);
+        assert_eq!(topology.residues[1].name, "GLY");
+        assert_eq!(topology.find_atom(2, "CA"), Some(3));
+        assert_eq!(topology.find_atom(3, "CA"), None);
+        assert_eq!(topology.atoms_named("N"), [0, 2]);
+        assert_eq!(topology.residue_of(3).map(|r| r.number), Some(2));
+        assert_eq!(topology.residue_of(7), None);
+
+        topology.guess_elements();
+        let elements: Vec<&str> = topology.atoms.iter().map(|a| a.element.as_str()).collect();
+        assert_eq!(elements, ["N", "C", "N", "C", "Na", "Cl", "H"]);
+        topology.guess_masses();
+        assert_eq!(
+            topology.masses(),
+            [14.007, 13.0, 14.007, 12.011, 22.99, 35.45, 1.008]
+        );
+
+        topology.bonds = vec![(0, 1), (1, 2), (2, 3)];
+        let subset = topology.subset(&[3, 2, 0])?;
+        assert_eq!(subset.len(), 3);
+        assert_eq!(subset.atoms[0].name, "CA");
+        assert_eq!(subset.bonds, [(1, 0)]);
+        assert_eq!(subset.residues.len(), 2);
+        assert!(topology.subset(&[7]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_gro() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("conf.gro");
+        fs::write(
+            &path,
+            "\
+Test
+    4
+    1ALA      N    1   0.100   0.200   0.300
+    1ALA     CA    2   0.200   0.200   0.300
+    2SOL     OW    3   1.000   1.000   1.000
+    3NA      NA    4   2.000   2.000   2.000
+   3.00000   3.00000   3.00000
+",
+        )?;
+        let topology = Topology::read_gro(&path)?;
+        assert_eq!(topology.len(), 4);
+        assert_eq!(topology.atoms[1], {
+            let mut atom = Atom::new("CA", "ALA", 1);
+            atom.element = "C".to_string();
+            atom.mass = 12.011;
+            atom
+        });
+        assert_eq!(topology.atoms[3].element, "Na");
+        assert_eq!(topology.residues.len(), 3);
+        assert!(topology.bonds.is_empty());
+
+        fs::write(&path, "Test\n    2\n    1ALA      N    1   0.1 0.2 0.3\n")?;
+        assert!(matches!(
+            Topology::read_gro(&path),
+            Err(Error::InvalidData(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_pdb() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("conf.pdb");
+        let mut atoms = vec![
+            Atom::new("N", "ALA", 1),
+            Atom::new("CA", "ALA", 1),
+            Atom::new("HD21", "ASN", 2),
+        ];
+        atoms[0].element = "N".to_string();
+        let frame = Frame {
+            coords: vec![[0.0; 3]; 3],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        crate::pdb::write_frame(&mut out, &frame, Some(&atoms))?;
+        let mut pdb = String::from_utf8(out).unwrap();
+        pdb = pdb.replace("END\n", "CONECT    1    2\nCONECT    2    1    3\nEND\n");
+        fs::write(&path, &pdb)?;
+
+        let topology = Topology::read_pdb(&path)?;
+        let names: Vec<&str> = topology.atoms.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["N", "CA", "HD21"]);
+        assert_eq!(topology.atoms[2].residue_name, "ASN");
+        assert_eq!(topology.atoms[2].residue_number, 2);
+        assert_eq!(topology.atoms[2].element, "H");
+        assert_eq!(topology.masses(), [14.007, 12.011, 1.008]);
+        assert_eq!(topology.bonds, [(0, 1), (1, 2)]);
+
+        // only the first model is read
+        let models = format!("MODEL 1\n{}ENDMDL\nMODEL 2\n{}ENDMDL\n", pdb, pdb);
+        fs::write(&path, models)?;
+        assert_eq!(Topology::read_pdb(&path)?.len(), 3);
+
+        fs::write(&path, pdb.replace("    3\nEND", "    4\nEND"))?;
+        assert!(matches!(
+            Topology::read_pdb(&path),
+            Err(Error::InvalidData(_))
+        ));
+        Ok(())
+    }
+}
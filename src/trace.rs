@@ -0,0 +1,142 @@
+//! Spans and events of the `tracing` feature
+//!
+//! Opening, reading, writing and seeking XTC and TRR files and building
+//! frame indices each run in a span named after the operation, with the
+//! path, frame number and byte offset as fields where they are known. When
+//! the operation is done, an event with its duration is emitted, at trace
+//! level if it succeeded and at debug level with the error if it failed.
+//! Without the feature, `operation!` expands to a unit struct that does
+//! nothing.
+
+use crate::*;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Start an `Operation` in a new span at the given level, e.g.
+/// `operation!(TRACE, "read", offset)`. The fields are only evaluated if the
+/// span is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! operation {
+    ($level:ident, $name:expr $(, $($fields:tt)*)?) => {
+        crate::trace::Operation::new(tracing::span!(
+            tracing::Level::$level,
+            $name
+            $(, $($fields)*)?
+        ))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! operation {
+    ($($args:tt)*) => {
+        crate::trace::Operation
+    };
+}
+
+pub(crate) use operation;
+
+/// An operation on a file that runs in its own span, see `operation!`
+#[cfg(feature = "tracing")]
+pub(crate) struct Operation {
+    span: tracing::span::EnteredSpan,
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Operation {
+    pub(crate) fn new(span: tracing::Span) -> Operation {
+        Operation {
+            span: span.entered(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Emit the duration and the outcome of the operation and leave its span
+    pub(crate) fn finish<T>(self, result: &Result<T>) {
+        let elapsed = self.start.elapsed();
+        match result {
+            Ok(_) => tracing::trace!(?elapsed, "done"),
+            Err(e) => tracing::debug!(?elapsed, error = %e, "failed"),
+        }
+        drop(self.span);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Operation;
+
+#[cfg(not(feature = "tracing"))]
+impl Operation {
+    #[inline]
+    pub(crate) fn finish<T>(self, _result: &Result<T>) {}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Remembers the names of new spans and the messages of events
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<String>>>,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Message(String);
+
+    impl tracing::field::Visit for Message {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name().to_string());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.events.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_tracing() -> Result<()> {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || -> Result<()> {
+            let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+            let mut frame = Frame::with_len(xtc.get_num_atoms()?);
+            xtc.read(&mut frame)?;
+            xtc.rewind()?;
+            FrameIndex::build("tests/1l2y.xtc")?;
+            assert!(XTCTrajectory::open_read("does/not/exist.xtc").is_err());
+            Ok(())
+        })?;
+        let spans = recorder.spans.lock().unwrap().clone();
+        assert_eq!(spans, ["open", "read", "seek", "build_index", "open"]);
+        let events = recorder.events.lock().unwrap().clone();
+        assert_eq!(events, ["done", "done", "done", "done", "failed"]);
+        Ok(())
+    }
+}
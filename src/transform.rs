@@ -0,0 +1,308 @@
+//! Transformations applied to every frame of a trajectory while copying it
+//!
+//! ```no_run
+//! use xdrfile::*;
+//! use xdrfile::transform::fit_trajectory;
+//!
+//! # fn main() -> Result<()> {
+//! let mut input = XTCTrajectory::open_read("traj.xtc")?;
+//! let mut reference = Frame::with_len(input.get_num_atoms()?);
+//! XTCTrajectory::open_read("traj.xtc")?.read(&mut reference)?;
+//! let mut output = XTCTrajectory::open_write("fitted.xtc")?;
+//! fit_trajectory(&mut input, &mut output, &reference, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::frame::sub;
+use crate::*;
+
+/// Fit every remaining frame of `input` onto `reference` and write it to
+/// `output`, like `gmx trjconv -fit rot+trans`. Returns the number of frames
+/// written.
+///
+/// The translation and rotation of each frame are computed from the atoms in
+/// `mask` (or all atoms if `None`), see `Frame::superpose_onto`, so the
+/// motion of their center and their overall rotation are removed. All atoms
+/// are moved, and velocities and forces are rotated along. Only one frame is
+/// kept in memory at a time.
+pub fn fit_trajectory(
+    input: &mut impl Trajectory,
+    output: &mut impl Trajectory,
+    reference: &Frame,
+    mask: Option<&[usize]>,
+) -> Result<usize> {
+    let mut frame = Frame::with_len(input.get_num_atoms()?);
+    if frame.len() != reference.len() {
+        return Err(Error::WrongSizeFrame {
+            expected: reference.len(),
+            found: frame.len(),
+        });
+    }
+    if let Some(indices) = mask {
+        frame.check_indices(indices)?;
+    }
+    let mut frames_written = 0;
+    while read_next(input, &mut frame)? {
+        frame.superpose_onto(reference, mask)?;
+        output.write(&frame)?;
+        frames_written += 1;
+    }
+    output.flush()?;
+    Ok(frames_written)
+}
+
+/// Make every molecule broken across periodic boundaries in the remaining
+/// frames of `input` whole and write the frames to `output`, like
+/// `gmx trjconv -pbc mol`. Returns the number of frames written.
+///
+/// Molecules are the groups of atoms connected by `bonds`, given as pairs of
+/// atom indices, see `Frame::make_whole`. If `unwrap` is true, the
+/// trajectory is also unwrapped continuously over time, like
+/// `gmx trjconv -pbc nojump`: the first atom of each molecule takes the
+/// shortest periodic step from its position in the previous frame, so
+/// molecules never jump across the box, which is needed for diffusion
+/// analysis. Otherwise, the first atom of each molecule stays where it is.
+pub fn make_whole(
+    input: &mut impl Trajectory,
+    output: &mut impl Trajectory,
+    bonds: &[(usize, usize)],
+    unwrap: bool,
+) -> Result<usize> {
+    let mut frame = Frame::with_len(input.get_num_atoms()?);
+    let molecules = Molecules::new(&frame, bonds)?;
+    // original and unwrapped position of the first atom of each molecule
+    let mut previous: Option<Vec<([f32; 3], [f32; 3])>> = None;
+    let mut frames_written = 0;
+    while read_next(input, &mut frame)? {
+        if frame.len() != molecules.num_atoms {
+            return Err(Error::WrongSizeFrame {
+                expected: molecules.num_atoms,
+                found: frame.len(),
+            });
+        }
+        if unwrap {
+            let roots: Vec<([f32; 3], [f32; 3])> = match &previous {
+                Some(previous) => molecules
+                    .roots
+                    .iter()
+                    .zip(previous)
+                    .map(|(&root, &(original, unwrapped))| {
+                        let position = frame.coords[root];
+                        let jump = frame.box_vector.minimum_image(sub(position, original));
+                        (position, [0, 1, 2].map(|k| unwrapped[k] + jump[k]))
+                    })
+                    .collect(),
+                None => molecules
+                    .roots
+                    .iter()
+                    .map(|&root| (frame.coords[root], frame.coords[root]))
+                    .collect(),
+            };
+            for (&root, &(_, unwrapped)) in molecules.roots.iter().zip(&roots) {
+                frame.coords[root] = unwrapped;
+            }
+            previous = Some(roots);
+        }
+        molecules.make_whole(&mut frame);
+        output.write(&frame)?;
+        frames_written += 1;
+    }
+    output.flush()?;
+    Ok(frames_written)
+}
+
+impl Frame {
+    /// Make the molecules of the frame whole by moving each atom to the
+    /// periodic image closest to the atom it is bonded to. Molecules are the
+    /// groups of atoms connected by `bonds`, given as pairs of atom indices,
+    /// and the first atom of each molecule stays where it is. Frames without
+    /// a box are left unchanged.
+    pub fn make_whole(&mut self, bonds: &[(usize, usize)]) -> Result<()> {
+        Molecules::new(self, bonds)?.make_whole(self);
+        Ok(())
+    }
+}
+
+/// The molecules of a system as a spanning tree of its bonds
+struct Molecules {
+    num_atoms: usize,
+    /// The atom of each molecule with the lowest index
+    roots: Vec<usize>,
+    /// Pairs of a bonded atom and the atom it is placed next to, in the order
+    /// of a breadth-first search from the roots
+    tree: Vec<(usize, usize)>,
+}
+
+impl Molecules {
+    fn new(frame: &Frame, bonds: &[(usize, usize)]) -> Result<Molecules> {
+        let mut neighbors = vec![Vec::new(); frame.len()];
+        for &(i, j) in bonds {
+            frame.check_indices(&[i, j])?;
+            neighbors[i].push(j);
+            neighbors[j].push(i);
+        }
+        let mut visited = vec![false; frame.len()];
+        let mut roots = Vec::new();
+        let mut tree = Vec::new();
+        for root in 0..frame.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            roots.push(root);
+            let mut next = tree.len();
+            for &j in &neighbors[root] {
+                if !visited[j] {
+                    visited[j] = true;
+                    tree.push((root, j));
+                }
+            }
+            while next < tree.len() {
+                let (_, i) = tree[next];
+                next += 1;
+                for &j in &neighbors[i] {
+                    if !visited[j] {
+                        visited[j] = true;
+                        tree.push((i, j));
+                    }
+                }
+            }
+        }
+        Ok(Molecules {
+            num_atoms: frame.len(),
+            roots,
+            tree,
+        })
+    }
+
+    fn make_whole(&self, frame: &mut Frame) {
+        for &(i, j) in &self.tree {
+            let bond = frame
+                .box_vector
+                .minimum_image(sub(frame.coords[j], frame.coords[i]));
+            frame.coords[j] = [0, 1, 2].map(|k| frame.coords[i][k] + bond[k]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fit_trajectory() -> Result<()> {
+        let mut reference = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut reference)?;
+
+        // rigidly rotated and shifted copies of the reference
+        let dir = TempDir::new()?;
+        let moved = dir.path().join("moved.trr");
+        let mut trr = TRRTrajectory::open_write(&moved)?;
+        for step in 0..4 {
+            let (sin, cos) = (0.4 * step as f32).sin_cos();
+            let mut frame = reference.clone();
+            frame.step = step;
+            for xyz in frame.coords.iter_mut() {
+                *xyz = [
+                    cos * xyz[0] - sin * xyz[1],
+                    sin * xyz[0] + cos * xyz[1],
+                    xyz[2],
+                ];
+            }
+            frame.translate([step as f32, -2.0, 0.5]);
+            trr.write(&frame)?;
+        }
+        drop(trr);
+
+        let fitted = dir.path().join("fitted.trr");
+        let mut input = TRRTrajectory::open_read(&moved)?;
+        let mut output = TRRTrajectory::open_write(&fitted)?;
+        let mask: Vec<usize> = (0..50).collect();
+        assert_eq!(
+            fit_trajectory(&mut input, &mut output, &reference, Some(&mask))?,
+            4
+        );
+        drop(output);
+
+        let frames: Vec<Frame> = TRRTrajectory::open_read(&fitted)?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 4);
+        for (step, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.step, step);
+            assert!(frame.rmsd_with(&reference, None, false)? < 1e-4);
+        }
+
+        let mut input = TRRTrajectory::open_read(&moved)?;
+        let mut output = TRRTrajectory::open_write(&fitted)?;
+        assert!(fit_trajectory(&mut input, &mut output, &Frame::with_len(3), None).is_err());
+        let mut input = TRRTrajectory::open_read(&moved)?;
+        assert!(fit_trajectory(&mut input, &mut output, &reference, Some(&[304])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_whole() -> Result<()> {
+        // a chain of three atoms broken across the boundary, moving along x
+        let dir = TempDir::new()?;
+        let broken = dir.path().join("broken.trr");
+        let mut trr = TRRTrajectory::open_write(&broken)?;
+        let chains = [
+            [
+                [1.95, 1.0, 1.0],
+                [0.05, 1.0, 1.0],
+                [0.15, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+            ],
+            [
+                [0.05, 1.0, 1.0],
+                [0.15, 1.0, 1.0],
+                [0.25, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+            ],
+        ];
+        for coords in &chains {
+            let frame = Frame {
+                box_vector: BoxVector::rectangular(2.0, 2.0, 2.0),
+                coords: coords.to_vec(),
+                ..Default::default()
+            };
+            trr.write(&frame)?;
+        }
+        drop(trr);
+
+        let bonds = [(2, 1), (0, 1)];
+        let xs = |unwrap: bool| -> Result<Vec<Vec<f32>>> {
+            let whole = dir.path().join("whole.trr");
+            let mut input = TRRTrajectory::open_read(&broken)?;
+            let mut output = TRRTrajectory::open_write(&whole)?;
+            assert_eq!(make_whole(&mut input, &mut output, &bonds, unwrap)?, 2);
+            drop(output);
+            TRRTrajectory::open_read(&whole)?
+                .into_frames()
+                .map(|frame| Ok(frame?.coords.iter().map(|xyz| xyz[0]).collect()))
+                .collect()
+        };
+        let expected = [[1.95, 2.05, 2.15, 1.0], [0.05, 0.15, 0.25, 1.0]];
+        for (found, expected) in xs(false)?.iter().zip(&expected) {
+            for (x, y) in found.iter().zip(expected) {
+                assert_approx_eq!(x, y, 1e-5);
+            }
+        }
+        let expected = [[1.95, 2.05, 2.15, 1.0], [2.05, 2.15, 2.25, 1.0]];
+        for (found, expected) in xs(true)?.iter().zip(&expected) {
+            for (x, y) in found.iter().zip(expected) {
+                assert_approx_eq!(x, y, 1e-5);
+            }
+        }
+
+        let mut frame = Frame::with_len(4);
+        assert!(frame.make_whole(&[(0, 4)]).is_err());
+        frame.coords[1] = [3.0, 0.0, 0.0];
+        frame.make_whole(&[(0, 1)])?;
+        assert_eq!(frame.coords[1], [3.0, 0.0, 0.0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! Best-effort reading of old GROMACS .trj trajectories
+//!
+//! Before TRR, GROMACS wrote full-precision trajectories as .trj files. They
+//! store the same frames as TRR files, but in the byte order of the machine
+//! that wrote them instead of XDR, and with an unpadded version string.
+
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::rc::Rc;
+
+const MAGIC: i32 = 1993;
+const VERSION: &[u8] = b"GMX_trn_file";
+
+/// Reads numbers in the byte order detected from the magic number
+struct TrjReader {
+    reader: BufReader<File>,
+    big_endian: bool,
+}
+
+impl TrjReader {
+    fn read_bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        if !self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn read_i32(&mut self) -> io::Result<i32> {
+        self.read_bytes().map(i32::from_be_bytes)
+    }
+
+    fn read_size(&mut self) -> io::Result<usize> {
+        let size = self.read_i32()?;
+        size.try_into()
+            .map_err(|_| invalid_data(format!("negative size {}", size)))
+    }
+
+    fn read_real(&mut self, double: bool) -> io::Result<f32> {
+        if double {
+            self.read_bytes().map(|b| f64::from_be_bytes(b) as f32)
+        } else {
+            self.read_bytes().map(f32::from_be_bytes)
+        }
+    }
+
+    fn read_reals(&mut self, double: bool, out: &mut [f32]) -> io::Result<()> {
+        for value in out.iter_mut() {
+            *value = self.read_real(double)?;
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, num_bytes: usize) -> io::Result<()> {
+        let skipped = io::copy(
+            &mut self.reader.by_ref().take(num_bytes as u64),
+            &mut io::sink(),
+        )?;
+        if skipped < num_bytes as u64 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::InvalidData => Error::InvalidData(format!("trj: {}", err)),
+        io::ErrorKind::UnexpectedEof => Error::InvalidData("trj: truncated frame".to_string()),
+        _ => err.into(),
+    }
+}
+
+/// Sizes of the blocks of a frame in bytes, as given in its header
+struct Header {
+    box_size: usize,
+    vir_size: usize,
+    pres_size: usize,
+    x_size: usize,
+    v_size: usize,
+    f_size: usize,
+    num_atoms: usize,
+    step: usize,
+}
+
+/// Read-only handle to an old-style GROMACS .trj trajectory.
+///
+/// The byte order is detected from the first frame, so files written on
+/// both little- and big-endian machines can be read. TRR files are read as
+/// well, since they share the layout. Single and double precision are
+/// supported.
+pub struct TrjTrajectory {
+    reader: TrjReader,
+    num_atoms: usize,
+    buffer: Vec<f32>,
+}
+
+impl TrjTrajectory {
+    /// Open the trajectory at `path` for reading
+    pub fn open_read(path: impl AsRef<Path>) -> Result<TrjTrajectory> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(to_error)?;
+        let big_endian = match (i32::from_be_bytes(magic), i32::from_le_bytes(magic)) {
+            (MAGIC, _) => true,
+            (_, MAGIC) => false,
+            _ => return Err(Error::InvalidData("not a trj file".to_string())),
+        };
+        let mut first = TrjReader { reader, big_endian };
+        let header = read_header(&mut first).map_err(to_error)?;
+
+        let reader = BufReader::new(File::open(path)?);
+        Ok(TrjTrajectory {
+            reader: TrjReader { reader, big_endian },
+            num_atoms: header.num_atoms,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Read the next header, including the magic number. Returns `None` at
+    /// the end of the file.
+    fn next_header(&mut self) -> io::Result<Option<Header>> {
+        let mut magic = [0; 4];
+        match self.reader.reader.read(&mut magic)? {
+            0 => return Ok(None),
+            4 => {}
+            n => self.reader.reader.read_exact(&mut magic[n..])?,
+        }
+        let magic = if self.reader.big_endian {
+            i32::from_be_bytes(magic)
+        } else {
+            i32::from_le_bytes(magic)
+        };
+        if magic != MAGIC {
+            return Err(invalid_data(format!("unknown magic number {}", magic)));
+        }
+        read_header(&mut self.reader).map(Some)
+    }
+
+    /// Read a block of `num_bytes` with three reals per atom into `out`
+    fn read_xyz(&mut self, num_bytes: usize, out: &mut Vec<[f32; 3]>) -> io::Result<()> {
+        let double = num_bytes == 3 * 8 * self.num_atoms;
+        self.buffer.resize(3 * self.num_atoms, 0.0);
+        self.reader.read_reals(double, &mut self.buffer)?;
+        out.clear();
+        out.extend(self.buffer.chunks_exact(3).map(|v| [v[0], v[1], v[2]]));
+        Ok(())
+    }
+}
+
+/// Read the frame header after the magic number
+fn read_header(reader: &mut TrjReader) -> io::Result<Header> {
+    // trj files store the length of the version string twice followed by
+    // the string and its nul byte; TRR files store it as an XDR string,
+    // whose length excludes the nul byte and whose data is padded
+    let version_len = reader.read_size()?;
+    let string_len = reader.read_size()?;
+    let padded_len = if string_len == version_len {
+        string_len
+    } else if string_len + 1 == version_len {
+        string_len.div_ceil(4) * 4
+    } else {
+        return Err(invalid_data("unexpected version string"));
+    };
+    let mut version = vec![0; padded_len];
+    reader.reader.read_exact(&mut version)?;
+    if !version.starts_with(VERSION) {
+        return Err(invalid_data("unexpected version string"));
+    }
+
+    // ir, e, box, vir, pres, top, sym, x, v and f sizes
+    let mut sizes = [0; 10];
+    for size in sizes.iter_mut() {
+        *size = reader.read_size()?;
+    }
+    let [_, _, box_size, vir_size, pres_size, _, _, x_size, v_size, f_size] = sizes;
+    let num_atoms = reader.read_size()?;
+    let step = reader.read_size()?;
+    let _nre = reader.read_i32()?;
+    Ok(Header {
+        box_size,
+        vir_size,
+        pres_size,
+        x_size,
+        v_size,
+        f_size,
+        num_atoms,
+        step,
+    })
+}
+
+impl Header {
+    /// Whether the frame is in double precision
+    fn is_double(&self) -> io::Result<bool> {
+        let real_size = if self.box_size != 0 {
+            self.box_size / 9
+        } else if self.num_atoms > 0 {
+            self.x_size.max(self.v_size).max(self.f_size) / (3 * self.num_atoms)
+        } else {
+            4
+        };
+        match real_size {
+            4 => Ok(false),
+            8 => Ok(true),
+            _ => Err(invalid_data("cannot determine precision")),
+        }
+    }
+}
+
+impl Trajectory for TrjTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        if frame.len() != self.num_atoms {
+            return Err((&*frame, self.num_atoms).into());
+        }
+        let header = match self.next_header().map_err(to_error)? {
+            Some(header) => header,
+            None => return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into()),
+        };
+        if header.num_atoms != self.num_atoms {
+            return Err(Error::WrongSizeFrame {
+                expected: self.num_atoms,
+                found: header.num_atoms,
+            });
+        }
+        let double = header.is_double().map_err(to_error)?;
+        let reader = &mut self.reader;
+        frame.time = reader.read_real(double).map_err(to_error)?;
+        reader.read_real(double).map_err(to_error)?; // lambda
+        frame.step = header.step;
+
+        frame.box_vector = BoxVector::default();
+        if header.box_size != 0 {
+            let mut values = [0.0; 9];
+            reader.read_reals(double, &mut values).map_err(to_error)?;
+            frame.box_vector = BoxVector::new([
+                [values[0], values[1], values[2]],
+                [values[3], values[4], values[5]],
+                [values[6], values[7], values[8]],
+            ]);
+        }
+        reader
+            .skip(header.vir_size + header.pres_size)
+            .map_err(to_error)?;
+
+        if header.x_size != 0 {
+            self.read_xyz(header.x_size, &mut frame.coords)
+                .map_err(to_error)?;
+        }
+        frame.velocities = if header.v_size != 0 {
+            let mut velocities = frame.velocities.take().unwrap_or_default();
+            self.read_xyz(header.v_size, &mut velocities)
+                .map_err(to_error)?;
+            Some(velocities)
+        } else {
+            None
+        };
+        frame.forces = if header.f_size != 0 {
+            let mut forces = frame.forces.take().unwrap_or_default();
+            self.read_xyz(header.f_size, &mut forces)
+                .map_err(to_error)?;
+            Some(forces)
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing trj trajectories"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        Ok(self.num_atoms)
+    }
+}
+
+impl IntoIterator for TrjTrajectory {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<TrjTrajectory>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    /// Write a little-endian double precision trj frame with two atoms
+    fn write_frame(out: &mut Vec<u8>, step: i32, time: f64) {
+        for value in &[MAGIC, 13, 13] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(b"GMX_trn_file\0");
+        // ir, e, box, vir, pres, top, sym, x, v, f, natoms, step, nre
+        for value in &[0, 0, 72, 72, 0, 0, 0, 48, 0, 48, 2, step, 0] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut reals = vec![time, 0.0];
+        reals.extend_from_slice(&[3.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 3.0]);
+        reals.extend_from_slice(&[9.0; 9]); // virial
+        reals.extend_from_slice(&[0.1, 0.2, 0.3, 1.0, 2.0, 3.0]);
+        reals.extend_from_slice(&[-1.0; 6]);
+        for value in reals {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_trj() -> Result<()> {
+        let mut trj = Vec::new();
+        write_frame(&mut trj, 0, 0.0);
+        write_frame(&mut trj, 10, 0.5);
+        let tempfile = NamedTempFile::new()?;
+        std::fs::write(tempfile.path(), &trj)?;
+
+        let frames: Vec<Rc<Frame>> = TrjTrajectory::open_read(tempfile.path())?
+            .into_iter()
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].step, 10);
+        assert_eq!(frames[1].time, 0.5);
+        assert_eq!(frames[1].box_vector, BoxVector::rectangular(3.0, 3.0, 3.0));
+        assert_eq!(frames[1].coords, vec![[0.1, 0.2, 0.3], [1.0, 2.0, 3.0]]);
+        assert_eq!(frames[1].velocities, None);
+        assert_eq!(frames[1].forces, Some(vec![[-1.0; 3]; 2]));
+
+        // truncated frames are errors, not the end of the file
+        std::fs::write(tempfile.path(), &trj[..trj.len() - 4])?;
+        let result: Result<Vec<_>> = TrjTrajectory::open_read(tempfile.path())?
+            .into_iter()
+            .collect();
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_read_trr() -> Result<()> {
+        let expected = TRRTrajectory::open_read("tests/1l2y.trr")?.into_frames();
+        let frames = TrjTrajectory::open_read("tests/1l2y.trr")?.into_iter();
+        let mut num_frames = 0;
+        for (expected, frame) in expected.zip(frames) {
+            let (expected, frame) = (expected?, frame?);
+            assert_eq!(frame.step, expected.step);
+            assert_eq!(frame.coords, expected.coords);
+            assert_eq!(frame.box_vector, expected.box_vector);
+            num_frames += 1;
+        }
+        assert_eq!(num_frames, 38);
+
+        let dir = tempfile::TempDir::new()?;
+        let trj = dir.path().join("1l2y.trj");
+        std::fs::copy("tests/1l2y.trr", &trj)?;
+        let xtc = dir.path().join("1l2y.xtc");
+        assert_eq!(convert_files(&trj, &xtc, ConvertOptions::new())?, 38);
+        Ok(())
+    }
+}
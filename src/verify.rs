@@ -0,0 +1,302 @@
+//! Checking the integrity of trajectory files
+
+use crate::index::{read_i32, XTC_MAGIC};
+use crate::*;
+use std::fmt;
+use std::fs::File;
+use std::io::Seek;
+
+/// Something wrong with a frame found by `verify`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProblemKind {
+    /// The frame header could not be read, e.g. because of a wrong magic
+    /// number. Nothing after it is checked.
+    InvalidHeader(Error),
+    /// The frame has a different number of atoms than the first one
+    NumAtomsChanged { expected: usize, found: usize },
+    /// The step is not larger than the one of the previous frame
+    StepNotIncreasing { previous: usize, step: usize },
+    /// The time is not later than the one of the previous frame
+    TimeNotIncreasing { previous: f32, time: f32 },
+    /// The coordinates could not be decoded
+    Undecodable(Error),
+    /// The decoded coordinates contain NaN or infinity
+    InvalidCoordinates(ValidationError),
+    /// The file ends with an incomplete frame of this many bytes
+    Truncated { bytes: u64 },
+}
+
+/// A problem with frame `frame`, which starts at byte `offset`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub frame: usize,
+    pub offset: u64,
+    pub kind: ProblemKind,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "frame {} (byte {}): ", self.frame, self.offset)?;
+        match &self.kind {
+            ProblemKind::InvalidHeader(e) => write!(f, "invalid header: {}", e),
+            ProblemKind::NumAtomsChanged { expected, found } => {
+                write!(f, "{} atoms instead of {}", found, expected)
+            }
+            ProblemKind::StepNotIncreasing { previous, step } => {
+                write!(f, "step {} follows step {}", step, previous)
+            }
+            ProblemKind::TimeNotIncreasing { previous, time } => {
+                write!(f, "time {} follows time {}", time, previous)
+            }
+            ProblemKind::Undecodable(e) => write!(f, "coordinates cannot be decoded: {}", e),
+            ProblemKind::InvalidCoordinates(e) => write!(f, "invalid coordinates: {}", e),
+            ProblemKind::Truncated { bytes } => write!(f, "truncated after {} bytes", bytes),
+        }
+    }
+}
+
+/// The result of `verify`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerificationReport {
+    /// Number of frames whose header could be read
+    pub frames: usize,
+
+    /// Number of atoms of the first frame
+    pub num_atoms: Option<usize>,
+
+    /// Everything that is wrong with the file, ordered by frame
+    pub problems: Vec<Problem>,
+}
+
+impl VerificationReport {
+    /// Whether no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check every frame of the XTC or TRR file at `path`: that its header can
+/// be read, that it has the same number of atoms as the first frame, that
+/// steps and times increase, and that its coordinates can be decoded and
+/// are finite. Use this to validate a trajectory, e.g. before deleting the
+/// raw data it was converted from.
+///
+/// Problems with the file contents are collected in the report; errors are
+/// only returned if the file cannot be read at all.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let report = verify("traj.xtc")?;
+/// for problem in &report.problems {
+///     println!("{}", problem);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn verify(path: impl AsRef<Path>) -> Result<VerificationReport> {
+    let path = path.as_ref();
+    let mut report = VerificationReport::default();
+    // frames with the expected number of atoms, whose coordinates are decoded
+    let mut decodable = Vec::new();
+    let mut previous: Option<FrameHeader> = None;
+    let mut headers = FrameHeaders::open(path)?;
+    let mut invalid_header = false;
+    while let Some(header) = headers.next() {
+        let frame = report.frames;
+        let problem = |kind| Problem {
+            frame,
+            offset: headers.end_offset(),
+            kind,
+        };
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                report.problems.push(problem(ProblemKind::InvalidHeader(e)));
+                invalid_header = true;
+                break;
+            }
+        };
+        let problem = |kind| Problem {
+            frame,
+            offset: header.offset,
+            kind,
+        };
+        report.frames += 1;
+
+        let expected = *report.num_atoms.get_or_insert(header.num_atoms);
+        if header.num_atoms == expected {
+            decodable.push((frame, header.offset));
+        } else {
+            report.problems.push(problem(ProblemKind::NumAtomsChanged {
+                expected,
+                found: header.num_atoms,
+            }));
+        }
+        if let Some(previous) = &previous {
+            if header.step <= previous.step {
+                report
+                    .problems
+                    .push(problem(ProblemKind::StepNotIncreasing {
+                        previous: previous.step,
+                        step: header.step,
+                    }));
+            }
+            if header.time <= previous.time {
+                report
+                    .problems
+                    .push(problem(ProblemKind::TimeNotIncreasing {
+                        previous: previous.time,
+                        time: header.time,
+                    }));
+            }
+        }
+        previous = Some(header);
+    }
+
+    let file_len = std::fs::metadata(path)?.len();
+    let end = headers.end_offset();
+    if !invalid_header && end < file_len {
+        report.problems.push(Problem {
+            frame: report.frames,
+            offset: end,
+            kind: ProblemKind::Truncated {
+                bytes: file_len - end,
+            },
+        });
+    }
+
+    if !decodable.is_empty() {
+        let is_xtc = read_i32(&mut File::open(path)?)? == XTC_MAGIC;
+        if is_xtc {
+            decode(XTCTrajectory::open_read(path)?, &decodable, &mut report)?;
+        } else {
+            decode(TRRTrajectory::open_read(path)?, &decodable, &mut report)?;
+        }
+        // keep the problems ordered by frame
+        report.problems.sort_by_key(|problem| problem.frame);
+    }
+    Ok(report)
+}
+
+/// Decode the frames at `offsets` and check their coordinates
+fn decode<T: Trajectory + Seek>(
+    mut trajectory: T,
+    offsets: &[(usize, u64)],
+    report: &mut VerificationReport,
+) -> Result<()> {
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    for &(index, offset) in offsets {
+        trajectory.seek(SeekFrom::Start(offset))?;
+        let kind = match trajectory.read(&mut frame) {
+            Ok(()) => {
+                let non_finite = frame
+                    .coords
+                    .iter()
+                    .position(|atom| !atom.iter().all(|x| x.is_finite()));
+                match non_finite {
+                    Some(index) => {
+                        ProblemKind::InvalidCoordinates(ValidationError::NonFiniteCoordinates {
+                            index,
+                        })
+                    }
+                    None => continue,
+                }
+            }
+            Err(e) => ProblemKind::Undecodable(e),
+        };
+        report.problems.push(Problem {
+            frame: index,
+            offset,
+            kind,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        for path in &["tests/1l2y.xtc", "tests/1l2y.trr"] {
+            let report = verify(path)?;
+            assert!(report.is_ok(), "{:?}", report.problems);
+            assert_eq!(report.frames, 38);
+            assert_eq!(report.num_atoms, Some(304));
+        }
+
+        let dir = TempDir::new()?;
+        let path = dir.path().join("damaged.xtc");
+        let mut xtc = XTCTrajectory::open_write(&path)?;
+        let frames = [
+            (0, 0.0, 20),
+            (1, 1.0, 20),
+            (1, 2.0, 20),
+            (3, 3.0, 10),
+            (4, 4.0, 20),
+        ];
+        for (step, time, num_atoms) in &frames {
+            let mut frame = Frame::with_len(*num_atoms);
+            frame.step = *step;
+            frame.time = *time;
+            xtc.write(&frame)?;
+        }
+        drop(xtc);
+        // zero the precision of the second frame, and cut off the last one
+        let offsets = FrameIndex::build(&path)?.offsets().to_vec();
+        let mut bytes = std::fs::read(&path)?;
+        let precision = offsets[1] as usize + 56;
+        bytes[precision..precision + 4].copy_from_slice(&0f32.to_be_bytes());
+        bytes.truncate(bytes.len() - 8);
+        std::fs::write(&path, bytes)?;
+
+        let report = verify(&path)?;
+        assert_eq!(report.frames, 4);
+        let problems: Vec<(usize, &ProblemKind)> = report
+            .problems
+            .iter()
+            .map(|problem| (problem.frame, &problem.kind))
+            .collect();
+        assert_eq!(problems.len(), 4);
+        assert!(matches!(
+            problems[0],
+            (1, ProblemKind::InvalidCoordinates(_))
+        ));
+        assert_eq!(
+            problems[1],
+            (
+                2,
+                &ProblemKind::StepNotIncreasing {
+                    previous: 1,
+                    step: 1
+                }
+            )
+        );
+        assert_eq!(
+            problems[2],
+            (
+                3,
+                &ProblemKind::NumAtomsChanged {
+                    expected: 20,
+                    found: 10
+                }
+            )
+        );
+        assert!(matches!(problems[3], (4, ProblemKind::Truncated { .. })));
+        assert_eq!(report.problems[3].offset, offsets[4]);
+        assert!(report.problems[1].to_string().starts_with("frame 2"));
+
+        assert!(matches!(
+            verify("README.md")?.problems[..],
+            [Problem {
+                kind: ProblemKind::InvalidHeader(_),
+                ..
+            }]
+        ));
+        Ok(())
+    }
+}
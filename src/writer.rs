@@ -0,0 +1,160 @@
+//! Writing whole sequences of frames to a trajectory
+
+use crate::*;
+use std::borrow::Borrow;
+
+/// A sink that writes every frame it is extended with to a trajectory.
+///
+/// `Extend` cannot report errors, so the first error is kept, no further
+/// frames are written and the error is returned by `finish`.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let mut writer = TrajectoryWriter::new(XTCTrajectory::open_write("out.xtc")?);
+/// writer.extend((0..10).map(|step| Frame {
+///     step,
+///     ..Frame::with_len(100)
+/// }));
+/// let num_frames = writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TrajectoryWriter<T> {
+    trajectory: T,
+    frames_written: usize,
+    error: Option<Error>,
+}
+
+impl<T: Trajectory> TrajectoryWriter<T> {
+    /// Create a sink that writes to `trajectory`
+    pub fn new(trajectory: T) -> TrajectoryWriter<T> {
+        TrajectoryWriter {
+            trajectory,
+            frames_written: 0,
+            error: None,
+        }
+    }
+
+    /// Write a single frame, unless a previous write failed
+    pub fn push(&mut self, frame: &Frame) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.trajectory.write(frame) {
+            Ok(()) => self.frames_written += 1,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Number of frames written so far
+    pub fn frames_written(&self) -> usize {
+        self.frames_written
+    }
+
+    /// Flush the trajectory and return the number of frames written, or the
+    /// first error that occurred
+    pub fn finish(mut self) -> Result<usize> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.trajectory.flush()?;
+        Ok(self.frames_written)
+    }
+}
+
+impl<T, F> Extend<F> for TrajectoryWriter<T>
+where
+    T: Trajectory,
+    F: Borrow<Frame>,
+{
+    fn extend<I: IntoIterator<Item = F>>(&mut self, frames: I) {
+        for frame in frames {
+            if self.error.is_some() {
+                break;
+            }
+            self.push(frame.borrow());
+        }
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl XTCTrajectory {
+    /// Write all `frames` to a new XTC file at `path`, replacing an existing
+    /// file. Returns the number of frames written.
+    pub fn write_from_iter<I, F>(path: impl AsRef<Path>, frames: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = F>,
+        F: Borrow<Frame>,
+    {
+        let mut writer = TrajectoryWriter::new(XTCTrajectory::open_write(path)?);
+        writer.extend(frames);
+        writer.finish()
+    }
+}
+
+#[cfg(feature = "c-lib")]
+impl TRRTrajectory {
+    /// Write all `frames` to a new TRR file at `path`, replacing an existing
+    /// file. Returns the number of frames written.
+    pub fn write_from_iter<I, F>(path: impl AsRef<Path>, frames: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = F>,
+        F: Borrow<Frame>,
+    {
+        let mut writer = TrajectoryWriter::new(TRRTrajectory::open_write(path)?);
+        writer.extend(frames);
+        writer.finish()
+    }
+}
+
+#[cfg(all(test, feature = "c-lib"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_from_iter() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("copy.xtc");
+        let frames = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(XTCTrajectory::write_from_iter(&path, &frames)?, 38);
+        let copy = XTCTrajectory::open_read(&path)?.into_iter();
+        for (frame, expected) in copy.zip(&frames) {
+            assert!(frame?.approx_eq(expected, 1e-3));
+        }
+
+        let path = dir.path().join("generated.trr");
+        let generated = (0..5).map(|step| Frame {
+            step,
+            ..Frame::with_len(3)
+        });
+        assert_eq!(TRRTrajectory::write_from_iter(&path, generated)?, 5);
+        let steps: Vec<usize> = TRRTrajectory::open_read(&path)?
+            .into_iter()
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<_>>()?;
+        assert_eq!(steps, vec![0, 1, 2, 3, 4]);
+
+        // the second frame is rejected by strict mode
+        let path = dir.path().join("invalid.trr");
+        let mut trj = TRRTrajectory::open_write(&path)?;
+        trj.set_strict(true);
+        let mut writer = TrajectoryWriter::new(trj);
+        let valid = Frame {
+            box_vector: BoxVector::rectangular(2.0, 2.0, 2.0),
+            ..Frame::with_len(3)
+        };
+        let invalid = Frame {
+            time: f32::NAN,
+            ..valid.clone()
+        };
+        writer.extend(vec![valid.clone(), invalid, valid]);
+        assert_eq!(writer.frames_written(), 1);
+        assert!(matches!(writer.finish(), Err(Error::ValidationFailed(_))));
+        Ok(())
+    }
+}
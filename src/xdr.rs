@@ -0,0 +1,338 @@
+//! Safe access to the XDR primitives of libxdrfile
+//!
+//! `XdrFile` reads and writes the building blocks of XDR-based formats:
+//! big-endian integers and floating point numbers, opaque bytes, strings and
+//! the compressed coordinate blocks used by XTC files. Together with `Seek`,
+//! this is enough to implement readers for other XDR formats.
+//!
+//! ```no_run
+//! use xdrfile::xdr::XdrFile;
+//! use xdrfile::Result;
+//!
+//! # fn main() -> Result<()> {
+//! // read the header of the first frame of an XTC file
+//! let mut file = XdrFile::open_read("tests/1l2y.xtc")?;
+//! let magic = file.read_i32()?;
+//! let num_atoms = file.read_i32()?;
+//! let step = file.read_i32()?;
+//! let time = file.read_f32()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::*;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_double, c_uint};
+
+/// Handle to a file of XDR encoded data
+pub struct XdrFile {
+    handle: XDRFile,
+}
+
+/// Create an error for a read of `requested` values that returned `read`.
+/// Reading nothing at all is reported as end of file.
+fn read_error(read: c_int, requested: usize, code: ErrorCode) -> Option<Error> {
+    match usize::try_from(read) {
+        Ok(n) if n >= requested => None,
+        Ok(0) => Some((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into()),
+        _ => Some((code, ErrorTask::Read).into()),
+    }
+}
+
+/// Create an error for a write of `requested` values that returned `written`
+fn write_error(written: c_int, requested: usize, code: ErrorCode) -> Option<Error> {
+    match usize::try_from(written) {
+        Ok(n) if n >= requested => None,
+        _ => Some((code, ErrorTask::Write).into()),
+    }
+}
+
+macro_rules! xdr_primitive {
+    ($type:ty, $c_type:ty, $code:expr, $read_c:ident, $write_c:ident,
+     $read_one:ident, $read_many:ident, $write_one:ident, $write_many:ident, $name:literal) => {
+        #[doc = concat!("Read a single ", $name)]
+        pub fn $read_one(&mut self) -> Result<$type> {
+            let mut value = [<$type>::default()];
+            self.$read_many(&mut value)?;
+            Ok(value[0])
+        }
+
+        #[doc = concat!("Fill `values` with ", $name, "s read from the file")]
+        pub fn $read_many(&mut self, values: &mut [$type]) -> Result<()> {
+            let n = to(values.len(), ErrorTask::Read, "values.len()")?;
+            let read = unsafe {
+                xdrfile::$read_c(values.as_mut_ptr() as *mut $c_type, n, self.handle.xdrfile)
+            };
+            read_error(read, values.len(), $code).map_or(Ok(()), Err)
+        }
+
+        #[doc = concat!("Write a single ", $name)]
+        pub fn $write_one(&mut self, value: $type) -> Result<()> {
+            self.$write_many(&[value])
+        }
+
+        #[doc = concat!("Write all `values` as ", $name, "s")]
+        pub fn $write_many(&mut self, values: &[$type]) -> Result<()> {
+            let n = to(values.len(), ErrorTask::Write, "values.len()")?;
+            // SAFETY: the C code only reads from the pointer
+            let written = unsafe {
+                xdrfile::$write_c(values.as_ptr() as *mut $c_type, n, self.handle.xdrfile)
+            };
+            write_error(written, values.len(), $code).map_or(Ok(()), Err)
+        }
+    };
+}
+
+impl XdrFile {
+    /// Open an XDR file in the given mode
+    pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XdrFile> {
+        Ok(XdrFile {
+            handle: XDRFile::open(path, filemode)?,
+        })
+    }
+
+    /// Open a file in read mode
+    pub fn open_read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Read)
+    }
+
+    /// Open a file in append mode
+    pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Append)
+    }
+
+    /// Open a file in write mode
+    pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Write)
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Flush the file to disk
+    pub fn flush(&mut self) -> Result<()> {
+        let code = unsafe { xdr_seek::xdr_flush(self.handle.xdrfile) };
+        check_code(code, ErrorTask::Flush).map_or(Ok(()), Err)
+    }
+
+    xdr_primitive!(
+        i32,
+        c_int,
+        ErrorCode::ExdrInt,
+        xdrfile_read_int,
+        xdrfile_write_int,
+        read_i32,
+        read_i32s,
+        write_i32,
+        write_i32s,
+        "32 bit signed integer"
+    );
+    xdr_primitive!(
+        u32,
+        c_uint,
+        ErrorCode::ExdrUint,
+        xdrfile_read_uint,
+        xdrfile_write_uint,
+        read_u32,
+        read_u32s,
+        write_u32,
+        write_u32s,
+        "32 bit unsigned integer"
+    );
+    xdr_primitive!(
+        f32,
+        c_float,
+        ErrorCode::ExdrFloat,
+        xdrfile_read_float,
+        xdrfile_write_float,
+        read_f32,
+        read_f32s,
+        write_f32,
+        write_f32s,
+        "single precision float"
+    );
+    xdr_primitive!(
+        f64,
+        c_double,
+        ErrorCode::ExdrDouble,
+        xdrfile_read_double,
+        xdrfile_write_double,
+        read_f64,
+        read_f64s,
+        write_f64,
+        write_f64s,
+        "double precision float"
+    );
+
+    /// Fill `bytes` with raw bytes from the file
+    pub fn read_opaque(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let n = to(bytes.len(), ErrorTask::Read, "bytes.len()")?;
+        let read = unsafe {
+            xdrfile::xdrfile_read_opaque(bytes.as_mut_ptr() as *mut c_char, n, self.handle.xdrfile)
+        };
+        read_error(read, bytes.len(), ErrorCode::ExdrEndOfFile).map_or(Ok(()), Err)
+    }
+
+    /// Write raw bytes to the file
+    pub fn write_opaque(&mut self, bytes: &[u8]) -> Result<()> {
+        let n = to(bytes.len(), ErrorTask::Write, "bytes.len()")?;
+        // SAFETY: the C code only reads from the pointer
+        let written = unsafe {
+            xdrfile::xdrfile_write_opaque(bytes.as_ptr() as *mut c_char, n, self.handle.xdrfile)
+        };
+        match usize::try_from(written) {
+            Ok(n) if n >= bytes.len() => Ok(()),
+            _ => Err(Error::Io {
+                kind: io::ErrorKind::WriteZero,
+                message: format!("wrote {} of {} bytes", written, bytes.len()),
+            }),
+        }
+    }
+
+    /// Read a string of at most `max_len` bytes. Strings that are not valid
+    /// UTF-8 are converted lossily.
+    pub fn read_string(&mut self, max_len: usize) -> Result<String> {
+        // one more byte for the terminating nul
+        let mut buffer = vec![0u8; max_len + 1];
+        let n = to(buffer.len(), ErrorTask::Read, "max_len")?;
+        let read = unsafe {
+            xdrfile::xdrfile_read_string(buffer.as_mut_ptr() as *mut c_char, n, self.handle.xdrfile)
+        };
+        if read <= 0 {
+            return Err(read_error(read, 1, ErrorCode::ExdrString).expect("nothing was read"));
+        }
+        let string = CStr::from_bytes_until_nul(&buffer)
+            .map_err(|_| Error::from((ErrorCode::ExdrString, ErrorTask::Read)))?;
+        Ok(string.to_string_lossy().into_owned())
+    }
+
+    /// Write a string, which must not contain nul bytes
+    pub fn write_string(&mut self, string: &str) -> Result<()> {
+        let string = CString::new(string)
+            .map_err(|_| Error::InvalidData("string contains a nul byte".to_string()))?;
+        let length = string.as_bytes_with_nul().len();
+        // SAFETY: the C code only reads from the pointer
+        let written = unsafe {
+            xdrfile::xdrfile_write_string(string.as_ptr() as *mut c_char, self.handle.xdrfile)
+        };
+        write_error(written, length, ErrorCode::ExdrString).map_or(Ok(()), Err)
+    }
+
+    /// Read a block of compressed coordinates as written by
+    /// `write_compressed_coords` into `coords`, which must be large enough
+    /// for all coordinates of the block. Returns the number of coordinates
+    /// read and the precision they were stored with. Blocks of up to nine
+    /// coordinates are stored uncompressed and report a precision of 0.
+    pub fn read_compressed_coords(&mut self, coords: &mut [[f32; 3]]) -> Result<(usize, f32)> {
+        let mut num_coords = to(coords.len(), ErrorTask::Read, "coords.len()")?;
+        let mut precision: c_float = 0.0;
+        let read = unsafe {
+            xdrfile::xdrfile_decompress_coord_float(
+                coords.as_mut_ptr() as *mut c_float,
+                &mut num_coords,
+                &mut precision,
+                self.handle.xdrfile,
+            )
+        };
+        if read < 0 {
+            return Err((ErrorCode::Exdr3dx, ErrorTask::Read).into());
+        }
+        Ok((to(read, ErrorTask::Read, "read")?, precision))
+    }
+
+    /// Write `coords` as a block of compressed coordinates, as used in XTC
+    /// files. Coordinates are rounded to `1 / precision`.
+    pub fn write_compressed_coords(&mut self, coords: &[[f32; 3]], precision: f32) -> Result<()> {
+        let n = to(coords.len(), ErrorTask::Write, "coords.len()")?;
+        // SAFETY: the C code only reads from the pointer
+        let written = unsafe {
+            xdrfile::xdrfile_compress_coord_float(
+                coords.as_ptr() as *mut c_float,
+                n,
+                precision,
+                self.handle.xdrfile,
+            )
+        };
+        write_error(written, coords.len(), ErrorCode::Exdr3dx).map_or(Ok(()), Err)
+    }
+}
+
+impl io::Seek for XdrFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.handle.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Seek;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let tempfile = NamedTempFile::new()?;
+        let coords: Vec<[f32; 3]> = (0..20)
+            .map(|i| [i as f32 * 0.1, -(i as f32), 2.5])
+            .collect();
+        {
+            let mut file = XdrFile::open_write(tempfile.path())?;
+            file.write_i32(-7)?;
+            file.write_u32s(&[1, 2, 3])?;
+            file.write_f32(1.5)?;
+            file.write_f64(std::f64::consts::PI)?;
+            file.write_opaque(b"abcd")?;
+            file.write_string("GROMACS")?;
+            file.write_compressed_coords(&coords, 1000.0)?;
+            file.flush()?;
+        }
+        // 4 + 12 + 4 + 8 + 4 bytes before the string
+        assert_eq!(
+            std::fs::read(tempfile.path())?[..4],
+            [0xff, 0xff, 0xff, 0xf9]
+        );
+
+        let mut file = XdrFile::open_read(tempfile.path())?;
+        assert_eq!(file.read_i32()?, -7);
+        let mut values = [0; 3];
+        file.read_u32s(&mut values)?;
+        assert_eq!(values, [1, 2, 3]);
+        assert_eq!(file.read_f32()?, 1.5);
+        assert_eq!(file.read_f64()?, std::f64::consts::PI);
+        let mut bytes = [0; 4];
+        file.read_opaque(&mut bytes)?;
+        assert_eq!(&bytes, b"abcd");
+        // 4 + 12 + 4 + 8 + 4 bytes before the string
+        assert_eq!(file.tell()?, 32);
+        assert_eq!(file.read_string(64)?, "GROMACS");
+
+        let mut read_coords = vec![[0.0; 3]; 32];
+        let (num_coords, precision) = file.read_compressed_coords(&mut read_coords)?;
+        assert_eq!(num_coords, 20);
+        assert_eq!(precision, 1000.0);
+        for (read, expected) in read_coords.iter().zip(coords.iter()) {
+            for k in 0..3 {
+                assert_approx_eq!(read[k], expected[k], 1e-3);
+            }
+        }
+
+        let result = file.read_i32();
+        assert!(result.unwrap_err().is_eof());
+
+        file.seek(SeekFrom::Start(0))?;
+        assert_eq!(file.read_i32()?, -7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_xtc_header() -> Result<()> {
+        let mut file = XdrFile::open_read("tests/1l2y.xtc")?;
+        let mut header = [0; 3];
+        file.read_i32s(&mut header)?;
+        assert_eq!(header, [1995, 304, 1]);
+        assert_eq!(file.read_f32()?, 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Glue between the callbacks of the C library and Rust readers and writers
+//!
+//! `XDRFile::from_stream` opens an xdr handle that does not read from a file
+//! but calls the functions below, which forward to a boxed `Read + Seek` or
+//! `Write + Seek` object.
+
+use crate::c_abi::xdr_stream::xdr_stream_ops;
+use crate::*;
+use std::cell::RefCell;
+use std::io::{Read, Seek, Write};
+use std::os::raw::{c_char, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Object safe combination of `Read` and `Seek`
+pub(crate) trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Object safe combination of `Write` and `Seek`
+pub(crate) trait WriteSeek: Write + Seek {}
+impl<T: Write + Seek> WriteSeek for T {}
+
+/// The source or sink of a trajectory that is not a file. It must be `Send`
+/// for the trajectory to be.
+pub(crate) enum Stream {
+    Reader(Box<dyn ReadSeek + Send>),
+    Writer(Box<dyn WriteSeek + Send>),
+}
+
+/// Shared with the C library, which only gets a `*const XdrStream`
+pub(crate) struct XdrStream {
+    inner: RefCell<Stream>,
+}
+
+impl XdrStream {
+    pub(crate) fn new(stream: Stream) -> XdrStream {
+        XdrStream {
+            inner: RefCell::new(stream),
+        }
+    }
+}
+
+/// Callbacks handed to `xdr_stream_open`
+pub(crate) static OPS: xdr_stream_ops = xdr_stream_ops {
+    read: Some(read),
+    write: Some(write),
+    seek: Some(seek),
+    flush: Some(flush),
+};
+
+/// Run `f` on the stream behind `handle`, returning `failed` if it panics
+unsafe fn with_stream<T>(handle: *mut c_void, failed: T, f: impl FnOnce(&mut Stream) -> T) -> T {
+    // SAFETY: the handle is the `XdrStream` passed to `xdr_stream_open`,
+    // which outlives the C handle
+    let stream = &*(handle as *const XdrStream);
+    let mut stream = match stream.inner.try_borrow_mut() {
+        Ok(stream) => stream,
+        Err(_) => return failed,
+    };
+    catch_unwind(AssertUnwindSafe(|| f(&mut stream))).unwrap_or(failed)
+}
+
+unsafe extern "C" fn read(handle: *mut c_void, buf: *mut c_char, len: usize) -> usize {
+    let buf = std::slice::from_raw_parts_mut(buf as *mut u8, len);
+    with_stream(handle, 0, |stream| match stream {
+        Stream::Reader(reader) => {
+            let mut filled = 0;
+            while filled < len {
+                match reader.read(&mut buf[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            filled
+        }
+        Stream::Writer(_) => 0,
+    })
+}
+
+unsafe extern "C" fn write(handle: *mut c_void, buf: *const c_char, len: usize) -> usize {
+    let buf = std::slice::from_raw_parts(buf as *const u8, len);
+    with_stream(handle, 0, |stream| match stream {
+        Stream::Writer(writer) => writer.write_all(buf).map_or(0, |()| len),
+        Stream::Reader(_) => 0,
+    })
+}
+
+unsafe extern "C" fn seek(handle: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    with_stream(handle, -1, |stream| {
+        let pos = match whence {
+            0 => match u64::try_from(offset) {
+                Ok(offset) => SeekFrom::Start(offset),
+                Err(_) => return -1,
+            },
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return -1,
+        };
+        let result = match stream {
+            Stream::Reader(reader) => reader.seek(pos),
+            Stream::Writer(writer) => writer.seek(pos),
+        };
+        result
+            .ok()
+            .and_then(|pos| i64::try_from(pos).ok())
+            .unwrap_or(-1)
+    })
+}
+
+unsafe extern "C" fn flush(handle: *mut c_void) -> c_int {
+    with_stream(handle, -1, |stream| match stream {
+        Stream::Writer(writer) => writer.flush().map_or(-1, |()| 0),
+        Stream::Reader(_) => 0,
+    })
+}
@@ -0,0 +1,925 @@
+//! Pure Rust implementation of the XTC format
+//!
+//! XTC files store coordinates as integers (the coordinates multiplied by a
+//! precision) that are packed into as few bits as possible. Atoms that are
+//! close to the previous atom, like the atoms of a water molecule, are
+//! stored as small differences in "runs". This module encodes and decodes
+//! them without the C library, producing the same bytes.
+//!
+//! `XtcReader` and `XtcWriter` need no C code at all: with
+//! `default-features = false` and the `pure-rust` feature, the crate is
+//! built without libxdrfile. TRR files, the handles of `XTCTrajectory` and
+//! everything built on them need the default `c-lib` feature.
+
+use crate::index::{read_f32, read_i32, XTC_MAGIC};
+use crate::iterator::into_iter_inner;
+use crate::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::rc::Rc;
+
+/// Sizes of the differences within runs, indexed by `smallidx`. The first
+/// nine entries are unused.
+const MAGICINTS: [u32; 73] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 10, 12, 16, 20, 25, 32, 40, 50, 64, 80, 101, 128, 161, 203, 256,
+    322, 406, 512, 645, 812, 1024, 1290, 1625, 2048, 2580, 3250, 4096, 5060, 6501, 8192, 10321,
+    13003, 16384, 20642, 26007, 32768, 41285, 52015, 65536, 82570, 104031, 131072, 165140, 208063,
+    262144, 330280, 416127, 524287, 660561, 832255, 1048576, 1321122, 1664510, 2097152, 2642245,
+    3329021, 4194304, 5284491, 6658042, 8388607, 10568983, 13316085, 16777216,
+];
+const FIRSTIDX: usize = 9;
+
+/// Frames with at most this many atoms are stored uncompressed
+const MAX_UNCOMPRESSED: usize = 9;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Number of bits needed to store values in `0..=size`
+fn size_of_int(size: u32) -> u32 {
+    let mut num: u64 = 1;
+    let mut num_of_bits = 0;
+    while u64::from(size) >= num && num_of_bits < 32 {
+        num_of_bits += 1;
+        num <<= 1;
+    }
+    num_of_bits
+}
+
+/// Number of bits needed to store one value below each of `sizes` as a
+/// single large integer
+fn size_of_ints(sizes: &[u32]) -> u32 {
+    let mut bytes = [0u32; 32];
+    bytes[0] = 1;
+    let mut num_of_bytes = 1;
+    for &size in sizes {
+        let mut tmp = 0;
+        let mut byte_count = 0;
+        while byte_count < num_of_bytes {
+            tmp += bytes[byte_count] * size;
+            bytes[byte_count] = tmp & 0xff;
+            tmp >>= 8;
+            byte_count += 1;
+        }
+        while tmp != 0 {
+            bytes[byte_count] = tmp & 0xff;
+            byte_count += 1;
+            tmp >>= 8;
+        }
+        num_of_bytes = byte_count;
+    }
+    let mut num = 1;
+    let mut num_of_bits = 0;
+    num_of_bytes -= 1;
+    while bytes[num_of_bytes] >= num {
+        num_of_bits += 1;
+        num *= 2;
+    }
+    num_of_bits + num_of_bytes as u32 * 8
+}
+
+/// Number of bits for all three coordinates together, or 0 and the number
+/// of bits for each of them if the sizes are too large to be multiplied
+fn bit_sizes(sizeint: [u32; 3]) -> (u32, [u32; 3]) {
+    if (sizeint[0] | sizeint[1] | sizeint[2]) > 0xffffff {
+        (0, sizeint.map(size_of_int))
+    } else {
+        (size_of_ints(&sizeint), [0; 3])
+    }
+}
+
+/// Reads values of arbitrary bit widths from the packed coordinates
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    last_bits: u32,
+    last_byte: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            position: 0,
+            last_bits: 0,
+            last_byte: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<u32> {
+        let byte = self
+            .data
+            .get(self.position)
+            .ok_or_else(|| invalid_data("compressed coordinates end too early"))?;
+        self.position += 1;
+        Ok(u32::from(*byte))
+    }
+
+    fn decode_bits(&mut self, mut num_of_bits: u32) -> io::Result<u32> {
+        let mask = if num_of_bits >= 32 {
+            u32::MAX
+        } else {
+            (1 << num_of_bits) - 1
+        };
+        let mut num = 0;
+        while num_of_bits >= 8 {
+            self.last_byte = (self.last_byte << 8) | self.next_byte()?;
+            num |= (self.last_byte >> self.last_bits) << (num_of_bits - 8);
+            num_of_bits -= 8;
+        }
+        if num_of_bits > 0 {
+            if self.last_bits < num_of_bits {
+                self.last_bits += 8;
+                self.last_byte = (self.last_byte << 8) | self.next_byte()?;
+            }
+            self.last_bits -= num_of_bits;
+            num |= (self.last_byte >> self.last_bits) & ((1 << num_of_bits) - 1);
+        }
+        Ok(num & mask)
+    }
+
+    /// Decode three values below `sizes` packed into `num_of_bits` bits
+    fn decode_ints(&mut self, mut num_of_bits: u32, sizes: [u32; 3]) -> io::Result<[i32; 3]> {
+        let mut bytes = [0u32; 32];
+        let mut num_of_bytes = 0;
+        while num_of_bits > 8 {
+            bytes[num_of_bytes] = self.decode_bits(8)?;
+            num_of_bytes += 1;
+            num_of_bits -= 8;
+        }
+        if num_of_bits > 0 {
+            bytes[num_of_bytes] = self.decode_bits(num_of_bits)?;
+            num_of_bytes += 1;
+        }
+        let mut nums = [0; 3];
+        for i in (1..3).rev() {
+            let mut num: u32 = 0;
+            for byte in bytes[..num_of_bytes].iter_mut().rev() {
+                num = (num << 8) | *byte;
+                let p = num / sizes[i];
+                *byte = p;
+                num -= p * sizes[i];
+            }
+            nums[i] = num as i32;
+        }
+        nums[0] = (bytes[0] | (bytes[1] << 8) | (bytes[2] << 16) | (bytes[3] << 24)) as i32;
+        Ok(nums)
+    }
+}
+
+/// Writes values of arbitrary bit widths for the packed coordinates
+struct BitWriter {
+    bytes: Vec<u8>,
+    last_bits: u32,
+    last_byte: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            last_bits: 0,
+            last_byte: 0,
+        }
+    }
+
+    fn encode_bits(&mut self, mut num_of_bits: u32, num: u32) {
+        while num_of_bits >= 8 {
+            self.last_byte = (self.last_byte << 8) | (num >> (num_of_bits - 8));
+            self.bytes.push((self.last_byte >> self.last_bits) as u8);
+            num_of_bits -= 8;
+        }
+        if num_of_bits > 0 {
+            self.last_byte = (self.last_byte << num_of_bits) | num;
+            self.last_bits += num_of_bits;
+            if self.last_bits >= 8 {
+                self.last_bits -= 8;
+                self.bytes.push((self.last_byte >> self.last_bits) as u8);
+            }
+        }
+    }
+
+    /// Encode three values below `sizes` as a single large integer of
+    /// `num_of_bits` bits
+    fn encode_ints(&mut self, num_of_bits: u32, sizes: [u32; 3], nums: [u32; 3]) {
+        let mut bytes = [0u32; 32];
+        let mut num_of_bytes = 0;
+        let mut tmp = nums[0];
+        loop {
+            bytes[num_of_bytes] = tmp & 0xff;
+            num_of_bytes += 1;
+            tmp >>= 8;
+            if tmp == 0 {
+                break;
+            }
+        }
+        for i in 1..3 {
+            debug_assert!(nums[i] < sizes[i]);
+            tmp = nums[i];
+            let mut byte_count = 0;
+            while byte_count < num_of_bytes {
+                tmp = bytes[byte_count].wrapping_mul(sizes[i]).wrapping_add(tmp);
+                bytes[byte_count] = tmp & 0xff;
+                tmp >>= 8;
+                byte_count += 1;
+            }
+            while tmp != 0 {
+                bytes[byte_count] = tmp & 0xff;
+                byte_count += 1;
+                tmp >>= 8;
+            }
+            num_of_bytes = byte_count;
+        }
+        let num_of_bytes = num_of_bytes as u32;
+        if num_of_bits >= num_of_bytes * 8 {
+            for &byte in &bytes[..num_of_bytes as usize] {
+                self.encode_bits(8, byte);
+            }
+            self.encode_bits(num_of_bits - num_of_bytes * 8, 0);
+        } else {
+            let last = num_of_bytes as usize - 1;
+            for &byte in &bytes[..last] {
+                self.encode_bits(8, byte);
+            }
+            self.encode_bits(num_of_bits - (num_of_bytes - 1) * 8, bytes[last]);
+        }
+    }
+
+    /// Bytes written, including the last incomplete one
+    fn finish(mut self) -> Vec<u8> {
+        if self.last_bits > 0 {
+            self.bytes
+                .push((self.last_byte << (8 - self.last_bits)) as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Read a block of compressed coordinates into `coords`, which must have
+/// the length given in the block. Returns the precision, which is 0 for
+/// the uncompressed blocks of small systems.
+pub(crate) fn read_coords(reader: &mut impl Read, coords: &mut [[f32; 3]]) -> io::Result<f32> {
+    let size = read_i32(reader)?;
+    if usize::try_from(size) != Ok(coords.len()) {
+        return Err(invalid_data(format!(
+            "expected {} coordinates, found {}",
+            coords.len(),
+            size
+        )));
+    }
+    if coords.len() <= MAX_UNCOMPRESSED {
+        for coord in coords.iter_mut() {
+            for x in coord.iter_mut() {
+                *x = read_f32(reader)?;
+            }
+        }
+        return Ok(0.0);
+    }
+
+    let precision = read_f32(reader)?;
+    let mut minint = [0; 3];
+    let mut maxint = [0; 3];
+    for x in minint.iter_mut().chain(maxint.iter_mut()) {
+        *x = read_i32(reader)?;
+    }
+    let mut smallidx = usize::try_from(read_i32(reader)?)
+        .ok()
+        .filter(|&i| (FIRSTIDX..MAGICINTS.len()).contains(&i))
+        .ok_or_else(|| invalid_data("invalid size of small differences"))?;
+    let num_bytes = usize::try_from(read_i32(reader)?)
+        .map_err(|_| invalid_data("negative size of compressed coordinates"))?;
+    let mut data = vec![0; num_bytes.div_ceil(4) * 4];
+    reader.read_exact(&mut data)?;
+    let mut bits = BitReader::new(&data[..num_bytes]);
+
+    let mut sizeint = [0; 3];
+    for i in 0..3 {
+        sizeint[i] = maxint[i].wrapping_sub(minint[i]).wrapping_add(1) as u32;
+    }
+    let (bitsize, bitsizeint) = bit_sizes(sizeint);
+
+    let mut smaller = MAGICINTS[FIRSTIDX.max(smallidx - 1)] as i32 / 2;
+    let mut smallnum = MAGICINTS[smallidx] as i32 / 2;
+    let mut sizesmall = [MAGICINTS[smallidx]; 3];
+    let inv_precision = 1.0 / precision;
+    let num_atoms = coords.len();
+    let mut out = coords.iter_mut();
+    let mut store = |coord: [i32; 3]| -> io::Result<()> {
+        let target = out
+            .next()
+            .ok_or_else(|| invalid_data("more coordinates than atoms"))?;
+        *target = coord.map(|x| x as f32 * inv_precision);
+        Ok(())
+    };
+
+    // the length of a run is kept until the next one is read
+    let mut run = 0;
+    let mut i = 0;
+    while i < num_atoms {
+        let mut this = if bitsize == 0 {
+            [
+                bits.decode_bits(bitsizeint[0])? as i32,
+                bits.decode_bits(bitsizeint[1])? as i32,
+                bits.decode_bits(bitsizeint[2])? as i32,
+            ]
+        } else {
+            bits.decode_ints(bitsize, sizeint)?
+        };
+        i += 1;
+        for k in 0..3 {
+            this[k] = this[k].wrapping_add(minint[k]);
+        }
+        let mut prev = this;
+
+        let mut is_smaller = 0;
+        if bits.decode_bits(1)? == 1 {
+            run = bits.decode_bits(5)? as i32;
+            is_smaller = run % 3;
+            run -= is_smaller;
+            is_smaller -= 1;
+        }
+        if run > 0 {
+            for k in (0..run).step_by(3) {
+                let mut coord = bits.decode_ints(smallidx as u32, sizesmall)?;
+                i += 1;
+                for d in 0..3 {
+                    coord[d] += prev[d] - smallnum;
+                }
+                if k == 0 {
+                    // the first two atoms are swapped for better compression
+                    // of water molecules
+                    std::mem::swap(&mut coord, &mut prev);
+                    store(prev)?;
+                } else {
+                    prev = coord;
+                }
+                store(coord)?;
+            }
+        } else {
+            store(this)?;
+        }
+
+        smallidx = usize::try_from(smallidx as i32 + is_smaller)
+            .ok()
+            .filter(|&i| i < MAGICINTS.len())
+            .ok_or_else(|| invalid_data("invalid size of small differences"))?;
+        if is_smaller < 0 {
+            smallnum = smaller;
+            smaller = if smallidx > FIRSTIDX {
+                MAGICINTS[smallidx - 1] as i32 / 2
+            } else {
+                0
+            };
+        } else if is_smaller > 0 {
+            smaller = smallnum;
+            smallnum = MAGICINTS[smallidx] as i32 / 2;
+        }
+        sizesmall = [MAGICINTS[smallidx]; 3];
+        if sizesmall[0] == 0 {
+            return Err(invalid_data("invalid size of small differences"));
+        }
+    }
+    Ok(precision)
+}
+
+/// Largest magnitude of a coordinate multiplied by the precision
+const MAX_INT: f32 = (i32::MAX - 2) as f32;
+
+fn out_of_range(value: impl std::fmt::Display) -> Error {
+    Error::OutOfRange {
+        name: "coords",
+        task: ErrorTask::Write,
+        value: value.to_string(),
+        target: "i32",
+    }
+}
+
+fn put_i32(buf: &mut Vec<u8>, value: i32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_f32(buf: &mut Vec<u8>, value: f32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Append `coords` to `buf` as a block of compressed coordinates, in the
+/// same way as the C library. A precision of 0 or less means 1000.
+pub(crate) fn write_coords(buf: &mut Vec<u8>, coords: &[[f32; 3]], precision: f32) -> Result<()> {
+    let size = crate::to(coords.len(), ErrorTask::Write, "natoms")?;
+    put_i32(buf, size);
+    if coords.len() <= MAX_UNCOMPRESSED {
+        for &x in coords.iter().flatten() {
+            put_f32(buf, x);
+        }
+        return Ok(());
+    }
+    let precision = if precision <= 0.0 { 1000.0 } else { precision };
+    put_f32(buf, precision);
+
+    let mut ints = Vec::with_capacity(coords.len());
+    let mut minint = [i32::MAX; 3];
+    let mut maxint = [i32::MIN; 3];
+    let mut mindiff = i64::from(i32::MAX);
+    let mut old = [0; 3];
+    for (i, coord) in coords.iter().enumerate() {
+        let mut int = [0; 3];
+        for d in 0..3 {
+            let x = coord[d];
+            let scaled = if x >= 0.0 {
+                x * precision + 0.5
+            } else {
+                x * precision - 0.5
+            };
+            if scaled.is_nan() || scaled.abs() > MAX_INT {
+                return Err(out_of_range(x));
+            }
+            int[d] = scaled as i32;
+            minint[d] = minint[d].min(int[d]);
+            maxint[d] = maxint[d].max(int[d]);
+        }
+        let diff: i64 = (0..3)
+            .map(|d| (i64::from(old[d]) - i64::from(int[d])).abs())
+            .sum();
+        if i > 0 && diff < mindiff {
+            mindiff = diff;
+        }
+        old = int;
+        ints.push(int);
+    }
+    for &x in minint.iter().chain(maxint.iter()) {
+        put_i32(buf, x);
+    }
+
+    let mut sizeint = [0; 3];
+    for d in 0..3 {
+        if maxint[d] as f32 - minint[d] as f32 >= MAX_INT {
+            return Err(out_of_range(format!("{}..{}", minint[d], maxint[d])));
+        }
+        sizeint[d] = maxint[d].wrapping_sub(minint[d]).wrapping_add(1) as u32;
+    }
+    let (bitsize, bitsizeint) = bit_sizes(sizeint);
+
+    let mut smallidx = FIRSTIDX;
+    while smallidx < MAGICINTS.len() - 1 && i64::from(MAGICINTS[smallidx]) < mindiff {
+        smallidx += 1;
+    }
+    put_i32(buf, smallidx as i32);
+    // the C library reads past the end of its table for very sparse
+    // coordinates, which the last entry avoids
+    let maxidx = (smallidx + 8).min(MAGICINTS.len() - 1);
+    let minidx = maxidx - 8;
+    let mut smaller = MAGICINTS[FIRSTIDX.max(smallidx - 1)] as i32 / 2;
+    let mut smallnum = MAGICINTS[smallidx] as i32 / 2;
+    let mut sizesmall = [MAGICINTS[smallidx]; 3];
+    let larger = i64::from(MAGICINTS[maxidx] / 2);
+    let within = |a: [i32; 3], b: [i32; 3], limit: i64| {
+        (0..3).all(|d| (i64::from(a[d]) - i64::from(b[d])).abs() < limit)
+    };
+
+    let mut bits = BitWriter::new();
+    let mut prevrun = -1;
+    let mut prevcoord = [0; 3];
+    let mut i = 0;
+    while i < ints.len() {
+        let mut is_smaller = if smallidx < maxidx && i >= 1 && within(ints[i], prevcoord, larger) {
+            1
+        } else if smallidx > minidx {
+            -1
+        } else {
+            0
+        };
+        let mut is_small = false;
+        if i + 1 < ints.len() && within(ints[i], ints[i + 1], i64::from(smallnum)) {
+            // the first two atoms are swapped for better compression of
+            // water molecules
+            ints.swap(i, i + 1);
+            is_small = true;
+        }
+        let this = ints[i];
+        let offset = [0, 1, 2].map(|d| this[d].wrapping_sub(minint[d]) as u32);
+        if bitsize == 0 {
+            for d in 0..3 {
+                bits.encode_bits(bitsizeint[d], offset[d]);
+            }
+        } else {
+            bits.encode_ints(bitsize, sizeint, offset);
+        }
+        prevcoord = this;
+        i += 1;
+
+        let mut run = 0;
+        let mut small_coords = [[0u32; 3]; 8];
+        if !is_small && is_smaller == -1 {
+            is_smaller = 0;
+        }
+        while is_small && run < 8 {
+            let this = ints[i];
+            let distance: i64 = (0..3)
+                .map(|d| (i64::from(this[d]) - i64::from(prevcoord[d])).pow(2))
+                .sum();
+            if is_smaller == -1 && distance >= i64::from(smaller).pow(2) {
+                is_smaller = 0;
+            }
+            small_coords[run] = [0, 1, 2].map(|d| (this[d] - prevcoord[d] + smallnum) as u32);
+            run += 1;
+            prevcoord = this;
+            i += 1;
+            is_small = i < ints.len() && within(ints[i], prevcoord, i64::from(smallnum));
+        }
+        let run_length = 3 * run as i32;
+        if run_length != prevrun || is_smaller != 0 {
+            prevrun = run_length;
+            // flag the change in run length
+            bits.encode_bits(1, 1);
+            bits.encode_bits(5, (run_length + is_smaller + 1) as u32);
+        } else {
+            bits.encode_bits(1, 0);
+        }
+        for &coord in &small_coords[..run] {
+            bits.encode_ints(smallidx as u32, sizesmall, coord);
+        }
+        if is_smaller != 0 {
+            smallidx = (smallidx as i32 + is_smaller) as usize;
+            if is_smaller < 0 {
+                smallnum = smaller;
+                smaller = MAGICINTS[smallidx - 1] as i32 / 2;
+            } else {
+                smaller = smallnum;
+                smallnum = MAGICINTS[smallidx] as i32 / 2;
+            }
+            sizesmall = [MAGICINTS[smallidx]; 3];
+        }
+    }
+
+    let bytes = bits.finish();
+    put_i32(
+        buf,
+        crate::to(bytes.len(), ErrorTask::Write, "compressed size")?,
+    );
+    let padded = bytes.len().div_ceil(4) * 4;
+    buf.extend_from_slice(&bytes);
+    buf.resize(buf.len() + padded - bytes.len(), 0);
+    Ok(())
+}
+
+/// Reads XTC trajectories without the C library, from files or any other
+/// source of bytes.
+///
+/// Unlike `XTCTrajectory`, this reader cannot write or seek.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let trajectory = XtcReader::open("traj.xtc")?;
+/// for frame in trajectory {
+///     println!("{}", frame?.time);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct XtcReader<R> {
+    reader: R,
+    num_atoms: Option<usize>,
+    /// Step, time and number of atoms of a frame whose header was read to
+    /// get the number of atoms
+    pending: Option<(i32, f32, usize)>,
+    precision: f32,
+    max_atoms: usize,
+}
+
+impl XtcReader<BufReader<File>> {
+    /// Open the XTC file at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(XtcReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> XtcReader<R> {
+    /// Read an XTC trajectory from `reader`
+    pub fn new(reader: R) -> XtcReader<R> {
+        XtcReader {
+            reader,
+            num_atoms: None,
+            pending: None,
+            precision: 0.0,
+            max_atoms: DEFAULT_MAX_ATOMS,
+        }
+    }
+
+    /// Set the largest number of atoms that `get_num_atoms` accepts, see
+    /// `XTCTrajectory::set_max_atoms`
+    pub fn set_max_atoms(&mut self, max_atoms: usize) {
+        self.max_atoms = max_atoms;
+    }
+
+    /// Precision of the coordinates of the last frame read, or 0 if they
+    /// were stored uncompressed
+    pub fn precision(&self) -> f32 {
+        self.precision
+    }
+
+    /// Read the start of the next frame. Returns `None` at the end.
+    fn read_header(&mut self) -> Result<Option<(i32, f32, usize)>> {
+        let mut magic = [0; 4];
+        let mut filled = 0;
+        while filled < 4 {
+            match self.reader.read(&mut magic[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(truncated()),
+                n => filled += n,
+            }
+        }
+        if i32::from_be_bytes(magic) != XTC_MAGIC {
+            return Err((ErrorCode::ExdrMagic, ErrorTask::Read).into());
+        }
+        let header = (|| {
+            let num_atoms = read_i32(&mut self.reader)?;
+            let step = read_i32(&mut self.reader)?;
+            let time = read_f32(&mut self.reader)?;
+            Ok((num_atoms, step, time))
+        })()
+        .map_err(to_error)?;
+        let (num_atoms, step, time) = header;
+        let num_atoms = crate::to(num_atoms, ErrorTask::Read, "natoms")?;
+        Ok(Some((step, time, num_atoms)))
+    }
+}
+
+fn truncated() -> Error {
+    Error::InvalidData("xtc: truncated frame".to_string())
+}
+
+fn to_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::InvalidData => Error::InvalidData(format!("xtc: {}", err)),
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => err.into(),
+    }
+}
+
+impl<R: Read> Trajectory for XtcReader<R> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        let num_atoms = self.get_num_atoms()?;
+        if frame.len() != num_atoms {
+            return Err((&*frame, num_atoms).into());
+        }
+        let header = match self.pending.take() {
+            Some(header) => header,
+            None => match self.read_header()? {
+                Some(header) => header,
+                None => return Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into()),
+            },
+        };
+        let (step, time, frame_atoms) = header;
+        if frame_atoms != num_atoms {
+            return Err(Error::WrongSizeFrame {
+                expected: num_atoms,
+                found: frame_atoms,
+            });
+        }
+        let mut box_vector = [[0.0; 3]; 3];
+        for x in box_vector.iter_mut().flatten() {
+            *x = read_f32(&mut self.reader).map_err(to_error)?;
+        }
+        self.precision = read_coords(&mut self.reader, &mut frame.coords).map_err(to_error)?;
+        frame.step = crate::to(step, ErrorTask::Read, "step")?;
+        frame.time = time;
+        frame.box_vector = BoxVector::new(box_vector);
+        Ok(())
+    }
+
+    fn write(&mut self, _frame: &Frame) -> Result<()> {
+        Err(Error::Unsupported("writing with XtcReader"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        if let Some(num_atoms) = self.num_atoms {
+            return Ok(num_atoms);
+        }
+        let header = match self.read_header()? {
+            Some(header) => header,
+            None => return Err((ErrorCode::ExdrEndOfFile, ErrorTask::ReadNumAtoms).into()),
+        };
+        let num_atoms = check_num_atoms(header.2, self.max_atoms)?;
+        self.num_atoms = Some(num_atoms);
+        self.pending = Some(header);
+        Ok(num_atoms)
+    }
+}
+
+impl<R: Read> IntoIterator for XtcReader<R> {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<XtcReader<R>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+/// Writes XTC trajectories without the C library, to files or any other
+/// sink of bytes. The files are identical to those of `XTCTrajectory`.
+///
+/// ```no_run
+/// use xdrfile::*;
+///
+/// # fn main() -> Result<()> {
+/// let mut trajectory = XtcWriter::create("traj.xtc")?;
+/// trajectory.set_precision(100.0);
+/// trajectory.write(&Frame::with_len(10))?;
+/// trajectory.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct XtcWriter<W: Write> {
+    writer: W,
+    precision: f32,
+    num_atoms: Option<usize>,
+    buf: Vec<u8>,
+}
+
+impl XtcWriter<BufWriter<File>> {
+    /// Create or truncate the XTC file at `path`
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(XtcWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> XtcWriter<W> {
+    /// Write an XTC trajectory to `writer`
+    pub fn new(writer: W) -> XtcWriter<W> {
+        XtcWriter {
+            writer,
+            precision: 1000.0,
+            num_atoms: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Set the precision that coordinates are written with, see
+    /// `XTCTrajectory::set_precision`
+    pub fn set_precision(&mut self, precision: f32) {
+        self.precision = precision;
+    }
+
+    /// Precision that coordinates are written with
+    pub fn precision(&self) -> f32 {
+        self.precision
+    }
+
+    /// Get back the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Trajectory for XtcWriter<W> {
+    fn read(&mut self, _frame: &mut Frame) -> Result<()> {
+        Err(Error::Unsupported("reading with XtcWriter"))
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.buf.clear();
+        put_i32(&mut self.buf, XTC_MAGIC);
+        put_i32(
+            &mut self.buf,
+            crate::to(frame.num_atoms(), ErrorTask::Write, "natoms")?,
+        );
+        put_i32(
+            &mut self.buf,
+            crate::to(frame.step, ErrorTask::Write, "step")?,
+        );
+        put_f32(&mut self.buf, frame.time);
+        for &x in frame.box_vector.0.iter().flatten() {
+            put_f32(&mut self.buf, x);
+        }
+        write_coords(&mut self.buf, &frame.coords, self.precision)?;
+        self.writer.write_all(&self.buf)?;
+        self.num_atoms.get_or_insert(frame.num_atoms());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.num_atoms.ok_or(Error::Unsupported(
+            "reading the number of atoms before writing a frame",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_read_like_c() -> Result<()> {
+        let expected = XTCTrajectory::open_read("tests/1l2y.xtc")?.into_frames();
+        let frames = XtcReader::open("tests/1l2y.xtc")?.into_iter();
+        let mut num_frames = 0;
+        for (expected, frame) in expected.zip(frames) {
+            let (expected, frame) = (expected?, frame?);
+            assert_eq!(frame.step, expected.step);
+            assert_eq!(frame.time, expected.time);
+            assert_eq!(frame.box_vector, expected.box_vector);
+            assert_eq!(frame.coords, expected.coords);
+            num_frames += 1;
+        }
+        assert_eq!(num_frames, 38);
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_small_and_truncated() -> Result<()> {
+        let mut xtc = MemoryTrajectory::new(MemoryFormat::Xtc);
+        let small = Frame {
+            coords: vec![[1.0, 2.0, 3.0]; 3],
+            ..Frame::default()
+        };
+        xtc.write(&small)?;
+        let bytes = xtc.into_bytes();
+
+        let mut reader = XtcReader::new(&bytes[..]);
+        let mut frame = Frame::with_len(reader.get_num_atoms()?);
+        reader.read(&mut frame)?;
+        assert_eq!(frame.coords, small.coords);
+        assert_eq!(reader.precision(), 0.0);
+        assert!(reader.read(&mut frame).unwrap_err().is_eof());
+
+        let original = std::fs::read("tests/1l2y.xtc")?;
+        let reader = XtcReader::new(&original[..original.len() - 10]);
+        let result: Result<Vec<_>> = reader.into_iter().collect();
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_c() -> Result<()> {
+        let frames: Vec<Rc<Frame>> = XtcReader::open("tests/1l2y.xtc")?
+            .into_iter()
+            .collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        assert_eq!(frames[0].step, 1);
+        assert_eq!(frames[0][0], [-0.8901, 0.4127, -0.055499997]);
+
+        // the precision of 1l2y.xtc, so that the coordinates do not change
+        let mut writer = XtcWriter::new(Vec::new());
+        writer.set_precision(10000.0);
+        for frame in &frames {
+            writer.write(frame)?;
+        }
+        let bytes = writer.into_inner();
+        let reread: Vec<Rc<Frame>> = XtcReader::new(&bytes[..])
+            .into_iter()
+            .collect::<Result<_>>()?;
+        assert_eq!(reread.len(), frames.len());
+        for (frame, expected) in reread.iter().zip(&frames) {
+            assert_eq!(frame.step, expected.step);
+            assert_eq!(frame.coords, expected.coords);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "c-lib")]
+    #[test]
+    fn test_write_like_c() -> Result<()> {
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        // sparse atoms that need more than 24 bits per coordinate
+        let mut sparse = Frame::with_len(20);
+        for (i, coord) in sparse.coords.iter_mut().enumerate() {
+            *coord = [i as f32 * 1000.0, -(i as f32) * 0.5, 0.001];
+        }
+        for precision in [1000.0, 10.0] {
+            let mut expected = MemoryTrajectory::new(MemoryFormat::Xtc);
+            expected.set_precision(precision);
+            let mut writer = XtcWriter::new(Vec::new());
+            writer.set_precision(precision);
+            for frame in frames.iter().chain(std::iter::once(&sparse)) {
+                expected.write(frame)?;
+                writer.write(frame)?;
+            }
+            assert_eq!(writer.get_num_atoms()?, 304);
+            assert_eq!(writer.into_inner(), expected.into_bytes());
+        }
+
+        let mut writer = XtcWriter::new(Vec::new());
+        sparse.coords[0][0] = 1e10;
+        assert!(matches!(
+            writer.write(&sparse),
+            Err(Error::OutOfRange { .. })
+        ));
+        Ok(())
+    }
+}
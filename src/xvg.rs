@@ -0,0 +1,220 @@
+//! Reading and writing xmgrace (.xvg) files as written by GROMACS tools
+//!
+//! ```no_run
+//! use xdrfile::*;
+//! use xdrfile::xvg::XvgData;
+//!
+//! # fn main() -> Result<()> {
+//! let mut rmsd = XvgData::new();
+//! rmsd.title = "RMSD".to_string();
+//! rmsd.x_label = "Time (ps)".to_string();
+//! rmsd.y_label = "RMSD (nm)".to_string();
+//! rmsd.legends.push("Backbone".to_string());
+//! rmsd.rows.push(vec![0.0, 0.0]);
+//! rmsd.rows.push(vec![10.0, 0.12]);
+//! xvg::write(&mut std::fs::File::create("rmsd.xvg")?, &rmsd)?;
+//!
+//! let rmsd = xvg::read("rmsd.xvg")?;
+//! let times = rmsd.column(0);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Contents of an xvg file: labels, one legend per data set and the rows
+/// of numbers, where the first column usually holds the time
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XvgData {
+    /// Graph title
+    pub title: String,
+
+    /// Graph subtitle
+    pub subtitle: String,
+
+    /// Label of the x axis
+    pub x_label: String,
+
+    /// Label of the y axis
+    pub y_label: String,
+
+    /// Legend of each data set, i.e. of every column after the first
+    pub legends: Vec<String>,
+
+    /// Comment lines (starting with `#`) without the leading `#`
+    pub comments: Vec<String>,
+
+    /// Other xmgrace commands (starting with `@`) without the leading `@`
+    pub metadata: Vec<String>,
+
+    /// Rows of data, all with the same number of columns
+    pub rows: Vec<Vec<f64>>,
+}
+
+impl XvgData {
+    /// Create an empty data set without labels
+    pub fn new() -> XvgData {
+        XvgData::default()
+    }
+
+    /// Get all values of a column, e.g. `column(0)` for the times. Rows
+    /// that are too short are skipped.
+    pub fn column(&self, index: usize) -> Vec<f64> {
+        self.rows
+            .iter()
+            .filter_map(|row| row.get(index).copied())
+            .collect()
+    }
+}
+
+/// Read the xvg file at `path`
+pub fn read(path: impl AsRef<Path>) -> Result<XvgData> {
+    parse(BufReader::new(File::open(path)?))
+}
+
+/// Parse xvg data from `reader`. Data set separators (`&`) are skipped, so
+/// all rows must have the same number of columns.
+pub fn parse<R: BufRead>(reader: R) -> Result<XvgData> {
+    let mut data = XvgData::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let invalid = |message: &str| {
+            Error::InvalidData(format!("{} in line {}: '{}'", message, i + 1, line))
+        };
+        let trimmed = line.trim();
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            data.comments.push(comment.trim().to_string());
+        } else if let Some(command) = trimmed.strip_prefix('@') {
+            parse_command(&mut data, command.trim());
+        } else if !trimmed.is_empty() && trimmed != "&" {
+            let row = trimmed
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .map_err(|_| invalid("invalid number"))?;
+            if let Some(first) = data.rows.first() {
+                if first.len() != row.len() {
+                    return Err(invalid("inconsistent number of columns"));
+                }
+            }
+            data.rows.push(row);
+        }
+    }
+    Ok(data)
+}
+
+fn parse_command(data: &mut XvgData, command: &str) {
+    let mut words = command.split_whitespace();
+    let label = match (words.next(), words.next()) {
+        (Some("title"), _) => &mut data.title,
+        (Some("subtitle"), _) => &mut data.subtitle,
+        (Some("xaxis"), Some("label")) => &mut data.x_label,
+        (Some("yaxis"), Some("label")) => &mut data.y_label,
+        (Some(set), Some("legend")) if set.starts_with('s') => match set[1..].parse::<usize>() {
+            Ok(index) => {
+                if data.legends.len() <= index {
+                    data.legends.resize(index + 1, String::new());
+                }
+                &mut data.legends[index]
+            }
+            Err(_) => return data.metadata.push(command.to_string()),
+        },
+        // written by `write` itself
+        (Some("TYPE"), _) | (Some("legend"), Some("on")) => return,
+        _ => return data.metadata.push(command.to_string()),
+    };
+    *label = unquote(command).to_string();
+}
+
+/// The text between the first and the last double quote, if any
+fn unquote(command: &str) -> &str {
+    match (command.find('"'), command.rfind('"')) {
+        (Some(start), Some(end)) if start < end => &command[start + 1..end],
+        _ => "",
+    }
+}
+
+/// Write `data` in xvg format
+pub fn write<W: Write>(writer: &mut W, data: &XvgData) -> Result<()> {
+    for comment in &data.comments {
+        writeln!(writer, "# {}", comment)?;
+    }
+    writeln!(writer, "@    title \"{}\"", data.title)?;
+    if !data.subtitle.is_empty() {
+        writeln!(writer, "@    subtitle \"{}\"", data.subtitle)?;
+    }
+    writeln!(writer, "@    xaxis  label \"{}\"", data.x_label)?;
+    writeln!(writer, "@    yaxis  label \"{}\"", data.y_label)?;
+    writeln!(writer, "@TYPE xy")?;
+    for command in &data.metadata {
+        writeln!(writer, "@ {}", command)?;
+    }
+    if !data.legends.is_empty() {
+        writeln!(writer, "@ legend on")?;
+    }
+    for (i, legend) in data.legends.iter().enumerate() {
+        writeln!(writer, "@ s{} legend \"{}\"", i, legend)?;
+    }
+    for row in &data.rows {
+        let values: Vec<String> = row.iter().map(|value| format!("{:>12}", value)).collect();
+        writeln!(writer, "{}", values.join(" "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XVG: &str = "\
+# This file was created by gmx rms
+#
+@    title \"RMSD\"
+@    xaxis  label \"Time (ns)\"
+@    yaxis  label \"RMSD (nm)\"
+@TYPE xy
+@ subtitle \"Backbone after lsq fit to Backbone\"
+@ view 0.15, 0.15, 0.75, 0.85
+@ legend on
+@ s1 legend \"Second\"
+@ s0 legend \"First\"
+0.0000000    0.0000005  1
+0.0010000    0.1240125  2
+&
+";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let data = parse(XVG.as_bytes())?;
+        assert_eq!(data.title, "RMSD");
+        assert_eq!(data.subtitle, "Backbone after lsq fit to Backbone");
+        assert_eq!(data.x_label, "Time (ns)");
+        assert_eq!(data.y_label, "RMSD (nm)");
+        assert_eq!(data.legends, vec!["First", "Second"]);
+        assert_eq!(data.comments, vec!["This file was created by gmx rms", ""]);
+        assert_eq!(data.metadata, vec!["view 0.15, 0.15, 0.75, 0.85"]);
+        assert_eq!(data.column(0), vec![0.0, 0.001]);
+        assert_eq!(data.column(1), vec![0.0000005, 0.1240125]);
+
+        let result = parse("1 2\n1 2 3\n".as_bytes());
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        let result = parse("1 x\n".as_bytes());
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> Result<()> {
+        let data = parse(XVG.as_bytes())?;
+        let mut out = Vec::new();
+        write(&mut out, &data)?;
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("@ s1 legend \"Second\"\n"));
+        assert!(text.ends_with("       0.001    0.1240125            2\n"));
+        assert_eq!(parse(text.as_bytes())?, data);
+        Ok(())
+    }
+}
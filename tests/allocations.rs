@@ -0,0 +1,46 @@
+//! Reading frames must not allocate once the buffers exist
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use xdrfile::*;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_read_without_allocations() -> Result<()> {
+    let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+    let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+    let mut frame = Frame::with_len(xtc.get_num_atoms()?);
+    trr.get_num_atoms()?;
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..10 {
+        xtc.read(&mut frame)?;
+        xtc.read_unchecked_len(&mut frame)?;
+        trr.read(&mut frame)?;
+        trr.read_unchecked_len(&mut frame)?;
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+
+    // frames that are too short are refused
+    let mut short = Frame::with_len(10);
+    assert!(xtc.read_unchecked_len(&mut short).is_err());
+    assert!(trr.read_unchecked_len(&mut short).is_err());
+    Ok(())
+}